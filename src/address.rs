@@ -0,0 +1,438 @@
+use crate::SettingsError;
+#[cfg(feature = "sendgrid")]
+use sendgrid::v3::Email;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+// Special characters RFC 5322 allows in an unquoted local part
+const LOCAL_SPECIALS: &str = "!#$%&'*+-/=?^_`{|}~";
+
+/// A pragmatic `local@domain` check: dot-atom local part, and a domain of at least
+/// two dot-separated labels made of letters, digits and inner hyphens. Quoted local
+/// parts and IP literals are deliberately not supported.
+pub(crate) fn is_valid_email(address: &str) -> bool {
+    let (local, domain) = match address.rsplit_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && local.split('.').all(|atom| {
+            !atom.is_empty()
+                && atom
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || LOCAL_SPECIALS.contains(c))
+        });
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = labels.len() >= 2
+        && domain.len() <= 253
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+
+    local_ok && domain_ok
+}
+
+// RFC 5322 specials; a display name with any of them is written quoted
+const NAME_SPECIALS: &str = "()<>[]:;@\\,.\"";
+
+/// A validated email address with an optional display name, parsed from a bare
+/// address or the `Name <address>` form, where the name may be quoted, e.g.
+/// `"Doe, Jane" <jane@example.com>`.
+///
+/// The address gets the same pragmatic `local@domain` check as
+/// [`crate::Settings::validate`]: a dot-atom local part and a domain of at
+/// least two labels; quoted local parts and IP literals are not supported.
+/// It displays, serializes and deserializes in the form it parses, as a plain
+/// string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailAddress {
+    name: Option<String>,
+    address: String,
+}
+
+impl EmailAddress {
+    /// The `local@domain` part.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// The display name, if the entry had a non-empty one.
+    pub fn display_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Parses a single trimmed entry, returning `None` if it is malformed or the
+    /// address isn't valid.
+    pub(crate) fn parse(entry: &str) -> Option<EmailAddress> {
+        let entry = entry.trim();
+        let (name, address) = match entry.strip_suffix('>') {
+            Some(rest) => {
+                let open = unquoted_positions(rest, |c| c == '<').next()?;
+                (
+                    parse_display_name(rest[..open].trim())?,
+                    rest[open + 1..].trim(),
+                )
+            }
+            None => (None, entry),
+        };
+
+        if is_valid_email(address) {
+            Some(EmailAddress {
+                name,
+                address: address.to_string(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for EmailAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EmailAddress, String> {
+        EmailAddress::parse(s).ok_or_else(|| format!("'{}' is not a valid email address", s.trim()))
+    }
+}
+
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) if name.contains(|c| NAME_SPECIALS.contains(c)) => {
+                write!(f, "\"")?;
+                for c in name.chars() {
+                    if c == '"' || c == '\\' {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                write!(f, "\" <{}>", self.address)
+            }
+            Some(name) => write!(f, "{} <{}>", name, self.address),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+impl Serialize for EmailAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EmailAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<EmailAddress, D::Error> {
+        let entry = String::deserialize(deserializer)?;
+        entry.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "sendgrid")]
+impl From<&EmailAddress> for Email {
+    fn from(address: &EmailAddress) -> Email {
+        match &address.name {
+            Some(name) => Email::new(&address.address).set_name(name),
+            None => Email::new(&address.address),
+        }
+    }
+}
+
+#[cfg(feature = "sendgrid")]
+impl From<EmailAddress> for Email {
+    fn from(address: EmailAddress) -> Email {
+        Email::from(&address)
+    }
+}
+
+// An empty name is no name; a quoted one may contain commas and `\"` escapes
+fn parse_display_name(raw: &str) -> Option<Option<String>> {
+    if raw.is_empty() {
+        return Some(None);
+    }
+    let quoted = match raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+        Some(quoted) => quoted,
+        _ if raw.contains('"') => return None,
+        _ => return Some(Some(raw.to_string())),
+    };
+
+    let mut name = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => name.push(chars.next()?),
+            '"' => return None,
+            c => name.push(c),
+        }
+    }
+    Some(Some(name).filter(|name| !name.trim().is_empty()))
+}
+
+// Byte offsets of the matching characters that are outside quoted strings
+fn unquoted_positions(raw: &str, is_target: fn(char) -> bool) -> impl Iterator<Item = usize> + '_ {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    raw.char_indices().filter_map(move |(i, c)| {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes && is_target(c) {
+            return Some(i);
+        }
+        None
+    })
+}
+
+/// Splits a recipient field on the commas or semicolons (as Outlook writes
+/// them) between entries, leaving separators inside quoted display names alone.
+/// Entries are returned untrimmed, empty ones included. Every recipient field is
+/// split by this function.
+pub(crate) fn split_entries(raw: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    for i in unquoted_positions(raw, |c| c == ',' || c == ';') {
+        entries.push(&raw[start..i]);
+        start = i + 1;
+    }
+    entries.push(&raw[start..]);
+    entries
+}
+
+/// Splits a recipient field into trimmed entries paired with their 1-based
+/// position in the list, skipping empty segments.
+pub(crate) fn split_address_list(raw: &str) -> impl Iterator<Item = (usize, &str)> {
+    split_entries(raw)
+        .into_iter()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, entry)| !entry.is_empty())
+        .map(|(i, entry)| (i + 1, entry))
+}
+
+/// Like [`split_address_list`], but parses each entry and fails on the first
+/// one that isn't a valid recipient. Repeated addresses are dropped, comparing
+/// case-insensitively and keeping the first.
+pub(crate) fn parse_address_list(
+    field: &'static str,
+    raw: &str,
+) -> Result<Vec<EmailAddress>, SettingsError> {
+    let mut seen = HashSet::new();
+    let mut addresses = Vec::new();
+    for (position, entry) in split_address_list(raw) {
+        let address =
+            EmailAddress::parse(entry).ok_or_else(|| SettingsError::InvalidEmailAddress {
+                field,
+                address: entry.to_string(),
+                position,
+            })?;
+        if seen.insert(address.address.to_lowercase()) {
+            addresses.push(address);
+        }
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_ordinary_addresses() {
+        for address in [
+            "user@example.com",
+            "first.last+tag@mail.example.co.uk",
+            "o'brien@example.com",
+            "a@b-c.io",
+        ] {
+            assert!(is_valid_email(address), "{}", address);
+        }
+    }
+
+    #[test]
+    fn test_rejects_malformed_addresses() {
+        for address in [
+            "",
+            "not-an-email",
+            "@example.com",
+            "user@",
+            "user@localhost",
+            "user@@example.com",
+            "user.@example.com",
+            "us..er@example.com",
+            "user@-example.com",
+            "user@exa mple.com",
+            "Jane <jane@example.com>",
+        ] {
+            assert!(!is_valid_email(address), "{}", address);
+        }
+    }
+
+    #[test]
+    fn test_parse_address_list_skips_empty_segments() {
+        let cases: [(&str, &[&str]); 12] = [
+            ("a@x.com", &["a@x.com"]),
+            ("a@x.com,", &["a@x.com"]),
+            ("  a@x.com ,\tb@x.com  ", &["a@x.com", "b@x.com"]),
+            (",, ,", &[]),
+            ("", &[]),
+            // Pasted from Outlook
+            ("a@x.com; b@x.com;", &["a@x.com", "b@x.com"]),
+            (
+                "a@x.com;b@x.com,c@x.com",
+                &["a@x.com", "b@x.com", "c@x.com"],
+            ),
+            ("a@x.com ;; , ;b@x.com", &["a@x.com", "b@x.com"]),
+            ("a@x.com,\r\n b@x.com", &["a@x.com", "b@x.com"]),
+            ("b@x.com, a@x.com, B@X.com", &["b@x.com", "a@x.com"]),
+            ("Ann <a@x.com>; a@x.com", &["a@x.com"]),
+            (r#""Doe; Jane" <j@x.com>; a@x.com"#, &["j@x.com", "a@x.com"]),
+        ];
+        for (raw, expected) in cases {
+            let addresses: Vec<String> = parse_address_list("EmailToAddresses", raw)
+                .unwrap()
+                .into_iter()
+                .map(|address| address.address)
+                .collect();
+            assert_eq!(addresses, expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn test_parse_address_list_names_bad_token() {
+        let err = parse_address_list("EmailToAddresses", "a@b.com,,  ,not-an-email").unwrap_err();
+
+        assert!(matches!(
+            err,
+            SettingsError::InvalidEmailAddress { field: "EmailToAddresses", ref address, position: 4 }
+                if address == "not-an-email"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "EmailToAddresses: 'not-an-email' (entry 4) is not a valid email address"
+        );
+    }
+
+    #[test]
+    fn test_parses_display_names() {
+        let named = |name: &str, address: &str| EmailAddress {
+            name: Some(name.to_string()),
+            address: address.to_string(),
+        };
+        let bare = |address: &str| EmailAddress {
+            name: None,
+            address: address.to_string(),
+        };
+
+        let cases = [
+            ("jane@corp.com", bare("jane@corp.com")),
+            ("<jane@corp.com>", bare("jane@corp.com")),
+            (
+                "Jane Doe <jane@corp.com>",
+                named("Jane Doe", "jane@corp.com"),
+            ),
+            (
+                "Jane Doe<jane@corp.com>",
+                named("Jane Doe", "jane@corp.com"),
+            ),
+            (
+                r#""Doe, Jane" <jane@corp.com>"#,
+                named("Doe, Jane", "jane@corp.com"),
+            ),
+            (
+                r#""Jane \"JD\" Doe" <jd@corp.com>"#,
+                named(r#"Jane "JD" Doe"#, "jd@corp.com"),
+            ),
+            (r#""" <jane@corp.com>"#, bare("jane@corp.com")),
+        ];
+        for (entry, expected) in cases {
+            assert_eq!(EmailAddress::parse(entry), Some(expected), "{}", entry);
+        }
+
+        for entry in [
+            "Jane <jane@corp.com",
+            "Jane jane@corp.com>",
+            "Jane <not-an-email>",
+            r#""Jane <jane@corp.com>"#,
+            r#"Ja"ne <jane@corp.com>"#,
+        ] {
+            assert_eq!(EmailAddress::parse(entry), None, "{}", entry);
+        }
+    }
+
+    #[test]
+    fn test_from_str_and_display_round_trip() {
+        for (entry, address, name, displayed) in [
+            ("  a@x.com ", "a@x.com", None, "a@x.com"),
+            ("<a@x.com>", "a@x.com", None, "a@x.com"),
+            (
+                "Jane Doe<jane@corp.com>",
+                "jane@corp.com",
+                Some("Jane Doe"),
+                "Jane Doe <jane@corp.com>",
+            ),
+            (
+                r#""Doe, Jane" <jane@corp.com>"#,
+                "jane@corp.com",
+                Some("Doe, Jane"),
+                r#""Doe, Jane" <jane@corp.com>"#,
+            ),
+            (
+                r#""Jane \"JD\" Doe" <jd@corp.com>"#,
+                "jd@corp.com",
+                Some(r#"Jane "JD" Doe"#),
+                r#""Jane \"JD\" Doe" <jd@corp.com>"#,
+            ),
+        ] {
+            let parsed: EmailAddress = entry.parse().unwrap();
+
+            assert_eq!(parsed.address(), address);
+            assert_eq!(parsed.display_name(), name);
+            assert_eq!(parsed.to_string(), displayed);
+            assert_eq!(displayed.parse::<EmailAddress>(), Ok(parsed));
+        }
+
+        assert_eq!(
+            " Jane <nope> ".parse::<EmailAddress>(),
+            Err("'Jane <nope>' is not a valid email address".to_string())
+        );
+    }
+
+    #[test]
+    fn test_serde_as_plain_string() {
+        let address: EmailAddress = serde_json::from_str(r#""Ops <ops@corp.com>""#).unwrap();
+
+        assert_eq!(address.display_name(), Some("Ops"));
+        assert_eq!(
+            serde_json::to_string(&address).unwrap(),
+            r#""Ops <ops@corp.com>""#
+        );
+        assert!(serde_json::from_str::<EmailAddress>(r#""not-an-email""#).is_err());
+    }
+
+    #[test]
+    fn test_split_keeps_commas_in_quoted_names() {
+        let entries: Vec<(usize, &str)> =
+            split_address_list(r#"a@x.com, "Doe, Jane" <jane@corp.com>,,Bob <bob@corp.com>"#)
+                .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                (1, "a@x.com"),
+                (2, r#""Doe, Jane" <jane@corp.com>"#),
+                (4, "Bob <bob@corp.com>"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use crate::sql::{ServerAddress, DEFAULT_APPLICATION_NAME};
+use crate::{DatabaseAuthMethod, DatabaseEncryption, FieldError, Settings};
+use connection_string::AdoNetString;
+#[cfg(feature = "mssql")]
+use ssql::prelude::tiberius::Config;
+
+// The keys tiberius reads, each with the aliases it accepts, in the order they
+// are written out. Keys not listed here are passed through as they are.
+const KNOWN_KEYS: [(&str, &[&str]); 10] = [
+    ("Server", &["server", "data source"]),
+    ("Database", &["database", "initial catalog", "databasename"]),
+    ("User Id", &["user id", "uid", "username", "user"]),
+    ("Password", &["password", "pwd"]),
+    (
+        "Integrated Security",
+        &["integrated security", "integratedsecurity"],
+    ),
+    ("Encrypt", &["encrypt"]),
+    ("TrustServerCertificate", &["trustservercertificate"]),
+    ("TrustServerCertificateCA", &["trustservercertificateca"]),
+    ("Application Name", &["application name", "applicationname"]),
+    ("ApplicationIntent", &["applicationintent"]),
+];
+
+impl Settings {
+    /// An ADO.NET connection string for the report database, built from the
+    /// same fields as [`Settings::get_sql_settings`], e.g.
+    /// `Server=host,1433;Database=reports;User Id=svc;Password=...;Encrypt=false`.
+    ///
+    /// Tiberius' ADO.NET parser has no separate "on if supported" level, so
+    /// `DatabaseEncryption` `On` and `Required` are both written as `Encrypt=true`.
+    pub fn to_ado_connection_string(&self) -> String {
+        render(&self.ado_pairs(
+            self.expose_database_password(),
+            &mut Vec::new(),
+            &mut Vec::new(),
+        ))
+    }
+
+    /// Like [`Settings::to_ado_connection_string`], with the password replaced by
+    /// `***` so the string can be logged.
+    pub fn to_ado_connection_string_masked(&self) -> String {
+        render(&self.ado_pairs("***", &mut Vec::new(), &mut Vec::new()))
+    }
+
+    /// Builds the tiberius config from `DatabaseConnectionString`, with any
+    /// individual database fields that are also set taking precedence.
+    #[cfg(feature = "mssql")]
+    pub(crate) fn connection_string_config(&self, errors: &mut Vec<FieldError>) -> Config {
+        ado_config(&self.connection_string_pairs(errors), errors)
+    }
+
+    /// `DatabaseConnectionString` merged with the individual database fields,
+    /// with every problem short of tiberius rejecting the result.
+    pub(crate) fn connection_string_pairs(
+        &self,
+        errors: &mut Vec<FieldError>,
+    ) -> Vec<(String, String)> {
+        let pairs = self.ado_pairs(self.expose_database_password(), errors, &mut Vec::new());
+
+        #[cfg(not(windows))]
+        if lookup(&pairs, "Integrated Security").is_some_and(is_true) {
+            errors.push(FieldError::new(
+                match self.database_auth_method {
+                    Some(_) => "DatabaseAuthMethod",
+                    None => "DatabaseConnectionString",
+                },
+                "Integrated authentication is only supported on Windows builds",
+            ));
+        }
+        pairs
+    }
+
+    /// Individual database fields that shadow a value in `DatabaseConnectionString`.
+    pub(crate) fn connection_string_warnings(&self) -> Vec<FieldError> {
+        let mut warnings = Vec::new();
+        self.ado_pairs("", &mut Vec::new(), &mut warnings);
+        warnings
+    }
+
+    pub(crate) fn ado_pairs(
+        &self,
+        password: &str,
+        errors: &mut Vec<FieldError>,
+        warnings: &mut Vec<FieldError>,
+    ) -> Vec<(String, String)> {
+        let parsed = self
+            .database_connection_string
+            .as_ref()
+            .map(|connection_string| connection_string.expose().parse::<AdoNetString>());
+        let mut parsed = match parsed {
+            Some(Ok(parsed)) => parsed,
+            Some(Err(e)) => {
+                errors.push(FieldError::new(
+                    "DatabaseConnectionString",
+                    format!("is not a valid connection string: {}", e),
+                ));
+                return Vec::new();
+            }
+            None => {
+                return self
+                    .field_pairs(password, false, errors)
+                    .into_iter()
+                    .map(|(key, value, _)| (key.to_string(), value))
+                    .collect()
+            }
+        };
+
+        let mut pairs = Vec::new();
+        for (key, aliases) in KNOWN_KEYS {
+            // Collected so every alias is removed, not just the first one found
+            let values: Vec<String> = aliases.iter().filter_map(|a| parsed.remove(*a)).collect();
+            if let Some(value) = values.into_iter().next() {
+                pairs.push((key.to_string(), value));
+            }
+        }
+        let mut rest: Vec<(String, String)> = parsed.drain().collect();
+        rest.sort();
+        pairs.extend(rest);
+
+        for (key, value, field) in self.field_pairs(password, true, errors) {
+            match pairs.iter_mut().find(|(k, _)| k == key) {
+                Some(pair) => {
+                    warnings.push(FieldError::new(
+                        field,
+                        "overrides the value in DatabaseConnectionString",
+                    ));
+                    pair.1 = value;
+                }
+                None => pairs.push((key.to_string(), value)),
+            }
+        }
+        if self.database_auth_method == Some(DatabaseAuthMethod::Integrated) {
+            pairs.retain(|(k, _)| k != "User Id" && k != "Password");
+        }
+        if lookup(&pairs, "Application Name").is_none() {
+            pairs.push((
+                "Application Name".to_string(),
+                DEFAULT_APPLICATION_NAME.to_string(),
+            ));
+        }
+        // tiberius panics when asked to both trust any certificate and a given CA
+        if lookup(&pairs, "TrustServerCertificate").is_some_and(is_true)
+            && lookup(&pairs, "TrustServerCertificateCA").is_some()
+        {
+            errors.push(FieldError::new(
+                "DatabaseConnectionString",
+                "TrustServerCertificate and TrustServerCertificateCA cannot be combined",
+            ));
+            pairs.retain(|(k, _)| k != "TrustServerCertificateCA");
+        }
+
+        pairs
+    }
+
+    // The individual database fields as ADO.NET pairs, each with the field it
+    // came from. On top of a connection string, only fields that are set count.
+    fn field_pairs(
+        &self,
+        password: &str,
+        over_connection_string: bool,
+        errors: &mut Vec<FieldError>,
+    ) -> Vec<(&'static str, String, &'static str)> {
+        let mut pairs = Vec::new();
+        let set = |value: &str| !over_connection_string || !value.trim().is_empty();
+
+        if set(&self.database_server) {
+            let server = ado_server(&self.sql_address(errors));
+            pairs.push(("Server", server, "DatabaseServer"));
+        } else if self.database_port.is_some() {
+            errors.push(FieldError::new(
+                "DatabasePort",
+                "needs DatabaseServer; put the port in DatabaseConnectionString instead",
+            ));
+        }
+        if set(&self.database_name) {
+            pairs.push(("Database", self.database_name.clone(), "DatabaseName"));
+        }
+
+        // Integrated auth is never the default, so it is always asked for
+        // explicitly; a SQL login only overrides a connection string if given
+        if self.database_auth_method() == DatabaseAuthMethod::Integrated {
+            pairs.push((
+                "Integrated Security",
+                "true".to_string(),
+                "DatabaseAuthMethod",
+            ));
+        } else {
+            if over_connection_string && self.database_auth_method.is_some() {
+                pairs.push((
+                    "Integrated Security",
+                    "false".to_string(),
+                    "DatabaseAuthMethod",
+                ));
+            }
+            if set(&self.database_username) {
+                pairs.push((
+                    "User Id",
+                    self.database_username.clone(),
+                    "DatabaseUsername",
+                ));
+            }
+            if set(self.expose_database_password()) {
+                pairs.push(("Password", password.to_string(), "DatabasePassword"));
+            }
+        }
+
+        if !over_connection_string || self.database_encryption.is_some() {
+            let encrypt = self.database_encryption() != DatabaseEncryption::Off;
+            pairs.push(("Encrypt", encrypt.to_string(), "DatabaseEncryption"));
+        }
+        match self.database_trust_cert {
+            Some(trust) if over_connection_string => pairs.push((
+                "TrustServerCertificate",
+                trust.to_string(),
+                "DatabaseTrustCert",
+            )),
+            _ if !over_connection_string && self.trusts_cert() => pairs.push((
+                "TrustServerCertificate",
+                "true".to_string(),
+                "DatabaseTrustCert",
+            )),
+            _ => {}
+        }
+        if let Some(path) = &self.database_ca_cert_path {
+            pairs.push((
+                "TrustServerCertificateCA",
+                path.clone(),
+                "DatabaseCaCertPath",
+            ));
+        }
+        match self.database_read_only_intent {
+            Some(read_only) if over_connection_string => pairs.push((
+                "ApplicationIntent",
+                if read_only { "ReadOnly" } else { "ReadWrite" }.to_string(),
+                "DatabaseReadOnlyIntent",
+            )),
+            _ => {}
+        }
+        if !over_connection_string || self.application_name.is_some() {
+            pairs.push((
+                "Application Name",
+                self.application_name().to_string(),
+                "ApplicationName",
+            ));
+        }
+        if !over_connection_string && self.database_read_only_intent() {
+            pairs.push((
+                "ApplicationIntent",
+                "ReadOnly".to_string(),
+                "DatabaseReadOnlyIntent",
+            ));
+        }
+
+        pairs
+    }
+}
+
+#[cfg(feature = "mssql")]
+pub(crate) fn ado_config(pairs: &[(String, String)], errors: &mut Vec<FieldError>) -> Config {
+    Config::from_ado_string(&render(pairs)).unwrap_or_else(|e| {
+        errors.push(FieldError::new(
+            "DatabaseConnectionString",
+            format!("is not a valid connection string: {}", e),
+        ));
+        Config::new()
+    })
+}
+
+/// An address as the `Server` value of a connection string, `host\\instance,port`.
+pub(crate) fn ado_server(address: &ServerAddress<'_>) -> String {
+    let mut server = address.host.to_string();
+    if let Some(instance) = address.instance {
+        server.push('\\');
+        server.push_str(instance);
+    }
+    if let Some(port) = address.port {
+        server.push_str(&format!(",{}", port));
+    }
+    server
+}
+
+pub(crate) fn lookup<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+// Same spellings tiberius accepts
+pub(crate) fn is_true(value: &str) -> bool {
+    matches!(value.trim().to_ascii_lowercase().as_str(), "true" | "yes")
+}
+
+fn render(pairs: &[(String, String)]) -> String {
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, quote(value)))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+// ADO.NET quoting: values that would end the pair or confuse the parser are
+// wrapped in double quotes, or single quotes if they contain a double quote, and
+// a quote matching the wrapping one is doubled
+fn quote(value: &str) -> String {
+    let plain = !value.contains([';', '=', '\'', '"', '{', '}'])
+        && value.trim() == value
+        && !value.is_empty();
+    if plain {
+        value.to_string()
+    } else if !value.contains('"') {
+        format!("\"{}\"", value)
+    } else if !value.contains('\'') {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SettingsBuilder;
+    #[cfg(feature = "mssql")]
+    use ssql::prelude::tiberius::Config;
+
+    fn settings() -> SettingsBuilder {
+        Settings::builder()
+            .database_server("localhost,14330")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+    }
+
+    #[test]
+    fn test_connection_string_fields() {
+        let settings = settings().build().unwrap();
+
+        assert_eq!(
+            settings.to_ado_connection_string(),
+            "Server=localhost,14330;Database=test_db;User Id=admin;Password=password123;\
+             Encrypt=false;TrustServerCertificate=true;Application Name=Report Service"
+        );
+    }
+
+    #[test]
+    fn test_masked_hides_password() {
+        let masked = settings()
+            .database_password("s3cr;t")
+            .build()
+            .unwrap()
+            .to_ado_connection_string_masked();
+
+        assert!(masked.contains("Password=***;"), "{}", masked);
+        assert!(!masked.contains("s3cr"), "{}", masked);
+    }
+
+    #[test]
+    fn test_quotes_special_values() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("pa;ss"), "\"pa;ss\"");
+        assert_eq!(quote("o'brien"), "\"o'brien\"");
+        assert_eq!(quote("say \"hi\""), "'say \"hi\"'");
+        assert_eq!(quote("it's \"x\""), "\"it's \"\"x\"\"\"");
+        assert_eq!(quote(""), "\"\"");
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_tiberius_parses_output() {
+        for builder in [
+            settings(),
+            settings().database_password("pa;ss'wo=rd"),
+            settings()
+                .database_server("SQLPROD01\\REPORTS")
+                .database_name("db;name")
+                .database_encryption(DatabaseEncryption::Required),
+        ] {
+            let settings = builder.build().unwrap();
+            let expected = settings.get_sql_settings();
+
+            let parsed = Config::from_ado_string(&settings.to_ado_connection_string()).unwrap();
+
+            assert_eq!(parsed.get_addr(), expected.get_addr());
+            assert_eq!(format!("{:?}", parsed), format!("{:?}", expected));
+        }
+    }
+
+    #[cfg(feature = "mssql")]
+    fn from_connection_string(connection_string: &str) -> SettingsBuilder {
+        Settings::builder()
+            .database_connection_string(connection_string)
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_config_from_connection_string() {
+        let settings = from_connection_string(
+            "Server=tcp:db.example.com,14330;Database=reports;User Id=svc;\
+             Password='pa;ss';Encrypt=true;TrustServerCertificate=true",
+        )
+        .build()
+        .unwrap();
+
+        assert_eq!(settings.validate(), Ok(()));
+        assert!(settings.warnings().is_empty());
+
+        let config = settings.try_get_sql_settings().unwrap();
+        let debug = format!("{:?}", config);
+        assert_eq!(config.get_addr(), "db.example.com:14330");
+        assert!(debug.contains("trust: TrustAll"), "{}", debug);
+        assert!(debug.contains("encryption: Required"), "{}", debug);
+        assert!(debug.contains(r#"database: Some("reports")"#), "{}", debug);
+        assert!(debug.contains(r#"user: "svc""#), "{}", debug);
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_individual_fields_win_with_warning() {
+        let settings = from_connection_string("Server=db.example.com;Database=reports;User Id=svc")
+            .database_server("replica,14330")
+            .database_trust_cert(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(settings.get_sql_settings().get_addr(), "replica:14330");
+        assert!(format!("{:?}", settings.get_sql_settings()).contains("trust: TrustAll"));
+        assert_eq!(
+            settings.warnings(),
+            vec![FieldError::new(
+                "DatabaseServer",
+                "overrides the value in DatabaseConnectionString"
+            )]
+        );
+        assert_eq!(settings.validate(), Ok(()));
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_connection_string_problems_are_reported() {
+        let unclosed = from_connection_string("Server='db").build().unwrap();
+        assert_eq!(
+            unclosed.validate().unwrap_err()[0].field,
+            "DatabaseConnectionString"
+        );
+
+        let both_trusts = from_connection_string(
+            "Server=db;TrustServerCertificate=true;TrustServerCertificateCA=/etc/ca.pem",
+        )
+        .build()
+        .unwrap();
+        assert_eq!(
+            both_trusts.try_get_sql_settings().unwrap_err().to_string(),
+            "Invalid settings: DatabaseConnectionString: TrustServerCertificate and \
+             TrustServerCertificateCA cannot be combined"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{FieldError, Settings};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The most categories SendGrid accepts on one message.
+pub const SENDGRID_MAX_CATEGORIES: usize = 10;
+
+/// The longest category SendGrid accepts, in bytes.
+pub const SENDGRID_MAX_CATEGORY_BYTES: usize = 255;
+
+impl Settings {
+    /// The `EmailCategories` entries, trimmed, with empty ones skipped. Empty if
+    /// `EmailCategories` is not set.
+    pub fn email_categories(&self) -> Vec<&str> {
+        self.email_categories
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|category| !category.is_empty())
+            .collect()
+    }
+
+    /// `EmailCustomArgs` as given. SendGrid only takes string values, which
+    /// [`Settings::validate`] checks.
+    pub fn email_custom_args(&self) -> Option<&BTreeMap<String, Value>> {
+        self.email_custom_args.as_ref()
+    }
+
+    pub(crate) fn analytics_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        let categories = self.email_categories();
+        if categories.len() > SENDGRID_MAX_CATEGORIES {
+            errors.push(FieldError::new(
+                "EmailCategories",
+                format!(
+                    "has {} categories, but SendGrid accepts at most {}",
+                    categories.len(),
+                    SENDGRID_MAX_CATEGORIES
+                ),
+            ));
+        }
+        for (index, category) in categories.iter().enumerate() {
+            if category.len() > SENDGRID_MAX_CATEGORY_BYTES {
+                errors.push(FieldError::new(
+                    "EmailCategories",
+                    format!(
+                        "entry {} is {} bytes, but SendGrid accepts at most {}",
+                        index + 1,
+                        category.len(),
+                        SENDGRID_MAX_CATEGORY_BYTES
+                    ),
+                ));
+            }
+        }
+
+        for (name, value) in self.email_custom_args().into_iter().flatten() {
+            if !value.is_string() {
+                errors.push(FieldError::new(
+                    "EmailCustomArgs",
+                    format!("'{}' must be a string, not {}", name, json_type(value)),
+                ));
+            }
+        }
+        errors
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categories_are_split_and_trimmed() {
+        let settings = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailCategories": " staging, , daily-costs "}"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.email_categories(), ["staging", "daily-costs"]);
+        assert!(settings.analytics_errors().is_empty());
+    }
+
+    #[test]
+    fn test_sendgrid_limits() {
+        let categories: Vec<String> = (0..11).map(|i| format!("c{}", i)).collect();
+        let mut settings = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailCustomArgs": {"Environment": "staging", "RunId": 42, "Tags": ["a"]}}"#,
+        )
+        .unwrap();
+        settings.email_categories = Some(format!("{}, {}", categories.join(","), "x".repeat(256)));
+
+        assert_eq!(
+            settings.analytics_errors(),
+            [
+                FieldError::new(
+                    "EmailCategories",
+                    "has 12 categories, but SendGrid accepts at most 10"
+                ),
+                FieldError::new(
+                    "EmailCategories",
+                    "entry 12 is 256 bytes, but SendGrid accepts at most 255"
+                ),
+                FieldError::new("EmailCustomArgs", "'RunId' must be a string, not a number"),
+                FieldError::new("EmailCustomArgs", "'Tags' must be a string, not an array"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use crate::{FieldError, Settings, SettingsSource};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::Location;
+use std::sync::{Arc, RwLock};
+
+static SINK: RwLock<Option<Arc<dyn AuditSink>>> = RwLock::new(None);
+
+thread_local! {
+    // Set while a sink runs, so that a sink reading a secret doesn't recurse
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Something that happened to the settings, as reported to the [`AuditSink`].
+/// No event carries a secret value: secrets are only ever named.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SettingsEvent {
+    /// Settings were loaded by [`Settings::get_settings`],
+    /// [`Settings::get_settings_from_var`], [`Settings::get_settings_from_file`]
+    /// or a [`crate::SettingsLoader`].
+    Loaded {
+        /// The sources that set at least one field, in the order they were read.
+        sources: Vec<SettingsSource>,
+        /// The source of each field, as in [`crate::LoadedSettings::provenance`].
+        /// Only a [`crate::SettingsLoader`] tracks this; it is empty otherwise.
+        provenance: BTreeMap<String, SettingsSource>,
+        /// The [`Settings::warnings`] of the loaded settings.
+        warnings: Vec<FieldError>,
+    },
+    /// A [`crate::SettingsHandle`] swapped in new settings.
+    Reloaded {
+        /// The fields that differ from the previous snapshot, as in
+        /// [`Settings::changed_fields`].
+        changed: Vec<&'static str>,
+        /// The [`Settings::fingerprint`] of the new snapshot.
+        fingerprint: String,
+    },
+    /// A blob was not strict JSON but could be read as JSON5, see
+    /// [`Settings::from_json5`]. The `Loaded` event that follows names the
+    /// source.
+    #[cfg(feature = "json5")]
+    ParsedAsJson5 {
+        /// Why strict JSON parsing failed, e.g. a trailing comma.
+        reason: String,
+    },
+    /// A reload by [`crate::SettingsHandle::spawn_refresh`] failed, and the
+    /// current snapshot was kept.
+    #[cfg(feature = "refresh")]
+    RefreshFailed {
+        /// The error, as [`crate::SettingsError`] displays it.
+        error: String,
+    },
+    /// A secret was read with [`crate::Secret::expose`].
+    SecretExposed {
+        /// The code that read it.
+        location: &'static Location<'static>,
+    },
+}
+
+impl fmt::Display for SettingsEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsEvent::Loaded {
+                sources,
+                provenance,
+                warnings,
+            } => {
+                let sources: Vec<String> = sources.iter().map(ToString::to_string).collect();
+                write!(f, "Settings loaded from {}", sources.join(", "))?;
+                if !provenance.is_empty() {
+                    let fields: Vec<String> = provenance
+                        .iter()
+                        .map(|(field, source)| format!("{} from {}", field, source))
+                        .collect();
+                    write!(f, "; {}", fields.join(", "))?;
+                }
+                if !warnings.is_empty() {
+                    let warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+                    write!(f, "; warnings: {}", warnings.join(", "))?;
+                }
+                Ok(())
+            }
+            SettingsEvent::Reloaded {
+                changed,
+                fingerprint,
+            } => {
+                write!(f, "Settings reloaded (fingerprint {}); ", fingerprint)?;
+                if changed.is_empty() {
+                    write!(f, "nothing changed")
+                } else {
+                    write!(f, "changed {}", changed.join(", "))
+                }
+            }
+            #[cfg(feature = "json5")]
+            SettingsEvent::ParsedAsJson5 { reason } => write!(
+                f,
+                "Settings blob is not strict JSON ({}); read it as JSON5",
+                reason
+            ),
+            #[cfg(feature = "refresh")]
+            SettingsEvent::RefreshFailed { error } => {
+                write!(
+                    f,
+                    "Settings refresh failed, keeping the current settings: {}",
+                    error
+                )
+            }
+            SettingsEvent::SecretExposed { location } => write!(f, "Secret read at {}", location),
+        }
+    }
+}
+
+/// Receives a [`SettingsEvent`] for every load, reload and secret read;
+/// install one with [`Settings::set_audit_sink`].
+///
+/// `record` runs on the thread that caused the event, so it should be quick.
+/// Secrets it reads itself are not reported.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, event: SettingsEvent);
+}
+
+/// The sink used until [`Settings::set_audit_sink`] installs another one. It
+/// writes each event as one line, through `tracing` with the `tracing`
+/// feature, or else through `log` with the `log` feature, under the
+/// `reportsettings::audit` target. Secret reads are logged at debug level,
+/// loads with warnings, blobs read as JSON5 and failed refreshes at warn
+/// level, and everything else at info level. Without either feature it drops the events.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogAuditSink;
+
+impl AuditSink for LogAuditSink {
+    // Without a logging feature there is nowhere to write to
+    #[allow(unused_variables)]
+    fn record(&self, event: SettingsEvent) {
+        let level = match &event {
+            SettingsEvent::SecretExposed { .. } => crate::LogLevel::Debug,
+            SettingsEvent::Loaded { warnings, .. } if !warnings.is_empty() => {
+                crate::LogLevel::Warning
+            }
+            #[cfg(feature = "json5")]
+            SettingsEvent::ParsedAsJson5 { .. } => crate::LogLevel::Warning,
+            #[cfg(feature = "refresh")]
+            SettingsEvent::RefreshFailed { .. } => crate::LogLevel::Warning,
+            _ => crate::LogLevel::Info,
+        };
+
+        #[cfg(feature = "tracing")]
+        match level {
+            crate::LogLevel::Debug => tracing::debug!(target: "reportsettings::audit", "{}", event),
+            crate::LogLevel::Warning => {
+                tracing::warn!(target: "reportsettings::audit", "{}", event)
+            }
+            _ => tracing::info!(target: "reportsettings::audit", "{}", event),
+        }
+
+        #[cfg(all(feature = "log", not(feature = "tracing")))]
+        {
+            let level = match level {
+                crate::LogLevel::Debug => log::Level::Debug,
+                crate::LogLevel::Warning => log::Level::Warn,
+                _ => log::Level::Info,
+            };
+            log::log!(target: "reportsettings::audit", level, "{}", event);
+        }
+    }
+}
+
+impl Settings {
+    /// Sends every later [`SettingsEvent`], from any thread, to `sink` instead
+    /// of the one installed before, which is [`LogAuditSink`] to begin with.
+    pub fn set_audit_sink(sink: impl AuditSink + 'static) {
+        *SINK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(sink));
+    }
+
+    // The `Loaded` event for these settings
+    pub(crate) fn record_load(
+        &self,
+        sources: Vec<SettingsSource>,
+        provenance: BTreeMap<String, SettingsSource>,
+    ) {
+        record(|| SettingsEvent::Loaded {
+            sources,
+            provenance,
+            warnings: self.warnings(),
+        });
+    }
+}
+
+// Builds the event only when it will be recorded
+pub(crate) fn record(event: impl FnOnce() -> SettingsEvent) {
+    if RECORDING.with(Cell::get) {
+        return;
+    }
+    RECORDING.with(|recording| recording.set(true));
+    let event = event();
+    // Cloned out so that a sink can install another sink without deadlocking
+    let sink = SINK.read().unwrap_or_else(|e| e.into_inner()).clone();
+    match sink {
+        Some(sink) => sink.record(event),
+        None => LogAuditSink.record(event),
+    }
+    RECORDING.with(|recording| recording.set(false));
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    #[cfg(feature = "reload")]
+    use crate::SettingsHandle;
+    use crate::{Secret, SettingsLoader};
+    use std::io::Write;
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    static RECORDED: Mutex<Vec<(ThreadId, SettingsEvent)>> = Mutex::new(Vec::new());
+
+    // Every test installs this one sink, so that tests running at the same
+    // time don't replace each other's
+    pub(crate) struct Recorder;
+
+    impl AuditSink for Recorder {
+        fn record(&self, event: SettingsEvent) {
+            RECORDED
+                .lock()
+                .unwrap()
+                .push((thread::current().id(), event));
+        }
+    }
+
+    // Tests run in parallel, so each only looks at the events of its thread
+    pub(crate) fn recorded_here() -> Vec<SettingsEvent> {
+        let here = thread::current().id();
+        RECORDED
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(thread, _)| *thread == here)
+            .map(|(_, event)| event.clone())
+            .collect()
+    }
+
+    const SECRETS: [&str; 4] = [
+        "blob-password",
+        "rotated-password",
+        "SG.blob-api-key",
+        "Password=conn-password",
+    ];
+
+    #[test]
+    fn test_events_never_carry_secrets() {
+        Settings::set_audit_sink(Recorder);
+        let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        write!(
+            file,
+            r#"{{
+                "DatabaseServer": "sql01",
+                "DatabaseName": "reports",
+                "DatabaseUsername": "svc_report",
+                "DatabasePassword": "blob-password",
+                "DatabaseConnectionString": "Server=sql01;Password=conn-password",
+                "SendgridApiKey": "SG.blob-api-key",
+                "HttpProxyUri": "http://proxy.example.com:3128",
+                "HttpProxyUsername": "svc_proxy",
+                "HttpProxyPassword": "blob-password"
+            }}"#
+        )
+        .unwrap();
+        let path = file.path().to_path_buf();
+        let loader = SettingsLoader::new().from_file(&path);
+
+        let loaded = loader.load().unwrap();
+        let secret = Secret::new("rotated-password");
+        #[cfg(feature = "reload")]
+        let handle = {
+            let passwords = Mutex::new(vec!["blob-password", "rotated-password"]);
+            let handle = SettingsHandle::with_loader(move || {
+                let password = passwords.lock().unwrap().remove(0);
+                Ok(Settings::for_tests().with_database_password(password))
+            })
+            .unwrap();
+            handle.reload().unwrap();
+            let _ = format!("{:?}", handle);
+            handle
+        };
+        assert_eq!(secret.expose(), "rotated-password");
+        let _ = format!("{:?}", loaded);
+
+        let events = recorded_here();
+        let Some(SettingsEvent::Loaded {
+            sources,
+            provenance,
+            warnings,
+        }) = events.first()
+        else {
+            panic!("no load event in {:?}", events);
+        };
+        assert_eq!(sources, &[SettingsSource::File(path.clone())]);
+        assert_eq!(
+            provenance.get("DatabasePassword"),
+            Some(&SettingsSource::File(path))
+        );
+        assert_eq!(
+            warnings,
+            &[
+                FieldError::new(
+                    "DatabaseServer",
+                    "overrides the value in DatabaseConnectionString"
+                ),
+                FieldError::new(
+                    "DatabasePassword",
+                    "overrides the value in DatabaseConnectionString"
+                ),
+            ]
+        );
+        #[cfg(feature = "reload")]
+        assert!(events.contains(&SettingsEvent::Reloaded {
+            changed: vec!["DatabasePassword"],
+            fingerprint: handle.current().fingerprint(),
+        }));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            SettingsEvent::SecretExposed { location } if location.file() == file!()
+        )));
+        for event in &events {
+            let shown = format!("{} {:?}", event, event);
+            for secret in SECRETS {
+                assert!(!shown.contains(secret), "{} in {}", secret, shown);
+            }
+        }
+    }
+
+    #[test]
+    fn test_event_lines() {
+        let loaded = SettingsEvent::Loaded {
+            sources: vec![
+                SettingsSource::EnvVar("SecretBlob".to_string()),
+                SettingsSource::FieldOverride("REPORTSETTINGS_DATABASE_NAME".to_string()),
+            ],
+            provenance: BTreeMap::from([
+                (
+                    "DatabaseName".to_string(),
+                    SettingsSource::FieldOverride("REPORTSETTINGS_DATABASE_NAME".to_string()),
+                ),
+                (
+                    "DatabaseServer".to_string(),
+                    SettingsSource::EnvVar("SecretBlob".to_string()),
+                ),
+            ]),
+            warnings: vec![FieldError::new(
+                "EmailToAddresses",
+                "has 120 recipients, over EmailRecipientSoftLimit (100)",
+            )],
+        };
+
+        assert_eq!(
+            loaded.to_string(),
+            "Settings loaded from env var SecretBlob, override REPORTSETTINGS_DATABASE_NAME; \
+             DatabaseName from override REPORTSETTINGS_DATABASE_NAME, DatabaseServer from env var \
+             SecretBlob; warnings: EmailToAddresses: has 120 recipients, over \
+             EmailRecipientSoftLimit (100)"
+        );
+        assert_eq!(
+            SettingsEvent::Reloaded {
+                changed: vec![],
+                fingerprint: "3f9a1c0b7d2e".to_string(),
+            }
+            .to_string(),
+            "Settings reloaded (fingerprint 3f9a1c0b7d2e); nothing changed"
+        );
+        assert_eq!(
+            SettingsEvent::Reloaded {
+                changed: vec!["DatabasePassword", "EmailToAddresses"],
+                fingerprint: "3f9a1c0b7d2e".to_string(),
+            }
+            .to_string(),
+            "Settings reloaded (fingerprint 3f9a1c0b7d2e); changed DatabasePassword, EmailToAddresses"
+        );
+    }
+}
@@ -0,0 +1,642 @@
+use crate::{
+    DatabaseAuthMethod, DatabaseEncryption, DatabaseTarget, FieldError, LogLevel, LogWebhookFormat,
+    Secret, Settings, SettingsError,
+};
+use std::collections::BTreeMap;
+
+/// Builds a [`Settings`] value in code, without going through a JSON blob.
+///
+/// The database fields (except the username and password under integrated auth,
+/// and all of them given a connection string), `LogWebhookUri`, `SendgridApiKey` and `EmailFromAddress`
+/// are required; `EmailFromName` and the recipient fields default to empty, and
+/// `DatabasePort` to unset, and encryption to `Off`.
+#[derive(Debug, Default, Clone)]
+pub struct SettingsBuilder {
+    database_server: Option<String>,
+    database_name: Option<String>,
+    database_username: Option<String>,
+    database_password: Option<Secret>,
+    database_port: Option<u16>,
+    database_failover_server: Option<String>,
+    database_encryption: Option<DatabaseEncryption>,
+    database_trust_cert: Option<bool>,
+    database_ca_cert_path: Option<String>,
+    database_auth_method: Option<DatabaseAuthMethod>,
+    database_connection_string: Option<Secret>,
+    database_read_only_intent: Option<bool>,
+    databases: BTreeMap<String, DatabaseTarget>,
+    application_name: Option<String>,
+    database_connect_timeout_seconds: Option<u64>,
+    database_command_timeout_seconds: Option<u64>,
+    database_pool_max_size: Option<u32>,
+    database_pool_timeout_seconds: Option<u64>,
+    database_pool_test_on_checkout: Option<bool>,
+    log_webhook_uri: Option<String>,
+    log_webhook_auth_header: Option<String>,
+    log_webhook_auth_value: Option<Secret>,
+    log_webhook_timeout_seconds: Option<u64>,
+    log_webhook_max_retries: Option<u32>,
+    log_webhook_batch_size: Option<u32>,
+    log_webhook_format: Option<LogWebhookFormat>,
+    log_webhook_min_level: Option<LogLevel>,
+    log_webhook_module_allow_list: Option<String>,
+    sendgrid_api_key: Option<Secret>,
+    email_from_name: Option<String>,
+    email_from_address: Option<String>,
+    email_to_addresses: Option<String>,
+    email_cc_addresses: Option<String>,
+    email_bcc_addresses: Option<String>,
+    email_reply_to_address: Option<String>,
+    email_reply_to_name: Option<String>,
+    email_max_retries: Option<u32>,
+    email_retry_base_ms: Option<u64>,
+    email_max_per_hour: Option<u32>,
+    email_allow_empty_recipients: Option<bool>,
+    email_recipient_soft_limit: Option<u32>,
+    sendgrid_template_id: Option<String>,
+    email_sandbox_mode: Option<bool>,
+    email_categories: Option<String>,
+    email_custom_args: Option<BTreeMap<String, serde_json::Value>>,
+    email_asm_group_id: Option<u32>,
+    email_asm_groups_to_display: Option<String>,
+    sendgrid_api_host: Option<String>,
+    smtp_host: Option<String>,
+    smtp_port: Option<u16>,
+    smtp_username: Option<String>,
+    smtp_password: Option<Secret>,
+    smtp_use_start_tls: Option<bool>,
+    http_proxy_uri: Option<String>,
+    http_proxy_username: Option<String>,
+    http_proxy_password: Option<Secret>,
+    http_connect_timeout_seconds: Option<u64>,
+    http_request_timeout_seconds: Option<u64>,
+    schema_version: Option<u32>,
+}
+
+impl SettingsBuilder {
+    pub fn new() -> SettingsBuilder {
+        SettingsBuilder::default()
+    }
+
+    pub fn database_server(mut self, database_server: impl Into<String>) -> SettingsBuilder {
+        self.database_server = Some(database_server.into());
+        self
+    }
+
+    pub fn database_name(mut self, database_name: impl Into<String>) -> SettingsBuilder {
+        self.database_name = Some(database_name.into());
+        self
+    }
+
+    pub fn database_username(mut self, database_username: impl Into<String>) -> SettingsBuilder {
+        self.database_username = Some(database_username.into());
+        self
+    }
+
+    pub fn database_password(mut self, database_password: impl Into<String>) -> SettingsBuilder {
+        self.database_password = Some(Secret::new(database_password));
+        self
+    }
+
+    pub fn database_port(mut self, database_port: u16) -> SettingsBuilder {
+        self.database_port = Some(database_port);
+        self
+    }
+
+    pub fn database_failover_server(mut self, server: impl Into<String>) -> SettingsBuilder {
+        self.database_failover_server = Some(server.into());
+        self
+    }
+
+    pub fn database_encryption(
+        mut self,
+        database_encryption: DatabaseEncryption,
+    ) -> SettingsBuilder {
+        self.database_encryption = Some(database_encryption);
+        self
+    }
+
+    pub fn database_trust_cert(mut self, database_trust_cert: bool) -> SettingsBuilder {
+        self.database_trust_cert = Some(database_trust_cert);
+        self
+    }
+
+    pub fn database_ca_cert_path(mut self, path: impl Into<String>) -> SettingsBuilder {
+        self.database_ca_cert_path = Some(path.into());
+        self
+    }
+
+    pub fn database_auth_method(
+        mut self,
+        database_auth_method: DatabaseAuthMethod,
+    ) -> SettingsBuilder {
+        self.database_auth_method = Some(database_auth_method);
+        self
+    }
+
+    pub fn database_connection_string(
+        mut self,
+        database_connection_string: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.database_connection_string = Some(Secret::new(database_connection_string));
+        self
+    }
+
+    pub fn database_read_only_intent(mut self, read_only: bool) -> SettingsBuilder {
+        self.database_read_only_intent = Some(read_only);
+        self
+    }
+
+    /// Adds an entry to the `Databases` map.
+    pub fn database(mut self, name: impl Into<String>, target: DatabaseTarget) -> SettingsBuilder {
+        self.databases.insert(name.into(), target);
+        self
+    }
+
+    pub fn application_name(mut self, application_name: impl Into<String>) -> SettingsBuilder {
+        self.application_name = Some(application_name.into());
+        self
+    }
+
+    pub fn database_connect_timeout_seconds(mut self, seconds: u64) -> SettingsBuilder {
+        self.database_connect_timeout_seconds = Some(seconds);
+        self
+    }
+
+    pub fn database_command_timeout_seconds(mut self, seconds: u64) -> SettingsBuilder {
+        self.database_command_timeout_seconds = Some(seconds);
+        self
+    }
+
+    pub fn database_pool_max_size(mut self, max_size: u32) -> SettingsBuilder {
+        self.database_pool_max_size = Some(max_size);
+        self
+    }
+
+    pub fn database_pool_timeout_seconds(mut self, seconds: u64) -> SettingsBuilder {
+        self.database_pool_timeout_seconds = Some(seconds);
+        self
+    }
+
+    pub fn database_pool_test_on_checkout(mut self, test_on_checkout: bool) -> SettingsBuilder {
+        self.database_pool_test_on_checkout = Some(test_on_checkout);
+        self
+    }
+
+    pub fn log_webhook_uri(mut self, log_webhook_uri: impl Into<String>) -> SettingsBuilder {
+        self.log_webhook_uri = Some(log_webhook_uri.into());
+        self
+    }
+
+    pub fn log_webhook_timeout_seconds(mut self, seconds: u64) -> SettingsBuilder {
+        self.log_webhook_timeout_seconds = Some(seconds);
+        self
+    }
+
+    pub fn log_webhook_auth_header(mut self, header: impl Into<String>) -> SettingsBuilder {
+        self.log_webhook_auth_header = Some(header.into());
+        self
+    }
+
+    pub fn log_webhook_auth_value(mut self, value: impl Into<String>) -> SettingsBuilder {
+        self.log_webhook_auth_value = Some(Secret::new(value.into()));
+        self
+    }
+
+    pub fn log_webhook_max_retries(mut self, retries: u32) -> SettingsBuilder {
+        self.log_webhook_max_retries = Some(retries);
+        self
+    }
+
+    pub fn log_webhook_batch_size(mut self, size: u32) -> SettingsBuilder {
+        self.log_webhook_batch_size = Some(size);
+        self
+    }
+
+    pub fn log_webhook_format(mut self, format: LogWebhookFormat) -> SettingsBuilder {
+        self.log_webhook_format = Some(format);
+        self
+    }
+
+    pub fn log_webhook_min_level(mut self, level: LogLevel) -> SettingsBuilder {
+        self.log_webhook_min_level = Some(level);
+        self
+    }
+
+    pub fn log_webhook_module_allow_list(mut self, modules: impl Into<String>) -> SettingsBuilder {
+        self.log_webhook_module_allow_list = Some(modules.into());
+        self
+    }
+
+    pub fn sendgrid_api_key(mut self, sendgrid_api_key: impl Into<String>) -> SettingsBuilder {
+        self.sendgrid_api_key = Some(Secret::new(sendgrid_api_key));
+        self
+    }
+
+    pub fn email_from_name(mut self, email_from_name: impl Into<String>) -> SettingsBuilder {
+        self.email_from_name = Some(email_from_name.into());
+        self
+    }
+
+    pub fn email_from_address(mut self, email_from_address: impl Into<String>) -> SettingsBuilder {
+        self.email_from_address = Some(email_from_address.into());
+        self
+    }
+
+    pub fn email_to_addresses(mut self, email_to_addresses: impl Into<String>) -> SettingsBuilder {
+        self.email_to_addresses = Some(email_to_addresses.into());
+        self
+    }
+
+    pub fn email_cc_addresses(mut self, email_cc_addresses: impl Into<String>) -> SettingsBuilder {
+        self.email_cc_addresses = Some(email_cc_addresses.into());
+        self
+    }
+
+    pub fn email_bcc_addresses(
+        mut self,
+        email_bcc_addresses: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.email_bcc_addresses = Some(email_bcc_addresses.into());
+        self
+    }
+
+    pub fn email_reply_to_address(
+        mut self,
+        email_reply_to_address: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.email_reply_to_address = Some(email_reply_to_address.into());
+        self
+    }
+
+    pub fn email_reply_to_name(
+        mut self,
+        email_reply_to_name: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.email_reply_to_name = Some(email_reply_to_name.into());
+        self
+    }
+
+    pub fn email_max_retries(mut self, retries: u32) -> SettingsBuilder {
+        self.email_max_retries = Some(retries);
+        self
+    }
+
+    pub fn email_retry_base_ms(mut self, milliseconds: u64) -> SettingsBuilder {
+        self.email_retry_base_ms = Some(milliseconds);
+        self
+    }
+
+    pub fn email_max_per_hour(mut self, limit: u32) -> SettingsBuilder {
+        self.email_max_per_hour = Some(limit);
+        self
+    }
+
+    pub fn email_allow_empty_recipients(mut self, allow: bool) -> SettingsBuilder {
+        self.email_allow_empty_recipients = Some(allow);
+        self
+    }
+
+    pub fn email_recipient_soft_limit(mut self, recipients: u32) -> SettingsBuilder {
+        self.email_recipient_soft_limit = Some(recipients);
+        self
+    }
+
+    pub fn sendgrid_template_id(
+        mut self,
+        sendgrid_template_id: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.sendgrid_template_id = Some(sendgrid_template_id.into());
+        self
+    }
+
+    pub fn email_sandbox_mode(mut self, sandboxed: bool) -> SettingsBuilder {
+        self.email_sandbox_mode = Some(sandboxed);
+        self
+    }
+
+    pub fn email_categories(mut self, email_categories: impl Into<String>) -> SettingsBuilder {
+        self.email_categories = Some(email_categories.into());
+        self
+    }
+
+    /// Adds `name` to `EmailCustomArgs`, or replaces its value.
+    pub fn email_custom_arg(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.email_custom_args
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name.into(), serde_json::Value::String(value.into()));
+        self
+    }
+
+    pub fn email_asm_group_id(mut self, group_id: u32) -> SettingsBuilder {
+        self.email_asm_group_id = Some(group_id);
+        self
+    }
+
+    pub fn email_asm_groups_to_display(
+        mut self,
+        email_asm_groups_to_display: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.email_asm_groups_to_display = Some(email_asm_groups_to_display.into());
+        self
+    }
+
+    pub fn sendgrid_api_host(mut self, sendgrid_api_host: impl Into<String>) -> SettingsBuilder {
+        self.sendgrid_api_host = Some(sendgrid_api_host.into());
+        self
+    }
+
+    pub fn smtp_host(mut self, smtp_host: impl Into<String>) -> SettingsBuilder {
+        self.smtp_host = Some(smtp_host.into());
+        self
+    }
+
+    pub fn smtp_port(mut self, smtp_port: u16) -> SettingsBuilder {
+        self.smtp_port = Some(smtp_port);
+        self
+    }
+
+    pub fn smtp_username(mut self, smtp_username: impl Into<String>) -> SettingsBuilder {
+        self.smtp_username = Some(smtp_username.into());
+        self
+    }
+
+    pub fn smtp_password(mut self, smtp_password: impl Into<String>) -> SettingsBuilder {
+        self.smtp_password = Some(Secret::new(smtp_password.into()));
+        self
+    }
+
+    pub fn smtp_use_start_tls(mut self, use_start_tls: bool) -> SettingsBuilder {
+        self.smtp_use_start_tls = Some(use_start_tls);
+        self
+    }
+
+    pub fn http_proxy_uri(mut self, http_proxy_uri: impl Into<String>) -> SettingsBuilder {
+        self.http_proxy_uri = Some(http_proxy_uri.into());
+        self
+    }
+
+    pub fn http_proxy_username(
+        mut self,
+        http_proxy_username: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.http_proxy_username = Some(http_proxy_username.into());
+        self
+    }
+
+    pub fn http_proxy_password(
+        mut self,
+        http_proxy_password: impl Into<String>,
+    ) -> SettingsBuilder {
+        self.http_proxy_password = Some(Secret::new(http_proxy_password.into()));
+        self
+    }
+
+    pub fn http_connect_timeout_seconds(mut self, seconds: u64) -> SettingsBuilder {
+        self.http_connect_timeout_seconds = Some(seconds);
+        self
+    }
+
+    pub fn http_request_timeout_seconds(mut self, seconds: u64) -> SettingsBuilder {
+        self.http_request_timeout_seconds = Some(seconds);
+        self
+    }
+
+    pub fn schema_version(mut self, version: u32) -> SettingsBuilder {
+        self.schema_version = Some(version);
+        self
+    }
+
+    /// Assembles the settings, reporting every required field that was not set.
+    pub fn build(self) -> Result<Settings, SettingsError> {
+        let mut missing = Vec::new();
+        let fields = self.database_connection_string.is_none();
+        let sql_login = fields
+            && self.database_auth_method.unwrap_or_default() == DatabaseAuthMethod::SqlServer;
+
+        let settings = Settings {
+            database_server: match self.database_server {
+                None if fields => required(&mut missing, None, "DatabaseServer"),
+                server => server.unwrap_or_default(),
+            },
+            database_name: match self.database_name {
+                None if fields => required(&mut missing, None, "DatabaseName"),
+                name => name.unwrap_or_default(),
+            },
+            database_username: match self.database_username {
+                None if sql_login => required(&mut missing, None, "DatabaseUsername"),
+                username => username.unwrap_or_default(),
+            },
+            database_password: match self.database_password {
+                None if sql_login => required(&mut missing, None, "DatabasePassword"),
+                password => password.unwrap_or_default(),
+            },
+            database_port: self.database_port,
+            database_failover_server: self.database_failover_server,
+            database_encryption: self.database_encryption,
+            database_trust_cert: self.database_trust_cert,
+            database_ca_cert_path: self.database_ca_cert_path,
+            database_auth_method: self.database_auth_method,
+            database_connection_string: self.database_connection_string,
+            database_read_only_intent: self.database_read_only_intent,
+            databases: self.databases,
+            application_name: self.application_name,
+            database_connect_timeout_seconds: self.database_connect_timeout_seconds,
+            database_command_timeout_seconds: self.database_command_timeout_seconds,
+            database_pool_max_size: self.database_pool_max_size,
+            database_pool_timeout_seconds: self.database_pool_timeout_seconds,
+            database_pool_test_on_checkout: self.database_pool_test_on_checkout,
+            log_webhook_uri: self.log_webhook_uri,
+            log_webhook_auth_header: self.log_webhook_auth_header,
+            log_webhook_auth_value: self.log_webhook_auth_value,
+            log_webhook_timeout_seconds: self.log_webhook_timeout_seconds,
+            log_webhook_max_retries: self.log_webhook_max_retries,
+            log_webhook_batch_size: self.log_webhook_batch_size,
+            log_webhook_format: self.log_webhook_format,
+            log_webhook_min_level: self.log_webhook_min_level,
+            log_webhook_module_allow_list: self.log_webhook_module_allow_list,
+            sendgrid_api_key: self.sendgrid_api_key,
+            email_from_name: self.email_from_name,
+            email_from_address: self.email_from_address,
+            email_to_addresses: self.email_to_addresses,
+            email_cc_addresses: self.email_cc_addresses,
+            email_bcc_addresses: self.email_bcc_addresses,
+            email_reply_to_address: self.email_reply_to_address,
+            email_reply_to_name: self.email_reply_to_name,
+            email_max_retries: self.email_max_retries,
+            email_retry_base_ms: self.email_retry_base_ms,
+            email_max_per_hour: self.email_max_per_hour,
+            email_allow_empty_recipients: self.email_allow_empty_recipients,
+            email_recipient_soft_limit: self.email_recipient_soft_limit,
+            sendgrid_template_id: self.sendgrid_template_id,
+            email_sandbox_mode: self.email_sandbox_mode,
+            email_categories: self.email_categories,
+            email_custom_args: self.email_custom_args,
+            email_asm_group_id: self.email_asm_group_id,
+            email_asm_groups_to_display: self.email_asm_groups_to_display,
+            sendgrid_api_host: self.sendgrid_api_host,
+            smtp_host: self.smtp_host,
+            smtp_port: self.smtp_port,
+            smtp_username: self.smtp_username,
+            smtp_password: self.smtp_password,
+            smtp_use_start_tls: self.smtp_use_start_tls,
+            http_proxy_uri: self.http_proxy_uri,
+            http_proxy_username: self.http_proxy_username,
+            http_proxy_password: self.http_proxy_password,
+            http_connect_timeout_seconds: self.http_connect_timeout_seconds,
+            http_request_timeout_seconds: self.http_request_timeout_seconds,
+            schema_version: self.schema_version,
+            #[cfg(feature = "remote")]
+            http_client: Default::default(),
+            #[cfg(feature = "sendgrid")]
+            email_rate_limiter: Default::default(),
+            absent_fields: Vec::new(),
+        };
+
+        if !missing.is_empty() {
+            return Err(SettingsError::Validation(missing));
+        }
+
+        Ok(settings)
+    }
+
+    // Required fields the settings have are taken as set, even when empty, so
+    // building again gives back the same settings
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn from_settings(settings: Settings) -> SettingsBuilder {
+        SettingsBuilder {
+            database_server: Some(settings.database_server),
+            database_name: Some(settings.database_name),
+            database_username: Some(settings.database_username),
+            database_password: Some(settings.database_password),
+            database_port: settings.database_port,
+            database_failover_server: settings.database_failover_server,
+            database_encryption: settings.database_encryption,
+            database_trust_cert: settings.database_trust_cert,
+            database_ca_cert_path: settings.database_ca_cert_path,
+            database_auth_method: settings.database_auth_method,
+            database_connection_string: settings.database_connection_string,
+            database_read_only_intent: settings.database_read_only_intent,
+            databases: settings.databases,
+            application_name: settings.application_name,
+            database_connect_timeout_seconds: settings.database_connect_timeout_seconds,
+            database_command_timeout_seconds: settings.database_command_timeout_seconds,
+            database_pool_max_size: settings.database_pool_max_size,
+            database_pool_timeout_seconds: settings.database_pool_timeout_seconds,
+            database_pool_test_on_checkout: settings.database_pool_test_on_checkout,
+            log_webhook_uri: settings.log_webhook_uri,
+            log_webhook_auth_header: settings.log_webhook_auth_header,
+            log_webhook_auth_value: settings.log_webhook_auth_value,
+            log_webhook_timeout_seconds: settings.log_webhook_timeout_seconds,
+            log_webhook_max_retries: settings.log_webhook_max_retries,
+            log_webhook_batch_size: settings.log_webhook_batch_size,
+            log_webhook_format: settings.log_webhook_format,
+            log_webhook_min_level: settings.log_webhook_min_level,
+            log_webhook_module_allow_list: settings.log_webhook_module_allow_list,
+            sendgrid_api_key: settings.sendgrid_api_key,
+            email_from_name: settings.email_from_name,
+            email_from_address: settings.email_from_address,
+            email_to_addresses: settings.email_to_addresses,
+            email_cc_addresses: settings.email_cc_addresses,
+            email_bcc_addresses: settings.email_bcc_addresses,
+            email_reply_to_address: settings.email_reply_to_address,
+            email_reply_to_name: settings.email_reply_to_name,
+            email_max_retries: settings.email_max_retries,
+            email_retry_base_ms: settings.email_retry_base_ms,
+            email_max_per_hour: settings.email_max_per_hour,
+            email_allow_empty_recipients: settings.email_allow_empty_recipients,
+            email_recipient_soft_limit: settings.email_recipient_soft_limit,
+            sendgrid_template_id: settings.sendgrid_template_id,
+            email_sandbox_mode: settings.email_sandbox_mode,
+            email_categories: settings.email_categories,
+            email_custom_args: settings.email_custom_args,
+            email_asm_group_id: settings.email_asm_group_id,
+            email_asm_groups_to_display: settings.email_asm_groups_to_display,
+            sendgrid_api_host: settings.sendgrid_api_host,
+            smtp_host: settings.smtp_host,
+            smtp_port: settings.smtp_port,
+            smtp_username: settings.smtp_username,
+            smtp_password: settings.smtp_password,
+            smtp_use_start_tls: settings.smtp_use_start_tls,
+            http_proxy_uri: settings.http_proxy_uri,
+            http_proxy_username: settings.http_proxy_username,
+            http_proxy_password: settings.http_proxy_password,
+            http_connect_timeout_seconds: settings.http_connect_timeout_seconds,
+            http_request_timeout_seconds: settings.http_request_timeout_seconds,
+            schema_version: settings.schema_version,
+        }
+    }
+}
+
+fn required<T: Default>(missing: &mut Vec<FieldError>, value: Option<T>, field: &'static str) -> T {
+    value.unwrap_or_else(|| {
+        missing.push(FieldError::new(field, "is required"));
+        T::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_with_all_fields() {
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_name("Test")
+            .email_from_address("test@example.com")
+            .email_to_addresses("user1@example.com")
+            .build()
+            .unwrap();
+
+        assert_eq!(settings.database_server(), "localhost");
+        assert_eq!(settings.email_to_addresses(), Some("user1@example.com"));
+        #[cfg(feature = "mssql")]
+        assert_eq!(settings.get_sql_settings().get_addr(), "localhost:1433");
+    }
+
+    #[test]
+    fn test_build_applies_defaults() {
+        let settings = SettingsBuilder::new()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+
+        assert_eq!(settings.email_from_name(), None);
+        assert_eq!(settings.email_to_addresses(), None);
+    }
+
+    #[test]
+    fn test_build_reports_missing_fields() {
+        let err = SettingsBuilder::new()
+            .database_server("localhost")
+            .build()
+            .unwrap_err();
+
+        match err {
+            SettingsError::Validation(errors) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field).collect();
+                assert_eq!(
+                    fields,
+                    vec!["DatabaseName", "DatabaseUsername", "DatabasePassword",]
+                );
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{Settings, SettingsError};
+use std::sync::{Mutex, OnceLock};
+
+static CACHED: OnceLock<Settings> = OnceLock::new();
+
+static GLOBAL: Global = Global {
+    cell: &CACHED,
+    init_error: Mutex::new(None),
+};
+
+/// The settings of the binary, from [`Settings::get_cached`]. Call
+/// [`try_init`] at startup so that a bad blob stops the binary there, with
+/// the full error; without it the first call here loads the settings.
+///
+/// # Panics
+///
+/// If [`try_init`] failed and no later call succeeded, with the message of
+/// that error, or else if loading the settings here fails.
+pub fn settings() -> &'static Settings {
+    GLOBAL.get(Settings::get_settings)
+}
+
+/// Loads the settings behind [`settings`] now, returning the error if that
+/// fails. The error is kept for [`settings`] to panic with rather than
+/// loading again, so that every later access reports the startup failure.
+/// Once loaded, calling it again returns the same settings.
+pub fn try_init() -> Result<&'static Settings, SettingsError> {
+    GLOBAL.try_init(Settings::get_settings)
+}
+
+/// Makes `settings` those of [`settings`] in place of the env variables, for
+/// tests of code that reads them. Calling it again with equal settings is
+/// fine, so every test can set the same fixture.
+///
+/// # Panics
+///
+/// If the global settings were already loaded or set and differ.
+#[cfg(any(test, feature = "test-util"))]
+pub fn init_for_tests(settings: Settings) -> &'static Settings {
+    GLOBAL.set(settings)
+}
+
+impl Settings {
+    /// Settings from [`Settings::get_settings`], loaded on the first call and
+    /// shared by every call after that.
+    ///
+    /// The cache never observes later changes to `SecretBlob` or the
+    /// `REPORTSETTINGS_` overrides; use a [`crate::SettingsHandle`] for settings
+    /// that can be reloaded. A failed load is not cached: the error is
+    /// returned and the next call tries again.
+    pub fn get_cached() -> Result<&'static Settings, SettingsError> {
+        Settings::get_cached_or_init(Settings::get_settings)
+    }
+
+    /// Like [`Settings::get_cached`], but loads with `init` instead of
+    /// [`Settings::get_settings`]. `init` only runs while nothing is cached; if
+    /// several threads load at once, the first to finish wins.
+    pub fn get_cached_or_init<F>(init: F) -> Result<&'static Settings, SettingsError>
+    where
+        F: FnOnce() -> Result<Settings, SettingsError>,
+    {
+        cached_in(&CACHED, init)
+    }
+}
+
+fn cached_in<F>(
+    cell: &'static OnceLock<Settings>,
+    init: F,
+) -> Result<&'static Settings, SettingsError>
+where
+    F: FnOnce() -> Result<Settings, SettingsError>,
+{
+    if let Some(settings) = cell.get() {
+        return Ok(settings);
+    }
+    // OnceLock has no stable fallible init, so load outside it and leave the
+    // cell empty on failure
+    let settings = init()?;
+    Ok(cell.get_or_init(|| settings))
+}
+
+// The cache behind `settings`, with the error of a failed `try_init`
+struct Global {
+    cell: &'static OnceLock<Settings>,
+    init_error: Mutex<Option<String>>,
+}
+
+impl Global {
+    fn try_init<F>(&self, init: F) -> Result<&'static Settings, SettingsError>
+    where
+        F: FnOnce() -> Result<Settings, SettingsError>,
+    {
+        let result = cached_in(self.cell, init);
+        *self.init_error.lock().unwrap_or_else(|e| e.into_inner()) =
+            result.as_ref().err().map(ToString::to_string);
+        result
+    }
+
+    fn get<F>(&self, init: F) -> &'static Settings
+    where
+        F: FnOnce() -> Result<Settings, SettingsError>,
+    {
+        if let Some(settings) = self.cell.get() {
+            return settings;
+        }
+        let init_error = self
+            .init_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(error) = init_error {
+            panic!("Settings failed to load at startup: {}", error);
+        }
+        match cached_in(self.cell, init) {
+            Ok(settings) => settings,
+            Err(error) => panic!("Could not load settings: {}", error),
+        }
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    fn set(&self, settings: Settings) -> &'static Settings {
+        let installed = self.cell.get_or_init(|| settings.clone());
+        assert!(
+            *installed == settings,
+            "The global settings are already set to different settings"
+        );
+        installed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(database_name: &str) -> Settings {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name(database_name)
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap()
+    }
+
+    fn missing() -> Result<Settings, SettingsError> {
+        Err(SettingsError::MissingEnvVar {
+            name: "SecretBlob".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_failure_is_reported_until_a_load_succeeds() {
+        static CELL: OnceLock<Settings> = OnceLock::new();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                cached_in(&CELL, missing),
+                Err(SettingsError::MissingEnvVar { .. })
+            ));
+        }
+
+        let first = cached_in(&CELL, || Ok(settings("first_db"))).unwrap();
+        assert_eq!(first.database_name(), "first_db");
+
+        let again = cached_in(&CELL, || panic!("loaded twice")).unwrap();
+        assert!(std::ptr::eq(first, again));
+    }
+
+    fn global(cell: &'static OnceLock<Settings>) -> Global {
+        Global {
+            cell,
+            init_error: Mutex::new(None),
+        }
+    }
+
+    #[test]
+    fn test_failed_init_is_reported_by_every_access() {
+        static CELL: OnceLock<Settings> = OnceLock::new();
+        let global = global(&CELL);
+
+        assert!(global.try_init(missing).is_err());
+        let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            global.get(|| Ok(settings("late_db")));
+        }))
+        .unwrap_err();
+
+        assert_eq!(
+            panic.downcast_ref::<String>().unwrap(),
+            "Settings failed to load at startup: Error getting env variable SecretBlob: \
+             environment variable not found"
+        );
+        let loaded = global.try_init(|| Ok(settings("retried_db"))).unwrap();
+        assert!(std::ptr::eq(global.get(missing), loaded));
+    }
+
+    #[test]
+    fn test_lazy_access_without_init() {
+        static CELL: OnceLock<Settings> = OnceLock::new();
+        let global = global(&CELL);
+
+        assert_eq!(
+            global.get(|| Ok(settings("lazy_db"))).database_name(),
+            "lazy_db"
+        );
+        assert_eq!(global.get(missing).database_name(), "lazy_db");
+    }
+
+    #[test]
+    fn test_fixture_replaces_the_loader() {
+        let fixture = init_for_tests(Settings::for_tests());
+
+        assert!(std::ptr::eq(super::settings(), fixture));
+        assert!(std::ptr::eq(try_init().unwrap(), fixture));
+        assert!(std::ptr::eq(init_for_tests(Settings::for_tests()), fixture));
+        let other = std::panic::catch_unwind(|| {
+            init_for_tests(Settings::for_tests().with_database_name("other_db"));
+        });
+        assert!(other.is_err());
+    }
+}
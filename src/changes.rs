@@ -0,0 +1,303 @@
+use crate::Settings;
+
+impl Settings {
+    /// The PascalCase names of the fields whose values differ between `self`
+    /// and `other`, in blob order, e.g. to log what a reload changed. Secrets
+    /// are named like any other field, but never shown; they are compared in
+    /// constant time (see [`crate::Secret`]).
+    ///
+    /// `Databases` is named once if any of its entries differ.
+    pub fn changed_fields(&self, other: &Settings) -> Vec<&'static str> {
+        // Every field is compared, so that how long this takes doesn't depend
+        // on where the first difference is
+        let fields = [
+            (
+                "DatabaseServer",
+                self.database_server == other.database_server,
+            ),
+            ("DatabaseName", self.database_name == other.database_name),
+            (
+                "DatabaseUsername",
+                self.database_username == other.database_username,
+            ),
+            (
+                "DatabasePassword",
+                self.database_password == other.database_password,
+            ),
+            ("DatabasePort", self.database_port == other.database_port),
+            (
+                "DatabaseFailoverServer",
+                self.database_failover_server == other.database_failover_server,
+            ),
+            (
+                "DatabaseEncryption",
+                self.database_encryption == other.database_encryption,
+            ),
+            (
+                "DatabaseTrustCert",
+                self.database_trust_cert == other.database_trust_cert,
+            ),
+            (
+                "DatabaseCaCertPath",
+                self.database_ca_cert_path == other.database_ca_cert_path,
+            ),
+            (
+                "DatabaseAuthMethod",
+                self.database_auth_method == other.database_auth_method,
+            ),
+            (
+                "DatabaseConnectionString",
+                self.database_connection_string == other.database_connection_string,
+            ),
+            (
+                "DatabaseReadOnlyIntent",
+                self.database_read_only_intent == other.database_read_only_intent,
+            ),
+            ("Databases", self.databases == other.databases),
+            (
+                "ApplicationName",
+                self.application_name == other.application_name,
+            ),
+            (
+                "DatabaseConnectTimeoutSeconds",
+                self.database_connect_timeout_seconds == other.database_connect_timeout_seconds,
+            ),
+            (
+                "DatabaseCommandTimeoutSeconds",
+                self.database_command_timeout_seconds == other.database_command_timeout_seconds,
+            ),
+            (
+                "DatabasePoolMaxSize",
+                self.database_pool_max_size == other.database_pool_max_size,
+            ),
+            (
+                "DatabasePoolTimeoutSeconds",
+                self.database_pool_timeout_seconds == other.database_pool_timeout_seconds,
+            ),
+            (
+                "DatabasePoolTestOnCheckout",
+                self.database_pool_test_on_checkout == other.database_pool_test_on_checkout,
+            ),
+            (
+                "LogWebhookUri",
+                self.log_webhook_uri == other.log_webhook_uri,
+            ),
+            (
+                "LogWebhookAuthHeader",
+                self.log_webhook_auth_header == other.log_webhook_auth_header,
+            ),
+            (
+                "LogWebhookAuthValue",
+                self.log_webhook_auth_value == other.log_webhook_auth_value,
+            ),
+            (
+                "LogWebhookTimeoutSeconds",
+                self.log_webhook_timeout_seconds == other.log_webhook_timeout_seconds,
+            ),
+            (
+                "LogWebhookMaxRetries",
+                self.log_webhook_max_retries == other.log_webhook_max_retries,
+            ),
+            (
+                "LogWebhookBatchSize",
+                self.log_webhook_batch_size == other.log_webhook_batch_size,
+            ),
+            (
+                "LogWebhookFormat",
+                self.log_webhook_format == other.log_webhook_format,
+            ),
+            (
+                "LogWebhookMinLevel",
+                self.log_webhook_min_level == other.log_webhook_min_level,
+            ),
+            (
+                "LogWebhookModuleAllowList",
+                self.log_webhook_module_allow_list == other.log_webhook_module_allow_list,
+            ),
+            (
+                "SendgridApiKey",
+                self.sendgrid_api_key == other.sendgrid_api_key,
+            ),
+            (
+                "EmailFromName",
+                self.email_from_name == other.email_from_name,
+            ),
+            (
+                "EmailFromAddress",
+                self.email_from_address == other.email_from_address,
+            ),
+            (
+                "EmailToAddresses",
+                self.email_to_addresses == other.email_to_addresses,
+            ),
+            (
+                "EmailCcAddresses",
+                self.email_cc_addresses == other.email_cc_addresses,
+            ),
+            (
+                "EmailBccAddresses",
+                self.email_bcc_addresses == other.email_bcc_addresses,
+            ),
+            (
+                "EmailReplyToAddress",
+                self.email_reply_to_address == other.email_reply_to_address,
+            ),
+            (
+                "EmailReplyToName",
+                self.email_reply_to_name == other.email_reply_to_name,
+            ),
+            (
+                "EmailMaxRetries",
+                self.email_max_retries == other.email_max_retries,
+            ),
+            (
+                "EmailRetryBaseMs",
+                self.email_retry_base_ms == other.email_retry_base_ms,
+            ),
+            (
+                "EmailMaxPerHour",
+                self.email_max_per_hour == other.email_max_per_hour,
+            ),
+            (
+                "EmailAllowEmptyRecipients",
+                self.email_allow_empty_recipients == other.email_allow_empty_recipients,
+            ),
+            (
+                "EmailRecipientSoftLimit",
+                self.email_recipient_soft_limit == other.email_recipient_soft_limit,
+            ),
+            (
+                "SendgridTemplateId",
+                self.sendgrid_template_id == other.sendgrid_template_id,
+            ),
+            (
+                "EmailSandboxMode",
+                self.email_sandbox_mode == other.email_sandbox_mode,
+            ),
+            (
+                "EmailCategories",
+                self.email_categories == other.email_categories,
+            ),
+            (
+                "EmailCustomArgs",
+                self.email_custom_args == other.email_custom_args,
+            ),
+            (
+                "EmailAsmGroupId",
+                self.email_asm_group_id == other.email_asm_group_id,
+            ),
+            (
+                "EmailAsmGroupsToDisplay",
+                self.email_asm_groups_to_display == other.email_asm_groups_to_display,
+            ),
+            (
+                "SendgridApiHost",
+                self.sendgrid_api_host == other.sendgrid_api_host,
+            ),
+            ("SmtpHost", self.smtp_host == other.smtp_host),
+            ("SmtpPort", self.smtp_port == other.smtp_port),
+            ("SmtpUsername", self.smtp_username == other.smtp_username),
+            ("SmtpPassword", self.smtp_password == other.smtp_password),
+            (
+                "SmtpUseStartTls",
+                self.smtp_use_start_tls == other.smtp_use_start_tls,
+            ),
+            ("HttpProxyUri", self.http_proxy_uri == other.http_proxy_uri),
+            (
+                "HttpProxyUsername",
+                self.http_proxy_username == other.http_proxy_username,
+            ),
+            (
+                "HttpProxyPassword",
+                self.http_proxy_password == other.http_proxy_password,
+            ),
+            (
+                "HttpConnectTimeoutSeconds",
+                self.http_connect_timeout_seconds == other.http_connect_timeout_seconds,
+            ),
+            (
+                "HttpRequestTimeoutSeconds",
+                self.http_request_timeout_seconds == other.http_request_timeout_seconds,
+            ),
+            ("SchemaVersion", self.schema_version == other.schema_version),
+        ];
+
+        fields
+            .into_iter()
+            .filter(|(_, same)| !same)
+            .map(|(field, _)| field)
+            .collect()
+    }
+}
+
+/// Field by field, with the secrets compared in constant time; see
+/// [`Settings::changed_fields`].
+impl PartialEq for Settings {
+    fn eq(&self, other: &Settings) -> bool {
+        self.changed_fields(other).is_empty()
+    }
+}
+
+impl Eq for Settings {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOB: &str = r#"{
+        "DatabaseServer": "localhost",
+        "DatabaseName": "reports",
+        "DatabaseUsername": "admin",
+        "DatabasePassword": "password123",
+        "Databases": {
+            "warehouse": {
+                "Server": "wh-sql",
+                "Name": "warehouse",
+                "Username": "reader",
+                "Password": "secret"
+            }
+        },
+        "SendgridApiKey": "SG.abcdefghijklmnop.wxyz",
+        "EmailToAddresses": "user1@example.com"
+    }"#;
+
+    #[test]
+    fn test_clone_is_equal() {
+        let settings = Settings::from_json_str(BLOB).unwrap();
+
+        let copy = settings.clone();
+
+        assert_eq!(copy, settings);
+        assert!(settings.changed_fields(&copy).is_empty());
+    }
+
+    #[test]
+    fn test_names_changed_fields() {
+        let before = Settings::from_json_str(BLOB).unwrap();
+        let after = Settings::from_json_str(
+            &BLOB
+                .replace("password123", "rotated-password")
+                .replace(r#""Password": "secret""#, r#""Password": "rotated""#)
+                .replace("user1@", "user2@"),
+        )
+        .unwrap();
+
+        assert_ne!(before, after);
+        assert_eq!(
+            before.changed_fields(&after),
+            ["DatabasePassword", "Databases", "EmailToAddresses"]
+        );
+    }
+
+    #[test]
+    fn test_unset_and_set_fields_differ() {
+        let before = Settings::from_json_str(BLOB).unwrap();
+        let after = Settings::from_json_str(&BLOB.replace(
+            r#""DatabaseName": "reports","#,
+            r#""DatabaseName": "reports", "DatabasePort": 1433,"#,
+        ))
+        .unwrap();
+
+        assert_eq!(after.changed_fields(&before), ["DatabasePort"]);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::SettingsError;
+
+const BOM: char = '\u{feff}';
+
+/// Parses `blob` with `parse` after undoing what pipelines and people do to a
+/// pasted blob: a leading UTF-8 byte order mark is dropped and surrounding
+/// whitespace trimmed, and a blob wrapped in single quotes that fails to parse
+/// is tried again without them.
+///
+/// When that doesn't help, the first parse error is kept, wrapped in
+/// [`SettingsError::InvalidAfterCleanup`] if a BOM or quotes were removed, so
+/// that the message says the blob was not taken exactly as given.
+pub(crate) fn parse_cleaned<T>(
+    blob: &str,
+    parse: impl Fn(&str) -> Result<T, SettingsError>,
+) -> Result<T, SettingsError> {
+    let mut removed = Vec::new();
+    let blob = match blob.strip_prefix(BOM) {
+        Some(rest) => {
+            removed.push("a leading UTF-8 BOM");
+            rest
+        }
+        None => blob,
+    };
+    let blob = blob.trim();
+
+    let error = match parse(blob) {
+        Err(error) if is_syntax_error(&error) => error,
+        result => return result,
+    };
+    if let Some(unquoted) = unquote(blob) {
+        match parse(unquoted) {
+            Err(retry) if is_syntax_error(&retry) => removed.push("the surrounding single quotes"),
+            result => return result,
+        }
+    }
+
+    if removed.is_empty() {
+        Err(error)
+    } else {
+        Err(SettingsError::InvalidAfterCleanup {
+            removed: removed.join(" and "),
+            source: Box::new(error),
+        })
+    }
+}
+
+fn unquote(blob: &str) -> Option<&str> {
+    blob.strip_prefix('\'')?.strip_suffix('\'')
+}
+
+// Only a blob that isn't JSON, TOML or YAML at all is worth another try; one
+// that parses but has, say, a field of the wrong type would fail again
+fn is_syntax_error(error: &SettingsError) -> bool {
+    match error {
+        SettingsError::InvalidJson(e) => e.is_syntax() || e.is_eof(),
+        SettingsError::InvalidJsonOrBase64 { .. } | SettingsError::InvalidToml(_) => true,
+        #[cfg(feature = "yaml")]
+        SettingsError::InvalidYaml(_) => true,
+        #[cfg(feature = "json5")]
+        SettingsError::InvalidJson5 { .. } => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profile::Profile;
+    use crate::{Settings, SettingsError};
+
+    // Sniffing the format, as for a blob from `SecretBlob`
+    fn from_blob(blob: &str) -> Result<Settings, SettingsError> {
+        Ok(Settings::parse_blob_in_format(blob, None, Profile::FromEnv)?.0)
+    }
+
+    const BLOB: &str = r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated"}"#;
+
+    #[test]
+    fn test_bom_and_whitespace_are_dropped() {
+        for blob in [
+            format!("\u{feff}{}", BLOB),
+            format!("\u{feff}  \n{}\n", BLOB),
+            "\u{feff}DatabaseServer = \"sql01\"\nDatabaseName = \"reports\"".to_string(),
+        ] {
+            let settings = from_blob(&blob).unwrap();
+            assert_eq!(settings.database_server(), "sql01");
+            assert_eq!(settings.database_name(), "reports");
+        }
+        assert_eq!(
+            Settings::from_json_str(&format!("\u{feff}{}", BLOB))
+                .unwrap()
+                .database_server(),
+            "sql01"
+        );
+
+        let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        std::io::Write::write_all(&mut file, format!("\u{feff}{}", BLOB).as_bytes()).unwrap();
+        let (settings, _) = Settings::read_blob_file(file.path(), Profile::FromEnv).unwrap();
+        assert_eq!(settings.database_name(), "reports");
+    }
+
+    #[test]
+    fn test_single_quotes_are_unwrapped() {
+        let quoted = format!(" '{}' ", BLOB);
+
+        assert_eq!(
+            Settings::from_json_str(&quoted).unwrap().database_server(),
+            "sql01"
+        );
+        assert_eq!(
+            from_blob(&format!("\u{feff}{}", quoted))
+                .unwrap()
+                .database_name(),
+            "reports"
+        );
+    }
+
+    #[test]
+    fn test_broken_blob_keeps_its_error() {
+        let err = Settings::from_json_str("\u{feff}'{\"DatabaseServer\": }'").unwrap_err();
+
+        let SettingsError::InvalidAfterCleanup { removed, source } = &err else {
+            panic!("unexpected error: {}", err);
+        };
+        assert_eq!(
+            removed,
+            "a leading UTF-8 BOM and the surrounding single quotes"
+        );
+        assert!(matches!(**source, SettingsError::InvalidJson(_)));
+        assert_eq!(
+            err.to_string(),
+            "Could not deserialize settings blob: expected value at line 1 column 1 (also \
+             tried after removing a leading UTF-8 BOM and the surrounding single quotes)"
+        );
+        assert!(matches!(
+            Settings::from_json_str("{\"DatabaseServer\": }"),
+            Err(SettingsError::InvalidJson(_))
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{DatabaseServerRole, Settings, SettingsError};
+use ssql::prelude::tiberius::{self, Client, Config};
+use ssql::prelude::{Compat, TcpStream, TokioAsyncWriteCompatExt};
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+impl Settings {
+    /// Connects to the report database and runs `SELECT 1`, to check the
+    /// settings before starting real work. `DatabaseConnectTimeoutSeconds`
+    /// bounds the connection and login, `DatabaseCommandTimeoutSeconds` the query.
+    ///
+    /// Needs a tokio runtime with the time driver enabled.
+    pub async fn test_database_connection(&self) -> Result<(), SettingsError> {
+        let config = self.try_get_sql_settings()?;
+        let address = config.get_addr();
+        let wrap = |source| SettingsError::DatabaseConnection {
+            address: address.clone(),
+            source,
+        };
+
+        let mut client = self.open(config).await?;
+
+        let query = async {
+            client.simple_query("SELECT 1").await?.into_row().await?;
+            Ok(())
+        };
+        with_timeout(self.command_timeout(), &address, query)
+            .await?
+            .map_err(wrap)
+    }
+
+    /// Connects to the report database, or, if it refuses the connection or
+    /// doesn't answer within `DatabaseConnectTimeoutSeconds`, to
+    /// `DatabaseFailoverServer`, and says which of the two it is connected to.
+    /// Any other failure, such as a rejected login, is returned without trying
+    /// the failover server; the primary is up, so that is not a failover.
+    ///
+    /// Without a failover server this only ever connects to the primary.
+    /// Needs a tokio runtime with the time driver enabled.
+    pub async fn connect_with_failover(
+        &self,
+    ) -> Result<(Client<Compat<TcpStream>>, DatabaseServerRole), SettingsError> {
+        let primary = self.try_get_sql_settings()?;
+        let mut errors = Vec::new();
+        let failover = self.failover_config(&mut errors);
+        if !errors.is_empty() {
+            return Err(SettingsError::Validation(errors));
+        }
+
+        let primary_error = match self.open(primary).await {
+            Ok(client) => return Ok((client, DatabaseServerRole::Primary)),
+            Err(e) => e,
+        };
+        match failover {
+            Some(failover) if is_unreachable(&primary_error) => match self.open(failover).await {
+                Ok(client) => Ok((client, DatabaseServerRole::Failover)),
+                Err(e) => Err(SettingsError::DatabaseFailover {
+                    primary: Box::new(primary_error),
+                    failover: Box::new(e),
+                }),
+            },
+            _ => Err(primary_error),
+        }
+    }
+
+    async fn open(&self, config: Config) -> Result<Client<Compat<TcpStream>>, SettingsError> {
+        let address = config.get_addr();
+        with_timeout(self.connect_timeout(), &address, connect(config))
+            .await?
+            .map_err(|source| SettingsError::DatabaseConnection { address, source })
+    }
+}
+
+// Refused or timed out, i.e. nothing is answering at the address, as opposed
+// to a server that answered and turned the connection down
+fn is_unreachable(error: &SettingsError) -> bool {
+    match error {
+        SettingsError::DatabaseTimeout { .. } => true,
+        SettingsError::DatabaseConnection {
+            source: tiberius::error::Error::Io { kind, .. },
+            ..
+        } => matches!(
+            kind,
+            io::ErrorKind::ConnectionRefused | io::ErrorKind::TimedOut
+        ),
+        _ => false,
+    }
+}
+
+pub(crate) async fn connect(
+    config: Config,
+) -> Result<Client<Compat<TcpStream>>, tiberius::error::Error> {
+    let tcp = TcpStream::connect(config.get_addr()).await?;
+    tcp.set_nodelay(true)?;
+    Client::connect(config, tcp.compat_write()).await
+}
+
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    address: &str,
+    future: impl Future<Output = T>,
+) -> Result<T, SettingsError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await.map_err(|_| {
+            SettingsError::DatabaseTimeout {
+                address: address.to_string(),
+                timeout,
+            }
+        }),
+        None => Ok(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::error::Error;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // A TCP server on a free port that hangs up on every connection, or with
+    // `silent` keeps it open without a word, and counts the connections
+    async fn fake_server(silent: bool) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("127.0.0.1,{}", listener.local_addr().unwrap().port());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                if silent {
+                    open.push(socket);
+                }
+            }
+        });
+        (address, connections)
+    }
+
+    fn failover_settings(primary: &str, failover: &str) -> Settings {
+        Settings::builder()
+            .database_server(primary)
+            .database_failover_server(failover)
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .database_connect_timeout_seconds(1)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_primary_fails_over() {
+        // Nothing listens on port 1, so the primary refuses
+        let (hangs_up, _) = fake_server(false).await;
+        let err = failover_settings("127.0.0.1,1", &hangs_up)
+            .connect_with_failover()
+            .await
+            .unwrap_err();
+
+        let SettingsError::DatabaseFailover { primary, failover } = &err else {
+            panic!("{:?}", err);
+        };
+        assert!(is_unreachable(primary), "{:?}", primary);
+        assert!(!is_unreachable(failover), "{:?}", failover);
+        assert!(err.to_string().contains("127.0.0.1:1"), "{}", err);
+        assert!(
+            err.to_string().contains(&hangs_up.replace(',', ":")),
+            "{}",
+            err
+        );
+
+        let (silent, _) = fake_server(true).await;
+        let err = failover_settings(&silent, "127.0.0.1,1")
+            .connect_with_failover()
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(
+                &err,
+                SettingsError::DatabaseFailover { primary, .. }
+                    if matches!(**primary, SettingsError::DatabaseTimeout { .. })
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_primary_that_answers_does_not_fail_over() {
+        let (hangs_up, _) = fake_server(false).await;
+        let (failover, connections) = fake_server(true).await;
+
+        let err = failover_settings(&hangs_up, &failover)
+            .connect_with_failover()
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(err, SettingsError::DatabaseConnection { .. }),
+            "{:?}",
+            err
+        );
+        assert!(
+            err.to_string().contains(&hangs_up.replace(',', ":")),
+            "{}",
+            err
+        );
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_refused_connection_keeps_source_and_hides_password() {
+        // Nothing listens on port 1, so this fails fast without a server
+        let settings = Settings::builder()
+            .database_server("127.0.0.1,1")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .database_connect_timeout_seconds(5)
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+
+        let err = settings.test_database_connection().await.unwrap_err();
+
+        assert!(
+            matches!(err, SettingsError::DatabaseConnection { .. }),
+            "{:?}",
+            err
+        );
+        assert!(err.source().is_some());
+        assert!(!err.to_string().contains("password123"), "{}", err);
+        assert!(err.to_string().contains("127.0.0.1:1"), "{}", err);
+    }
+
+    // Set REPORTSETTINGS_TEST_SQL_BLOB to a settings blob for a reachable
+    // server to run this against it
+    #[tokio::test]
+    async fn test_live_database_connection() {
+        let blob = match env::var("REPORTSETTINGS_TEST_SQL_BLOB") {
+            Ok(blob) => blob,
+            Err(_) => return,
+        };
+        let settings = Settings::from_json_str(&blob).unwrap();
+
+        settings.test_database_connection().await.unwrap();
+        // With the primary down, the same server is reached as the failover
+        let primary = settings.database_server().to_string();
+        let (client, role) = settings
+            .clone()
+            .with_database_server("127.0.0.1,1")
+            .with_database_failover_server(primary)
+            .connect_with_failover()
+            .await
+            .unwrap();
+        assert_eq!(role, DatabaseServerRole::Failover);
+        drop(client);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::sql::Target;
+#[cfg(feature = "mssql")]
+use crate::SettingsError;
+use crate::{DatabaseAuthMethod, FieldError, Secret, Settings};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "mssql")]
+use ssql::prelude::tiberius::Config;
+
+/// The name the flat `DatabaseServer`/`DatabaseName`/... fields are known by in
+/// [`Settings::get_sql_settings_named`].
+pub const DEFAULT_DATABASE: &str = "default";
+
+/// One entry of the `Databases` map. Encryption, the application name and the
+/// other connection options are shared with the flat database fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "PascalCase")]
+pub struct DatabaseTarget {
+    /// SQL Server host, optionally followed by `\instance` or `,port`.
+    #[serde(alias = "server")]
+    pub server: String,
+    /// The database to connect to.
+    #[serde(alias = "name")]
+    pub name: String,
+    /// SQL login name.
+    #[serde(alias = "username")]
+    pub username: String,
+    /// SQL login password.
+    #[serde(alias = "password")]
+    pub password: Secret,
+    /// TCP port of the server, 1433 unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "port")]
+    pub port: Option<u16>,
+}
+
+impl Settings {
+    /// The tiberius configuration for a database from the `Databases` map, or for
+    /// the flat database fields when `name` is [`DEFAULT_DATABASE`].
+    #[cfg(feature = "mssql")]
+    pub fn get_sql_settings_named(&self, name: &str) -> Result<Config, SettingsError> {
+        if name == DEFAULT_DATABASE {
+            return self.try_get_sql_settings();
+        }
+        let target = self
+            .databases
+            .get(name)
+            .ok_or_else(|| SettingsError::UnknownDatabase {
+                name: name.to_string(),
+                available: self.database_names(),
+            })?;
+
+        let (config, errors) = self.named_config(name, target);
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(SettingsError::Validation(errors))
+        }
+    }
+
+    /// The names [`Settings::get_sql_settings_named`] accepts, starting with
+    /// [`DEFAULT_DATABASE`].
+    pub fn database_names(&self) -> Vec<String> {
+        let mut names = vec![DEFAULT_DATABASE.to_string()];
+        names.extend(self.databases.keys().cloned());
+        names
+    }
+
+    pub(crate) fn databases_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        for (name, target) in &self.databases {
+            if name == DEFAULT_DATABASE {
+                errors.push(FieldError::new(
+                    "Databases",
+                    "'default' is reserved for the top-level database fields",
+                ));
+                continue;
+            }
+            let required = [
+                ("Server", target.server.as_str()),
+                ("Name", target.name.as_str()),
+                ("Username", target.username.as_str()),
+                ("Password", target.password.inspect()),
+            ];
+            for (field, value) in required {
+                if value.trim().is_empty() {
+                    errors.push(FieldError::new(
+                        "Databases",
+                        format!("{}.{}: must not be empty", name, field),
+                    ));
+                }
+            }
+            let mut target_errors = Vec::new();
+            self.check_target(&target_fields(target), &mut target_errors);
+            errors.extend(named_errors(name, target_errors));
+        }
+        errors
+    }
+
+    #[cfg(feature = "mssql")]
+    fn named_config(&self, name: &str, target: &DatabaseTarget) -> (Config, Vec<FieldError>) {
+        let mut errors = Vec::new();
+        let config = self.target_config(&target_fields(target), &mut errors);
+        (config, named_errors(name, errors))
+    }
+}
+
+fn target_fields(target: &DatabaseTarget) -> Target<'_> {
+    Target {
+        server: &target.server,
+        port: target.port,
+        name: &target.name,
+        username: &target.username,
+        password: target.password.expose(),
+        auth_method: DatabaseAuthMethod::SqlServer,
+    }
+}
+
+// Errors come back naming the flat fields, so they are renamed to point into
+// the map
+fn named_errors(name: &str, errors: Vec<FieldError>) -> Vec<FieldError> {
+    errors
+        .into_iter()
+        .map(|e| {
+            FieldError::new(
+                "Databases",
+                format!(
+                    "{}.{}: {}",
+                    name,
+                    e.field.trim_start_matches("Database"),
+                    e.reason
+                ),
+            )
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "mssql"))]
+mod tests {
+    use super::*;
+
+    const BLOB: &str = r#"{
+        "DatabaseServer": "transactions-sql",
+        "DatabaseName": "transactions",
+        "DatabaseUsername": "admin",
+        "DatabasePassword": "password123",
+        "Databases": {
+            "audit": {
+                "Server": "audit-sql",
+                "Name": "audit",
+                "Username": "auditor",
+                "Password": "hunter2",
+                "Port": 14330
+            }
+        },
+        "LogWebhookUri": "https://example.com",
+        "SendgridApiKey": "sendgrid-api-key",
+        "EmailFromName": "Test",
+        "EmailFromAddress": "test@example.com",
+        "EmailToAddresses": "user1@example.com"
+    }"#;
+
+    #[test]
+    fn test_named_and_default_targets() {
+        let settings: Settings = serde_json::from_str(BLOB).unwrap();
+
+        let audit = settings.get_sql_settings_named("audit").unwrap();
+        assert_eq!(audit.get_addr(), "audit-sql:14330");
+        assert!(format!("{:?}", audit).contains(r#"database: Some("audit")"#));
+
+        let default = settings.get_sql_settings_named("default").unwrap();
+        assert_eq!(default.get_addr(), settings.get_sql_settings().get_addr());
+        assert_eq!(settings.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_unknown_name_lists_available() {
+        let settings: Settings = serde_json::from_str(BLOB).unwrap();
+
+        let err = settings.get_sql_settings_named("reporting").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown database 'reporting', expected one of: default, audit"
+        );
+    }
+
+    #[test]
+    fn test_target_errors_point_into_map() {
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .database(
+                "audit",
+                DatabaseTarget {
+                    server: "audit-sql,1500".to_string(),
+                    name: "audit".to_string(),
+                    port: Some(1433),
+                    ..DatabaseTarget::default()
+                },
+            )
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            settings.validate(),
+            Err(vec![
+                FieldError::new("Databases", "audit.Username: must not be empty"),
+                FieldError::new("Databases", "audit.Password: must not be empty"),
+                FieldError::new(
+                    "Databases",
+                    "audit.Port: is 1433 but DatabaseServer 'audit-sql,1500' specifies port 1500"
+                ),
+            ])
+        );
+    }
+}
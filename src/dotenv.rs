@@ -0,0 +1,289 @@
+use crate::loader::missing_fields;
+use crate::{Settings, SettingsError};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The file [`Settings::get_settings`] falls back to when `SecretBlob` is not
+/// set, relative to the working directory. Only in debug builds, or with the
+/// `dotenv` feature.
+pub const DOTENV_FILE: &str = ".env";
+
+impl Settings {
+    /// Loads settings from a `.env` file of `REPORTSETTINGS_<FIELD>=value`
+    /// lines, with the same names and parsing as the per-field overrides (see
+    /// [`Settings::apply_env_overrides`]). Other keys are ignored, so the file
+    /// can be shared with other tools. `Databases` can't be given this way.
+    ///
+    /// Blank lines and lines starting with `#` are skipped, as is an `export `
+    /// before the key. A value may be wrapped in double quotes, which
+    /// understand `\n`, `\r`, `\t`, `\"` and `\\`, or in single quotes, which
+    /// take everything literally. An unquoted value ends at a ` #` comment.
+    /// Everything after the first `=` belongs to the value, so connection
+    /// strings need no quoting.
+    ///
+    /// Fails with [`SettingsError::Validation`] if the file lacks a database
+    /// field a connection needs.
+    pub fn from_dotenv(path: impl AsRef<Path>) -> Result<Settings, SettingsError> {
+        let settings = read_dotenv(path.as_ref())?;
+        let missing = missing_fields(&settings);
+        if missing.is_empty() {
+            settings.finish_load()
+        } else {
+            Err(SettingsError::Validation(missing))
+        }
+    }
+}
+
+// What `get_settings` does when `SecretBlob` is unset: without a file at
+// `path`, it reports the unset variable as before
+pub(crate) fn dotenv_fallback<F>(
+    path: &Path,
+    missing: SettingsError,
+    lookup: F,
+) -> Result<Settings, SettingsError>
+where
+    F: Fn(&str) -> Result<Option<String>, SettingsError>,
+{
+    if !cfg!(any(debug_assertions, feature = "dotenv")) || !path.is_file() {
+        return Err(missing);
+    }
+    let mut settings = read_dotenv(path)?;
+    // Variables that are really set win over the file, as with other dotenv tools
+    settings.apply_overrides_from(lookup)?;
+    settings.finish_load()
+}
+
+// The fields given in the file, with every other field unset
+fn read_dotenv(path: &Path) -> Result<Settings, SettingsError> {
+    let contents = fs::read_to_string(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => SettingsError::FileNotFound {
+            path: path.to_path_buf(),
+        },
+        _ => SettingsError::FileUnreadable {
+            path: path.to_path_buf(),
+            source: e,
+        },
+    })?;
+    let vars = parse_dotenv(&contents).map_err(|(line, reason)| SettingsError::InvalidDotenv {
+        path: PathBuf::from(path),
+        line,
+        reason,
+    })?;
+
+    let mut settings: Settings = serde_json::from_value(Value::Object(Map::new()))?;
+    settings.apply_overrides_from(|name| Ok(vars.get(name).cloned()))?;
+    Ok(settings)
+}
+
+// Keys and values by key, later lines winning; errors carry the 1-based line
+fn parse_dotenv(contents: &str) -> Result<HashMap<String, String>, (usize, String)> {
+    let mut vars = HashMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| (index + 1, "expected KEY=value".to_string()))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err((index + 1, format!("'{}' is not a variable name", key)));
+        }
+        let value = parse_value(value.trim_start()).map_err(|reason| (index + 1, reason))?;
+        vars.insert(key.to_string(), value);
+    }
+    Ok(vars)
+}
+
+fn parse_value(raw: &str) -> Result<String, String> {
+    let (value, rest) = if let Some(quoted) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((i, '"')) => break i,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, c @ ('"' | '\\'))) => value.push(c),
+                    Some((_, c)) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => return Err("double quote is never closed".to_string()),
+                },
+                Some((_, c)) => value.push(c),
+                None => return Err("double quote is never closed".to_string()),
+            }
+        };
+        (value, &quoted[end + 1..])
+    } else if let Some(quoted) = raw.strip_prefix('\'') {
+        let end = quoted
+            .find('\'')
+            .ok_or_else(|| "single quote is never closed".to_string())?;
+        (quoted[..end].to_string(), &quoted[end + 1..])
+    } else {
+        let end = raw
+            .char_indices()
+            .find(|&(i, c)| c == '#' && raw[..i].ends_with(char::is_whitespace))
+            .map_or(raw.len(), |(i, _)| i);
+        return Ok(raw[..end].trim_end().to_string());
+    };
+
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(value)
+    } else {
+        Err(format!("unexpected '{}' after the quoted value", rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dotenv_file(contents: &str) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), contents).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_parses_comments_quotes_and_equals() {
+        let vars = parse_dotenv(
+            "# local settings\n\
+             \n\
+             REPORTSETTINGS_DATABASE_SERVER=sql01 # the dev box\n\
+             export REPORTSETTINGS_DATABASE_NAME = reports\n\
+             REPORTSETTINGS_DATABASE_PASSWORD=\"p#ss \\\"word\\\"\\n\" # quoted\n\
+             REPORTSETTINGS_EMAIL_FROM_NAME='Costs in $USD \\n'\n\
+             REPORTSETTINGS_DATABASE_CONNECTION_STRING=Server=sql01;Password=a=b#c\n\
+             REPORTSETTINGS_DATABASE_USERNAME=\n",
+        )
+        .unwrap();
+
+        let expected: HashMap<String, String> = [
+            ("REPORTSETTINGS_DATABASE_SERVER", "sql01"),
+            ("REPORTSETTINGS_DATABASE_NAME", "reports"),
+            ("REPORTSETTINGS_DATABASE_PASSWORD", "p#ss \"word\"\n"),
+            ("REPORTSETTINGS_EMAIL_FROM_NAME", "Costs in $USD \\n"),
+            (
+                "REPORTSETTINGS_DATABASE_CONNECTION_STRING",
+                "Server=sql01;Password=a=b#c",
+            ),
+            ("REPORTSETTINGS_DATABASE_USERNAME", ""),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        assert_eq!(vars, expected);
+    }
+
+    #[test]
+    fn test_rejects_malformed_lines() {
+        for (contents, line, reason) in [
+            ("A=1\nnot a pair\n", 2, "expected KEY=value"),
+            ("=value", 1, "'' is not a variable name"),
+            ("A=\"open", 1, "double quote is never closed"),
+            ("A='open", 1, "single quote is never closed"),
+            ("A=\"x\" y", 1, "unexpected 'y' after the quoted value"),
+        ] {
+            assert_eq!(parse_dotenv(contents), Err((line, reason.to_string())));
+        }
+    }
+
+    #[test]
+    fn test_from_dotenv() {
+        let file = dotenv_file(
+            "OTHER_TOOL_TOKEN=ignored\n\
+             REPORTSETTINGS_DATABASE_SERVER=sql01\n\
+             REPORTSETTINGS_DATABASE_NAME=reports\n\
+             REPORTSETTINGS_DATABASE_USERNAME=svc_report\n\
+             REPORTSETTINGS_DATABASE_PASSWORD='hunter2'\n\
+             REPORTSETTINGS_DATABASE_PORT=14330\n",
+        );
+
+        let settings = Settings::from_dotenv(file.path()).unwrap();
+
+        assert_eq!(settings.database_server(), "sql01");
+        assert_eq!(settings.expose_database_password(), "hunter2");
+        assert_eq!(settings.database_port(), Some(14330));
+        assert_eq!(settings.email_from_address(), None);
+    }
+
+    #[test]
+    fn test_from_dotenv_errors() {
+        let partial = dotenv_file("REPORTSETTINGS_DATABASE_SERVER=sql01\n");
+        let malformed = dotenv_file("REPORTSETTINGS_DATABASE_SERVER=sql01\n\"oops\n");
+
+        let missing = Settings::from_dotenv(partial.path()).unwrap_err();
+        let invalid = Settings::from_dotenv(malformed.path()).unwrap_err();
+
+        assert!(matches!(missing, SettingsError::Validation(errors) if errors.len() == 3));
+        assert!(matches!(
+            invalid,
+            SettingsError::InvalidDotenv { line: 2, .. }
+        ));
+        assert!(matches!(
+            Settings::from_dotenv("/nonexistent/.env").unwrap_err(),
+            SettingsError::FileNotFound { .. }
+        ));
+    }
+
+    #[test]
+    fn test_fallback_prefers_set_variables() {
+        let file = dotenv_file(
+            "REPORTSETTINGS_DATABASE_SERVER=sql01\n\
+             REPORTSETTINGS_DATABASE_NAME=reports\n",
+        );
+        let lookup = |name: &str| {
+            Ok((name == "REPORTSETTINGS_DATABASE_NAME").then(|| "invoices".to_string()))
+        };
+
+        let settings = dotenv_fallback(file.path(), missing_blob(), lookup).unwrap();
+        let err =
+            dotenv_fallback(Path::new("/nonexistent/.env"), missing_blob(), lookup).unwrap_err();
+
+        assert_eq!(settings.database_server(), "sql01");
+        assert_eq!(settings.database_name(), "invoices");
+        assert!(matches!(err, SettingsError::MissingEnvVar { name } if name == "SecretBlob"));
+    }
+
+    #[test]
+    fn test_missing_ca_bundle_fails_the_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.pem");
+        let file = dotenv_file(&format!(
+            "REPORTSETTINGS_DATABASE_SERVER=sql01\n\
+             REPORTSETTINGS_DATABASE_NAME=reports\n\
+             REPORTSETTINGS_DATABASE_USERNAME=svc_report\n\
+             REPORTSETTINGS_DATABASE_PASSWORD=hunter2\n\
+             REPORTSETTINGS_DATABASE_CA_CERT_PATH={}\n",
+            missing.display()
+        ));
+
+        let fallback = dotenv_fallback(file.path(), missing_blob(), |_| Ok(None)).unwrap_err();
+        let direct = Settings::from_dotenv(file.path()).unwrap_err();
+
+        assert!(
+            fallback
+                .to_string()
+                .contains(&format!("could not read '{}'", missing.display())),
+            "{}",
+            fallback
+        );
+        assert_eq!(direct.to_string(), fallback.to_string());
+    }
+
+    fn missing_blob() -> SettingsError {
+        SettingsError::MissingEnvVar {
+            name: "SecretBlob".to_string(),
+        }
+    }
+}
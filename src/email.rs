@@ -0,0 +1,250 @@
+use crate::address::{split_entries, EmailAddress};
+use crate::{Settings, SettingsError};
+use sendgrid::v3::Email;
+
+impl Settings {
+    /// The sender, `EmailFromAddress` with `EmailFromName` as its display name,
+    /// or `None` if no from address is set. A missing or empty name means no
+    /// display name. [`Settings::validate`] checks the address.
+    pub fn get_email_from(&self) -> Option<Email> {
+        let from = Email::new(self.email_from_address()?.trim());
+        Some(match self.email_from_name().unwrap_or_default().trim() {
+            "" => from,
+            name => from.set_name(name),
+        })
+    }
+
+    /// Entries in the `Name <address>` form get a display name; anything that
+    /// doesn't parse is passed through as the address, as before. Empty and
+    /// whitespace-only entries are skipped, so an empty or blank
+    /// `EmailToAddresses` has no recipients, as does an unset one.
+    #[deprecated(note = "does not validate addresses; use try_get_email_destinations")]
+    pub fn get_email_destinations(&self) -> Vec<Email> {
+        let raw = match self.email_to_addresses() {
+            Some(raw) => raw,
+            None => return Vec::new(),
+        };
+        split_entries(raw)
+            .into_iter()
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| match EmailAddress::parse(entry) {
+                Some(address) => Email::from(address),
+                None => Email::new(entry),
+            })
+            .collect()
+    }
+
+    /// `EmailReplyToAddress` with `EmailReplyToName` as its display name, or
+    /// `None` if no reply-to address is set. [`Settings::validate`] checks the address.
+    pub fn get_email_reply_to(&self) -> Option<Email> {
+        let reply_to = match self.email_reply_to_address().trim() {
+            "" => return None,
+            address => Email::new(address),
+        };
+        Some(match self.email_reply_to_name().trim() {
+            "" => reply_to,
+            name => reply_to.set_name(name),
+        })
+    }
+
+    /// [`Settings::email_to_recipients`] as SendGrid addresses.
+    pub fn try_get_email_destinations(&self) -> Result<Vec<Email>, SettingsError> {
+        Ok(to_emails(self.email_to_recipients()?))
+    }
+
+    /// [`Settings::email_cc_recipients`] as SendGrid addresses.
+    pub fn get_email_cc(&self) -> Result<Vec<Email>, SettingsError> {
+        Ok(to_emails(self.email_cc_recipients()?))
+    }
+
+    /// [`Settings::email_bcc_recipients`] as SendGrid addresses.
+    pub fn get_email_bcc(&self) -> Result<Vec<Email>, SettingsError> {
+        Ok(to_emails(self.email_bcc_recipients()?))
+    }
+}
+
+fn to_emails(addresses: Vec<EmailAddress>) -> Vec<Email> {
+    addresses.into_iter().map(Email::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_recipients(email_to_addresses: &str) -> Settings {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .email_to_addresses(email_to_addresses)
+            .build()
+            .unwrap()
+    }
+
+    fn recipients(email_to_addresses: &str) -> Result<Vec<String>, SettingsError> {
+        with_recipients(email_to_addresses)
+            .try_get_email_destinations()
+            .map(|emails| {
+                emails
+                    .iter()
+                    .map(|e| serde_json::to_value(e).unwrap()["email"].to_string())
+                    .collect()
+            })
+    }
+
+    fn to_json(emails: &[Email]) -> serde_json::Value {
+        serde_json::to_value(emails).unwrap()
+    }
+
+    #[test]
+    fn test_email_from_uses_name_when_given() {
+        let named = Settings::builder()
+            .log_webhook_uri("https://example.com")
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_name("Reports")
+            .email_from_address("reports@example.com")
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(named.get_email_from()).unwrap(),
+            serde_json::json!({ "email": "reports@example.com", "name": "Reports" })
+        );
+
+        assert_eq!(
+            serde_json::to_value(with_recipients("").get_email_from()).unwrap(),
+            serde_json::json!({ "email": "test@example.com" })
+        );
+    }
+
+    #[test]
+    fn test_email_reply_to_is_optional() {
+        assert!(with_recipients("").get_email_reply_to().is_none());
+
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("no-reply@example.com")
+            .email_reply_to_address("support@example.com")
+            .email_reply_to_name("Support")
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(settings.get_email_reply_to()).unwrap(),
+            serde_json::json!({ "email": "support@example.com", "name": "Support" })
+        );
+    }
+
+    #[test]
+    fn test_trailing_commas_and_padding() {
+        assert_eq!(
+            recipients(" a@b.com , c@d.com,").unwrap(),
+            vec!["\"a@b.com\"", "\"c@d.com\""]
+        );
+    }
+
+    #[test]
+    fn test_semicolons_and_repeats() {
+        assert_eq!(
+            recipients("a@b.com; c@d.com; A@B.com;").unwrap(),
+            vec!["\"a@b.com\"", "\"c@d.com\""]
+        );
+    }
+
+    #[test]
+    fn test_empty_string_has_no_recipients() {
+        assert!(recipients("").unwrap().is_empty());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_lenient_destinations_skip_blank_entries() {
+        for raw in ["", ",", "  \t ", " , ;"] {
+            assert!(
+                with_recipients(raw).get_email_destinations().is_empty(),
+                "{:?}",
+                raw
+            );
+            assert!(recipients(raw).unwrap().is_empty(), "{:?}", raw);
+        }
+        assert_eq!(
+            to_json(&with_recipients(", a@b.com,,").get_email_destinations()),
+            serde_json::json!([{ "email": "a@b.com" }])
+        );
+    }
+
+    #[test]
+    fn test_invalid_address_is_named() {
+        let err = recipients("a@b.com,,  ,not-an-email").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "EmailToAddresses: 'not-an-email' (entry 4) is not a valid email address"
+        );
+    }
+
+    #[test]
+    fn test_mixed_named_and_bare_recipients() {
+        let settings = with_recipients(
+            r#"ops@corp.com, Jane Doe <jane@corp.com>, "Doe, John" <john@corp.com>"#,
+        );
+        let expected = serde_json::json!([
+            { "email": "ops@corp.com" },
+            { "email": "jane@corp.com", "name": "Jane Doe" },
+            { "email": "john@corp.com", "name": "Doe, John" },
+        ]);
+
+        assert_eq!(
+            to_json(&settings.try_get_email_destinations().unwrap()),
+            expected
+        );
+        #[allow(deprecated)]
+        let lenient = settings.get_email_destinations();
+        assert_eq!(to_json(&lenient), expected);
+    }
+
+    #[test]
+    fn test_cc_and_bcc_skip_earlier_recipients() {
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .email_to_addresses("ops@corp.com, Jane <jane@corp.com>")
+            .email_cc_addresses("finance@corp.com, JANE@corp.com")
+            .email_bcc_addresses("audit@corp.com, Finance <finance@corp.com>, ops@corp.com")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            to_json(&settings.get_email_cc().unwrap()),
+            serde_json::json!([{ "email": "finance@corp.com" }])
+        );
+        assert_eq!(
+            to_json(&settings.get_email_bcc().unwrap()),
+            serde_json::json!([{ "email": "audit@corp.com" }])
+        );
+    }
+
+    #[test]
+    fn test_cc_and_bcc_default_to_empty() {
+        let settings = with_recipients("ops@corp.com");
+
+        assert!(settings.get_email_cc().unwrap().is_empty());
+        assert!(settings.get_email_bcc().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::required::absent_fields;
+use crate::{Settings, SettingsError};
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+
+const CONFIG: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, CONFIG);
+const URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, CONFIG);
+
+/// Whether the value consists only of base64 characters (either alphabet), allowing
+/// surrounding whitespace and line breaks from wrapped encoder output.
+pub(crate) fn looks_like_base64(blob: &str) -> bool {
+    let mut chars = blob
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '\r' | '\n'))
+        .peekable();
+    chars.peek().is_some()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '-' | '_' | '='))
+}
+
+/// Decodes standard or URL-safe base64, with or without padding. Whitespace,
+/// including the trailing newline most encoders add, is ignored.
+pub(crate) fn decode(blob: &str) -> Result<String, SettingsError> {
+    String::from_utf8(decode_bytes(blob)?)
+        .map_err(|_| SettingsError::InvalidBase64("decoded blob is not valid UTF-8".to_string()))
+}
+
+/// Like [`decode`], for base64 that isn't text, such as a key or ciphertext.
+pub(crate) fn decode_bytes(blob: &str) -> Result<Vec<u8>, SettingsError> {
+    let compact: String = blob.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let engine = if compact.contains(['-', '_']) {
+        &URL_SAFE
+    } else {
+        &STANDARD
+    };
+
+    engine
+        .decode(compact)
+        .map_err(|e| SettingsError::InvalidBase64(e.to_string()))
+}
+
+impl Settings {
+    /// Parses a base64-encoded JSON blob, in either the standard or URL-safe alphabet.
+    pub fn from_base64_json(blob: &str) -> Result<Settings, SettingsError> {
+        let decoded = decode(blob)?;
+        let settings: Settings = serde_json::from_str(&decoded)?;
+        let absent = serde_json::from_str(&decoded)
+            .map(|blob| absent_fields(&blob))
+            .unwrap_or_default();
+        settings.with_absent_fields(absent).require_fields()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{"A":"??>>"}"#;
+
+    #[test]
+    fn test_decodes_standard_and_url_safe() {
+        assert_eq!(decode("eyJBIjoiPz8+PiJ9\n").unwrap(), JSON);
+        assert_eq!(decode("eyJBIjoiPz8-PiJ9").unwrap(), JSON);
+        assert_eq!(decode("e30=\r\n").unwrap(), "{}");
+        assert_eq!(decode("e30").unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_looks_like_base64() {
+        assert!(looks_like_base64("e30=\n"));
+        assert!(looks_like_base64("ab-_"));
+        assert!(!looks_like_base64("invalid json"));
+        assert!(!looks_like_base64("{}"));
+        assert!(!looks_like_base64("  "));
+    }
+
+    #[test]
+    fn test_rejects_invalid_base64() {
+        let err = decode("e30=e30=").unwrap_err();
+        assert!(matches!(err, SettingsError::InvalidBase64(_)));
+    }
+}
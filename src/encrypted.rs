@@ -0,0 +1,165 @@
+use crate::encoded::decode_bytes;
+use crate::profile::Profile;
+use crate::{Settings, SettingsError};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use std::env;
+use zeroize::Zeroizing;
+
+/// The length of an AES-256-GCM key, in bytes.
+pub const BLOB_KEY_LEN: usize = 32;
+
+// The authentication tag AES-GCM appends to the ciphertext
+const TAG_LEN: usize = 16;
+
+impl Settings {
+    /// Parses a JSON blob encrypted with AES-256-GCM under `key`, which must be
+    /// [`BLOB_KEY_LEN`] bytes. `ciphertext_b64` is the base64 of the 12-byte
+    /// nonce followed by the ciphertext and its 16-byte tag, with no
+    /// associated data; once decrypted, the blob is read like
+    /// [`Settings::from_json_str`] reads one.
+    ///
+    /// A wrong key, a tampered blob or one too short to hold a nonce and tag
+    /// fails with [`SettingsError::BlobDecryption`], and a key of the wrong
+    /// length with [`SettingsError::InvalidBlobKey`], so neither is mistaken
+    /// for a malformed blob.
+    pub fn from_encrypted_blob(
+        ciphertext_b64: &str,
+        key: &[u8],
+    ) -> Result<Settings, SettingsError> {
+        let plaintext = decrypt(ciphertext_b64, key)?;
+        Settings::from_json_str(&plaintext)
+    }
+
+    // The blob in the env var `name`, decrypted with `<name>Key`
+    pub(crate) fn parse_encrypted_blob(
+        blob: &str,
+        key: &[u8],
+        profile: Profile,
+    ) -> Result<(Settings, Vec<String>), SettingsError> {
+        let plaintext = decrypt(blob, key)?;
+        Settings::parse_blob_reporting_unknown(&plaintext, profile)
+    }
+}
+
+/// The key in `<name>Key`, e.g. `SecretBlobKey`, if that variable is set.
+pub(crate) fn blob_key(name: &str) -> Result<Option<Zeroizing<Vec<u8>>>, SettingsError> {
+    let var = format!("{}Key", name);
+    let encoded = match env::var(&var) {
+        Ok(encoded) => Zeroizing::new(encoded),
+        Err(env::VarError::NotPresent) => return Ok(None),
+        Err(env::VarError::NotUnicode(_)) => {
+            return Err(SettingsError::InvalidEnvVar { name: var })
+        }
+    };
+    let key = decode_bytes(&encoded)
+        .map_err(|_| SettingsError::InvalidBlobKey(format!("{} is not valid base64", var)))?;
+    Ok(Some(Zeroizing::new(key)))
+}
+
+fn decrypt(ciphertext_b64: &str, key: &[u8]) -> Result<Zeroizing<String>, SettingsError> {
+    if key.len() != BLOB_KEY_LEN {
+        return Err(SettingsError::InvalidBlobKey(format!(
+            "must be {} bytes, got {}",
+            BLOB_KEY_LEN,
+            key.len()
+        )));
+    }
+    let key = UnboundKey::new(&AES_256_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| SettingsError::InvalidBlobKey("is not an AES-256 key".to_string()))?;
+
+    let mut sealed = Zeroizing::new(decode_bytes(ciphertext_b64)?);
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return Err(SettingsError::BlobDecryption(format!(
+            "is {} bytes, too short to hold the {}-byte nonce and {}-byte tag",
+            sealed.len(),
+            NONCE_LEN,
+            TAG_LEN
+        )));
+    }
+    let (nonce, ciphertext) = sealed.split_at_mut(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).expect("the nonce is NONCE_LEN bytes");
+    let plaintext = key
+        .open_in_place(nonce, Aad::empty(), ciphertext)
+        .map_err(|_| {
+            SettingsError::BlobDecryption(
+                "authentication failed: wrong key, or the blob was altered".to_string(),
+            )
+        })?;
+
+    match std::str::from_utf8(plaintext) {
+        Ok(plaintext) => Ok(Zeroizing::new(plaintext.to_string())),
+        Err(_) => Err(SettingsError::BlobDecryption(
+            "decrypted blob is not valid UTF-8".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    pub(crate) const KEY: [u8; 32] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+        25, 26, 27, 28, 29, 30, 31,
+    ];
+    const NONCE: [u8; NONCE_LEN] = [
+        0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xab,
+    ];
+    const BLOB: &str =
+        r#"{"DatabaseServer":"sql01","DatabaseName":"reports","DatabaseAuthMethod":"Integrated"}"#;
+    // `BLOB` sealed under `KEY` with `NONCE`; any AES-GCM implementation
+    // gives the same
+    pub(crate) const SEALED: &str = "oKGio6SlpqeoqaqrnTo4TDGqYN4RANS2dQylrFKWe2Pj23JdviIEwh7fFGOzBSKxzk82H2W+dq15FfGNNDlqaiaxbh8jP3g75QXx2PnZ8Qdfwcba1quRbOqpvtu5bs+GuJTg9QXQhDpimDkRN7Bods0=";
+
+    // How a deploy pipeline would produce a blob, for the test vector
+    fn seal(plaintext: &str, key: &[u8], nonce: [u8; NONCE_LEN]) -> String {
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).unwrap());
+        let mut sealed = plaintext.as_bytes().to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut sealed,
+        )
+        .unwrap();
+        STANDARD.encode([nonce.as_slice(), &sealed].concat())
+    }
+
+    #[test]
+    fn test_format_is_pinned() {
+        assert_eq!(seal(BLOB, &KEY, NONCE), SEALED);
+
+        let settings = Settings::from_encrypted_blob(SEALED, &KEY).unwrap();
+
+        assert_eq!(settings.database_server(), "sql01");
+        assert_eq!(settings.database_name(), "reports");
+    }
+
+    #[test]
+    fn test_decryption_failures_are_not_parse_failures() {
+        let mut wrong_key = KEY;
+        wrong_key[0] ^= 1;
+        let mut tampered = STANDARD.decode(SEALED).unwrap();
+        tampered[NONCE_LEN] ^= 1;
+        let not_json = seal("not json", &KEY, NONCE);
+
+        for (blob, key) in [
+            (SEALED.to_string(), wrong_key.as_slice()),
+            (STANDARD.encode(tampered), KEY.as_slice()),
+            (STANDARD.encode([0u8; 20]), KEY.as_slice()),
+        ] {
+            let err = Settings::from_encrypted_blob(&blob, key).unwrap_err();
+            assert!(matches!(err, SettingsError::BlobDecryption(_)), "{}", err);
+        }
+        assert!(matches!(
+            Settings::from_encrypted_blob(SEALED, &KEY[..16]),
+            Err(SettingsError::InvalidBlobKey(_))
+        ));
+        assert!(matches!(
+            Settings::from_encrypted_blob(&not_json, &KEY),
+            Err(SettingsError::InvalidJson(_))
+        ));
+    }
+}
@@ -0,0 +1,737 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[cfg(feature = "mssql")]
+use ssql::prelude::tiberius;
+
+#[cfg(feature = "remote")]
+use crate::RemoteBlobError;
+#[cfg(feature = "schema")]
+use crate::SchemaViolation;
+use crate::SecretStoreError;
+#[cfg(feature = "sendgrid")]
+use crate::SendError;
+#[cfg(feature = "smtp")]
+use crate::SmtpError;
+#[cfg(feature = "remote")]
+use crate::{LogEvent, LogLevel, WebhookError};
+
+/// A single problem found in one field of the settings blob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The PascalCase name of the field as it appears in the blob.
+    pub field: &'static str,
+    /// A human-readable explanation of what is wrong with the value.
+    pub reason: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, reason: impl Into<String>) -> FieldError {
+        FieldError {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+/// Everything that can go wrong while loading settings.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SettingsError {
+    /// The environment variable holding the blob is not set.
+    MissingEnvVar { name: String },
+    /// The environment variable is set but does not contain valid unicode.
+    InvalidEnvVar { name: String },
+    /// The prefix given to [`crate::Settings::get_settings_with_prefix`]
+    /// cannot start an env variable name.
+    InvalidSettingsPrefix { prefix: String },
+    /// A `REPORTSETTINGS_<FIELD>` override, or a file read by
+    /// [`crate::Settings::from_secrets_dir`], holds a value the field cannot
+    /// take; `name` is the variable or the path of the file.
+    InvalidOverride { name: String, reason: String },
+    /// The settings file does not exist.
+    FileNotFound { path: PathBuf },
+    /// The settings file exists but could not be read.
+    FileUnreadable { path: PathBuf, source: io::Error },
+    /// The blob leaves out these required fields, in declaration order, and
+    /// no override sets them.
+    MissingFields { fields: Vec<&'static str> },
+    /// A secrets directory has no file for these required fields.
+    MissingSecretFiles { dir: PathBuf, files: Vec<String> },
+    /// A line of a `.env` file is not a `KEY=value` pair; `line` is 1-based.
+    InvalidDotenv {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    /// The reader passed to [`crate::Settings::from_reader`] failed, or did not
+    /// yield valid unicode.
+    ReaderFailed(io::Error),
+    /// The blob is not valid JSON or does not match the expected shape.
+    InvalidJson(serde_json::Error),
+    /// The blob could not be decoded as base64.
+    InvalidBase64(String),
+    /// The key for an encrypted blob, given to
+    /// [`crate::Settings::from_encrypted_blob`] or in `SecretBlobKey`, is not
+    /// a base64 AES-256 key.
+    InvalidBlobKey(String),
+    /// An encrypted blob could not be decrypted: the key is wrong, or the blob
+    /// was altered or cut short.
+    BlobDecryption(String),
+    /// The blob is neither valid JSON nor valid base64-encoded JSON.
+    InvalidJsonOrBase64 {
+        json: serde_json::Error,
+        base64: Box<SettingsError>,
+    },
+    /// The blob still failed to parse after a leading BOM or the single quotes
+    /// around it were `removed`; `source` is the error for the blob as given.
+    InvalidAfterCleanup {
+        removed: String,
+        source: Box<SettingsError>,
+    },
+    /// The blob is neither strict JSON nor JSON5; read with `SecretBlobFormat`
+    /// set to `json5`, or by [`crate::Settings::from_json5`].
+    #[cfg(feature = "json5")]
+    InvalidJson5 {
+        json: serde_json::Error,
+        json5: String,
+    },
+    /// The blob is not valid TOML or does not match the expected shape.
+    InvalidToml(toml::de::Error),
+    /// The blob is not valid YAML, uses anchors, or does not match the expected shape.
+    #[cfg(feature = "yaml")]
+    InvalidYaml(String),
+    /// The settings could not be extracted from a figment, see
+    /// [`crate::Settings::from_figment`].
+    #[cfg(feature = "figment")]
+    Figment(Box<figment::Error>),
+    /// `SecretBlobFormat` names a format this crate cannot parse.
+    UnsupportedFormat {
+        format: String,
+        supported: &'static str,
+    },
+    /// A nested blob gives a field in its group, as `nested`, and also under
+    /// its flat key `field`.
+    DuplicateField { field: String, nested: String },
+    /// The blob's `SchemaVersion` is newer than
+    /// [`crate::CURRENT_SCHEMA_VERSION`], so it may be laid out in a way this
+    /// crate would misread.
+    UnsupportedSchemaVersion { version: u64 },
+    /// The blob has keys that are not settings fields; only reported by
+    /// [`crate::Settings::get_settings_strict`].
+    UnknownFields { keys: Vec<String> },
+    /// A secret store could not provide the blob.
+    SecretStore {
+        store: &'static str,
+        name: String,
+        source: SecretStoreError,
+    },
+    /// An entry in a recipient list is not a valid email address.
+    InvalidEmailAddress {
+        field: &'static str,
+        address: String,
+        /// 1-based position of the entry in the comma-separated list.
+        position: usize,
+    },
+    /// An optional field is not set, but the operation needs it, e.g.
+    /// `LogWebhookUri` for logging to the webhook.
+    MissingField { field: &'static str },
+    /// A message was built while `EmailToAddresses` lists nobody; SendGrid
+    /// needs at least one To recipient.
+    NoRecipients,
+    /// A message was built for more recipients across To, Cc and Bcc than
+    /// SendGrid accepts, [`crate::SENDGRID_MAX_RECIPIENTS`].
+    TooManyRecipients { count: usize },
+    /// The dynamic data for a SendGrid template is not a JSON object.
+    InvalidTemplateData,
+    /// An attachment was given an empty filename.
+    #[cfg(feature = "sendgrid")]
+    EmptyAttachmentName {
+        /// 1-based position of the attachment.
+        position: usize,
+    },
+    /// A message with its attachments is larger than SendGrid accepts,
+    /// [`crate::SENDGRID_MAX_MESSAGE_BYTES`].
+    #[cfg(feature = "sendgrid")]
+    MessageTooLarge { size: usize },
+    /// A line could not be posted to `LogWebhookUri`, even after retrying. The
+    /// line is handed back so it can be written somewhere else.
+    #[cfg(feature = "remote")]
+    LogWebhook {
+        level: LogLevel,
+        message: String,
+        attempts: u32,
+        source: WebhookError,
+    },
+    /// A batch of `WebhookLogger::post_batch` could not be posted, even after
+    /// retrying. The batches before it were delivered and the ones after it
+    /// not tried; the events of both are handed back, in order.
+    #[cfg(feature = "remote")]
+    LogWebhookBatch {
+        delivered: usize,
+        undelivered: Vec<LogEvent>,
+        attempts: u32,
+        source: WebhookError,
+    },
+    /// An HTTP client could not be set up.
+    HttpClient(String),
+    /// `Settings::from_url` could not fetch the blob, after retrying where
+    /// that could help. `url` has any credentials and query removed.
+    #[cfg(feature = "remote")]
+    RemoteBlob {
+        url: String,
+        attempts: u32,
+        source: RemoteBlobError,
+    },
+    /// `Settings::init_webhook_logging` was called when a `log` logger was
+    /// already installed.
+    #[cfg(feature = "log")]
+    LoggerAlreadySet,
+    /// SendGrid did not accept the message, after retrying where that could help.
+    #[cfg(feature = "sendgrid")]
+    EmailSend { attempts: u32, source: SendError },
+    /// `EmailMaxPerHour` emails have been sent in the last hour; nothing was
+    /// sent. The next send is allowed after `retry_after`.
+    #[cfg(feature = "sendgrid")]
+    RateLimited { retry_after: Duration },
+    /// The `SmtpHost` relay did not take the message, with no SendGrid to try.
+    #[cfg(feature = "smtp")]
+    SmtpSend { host: String, source: SmtpError },
+    /// SendGrid failed with a server error or timeout, and so did the SMTP
+    /// relay the message was then sent to.
+    #[cfg(feature = "smtp")]
+    EmailFallback {
+        sendgrid_attempts: u32,
+        sendgrid: SendError,
+        host: String,
+        smtp: SmtpError,
+    },
+    /// The database could not be reached, or rejected the login or test query.
+    #[cfg(feature = "mssql")]
+    DatabaseConnection {
+        address: String,
+        source: tiberius::error::Error,
+    },
+    /// Connecting to the database, or the test query, took longer than allowed.
+    DatabaseTimeout { address: String, timeout: Duration },
+    /// Neither `DatabaseServer` nor `DatabaseFailoverServer` could be reached;
+    /// each error is a `DatabaseConnection` or `DatabaseTimeout`.
+    #[cfg(feature = "mssql")]
+    DatabaseFailover {
+        primary: Box<SettingsError>,
+        failover: Box<SettingsError>,
+    },
+    /// `get_sql_settings_named` was asked for a database the blob doesn't define.
+    UnknownDatabase {
+        name: String,
+        available: Vec<String>,
+    },
+    /// A profile was asked for that the blob doesn't define; `available` is
+    /// empty when the blob has no profiles at all.
+    UnknownProfile {
+        name: String,
+        available: Vec<String>,
+    },
+    /// The blob has no `Tenants` object, or its tenants are malformed; see
+    /// [`crate::TenantSettings`].
+    InvalidTenants(String),
+    /// [`crate::TenantSettings::get`] was asked for a tenant the blob doesn't have.
+    UnknownTenant {
+        name: String,
+        available: Vec<String>,
+    },
+    /// The settings of tenant `name`, merged over `Shared`, failed to load or
+    /// validate.
+    Tenant {
+        name: String,
+        source: Box<SettingsError>,
+    },
+    /// A `${NAME}` in the blob field `field` names an env variable that is
+    /// not set; only reported by [`crate::Settings::get_settings_interpolated`].
+    MissingInterpolatedVar { name: String, field: String },
+    /// The blob field `field` has a malformed `${...}`, such as one that is
+    /// never closed.
+    InvalidInterpolation { field: String, reason: String },
+    /// A URL field could not be parsed or uses an unsupported scheme.
+    InvalidUrl { field: &'static str, reason: String },
+    /// `Settings::validate_against_schema` found values that don't match the schema.
+    #[cfg(feature = "schema")]
+    SchemaViolations(Vec<SchemaViolation>),
+    /// The blob deserialized, but one or more fields hold unusable values.
+    Validation(Vec<FieldError>),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::MissingEnvVar { name } => write!(
+                f,
+                "Error getting env variable {}: environment variable not found",
+                name
+            ),
+            SettingsError::InvalidSettingsPrefix { prefix } => write!(
+                f,
+                "Invalid settings prefix '{}': use ASCII letters, digits and underscores",
+                prefix
+            ),
+            SettingsError::InvalidEnvVar { name } => write!(
+                f,
+                "Error getting env variable {}: environment variable was not valid unicode",
+                name
+            ),
+            SettingsError::InvalidOverride { name, reason } => {
+                write!(f, "Invalid override {}: {}", name, reason)
+            }
+            SettingsError::FileNotFound { path } => {
+                write!(f, "Settings file not found: {}", path.display())
+            }
+            SettingsError::FileUnreadable { path, source } => {
+                write!(
+                    f,
+                    "Could not read settings file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            SettingsError::MissingFields { fields } => {
+                let fields: Vec<String> = fields.iter().map(|f| format!("`{}`", f)).collect();
+                write!(
+                    f,
+                    "Incomplete settings blob: missing field{} {}",
+                    if fields.len() == 1 { "" } else { "s" },
+                    fields.join(", ")
+                )
+            }
+            SettingsError::MissingSecretFiles { dir, files } => write!(
+                f,
+                "Secrets directory {} is missing files: {}",
+                dir.display(),
+                files.join(", ")
+            ),
+            SettingsError::InvalidDotenv { path, line, reason } => {
+                write!(f, "Invalid line {} in {}: {}", line, path.display(), reason)
+            }
+            SettingsError::ReaderFailed(e) => {
+                write!(f, "Could not read settings blob: {}", e)
+            }
+            SettingsError::InvalidJson(e) => {
+                write!(f, "Could not deserialize settings blob: {}", e)
+            }
+            SettingsError::InvalidBase64(reason) => {
+                write!(f, "Could not decode base64 settings blob: {}", reason)
+            }
+            SettingsError::InvalidBlobKey(reason) => {
+                write!(f, "Invalid settings blob key: {}", reason)
+            }
+            SettingsError::BlobDecryption(reason) => {
+                write!(f, "Could not decrypt settings blob: {}", reason)
+            }
+            SettingsError::InvalidJsonOrBase64 { json, base64 } => write!(
+                f,
+                "Could not deserialize settings blob: {}; also tried it as base64: {}",
+                json, base64
+            ),
+            SettingsError::InvalidAfterCleanup { removed, source } => {
+                write!(f, "{} (also tried after removing {})", source, removed)
+            }
+            #[cfg(feature = "json5")]
+            SettingsError::InvalidJson5 { json, json5 } => write!(
+                f,
+                "Could not deserialize settings blob: {}; also tried it as JSON5: {}",
+                json, json5
+            ),
+            SettingsError::InvalidToml(e) => {
+                write!(
+                    f,
+                    "Could not deserialize TOML settings blob: {}",
+                    e.message()
+                )
+            }
+            #[cfg(feature = "yaml")]
+            SettingsError::InvalidYaml(reason) => {
+                write!(f, "Could not deserialize YAML settings blob: {}", reason)
+            }
+            #[cfg(feature = "figment")]
+            SettingsError::Figment(e) => {
+                write!(f, "Could not extract settings from figment: {}", e)
+            }
+            SettingsError::UnsupportedFormat { format, supported } => write!(
+                f,
+                "Unsupported settings blob format '{}', expected one of: {}",
+                format, supported
+            ),
+            SettingsError::DuplicateField { field, nested } => write!(
+                f,
+                "{} is given both at the top level and as {}",
+                field, nested
+            ),
+            SettingsError::UnsupportedSchemaVersion { version } => write!(
+                f,
+                "Settings blob schema version {} is newer than this crate supports",
+                version
+            ),
+            SettingsError::UnknownFields { keys } => {
+                write!(f, "Unknown fields in settings blob: {}", keys.join(", "))
+            }
+            SettingsError::SecretStore {
+                store,
+                name,
+                source,
+            } => write!(
+                f,
+                "Could not read secret '{}' from {}: {}",
+                name, store, source
+            ),
+            SettingsError::InvalidEmailAddress {
+                field,
+                address,
+                position,
+            } => write!(
+                f,
+                "{}: '{}' (entry {}) is not a valid email address",
+                field, address, position
+            ),
+            SettingsError::MissingField { field } => write!(f, "{}: not set", field),
+            SettingsError::NoRecipients => {
+                write!(f, "EmailToAddresses: no recipients to send the message to")
+            }
+            SettingsError::TooManyRecipients { count } => write!(
+                f,
+                "{} recipients across To, Cc and Bcc, but SendGrid accepts at most {}",
+                count,
+                crate::SENDGRID_MAX_RECIPIENTS
+            ),
+            SettingsError::InvalidTemplateData => {
+                write!(
+                    f,
+                    "SendgridTemplateId: dynamic template data must be a JSON object"
+                )
+            }
+            #[cfg(feature = "sendgrid")]
+            SettingsError::EmptyAttachmentName { position } => {
+                write!(f, "Attachment {} has an empty filename", position)
+            }
+            #[cfg(feature = "sendgrid")]
+            SettingsError::MessageTooLarge { size } => write!(
+                f,
+                "The message is {} bytes with its attachments, but SendGrid accepts at most {}",
+                size,
+                crate::SENDGRID_MAX_MESSAGE_BYTES
+            ),
+            #[cfg(feature = "remote")]
+            SettingsError::LogWebhook {
+                attempts: 1,
+                source,
+                ..
+            } => write!(f, "Could not post to log webhook: {}", source),
+            #[cfg(feature = "remote")]
+            SettingsError::LogWebhook {
+                attempts, source, ..
+            } => write!(
+                f,
+                "Could not post to log webhook after {} attempts: {}",
+                attempts, source
+            ),
+            #[cfg(feature = "remote")]
+            SettingsError::LogWebhookBatch {
+                delivered,
+                undelivered,
+                source,
+                ..
+            } => write!(
+                f,
+                "Could not post {} of {} log events to log webhook: {}",
+                undelivered.len(),
+                delivered + undelivered.len(),
+                source
+            ),
+            SettingsError::HttpClient(reason) => {
+                write!(f, "Could not create HTTP client: {}", reason)
+            }
+            #[cfg(feature = "remote")]
+            SettingsError::RemoteBlob {
+                url,
+                attempts: 1,
+                source,
+            } => write!(f, "Could not fetch settings blob from {}: {}", url, source),
+            #[cfg(feature = "remote")]
+            SettingsError::RemoteBlob {
+                url,
+                attempts,
+                source,
+            } => write!(
+                f,
+                "Could not fetch settings blob from {} after {} attempts: {}",
+                url, attempts, source
+            ),
+            #[cfg(feature = "log")]
+            SettingsError::LoggerAlreadySet => write!(f, "A logger is already installed"),
+            #[cfg(feature = "sendgrid")]
+            SettingsError::EmailSend {
+                attempts: 1,
+                source,
+            } => {
+                write!(f, "Could not send email: {}", source)
+            }
+            #[cfg(feature = "sendgrid")]
+            SettingsError::EmailSend { attempts, source } => write!(
+                f,
+                "Could not send email after {} attempts: {}",
+                attempts, source
+            ),
+            #[cfg(feature = "sendgrid")]
+            SettingsError::RateLimited { retry_after } => write!(
+                f,
+                "Email not sent: EmailMaxPerHour reached, try again in {}s",
+                // Rounded up, so that retrying after that long succeeds
+                retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)
+            ),
+            #[cfg(feature = "smtp")]
+            SettingsError::SmtpSend { host, source } => {
+                write!(
+                    f,
+                    "Could not send email through SMTP relay {}: {}",
+                    host, source
+                )
+            }
+            #[cfg(feature = "smtp")]
+            SettingsError::EmailFallback {
+                sendgrid_attempts,
+                sendgrid,
+                host,
+                smtp,
+            } => write!(
+                f,
+                "Could not send email through SendGrid or the SMTP fallback; \
+                 SendGrid after {1} attempt{2}: {0}; SMTP relay {3}: {4}",
+                sendgrid,
+                sendgrid_attempts,
+                if *sendgrid_attempts == 1 { "" } else { "s" },
+                host,
+                smtp
+            ),
+            #[cfg(feature = "mssql")]
+            SettingsError::DatabaseConnection { address, source } => {
+                write!(f, "Could not connect to database {}: {}", address, source)
+            }
+            SettingsError::DatabaseTimeout { address, timeout } => write!(
+                f,
+                "Timed out after {}s connecting to database {}",
+                timeout.as_secs(),
+                address
+            ),
+            #[cfg(feature = "mssql")]
+            SettingsError::DatabaseFailover { primary, failover } => write!(
+                f,
+                "Could not connect to the primary or the failover database; \
+                 primary: {}; failover: {}",
+                primary, failover
+            ),
+            SettingsError::UnknownDatabase { name, available } => write!(
+                f,
+                "Unknown database '{}', expected one of: {}",
+                name,
+                available.join(", ")
+            ),
+            SettingsError::UnknownProfile { name, available } if available.is_empty() => {
+                write!(f, "Unknown profile '{}': the blob has no profiles", name)
+            }
+            SettingsError::UnknownProfile { name, available } => write!(
+                f,
+                "Unknown profile '{}', expected one of: {}",
+                name,
+                available.join(", ")
+            ),
+            SettingsError::InvalidTenants(reason) => write!(f, "Invalid tenants blob: {}", reason),
+            SettingsError::UnknownTenant { name, available } => write!(
+                f,
+                "Unknown tenant '{}', expected one of: {}",
+                name,
+                available.join(", ")
+            ),
+            SettingsError::Tenant { name, source } => write!(f, "Tenant '{}': {}", name, source),
+            SettingsError::MissingInterpolatedVar { name, field } => write!(
+                f,
+                "Env variable {} is not set, but {} refers to it as ${{{}}}",
+                name, field, name
+            ),
+            SettingsError::InvalidInterpolation { field, reason } => {
+                write!(f, "Invalid interpolation in {}: {}", field, reason)
+            }
+            SettingsError::InvalidUrl { field, reason } => write!(f, "{}: {}", field, reason),
+            #[cfg(feature = "schema")]
+            SettingsError::SchemaViolations(violations) => {
+                write!(f, "Blob does not match the settings schema: ")?;
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", violation)?;
+                }
+                Ok(())
+            }
+            SettingsError::Validation(errors) => {
+                write!(f, "Invalid settings: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl SettingsError {
+    /// Whether the failure is transient, so trying the same load again later may succeed:
+    /// a throttled secret store, a remote blob that timed out, failed to connect or got a
+    /// 5xx, or a database that did not answer in time. Structural problems with the blob
+    /// itself are never retryable.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SettingsError::SecretStore { source, .. } => {
+                matches!(source, SecretStoreError::Throttled(_))
+            }
+            #[cfg(feature = "remote")]
+            SettingsError::RemoteBlob { source, .. } => source.is_retryable(),
+            SettingsError::DatabaseTimeout { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether an outbound HTTP call ran out of time, against the webhook,
+    /// SendGrid or the URL of `Settings::from_url`. Those were already retried
+    /// as the settings allow; a caller may still want to try again later
+    /// rather than give up.
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            #[cfg(feature = "remote")]
+            SettingsError::LogWebhook { source, .. }
+            | SettingsError::LogWebhookBatch { source, .. } => {
+                matches!(source, WebhookError::Timeout(_))
+            }
+            #[cfg(feature = "sendgrid")]
+            SettingsError::EmailSend { source, .. } => matches!(source, SendError::Timeout(_)),
+            #[cfg(feature = "smtp")]
+            SettingsError::SmtpSend { source, .. }
+            | SettingsError::EmailFallback { smtp: source, .. } => {
+                matches!(source, SmtpError::Timeout(_))
+            }
+            #[cfg(feature = "remote")]
+            SettingsError::RemoteBlob { source, .. } => {
+                matches!(source, RemoteBlobError::Timeout(_))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Error for SettingsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SettingsError::FileUnreadable { source, .. } => Some(source),
+            SettingsError::ReaderFailed(e) => Some(e),
+            SettingsError::InvalidJson(e) => Some(e),
+            SettingsError::InvalidJsonOrBase64 { json, .. } => Some(json),
+            SettingsError::InvalidAfterCleanup { source, .. } => Some(source),
+            SettingsError::Tenant { source, .. } => Some(source),
+            #[cfg(feature = "json5")]
+            SettingsError::InvalidJson5 { json, .. } => Some(json),
+            SettingsError::InvalidToml(e) => Some(e),
+            #[cfg(feature = "figment")]
+            SettingsError::Figment(e) => Some(e),
+            SettingsError::SecretStore { source, .. } => Some(source),
+            #[cfg(feature = "mssql")]
+            SettingsError::DatabaseConnection { source, .. } => Some(source),
+            #[cfg(feature = "mssql")]
+            SettingsError::DatabaseFailover { failover, .. } => Some(failover.as_ref()),
+            #[cfg(feature = "sendgrid")]
+            SettingsError::EmailSend { source, .. } => Some(source),
+            #[cfg(feature = "smtp")]
+            SettingsError::SmtpSend { source, .. }
+            | SettingsError::EmailFallback { smtp: source, .. } => Some(source),
+            #[cfg(feature = "remote")]
+            SettingsError::LogWebhook { source, .. }
+            | SettingsError::LogWebhookBatch { source, .. } => Some(source),
+            #[cfg(feature = "remote")]
+            SettingsError::RemoteBlob { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SettingsError {
+    fn from(e: serde_json::Error) -> SettingsError {
+        SettingsError::InvalidJson(e)
+    }
+}
+
+impl From<toml::de::Error> for SettingsError {
+    fn from(e: toml::de::Error) -> SettingsError {
+        SettingsError::InvalidToml(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_display_lists_every_field() {
+        let err = SettingsError::Validation(vec![
+            FieldError::new("DatabaseServer", "must not be empty"),
+            FieldError::new("SendgridApiKey", "must not be empty"),
+        ]);
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid settings: DatabaseServer: must not be empty; SendgridApiKey: must not be empty"
+        );
+    }
+
+    #[test]
+    fn test_invalid_json_exposes_source() {
+        let json_err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let err = SettingsError::from(json_err);
+
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn test_transient_failures_are_retryable() {
+        let timeout = SettingsError::DatabaseTimeout {
+            address: "sql01:1433".to_string(),
+            timeout: Duration::from_secs(15),
+        };
+
+        assert!(timeout.is_retryable());
+        assert!(!SettingsError::UnknownFields { keys: Vec::new() }.is_retryable());
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn test_remote_blob_is_retryable_unless_refused() {
+        let remote = |source| SettingsError::RemoteBlob {
+            url: "https://config.example.com/settings".to_string(),
+            attempts: 3,
+            source,
+        };
+
+        assert!(remote(RemoteBlobError::Status { status: 503 }).is_retryable());
+        assert!(remote(RemoteBlobError::Transport("refused".to_string())).is_retryable());
+        assert!(remote(RemoteBlobError::Timeout("30s".to_string())).is_retryable());
+        assert!(!remote(RemoteBlobError::Status { status: 403 }).is_retryable());
+        assert!(!remote(RemoteBlobError::TooLarge { limit: 1024 }).is_retryable());
+    }
+}
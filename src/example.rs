@@ -0,0 +1,401 @@
+use crate::Settings;
+use serde_json::Value;
+
+// Every blob field, in the order of the struct: the key, its placeholder as
+// JSON, and what goes there. Numbers, flags and enums get their default, or a
+// typical value where there is none, since a placeholder string would not
+// deserialize
+const FIELDS: &[(&str, &str, &str)] = &[
+    (
+        "DatabaseServer",
+        r#""<database server hostname>""#,
+        "SQL Server host, optionally followed by `\\instance` or `,port`.",
+    ),
+    (
+        "DatabaseName",
+        r#""<database name>""#,
+        "The database to connect to.",
+    ),
+    (
+        "DatabaseUsername",
+        r#""<sql login name>""#,
+        "SQL login name; not needed with integrated authentication.",
+    ),
+    (
+        "DatabasePassword",
+        r#""<sql login password>""#,
+        "SQL login password; not needed with integrated authentication.",
+    ),
+    (
+        "DatabasePort",
+        "1433",
+        "TCP port of the server, 1433 unless set.",
+    ),
+    (
+        "DatabaseFailoverServer",
+        r#""<mirror server hostname>""#,
+        "The mirror to connect to when DatabaseServer is down; uses DatabasePort unless it names its own.",
+    ),
+    (
+        "DatabaseEncryption",
+        r#""Off""#,
+        "Off (the default), On or Required.",
+    ),
+    (
+        "DatabaseTrustCert",
+        "false",
+        "Whether to accept any server certificate; by default only with encryption Off and no DatabaseCaCertPath.",
+    ),
+    (
+        "DatabaseCaCertPath",
+        r#""<path to the CA bundle, .pem or .crt>""#,
+        "A PEM bundle with the CA the server certificate must chain to; leave out to use the system roots.",
+    ),
+    (
+        "DatabaseAuthMethod",
+        r#""SqlServer""#,
+        "SqlServer (the default) or Integrated.",
+    ),
+    (
+        "DatabaseConnectionString",
+        r#""<ado.net connection string, or remove this field>""#,
+        "An ADO.NET connection string; the database fields above override the values in it.",
+    ),
+    (
+        "DatabaseReadOnlyIntent",
+        "false",
+        "Whether to connect with `ApplicationIntent=ReadOnly`.",
+    ),
+    (
+        "Databases",
+        r#"{
+            "<database name>": {
+                "Server": "<database server hostname>",
+                "Name": "<database name>",
+                "Username": "<sql login name>",
+                "Password": "<sql login password>",
+                "Port": 1433
+            }
+        }"#,
+        "Further databases, by the name passed to `get_sql_settings_named`.",
+    ),
+    (
+        "ApplicationName",
+        r#""<application name>""#,
+        "Reported to SQL Server as the connection's `APP_NAME()`.",
+    ),
+    (
+        "DatabaseConnectTimeoutSeconds",
+        "15",
+        "How long to wait for the database connection, in seconds.",
+    ),
+    (
+        "DatabaseCommandTimeoutSeconds",
+        "30",
+        "How long a single query may run, in seconds.",
+    ),
+    (
+        "DatabasePoolMaxSize",
+        "10",
+        "Most connections `build_pool` keeps open, 10 unless set.",
+    ),
+    (
+        "DatabasePoolTimeoutSeconds",
+        "30",
+        "How long to wait for a free pooled connection, in seconds.",
+    ),
+    (
+        "DatabasePoolTestOnCheckout",
+        "false",
+        "Whether pooled connections run `SELECT 1` before they are reused.",
+    ),
+    (
+        "LogWebhookUri",
+        r#""<log webhook url>""#,
+        "Webhook that log lines are posted to.",
+    ),
+    (
+        "LogWebhookAuthHeader",
+        r#""<header, e.g. x-functions-key>""#,
+        "Header sent with every log post; leave out if the webhook needs none.",
+    ),
+    (
+        "LogWebhookAuthValue",
+        r#""<header value>""#,
+        "Value of LogWebhookAuthHeader, e.g. a function key or `Bearer <token>`.",
+    ),
+    (
+        "LogWebhookTimeoutSeconds",
+        "10",
+        "Request timeout for the log webhook, in seconds.",
+    ),
+    (
+        "LogWebhookMaxRetries",
+        "3",
+        "How often a failed log post is retried.",
+    ),
+    (
+        "LogWebhookBatchSize",
+        "50",
+        "How many events a batched log post sends per request.",
+    ),
+    (
+        "LogWebhookFormat",
+        r#""Json""#,
+        "Json (the default), Teams or Slack.",
+    ),
+    (
+        "LogWebhookMinLevel",
+        r#""Debug""#,
+        "The least severe level posted: Debug, Info, Warning or Error.",
+    ),
+    (
+        "LogWebhookModuleAllowList",
+        r#""report, report_core::load""#,
+        "Module path prefixes whose log records are posted, comma-separated; leave out for all.",
+    ),
+    (
+        "SendgridApiKey",
+        r#""<sendgrid api key>""#,
+        "SendGrid API key for report emails.",
+    ),
+    (
+        "EmailFromName",
+        r#""<sender display name>""#,
+        "Display name of the report email sender.",
+    ),
+    (
+        "EmailFromAddress",
+        r#""<sender email address>""#,
+        "Sender address of report emails.",
+    ),
+    (
+        "EmailToAddresses",
+        r#""<recipient address>; <recipient address>""#,
+        "Report recipients, separated by commas or semicolons.",
+    ),
+    (
+        "EmailCcAddresses",
+        r#""<cc address>""#,
+        "Cc recipients, separated by commas or semicolons.",
+    ),
+    (
+        "EmailBccAddresses",
+        r#""<bcc address>""#,
+        "Bcc recipients, separated by commas or semicolons.",
+    ),
+    (
+        "EmailReplyToAddress",
+        r#""<reply-to address>""#,
+        "Address replies to report emails go to.",
+    ),
+    (
+        "EmailReplyToName",
+        r#""<reply-to display name>""#,
+        "Display name for the reply-to address.",
+    ),
+    (
+        "EmailMaxRetries",
+        "3",
+        "How often a failed send is retried.",
+    ),
+    (
+        "EmailRetryBaseMs",
+        "500",
+        "Delay before the first email retry, in milliseconds; it doubles after each retry.",
+    ),
+    (
+        "EmailMaxPerHour",
+        "100",
+        "How many emails may be sent per hour; unlimited if absent.",
+    ),
+    (
+        "EmailAllowEmptyRecipients",
+        "false",
+        "Whether a report with no recipients at all is skipped instead of failing.",
+    ),
+    (
+        "EmailRecipientSoftLimit",
+        "100",
+        "Recipients across To, Cc and Bcc above which a warning is reported; 100 unless set.",
+    ),
+    (
+        "SendgridTemplateId",
+        r#""<dynamic template id>""#,
+        "The SendGrid dynamic template report emails are built from.",
+    ),
+    (
+        "EmailSandboxMode",
+        "false",
+        "Whether SendGrid only validates report emails instead of delivering them.",
+    ),
+    (
+        "EmailCategories",
+        r#""<environment>, <report name>""#,
+        "Comma-separated SendGrid categories report emails are tagged with.",
+    ),
+    (
+        "EmailCustomArgs",
+        r#"{"Environment": "<environment>"}"#,
+        "SendGrid custom args for report emails, a JSON object of string values.",
+    ),
+    (
+        "EmailAsmGroupId",
+        "12345",
+        "The SendGrid unsubscribe group report emails belong to.",
+    ),
+    (
+        "EmailAsmGroupsToDisplay",
+        r#""12345, 12346""#,
+        "Comma-separated unsubscribe group ids shown on the preferences page.",
+    ),
+    (
+        "SendgridApiHost",
+        r#""https://api.sendgrid.com""#,
+        "The SendGrid API host; https://api.eu.sendgrid.com for EU data residency.",
+    ),
+    (
+        "SmtpHost",
+        r#""smtp.example.com""#,
+        "The SMTP relay report emails fall back to when SendGrid is down; leave out to not fall back.",
+    ),
+    (
+        "SmtpPort",
+        "587",
+        "The SMTP relay's port; 587, or 25 with SmtpUseStartTls off, if absent.",
+    ),
+    (
+        "SmtpUsername",
+        r#""<smtp username>""#,
+        "Username for the SMTP relay; leave out if it takes mail without a login.",
+    ),
+    (
+        "SmtpPassword",
+        r#""<smtp password>""#,
+        "Password for the SMTP relay.",
+    ),
+    (
+        "SmtpUseStartTls",
+        "true",
+        "Whether the SMTP connection must be upgraded with STARTTLS.",
+    ),
+    (
+        "HttpProxyUri",
+        r#""http://proxy.example.com:3128""#,
+        "The proxy outbound HTTP calls go through; leave out to connect directly.",
+    ),
+    (
+        "HttpProxyUsername",
+        r#""<proxy username>""#,
+        "Username for the HTTP proxy.",
+    ),
+    (
+        "HttpProxyPassword",
+        r#""<proxy password>""#,
+        "Password for the HTTP proxy.",
+    ),
+    (
+        "HttpConnectTimeoutSeconds",
+        "10",
+        "Connect timeout for the SendGrid API and the log webhook, in seconds, up to 300.",
+    ),
+    (
+        "HttpRequestTimeoutSeconds",
+        "30",
+        "Request timeout for the SendGrid API, in seconds, up to 300.",
+    ),
+    (
+        "SchemaVersion",
+        "1",
+        "The layout of this blob: 1 for these flat keys, 2 for the groups of the nested shape.",
+    ),
+];
+
+impl Settings {
+    /// A template for a new environment's blob: pretty-printed JSON with every
+    /// field present and `<placeholder>` strings where a value must be filled
+    /// in. Numbers, flags and enums are set to their defaults, or to typical
+    /// values. Fields a service doesn't use can simply be removed.
+    ///
+    /// JSON has no comments; [`Settings::example_blob_annotated`] describes
+    /// each field.
+    pub fn example_blob() -> String {
+        let fields: Vec<String> = FIELDS
+            .iter()
+            .map(|(key, placeholder, _)| {
+                let value: Value =
+                    serde_json::from_str(placeholder).expect("placeholders are valid JSON");
+                let value = serde_json::to_string_pretty(&value)
+                    .expect("a JSON value serializes")
+                    .replace('\n', "\n  ");
+                format!("  \"{}\": {}", key, value)
+            })
+            .collect();
+        format!("{{\n{}\n}}", fields.join(",\n"))
+    }
+
+    /// The fields of [`Settings::example_blob`], in the same order, each with
+    /// a one-line description.
+    pub fn example_blob_annotated() -> Vec<(&'static str, &'static str)> {
+        FIELDS
+            .iter()
+            .map(|(key, _, description)| (*key, *description))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Profile;
+
+    #[test]
+    fn test_example_blob_round_trips() {
+        let blob = Settings::example_blob();
+        let example: Value = serde_json::from_str(&blob).unwrap();
+
+        let (settings, unknown) =
+            Settings::parse_json_reporting_unknown(&blob, Profile::FromEnv).unwrap();
+        assert!(unknown.is_empty(), "{:?}", unknown);
+        assert_eq!(serde_json::to_value(&settings).unwrap(), example);
+        assert_eq!(settings.database_server(), "<database server hostname>");
+    }
+
+    #[test]
+    fn test_example_blob_keeps_field_order() {
+        let blob = Settings::example_blob();
+
+        assert!(blob.starts_with("{\n  \"DatabaseServer\": \"<database server hostname>\",\n"));
+        assert!(blob.ends_with("\n  \"SchemaVersion\": 1\n}"));
+        let keys: Vec<&str> = blob
+            .lines()
+            .filter_map(|line| line.strip_prefix("  \""))
+            .filter_map(|line| line.split('"').next())
+            .collect();
+        let annotated: Vec<&str> = Settings::example_blob_annotated()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, annotated);
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn test_example_blob_has_every_field() {
+        let schema = Settings::json_schema();
+        let mut properties: Vec<&str> = schema["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut annotated: Vec<&str> = Settings::example_blob_annotated()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        properties.sort();
+        annotated.sort();
+        assert_eq!(annotated, properties);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::sql::{parse_server, ServerAddress};
+use crate::{FieldError, Settings};
+#[cfg(feature = "mssql")]
+use ssql::prelude::tiberius::Config;
+#[cfg(feature = "mssql")]
+use std::fmt;
+
+/// Which server [`Settings::connect_with_failover`] ended up connected to.
+#[cfg(feature = "mssql")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseServerRole {
+    /// `DatabaseServer`.
+    Primary,
+    /// `DatabaseFailoverServer`, after the primary refused the connection or
+    /// timed out.
+    Failover,
+}
+
+#[cfg(feature = "mssql")]
+impl fmt::Display for DatabaseServerRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DatabaseServerRole::Primary => "primary",
+            DatabaseServerRole::Failover => "failover",
+        })
+    }
+}
+
+impl Settings {
+    /// `DatabaseFailoverServer` as given in the blob, if any.
+    pub fn database_failover_server(&self) -> Option<&str> {
+        self.database_failover_server.as_deref()
+    }
+
+    /// The tiberius configuration for `DatabaseFailoverServer`, or `None` if
+    /// it is not set: the same as [`Settings::get_sql_settings`] but for the
+    /// server. The failover server takes the forms `DatabaseServer` does, and
+    /// uses `DatabasePort` unless it names its own port or instance.
+    ///
+    /// Only the default database fails over; the entries of `Databases` don't.
+    #[cfg(feature = "mssql")]
+    pub fn get_sql_settings_failover(&self) -> Option<Config> {
+        self.failover_config(&mut Vec::new())
+    }
+
+    // Only the problems with the failover server itself are collected; those
+    // it shares with the primary are reported by `sql_config`
+    #[cfg(feature = "mssql")]
+    pub(crate) fn failover_config(&self, errors: &mut Vec<FieldError>) -> Option<Config> {
+        let address = self.failover_address(errors)?;
+        Some(match self.database_connection_string {
+            Some(_) => {
+                let mut pairs = self.connection_string_pairs(&mut Vec::new());
+                let server = crate::ado::ado_server(&address);
+                match pairs.iter_mut().find(|(key, _)| key == "Server") {
+                    Some(pair) => pair.1 = server,
+                    None => pairs.insert(0, ("Server".to_string(), server)),
+                }
+                crate::ado::ado_config(&pairs, &mut Vec::new())
+            }
+            None => self.config_at(&address, &self.flat_target()),
+        })
+    }
+
+    pub(crate) fn failover_address(
+        &self,
+        errors: &mut Vec<FieldError>,
+    ) -> Option<ServerAddress<'_>> {
+        let server = self.database_failover_server.as_deref()?;
+        if server.trim().is_empty() {
+            errors.push(FieldError::new(
+                "DatabaseFailoverServer",
+                "must not be empty",
+            ));
+            return None;
+        }
+        let mut address = parse_server(server).unwrap_or_else(|reason| {
+            errors.push(FieldError::new("DatabaseFailoverServer", reason));
+            ServerAddress {
+                host: server.trim(),
+                instance: None,
+                port: None,
+            }
+        });
+        // A mirror usually listens where the primary does
+        if address.instance.is_none() && address.port.is_none() {
+            address.port = self.database_port;
+        }
+        Some(address)
+    }
+
+    pub(crate) fn failover_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        self.failover_address(&mut errors);
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failover_server_is_checked() {
+        for (server, error) in [
+            (" ", "DatabaseFailoverServer: must not be empty"),
+            (
+                "sql02,port",
+                "DatabaseFailoverServer: 'port' is not a valid port",
+            ),
+            (
+                "sql02\\",
+                "DatabaseFailoverServer: 'sql02\\' is not a valid host\\instance name",
+            ),
+        ] {
+            let settings = Settings::for_tests().with_database_failover_server(server);
+
+            assert_eq!(
+                settings.validate().unwrap_err()[0].to_string(),
+                error,
+                "{}",
+                server
+            );
+        }
+        assert_eq!(
+            Settings::for_tests()
+                .with_database_failover_server("sql02\\MIRROR")
+                .validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_failover_config_swaps_only_the_server() {
+        let settings = Settings::for_tests()
+            .with_database_server("sql01")
+            .with_database_port(14330);
+        assert!(settings.get_sql_settings_failover().is_none());
+
+        for (failover, addr) in [
+            ("sql02", "sql02:14330"),
+            ("sql02,1533", "sql02:1533"),
+            ("sql02\\MIRROR", "sql02:1434"),
+        ] {
+            let config = settings
+                .clone()
+                .with_database_failover_server(failover)
+                .get_sql_settings_failover()
+                .unwrap();
+
+            assert_eq!(config.get_addr(), addr, "{}", failover);
+        }
+        assert_eq!(settings.get_sql_settings().get_addr(), "sql01:14330");
+
+        let from_string = Settings::builder()
+            .database_connection_string("Server=sql01;Database=reports;User Id=svc;Password=pw")
+            .database_failover_server("sql02,1533")
+            .build()
+            .unwrap();
+        let primary = format!("{:?}", from_string.get_sql_settings());
+        let failover = format!("{:?}", from_string.get_sql_settings_failover().unwrap());
+        assert_eq!(
+            failover,
+            primary
+                .replace("\"sql01\"", "\"sql02\"")
+                .replace("port: None", "port: Some(1533)")
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use crate::loader::set_fields;
+use crate::{Settings, SettingsError};
+use figment::providers::Serialized;
+use figment::value::{Dict, Map};
+use figment::{Error, Figment, Metadata, Profile, Provider};
+
+/// A [`figment::Provider`] of report settings, so they can be composed with
+/// an application's other configuration:
+///
+/// ```no_run
+/// use figment::providers::{Format, Toml};
+/// use figment::Figment;
+/// use reportsettings_rust::{ReportSettingsProvider, Settings};
+///
+/// let figment = Figment::new()
+///     .merge(Toml::file("app.toml"))
+///     .merge(ReportSettingsProvider::new().prefix("report"));
+/// let settings = Settings::from_figment(&figment.focus("report"))?;
+/// # Ok::<(), reportsettings_rust::SettingsError>(())
+/// ```
+///
+/// The fields are emitted under their PascalCase blob keys. Empty string
+/// fields are left out, like unset ones, so they don't replace values from
+/// providers merged earlier.
+#[derive(Debug, Clone, Default)]
+pub struct ReportSettingsProvider {
+    settings: Option<Settings>,
+    prefix: Option<String>,
+    profile: Option<String>,
+}
+
+impl ReportSettingsProvider {
+    /// Provides the settings [`Settings::get_settings`] loads, read when the
+    /// figment asks for them.
+    pub fn new() -> ReportSettingsProvider {
+        ReportSettingsProvider::default()
+    }
+
+    /// Provides these settings rather than loading them.
+    pub fn from_settings(settings: Settings) -> ReportSettingsProvider {
+        ReportSettingsProvider {
+            settings: Some(settings),
+            ..ReportSettingsProvider::default()
+        }
+    }
+
+    /// Nests the fields under `prefix`, which may be a dotted path such as
+    /// `app.report`, instead of emitting them at the top level.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> ReportSettingsProvider {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Loads the blob profile `name` (see [`Settings::get_settings_for_profile`])
+    /// and emits it into the figment profile of the same name, which
+    /// [`Figment::select`] picks. Without this, the blob profile named by
+    /// `Environment` goes into figment's default profile.
+    ///
+    /// Settings given to [`ReportSettingsProvider::from_settings`] are only
+    /// emitted into that profile.
+    pub fn profile(mut self, name: impl Into<String>) -> ReportSettingsProvider {
+        self.profile = Some(name.into());
+        self
+    }
+}
+
+impl Provider for ReportSettingsProvider {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("report settings")
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let loaded;
+        let settings = match (&self.settings, &self.profile) {
+            (Some(settings), _) => settings,
+            (None, profile) => {
+                loaded = match profile {
+                    Some(name) => Settings::get_settings_for_profile(name),
+                    None => Settings::get_settings(),
+                }
+                .map_err(|e| Error::from(e.to_string()))?;
+                &loaded
+            }
+        };
+
+        let fields = set_fields(settings).map_err(|e| Error::from(e.to_string()))?;
+        let profile = self
+            .profile
+            .as_deref()
+            .map_or(Profile::Default, Profile::new);
+        let provider = Serialized::from(fields, profile);
+        match &self.prefix {
+            Some(prefix) => provider.key(prefix).data(),
+            None => provider.data(),
+        }
+    }
+}
+
+impl Settings {
+    /// Extracts settings from the top level of a composed figment, with the
+    /// same keys and aliases as the JSON blob. When the fields are nested
+    /// under a prefix, pass `figment.focus(prefix)`.
+    pub fn from_figment(figment: &Figment) -> Result<Settings, SettingsError> {
+        figment
+            .extract()
+            .map_err(|e| SettingsError::Figment(Box::new(e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::providers::{Format, Toml};
+
+    fn toml_file(contents: &str) -> tempfile::NamedTempFile {
+        let file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        std::fs::write(file.path(), contents).unwrap();
+        file
+    }
+
+    fn provider() -> ReportSettingsProvider {
+        ReportSettingsProvider::from_settings(
+            Settings::builder()
+                .database_server("sql01")
+                .database_name("reports")
+                .database_username("svc_report")
+                .database_password("hunter2")
+                .build()
+                .unwrap(),
+        )
+    }
+
+    const APP_TOML: &str = r#"
+        name = "costs"
+
+        [report]
+        DatabaseServer = "from-toml"
+        DatabaseUsername = ""
+        EmailFromName = "Cost Reports"
+    "#;
+
+    #[test]
+    fn test_merged_provider_wins_over_toml() {
+        let file = toml_file(APP_TOML);
+
+        let figment = Figment::new()
+            .merge(Toml::file(file.path()))
+            .merge(provider().prefix("report"));
+        let settings = Settings::from_figment(&figment.focus("report")).unwrap();
+
+        assert_eq!(settings.database_server(), "sql01");
+        assert_eq!(settings.database_username(), "svc_report");
+        assert_eq!(settings.email_from_name(), Some("Cost Reports"));
+        assert_eq!(figment.extract_inner::<String>("name").unwrap(), "costs");
+    }
+
+    #[test]
+    fn test_joined_provider_only_fills_gaps() {
+        let file = toml_file(APP_TOML);
+
+        let figment = Figment::new()
+            .merge(Toml::file(file.path()))
+            .join(provider().prefix("report"));
+        let settings = Settings::from_figment(&figment.focus("report")).unwrap();
+
+        assert_eq!(settings.database_server(), "from-toml");
+        assert_eq!(settings.database_username(), "");
+        assert_eq!(settings.database_name(), "reports");
+        assert_eq!(settings.expose_database_password(), "hunter2");
+    }
+
+    #[test]
+    fn test_profile_is_selected_by_name() {
+        let staging = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports_staging",
+                "DatabaseUsername": "svc_report", "DatabasePassword": "hunter2"}"#,
+        )
+        .unwrap();
+        let figment = Figment::new()
+            .merge(provider())
+            .merge(ReportSettingsProvider::from_settings(staging).profile("Staging"));
+
+        let selected = Settings::from_figment(&figment.clone().select("staging")).unwrap();
+        let default = Settings::from_figment(&figment).unwrap();
+
+        assert_eq!(selected.database_name(), "reports_staging");
+        assert_eq!(selected.database_server(), "sql01");
+        assert_eq!(default.database_name(), "reports");
+    }
+
+    #[test]
+    fn test_extract_error() {
+        let figment = Figment::new().merge(("DatabasePort", "lots"));
+
+        let err = Settings::from_figment(&figment).unwrap_err();
+
+        assert!(matches!(err, SettingsError::Figment(_)));
+        assert!(err.to_string().contains("DatabasePort"), "{}", err);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::redacted::mask;
+use crate::Settings;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+impl Settings {
+    /// A short hash of every field, such as `3f9a1c0b7d2e`, for telling from
+    /// logs whether two runs had the same settings without showing them.
+    ///
+    /// It is the first 12 hex digits of the SHA-256 of the settings serialized
+    /// with sorted keys, each secret replaced by its own SHA-256. Changing any
+    /// field, a password included, changes it, but it can't be turned back
+    /// into the secrets. Unset fields and fields left at their default aren't
+    /// told apart, and the same settings keep the same fingerprint across runs
+    /// and machines.
+    pub fn fingerprint(&self) -> String {
+        let mut fields = match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields,
+            _ => unreachable!("settings serialize to a JSON object"),
+        };
+
+        for field in [
+            "DatabasePassword",
+            "DatabaseConnectionString",
+            "SendgridApiKey",
+            "HttpProxyPassword",
+            "LogWebhookAuthValue",
+            "SmtpPassword",
+        ] {
+            if let Some(secret) = fields.get_mut(field) {
+                mask_with_hash(secret);
+            }
+        }
+        if let Some(Value::Object(targets)) = fields.get_mut("Databases") {
+            for target in targets.values_mut() {
+                if let Some(password) = target.get_mut("Password") {
+                    mask_with_hash(password);
+                }
+            }
+        }
+
+        let canonical = sorted(Value::Object(fields)).to_string();
+        hex(&Sha256::digest(canonical.as_bytes()))[..12].to_string()
+    }
+}
+
+fn mask_with_hash(secret: &mut Value) {
+    let hash = match secret {
+        Value::String(value) => hex(&Sha256::digest(value.as_bytes())),
+        _ => return,
+    };
+    mask(secret, hash);
+}
+
+// Objects rebuilt with their keys in order, whatever order the map keeps
+fn sorted(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => {
+            let mut fields: Vec<(String, Value)> = fields.into_iter().collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, sorted(value)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sorted).collect()),
+        value => value,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DatabaseTarget;
+
+    #[test]
+    fn test_same_settings_same_fingerprint() {
+        let blob = r#"{"DatabaseServer": "sql01", "DatabaseName": "reports",
+            "DatabaseUsername": "svc", "DatabasePassword": "hunter2"}"#;
+        let reordered = r#"{"DatabasePassword": "hunter2", "DatabaseUsername": "svc",
+            "DatabaseName": "reports", "DatabaseServer": "sql01"}"#;
+        let settings = Settings::from_json_str(blob).unwrap();
+
+        // Pinned, so that a change to the hashing shows up here
+        assert_eq!(settings.fingerprint(), "9cada4adcdaa");
+        assert_eq!(
+            Settings::from_json_str(reordered).unwrap().fingerprint(),
+            settings.fingerprint()
+        );
+        assert_eq!(settings.fingerprint().len(), 12);
+    }
+
+    #[test]
+    fn test_any_change_changes_fingerprint() {
+        let base = Settings::for_tests();
+        let changed = [
+            base.clone().with_database_name("invoices"),
+            base.clone().with_database_password("rotated-password"),
+            base.clone().with_sendgrid_api_key("SG.rotated-key"),
+            base.clone().with_email_to_addresses("other@example.com"),
+            base.clone().with_http_proxy_password("proxy-password"),
+            base.clone().with_smtp_password("smtp-password"),
+            base.clone().with_database(
+                "warehouse",
+                DatabaseTarget {
+                    password: "warehouse-password".into(),
+                    ..DatabaseTarget::default()
+                },
+            ),
+            base.clone().with_application_name("Nightly Sales"),
+        ];
+
+        let fingerprint = base.fingerprint();
+        for settings in &changed {
+            assert_ne!(settings.fingerprint(), fingerprint, "{:?}", settings);
+        }
+        assert_eq!(base.clone().fingerprint(), fingerprint);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::migrate;
+use crate::nested;
+use crate::profile::{self, Profile};
+use crate::required::absent_fields;
+use crate::{Settings, SettingsError};
+use serde::de::{Deserializer, Error};
+use serde_json::Value;
+use std::path::Path;
+
+/// The env variable naming the format of the blob, when it isn't sniffed.
+pub(crate) const FORMAT_VAR: &str = "SecretBlobFormat";
+
+/// The serialization formats a settings blob may be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BlobFormat {
+    Json,
+    Toml,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// JSON, falling back to JSON5 for a blob that isn't strict JSON.
+    #[cfg(feature = "json5")]
+    Json5,
+}
+
+impl BlobFormat {
+    #[cfg(all(not(feature = "yaml"), not(feature = "json5")))]
+    const NAMES: &'static str = "json, toml";
+    #[cfg(all(feature = "yaml", not(feature = "json5")))]
+    const NAMES: &'static str = "json, toml, yaml";
+    #[cfg(all(not(feature = "yaml"), feature = "json5"))]
+    const NAMES: &'static str = "json, json5, toml";
+    #[cfg(all(feature = "yaml", feature = "json5"))]
+    const NAMES: &'static str = "json, json5, toml, yaml";
+
+    fn from_name(name: &str) -> Option<BlobFormat> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(BlobFormat::Json),
+            "toml" => Some(BlobFormat::Toml),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(BlobFormat::Yaml),
+            #[cfg(feature = "json5")]
+            "json5" => Some(BlobFormat::Json5),
+            _ => None,
+        }
+    }
+
+    /// Picks the format from a file extension, if the extension names one.
+    pub(crate) fn from_path(path: &Path) -> Result<Option<BlobFormat>, SettingsError> {
+        let extension = match path.extension().and_then(|e| e.to_str()) {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+
+        match BlobFormat::from_name(extension) {
+            Some(format) => Ok(Some(format)),
+            None if matches!(extension, "yaml" | "yml" | "json5") => {
+                Err(SettingsError::UnsupportedFormat {
+                    format: extension.to_string(),
+                    supported: BlobFormat::NAMES,
+                })
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Picks the format `name`d by `SecretBlobFormat`, or sniffs the blob when
+    /// that variable is not set. Anything that doesn't look like TOML is treated
+    /// as JSON, so garbage input keeps producing the familiar JSON error.
+    pub(crate) fn detect(blob: &str, name: Option<&str>) -> Result<BlobFormat, SettingsError> {
+        if let Some(name) = name {
+            return BlobFormat::from_name(name).ok_or_else(|| SettingsError::UnsupportedFormat {
+                format: name.to_string(),
+                supported: BlobFormat::NAMES,
+            });
+        }
+
+        Ok(BlobFormat::sniff(blob))
+    }
+
+    fn sniff(blob: &str) -> BlobFormat {
+        let trimmed = blob.trim_start();
+        let looks_like_toml = match trimmed.chars().next() {
+            Some('{') | None => false,
+            Some('[') | Some('#') => true,
+            // A `key = value` line; a bare `=` run at the end is base64 padding
+            Some(_) => trimmed
+                .lines()
+                .next()
+                .and_then(|line| line.split_once('='))
+                .is_some_and(|(key, value)| {
+                    !key.trim().is_empty() && !value.trim().is_empty() && !value.starts_with('=')
+                }),
+        };
+
+        if looks_like_toml {
+            BlobFormat::Toml
+        } else {
+            BlobFormat::Json
+        }
+    }
+
+    pub(crate) fn parse(self, blob: &str) -> Result<Settings, SettingsError> {
+        self.parse_reporting_unknown(blob, Profile::FromEnv)?
+            .0
+            .require_fields()
+    }
+
+    /// Like [`BlobFormat::parse`], but also returns the path of every key that is
+    /// not a settings field, e.g. `EmailToAddreses` or `Databases.warehouse.Sever`.
+    ///
+    /// Only JSON blobs can have profiles; see [`Settings::get_settings_for_profile`].
+    pub(crate) fn parse_reporting_unknown(
+        self,
+        blob: &str,
+        profile: Profile,
+    ) -> Result<(Settings, Vec<String>), SettingsError> {
+        let mut unknown = Vec::new();
+        let record = |path: serde_ignored::Path| unknown.push(path.to_string());
+        let absent;
+        let settings = match self {
+            BlobFormat::Json => {
+                let parsed: Option<Value> = serde_json::from_str(blob).ok();
+                let is_nested = parsed.as_ref().is_some_and(nested::is_nested);
+                if let Some(parsed) = &parsed {
+                    migrate::schema_version(parsed)?;
+                }
+                let plain_absent = parsed.as_ref().map(absent_fields).unwrap_or_default();
+                match (profile::select(parsed, profile)?, is_nested) {
+                    (Some(mut merged), _) => {
+                        migrate::migrate(&mut merged)?;
+                        absent = absent_fields(&merged);
+                        deserialize(merged, record)?
+                    }
+                    (None, true) => {
+                        let mut flat = serde_json::from_str(blob)?;
+                        migrate::migrate(&mut flat)?;
+                        absent = absent_fields(&flat);
+                        deserialize(flat, record)?
+                    }
+                    (None, false) => {
+                        absent = plain_absent;
+                        let mut deserializer = serde_json::Deserializer::from_str(blob);
+                        let settings = deserialize(&mut deserializer, record)?;
+                        deserializer.end()?;
+                        settings
+                    }
+                }
+            }
+            BlobFormat::Toml => {
+                profile::without_profiles(profile)?;
+                let table: toml::Table = toml::from_str(blob)?;
+                migrate::toml_schema_version(&table)?;
+                if nested::groups().any(|group| table.get(group).is_some_and(toml::Value::is_table))
+                {
+                    // Flattened through JSON, then deserialized as TOML again
+                    // so that type errors keep their TOML wording
+                    let mut flat = serde_json::to_value(table)?;
+                    migrate::migrate(&mut flat)?;
+                    absent = absent_fields(&flat);
+                    let flat = toml::Value::try_from(flat)
+                        .map_err(|e| SettingsError::InvalidToml(serde::de::Error::custom(e)))?;
+                    deserialize(flat, record)?
+                } else {
+                    absent = serde_json::to_value(table)
+                        .map(|table| absent_fields(&table))
+                        .unwrap_or_default();
+                    deserialize(toml::Deserializer::parse(blob)?, record)?
+                }
+            }
+            #[cfg(feature = "yaml")]
+            BlobFormat::Yaml => {
+                profile::without_profiles(profile)?;
+                return crate::yaml::parse(blob);
+            }
+            #[cfg(feature = "json5")]
+            BlobFormat::Json5 => return crate::relaxed::parse(blob, profile),
+        };
+        Ok((settings.with_absent_fields(absent), unknown))
+    }
+}
+
+/// Reads settings from a JSON blob that was parsed some other way, such as
+/// JSON5, or put together from parts of one, like a tenant's settings, with
+/// profiles and nested groups handled as for a JSON blob.
+pub(crate) fn parse_json_value(
+    blob: Value,
+    profile: Profile,
+) -> Result<(Settings, Vec<String>), SettingsError> {
+    migrate::schema_version(&blob)?;
+    let mut merged = match profile::select(Some(blob.clone()), profile)? {
+        Some(merged) => merged,
+        None => blob,
+    };
+    migrate::migrate(&mut merged)?;
+    let absent = absent_fields(&merged);
+    let mut unknown = Vec::new();
+    let settings = deserialize(merged, |path| unknown.push(path.to_string()))?;
+    Ok((settings.with_absent_fields(absent), unknown))
+}
+
+/// Deserializes settings, passing the path of each key that is not a settings
+/// field to `record`. A field of the wrong type fails with its path in front of
+/// the message, e.g. `EmailToAddresses: invalid type: sequence, expected a
+/// string at line 7 column 31`, since serde's message alone doesn't name it.
+pub(crate) fn deserialize<'de, D, F>(deserializer: D, mut record: F) -> Result<Settings, D::Error>
+where
+    D: Deserializer<'de>,
+    D::Error: FieldMessage,
+    F: FnMut(serde_ignored::Path),
+{
+    let deserializer = serde_ignored::Deserializer::new(deserializer, &mut record);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let inner = e.into_inner();
+        // Errors about the blob as a whole, such as a duplicate field, have
+        // no path to add
+        match inner.field_message() {
+            Some(message) if path != "." => D::Error::custom(format!("{}: {}", path, message)),
+            _ => inner,
+        }
+    })
+}
+
+/// The message of a deserialization error, to follow the path of the field,
+/// or `None` if the error is not about the value of a field.
+pub(crate) trait FieldMessage: Error {
+    fn field_message(&self) -> Option<String>;
+}
+
+// Broken JSON keeps its own error, which says where it broke and is still
+// known to be a syntax error
+impl FieldMessage for serde_json::Error {
+    fn field_message(&self) -> Option<String> {
+        match self.classify() {
+            serde_json::error::Category::Data => Some(self.to_string()),
+            _ => None,
+        }
+    }
+}
+
+// The full error shows the blob with the error marked, which doesn't fit after
+// the path
+impl FieldMessage for toml::de::Error {
+    fn field_message(&self) -> Option<String> {
+        Some(self.message().trim_end().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniffs_json_and_toml() {
+        assert_eq!(BlobFormat::sniff(" \n{\"A\": 1}"), BlobFormat::Json);
+        assert_eq!(
+            BlobFormat::sniff("DatabaseServer = \"x\""),
+            BlobFormat::Toml
+        );
+        assert_eq!(BlobFormat::sniff("# comment\nA = 1"), BlobFormat::Toml);
+        assert_eq!(
+            BlobFormat::sniff("[Database]\nServer = 1"),
+            BlobFormat::Toml
+        );
+        assert_eq!(BlobFormat::sniff("invalid json"), BlobFormat::Json);
+        assert_eq!(BlobFormat::sniff("eyJrIjoi//4ifQ=="), BlobFormat::Json);
+        assert_eq!(BlobFormat::sniff("e30=\n"), BlobFormat::Json);
+    }
+
+    #[test]
+    fn test_from_name_is_case_insensitive() {
+        assert_eq!(BlobFormat::from_name("TOML"), Some(BlobFormat::Toml));
+        assert_eq!(BlobFormat::from_name(" Json "), Some(BlobFormat::Json));
+        assert_eq!(BlobFormat::from_name("xml"), None);
+    }
+
+    #[test]
+    fn test_from_path_uses_extension() {
+        assert_eq!(
+            BlobFormat::from_path(Path::new("a.toml")).unwrap(),
+            Some(BlobFormat::Toml)
+        );
+        assert_eq!(
+            BlobFormat::from_path(Path::new("a.JSON")).unwrap(),
+            Some(BlobFormat::Json)
+        );
+        assert_eq!(BlobFormat::from_path(Path::new("settings")).unwrap(), None);
+        assert_eq!(BlobFormat::from_path(Path::new("a.txt")).unwrap(), None);
+
+        #[cfg(feature = "yaml")]
+        assert_eq!(
+            BlobFormat::from_path(Path::new("a.yml")).unwrap(),
+            Some(BlobFormat::Yaml)
+        );
+        #[cfg(not(feature = "yaml"))]
+        assert!(BlobFormat::from_path(Path::new("a.yml")).is_err());
+    }
+
+    #[test]
+    fn test_type_errors_name_the_field() {
+        let wrong_type = r#"{
+            "DatabaseServer": "sql01",
+            "EmailToAddresses": ["a@x.com"]
+        }"#;
+        let nested = r#"{"Databases": {"warehouse": {"Server": "wh-sql", "Port": true}}}"#;
+
+        assert_eq!(
+            Settings::from_json_str(wrong_type).unwrap_err().to_string(),
+            "Could not deserialize settings blob: EmailToAddresses: invalid type: sequence, expected \
+             a string at line 3 column 32"
+        );
+        assert!(Settings::from_json_str(nested)
+            .unwrap_err()
+            .to_string()
+            .contains(
+                "Databases.warehouse.Port: invalid type: boolean `true`, expected a whole number"
+            ));
+        let toml = Settings::from_toml("EmailToAddresses = [\"a@x.com\"]").unwrap_err();
+        assert!(
+            toml.to_string()
+                .contains("EmailToAddresses: invalid type: sequence, expected a string"),
+            "{}",
+            toml
+        );
+        #[cfg(feature = "yaml")]
+        assert!(Settings::from_yaml("DatabaseTrustCert: maybe")
+            .unwrap_err()
+            .to_string()
+            .contains(
+                "DatabaseTrustCert: invalid value: string \"maybe\", expected true or false"
+            ));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{LogLevel, SettingsError, WebhookLogger};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+// Lines are posted in batches of up to this many, or whatever has arrived
+// after FLUSH_INTERVAL, whichever comes first
+const BATCH_SIZE: usize = 50;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+// Lines sent while this many are waiting are dropped rather than blocking the
+// caller
+const QUEUE_CAPACITY: usize = 1024;
+
+enum Command {
+    Line(LogLevel, String),
+    Flush(mpsc::Sender<()>),
+}
+
+/// Queues log lines for a background thread that posts them to the webhook in
+/// batches, each batch as one message at the level of its most severe line.
+/// Lines that can't be delivered are written to stderr.
+#[derive(Clone)]
+pub(crate) struct Forwarder {
+    sender: SyncSender<Command>,
+    worker: ThreadId,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Forwarder {
+    pub(crate) fn spawn(logger: WebhookLogger) -> Result<Forwarder, SettingsError> {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let worker = thread::Builder::new()
+            .name("webhook-log-forwarder".to_string())
+            .spawn(move || run(logger, receiver))
+            .map_err(|e| SettingsError::HttpClient(e.to_string()))?;
+
+        Ok(Forwarder {
+            sender,
+            worker: worker.thread().id(),
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Whether the caller is the background thread. The HTTP client logs too,
+    /// and forwarding its lines would feed back into itself.
+    pub(crate) fn on_worker(&self) -> bool {
+        thread::current().id() == self.worker
+    }
+
+    /// Queues a line without waiting; if the queue is full it is dropped and counted.
+    pub(crate) fn send(&self, level: LogLevel, line: String) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(Command::Line(level, line)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Blocks until every line queued so far has been posted.
+    pub(crate) fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        if self.sender.send(Command::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+
+    /// How many lines were dropped because the queue was full.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+fn run(logger: WebhookLogger, receiver: Receiver<Command>) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("webhook log forwarder could not start: {}", e);
+            return;
+        }
+    };
+
+    let mut batch: Vec<(LogLevel, String)> = Vec::new();
+    let mut deadline = Instant::now() + FLUSH_INTERVAL;
+    loop {
+        let command = receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        let done = match command {
+            Ok(Command::Line(level, line)) => {
+                batch.push((level, line));
+                if batch.len() < BATCH_SIZE {
+                    continue;
+                }
+                None
+            }
+            Ok(Command::Flush(done)) => Some(done),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => {
+                runtime.block_on(post_batch(&logger, &mut batch));
+                return;
+            }
+        };
+
+        runtime.block_on(post_batch(&logger, &mut batch));
+        deadline = Instant::now() + FLUSH_INTERVAL;
+        if let Some(done) = done {
+            let _ = done.send(());
+        }
+    }
+}
+
+async fn post_batch(logger: &WebhookLogger, batch: &mut Vec<(LogLevel, String)>) {
+    let level = match batch.iter().map(|(level, _)| *level).max() {
+        Some(level) => level,
+        None => return,
+    };
+    let lines: Vec<String> = batch.drain(..).map(|(_, line)| line).collect();
+
+    if let Err(SettingsError::LogWebhook {
+        message, source, ..
+    }) = logger.post_log(level, &lines.join("\n")).await
+    {
+        eprintln!("could not post to log webhook ({}):\n{}", source, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_queue_drops_and_counts() {
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let forwarder = Forwarder {
+            sender,
+            worker: thread::current().id(),
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+
+        forwarder.send(LogLevel::Error, "kept".to_string());
+        forwarder.send(LogLevel::Error, "dropped".to_string());
+        forwarder.send(LogLevel::Error, "dropped".to_string());
+
+        assert_eq!(forwarder.dropped(), 2);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{DatabaseAuthMethod, DatabaseEncryption, LogLevel, LogWebhookFormat, Secret, Settings};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// The `Database*` fields of the default database, from [`Settings::database`].
+/// Each accessor is the matching `Settings::database_*` one without the prefix,
+/// with the same defaults.
+#[derive(Debug, Clone, Copy)]
+pub struct DatabaseSettings<'a> {
+    settings: &'a Settings,
+}
+
+/// The `Sendgrid*` and `Email*` fields, from [`Settings::email`].
+#[derive(Debug, Clone, Copy)]
+pub struct EmailSettings<'a> {
+    settings: &'a Settings,
+}
+
+/// The `LogWebhook*` fields, from [`Settings::logging`].
+#[derive(Debug, Clone, Copy)]
+pub struct LoggingSettings<'a> {
+    settings: &'a Settings,
+}
+
+impl Settings {
+    /// The default database's fields, e.g. `settings.database().server()`.
+    /// The other entries of `Databases` aren't included; see
+    /// [`Settings::database_names`].
+    pub fn database(&self) -> DatabaseSettings<'_> {
+        DatabaseSettings { settings: self }
+    }
+
+    /// The email fields, e.g. `settings.email().from()`.
+    pub fn email(&self) -> EmailSettings<'_> {
+        EmailSettings { settings: self }
+    }
+
+    /// The log webhook fields, e.g. `settings.logging().webhook_uri()`.
+    pub fn logging(&self) -> LoggingSettings<'_> {
+        LoggingSettings { settings: self }
+    }
+}
+
+impl<'a> DatabaseSettings<'a> {
+    pub fn server(&self) -> &'a str {
+        self.settings.database_server()
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.settings.database_name()
+    }
+
+    pub fn username(&self) -> &'a str {
+        self.settings.database_username()
+    }
+
+    /// See [`Settings::expose_database_password`].
+    pub fn expose_password(&self) -> &'a str {
+        self.settings.expose_database_password()
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.settings.database_port()
+    }
+
+    pub fn failover_server(&self) -> Option<&'a str> {
+        self.settings.database_failover_server()
+    }
+
+    pub fn encryption(&self) -> DatabaseEncryption {
+        self.settings.database_encryption()
+    }
+
+    pub fn trust_cert(&self) -> bool {
+        self.settings.database_trust_cert()
+    }
+
+    pub fn ca_cert_path(&self) -> Option<&Path> {
+        self.settings.database_ca_cert_path()
+    }
+
+    pub fn auth_method(&self) -> DatabaseAuthMethod {
+        self.settings.database_auth_method()
+    }
+
+    pub fn connection_string(&self) -> Option<&'a Secret> {
+        self.settings.database_connection_string()
+    }
+
+    pub fn read_only_intent(&self) -> bool {
+        self.settings.database_read_only_intent()
+    }
+
+    pub fn connect_timeout_seconds(&self) -> Option<u64> {
+        self.settings.database_connect_timeout_seconds()
+    }
+
+    pub fn command_timeout_seconds(&self) -> Option<u64> {
+        self.settings.database_command_timeout_seconds()
+    }
+
+    /// See [`Settings::connect_timeout`].
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.settings.connect_timeout()
+    }
+
+    /// See [`Settings::command_timeout`].
+    pub fn command_timeout(&self) -> Option<Duration> {
+        self.settings.command_timeout()
+    }
+
+    #[cfg(feature = "pool")]
+    pub fn pool_max_size(&self) -> u32 {
+        self.settings.database_pool_max_size()
+    }
+
+    #[cfg(feature = "pool")]
+    pub fn pool_timeout(&self) -> Option<Duration> {
+        self.settings.database_pool_timeout()
+    }
+
+    #[cfg(feature = "pool")]
+    pub fn pool_test_on_checkout(&self) -> bool {
+        self.settings.database_pool_test_on_checkout()
+    }
+}
+
+impl<'a> EmailSettings<'a> {
+    pub fn sendgrid_api_key(&self) -> Option<&'a Secret> {
+        self.settings.sendgrid_api_key()
+    }
+
+    pub fn sendgrid_template_id(&self) -> Option<&'a str> {
+        self.settings.sendgrid_template_id()
+    }
+
+    /// See [`Settings::sendgrid_api_host`].
+    pub fn sendgrid_api_host(&self) -> &'a str {
+        self.settings.sendgrid_api_host()
+    }
+
+    /// `EmailFromAddress`.
+    pub fn from(&self) -> Option<&'a str> {
+        self.settings.email_from_address()
+    }
+
+    pub fn from_name(&self) -> Option<&'a str> {
+        self.settings.email_from_name()
+    }
+
+    pub fn to_addresses(&self) -> Option<&'a str> {
+        self.settings.email_to_addresses()
+    }
+
+    pub fn cc_addresses(&self) -> &'a str {
+        self.settings.email_cc_addresses()
+    }
+
+    pub fn bcc_addresses(&self) -> &'a str {
+        self.settings.email_bcc_addresses()
+    }
+
+    pub fn reply_to_address(&self) -> &'a str {
+        self.settings.email_reply_to_address()
+    }
+
+    pub fn reply_to_name(&self) -> &'a str {
+        self.settings.email_reply_to_name()
+    }
+
+    pub fn allow_empty_recipients(&self) -> bool {
+        self.settings.email_allow_empty_recipients()
+    }
+
+    pub fn recipient_soft_limit(&self) -> u32 {
+        self.settings.email_recipient_soft_limit()
+    }
+
+    /// See [`Settings::is_email_sandboxed`].
+    pub fn is_sandboxed(&self) -> bool {
+        self.settings.is_email_sandboxed()
+    }
+
+    /// See [`Settings::email_categories`].
+    pub fn categories(&self) -> Vec<&'a str> {
+        self.settings.email_categories()
+    }
+
+    pub fn custom_args(&self) -> Option<&'a BTreeMap<String, Value>> {
+        self.settings.email_custom_args()
+    }
+
+    pub fn asm_group_id(&self) -> Option<u32> {
+        self.settings.email_asm_group_id()
+    }
+
+    /// See [`Settings::email_asm_groups_to_display`].
+    pub fn asm_groups_to_display(&self) -> Vec<u32> {
+        self.settings.email_asm_groups_to_display()
+    }
+
+    #[cfg(feature = "sendgrid")]
+    pub fn max_retries(&self) -> u32 {
+        self.settings.email_max_retries()
+    }
+
+    #[cfg(feature = "sendgrid")]
+    pub fn retry_base_delay(&self) -> Duration {
+        self.settings.email_retry_base_delay()
+    }
+
+    #[cfg(feature = "sendgrid")]
+    pub fn max_per_hour(&self) -> Option<u32> {
+        self.settings.email_max_per_hour()
+    }
+}
+
+impl<'a> LoggingSettings<'a> {
+    pub fn webhook_uri(&self) -> Option<&'a str> {
+        self.settings.log_webhook_uri()
+    }
+
+    pub fn webhook_auth_header(&self) -> Option<&'a str> {
+        self.settings.log_webhook_auth_header()
+    }
+
+    pub fn webhook_auth_value(&self) -> Option<&'a Secret> {
+        self.settings.log_webhook_auth_value()
+    }
+
+    pub fn webhook_timeout(&self) -> Duration {
+        self.settings.log_webhook_timeout()
+    }
+
+    pub fn webhook_max_retries(&self) -> u32 {
+        self.settings.log_webhook_max_retries()
+    }
+
+    pub fn webhook_batch_size(&self) -> u32 {
+        self.settings.log_webhook_batch_size()
+    }
+
+    pub fn webhook_format(&self) -> LogWebhookFormat {
+        self.settings.log_webhook_format()
+    }
+
+    pub fn webhook_min_level(&self) -> LogLevel {
+        self.settings.log_webhook_min_level()
+    }
+
+    pub fn webhook_module_allow_list(&self) -> Vec<&'a str> {
+        self.settings.log_webhook_module_allow_list()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_groups_read_the_flat_fields() {
+        let settings = Settings::for_tests();
+
+        assert_eq!(settings.database().server(), "localhost");
+        assert_eq!(settings.database().expose_password(), "test-password");
+        assert_eq!(settings.database().port(), Some(1433));
+        assert_eq!(settings.database().connect_timeout_seconds(), Some(5));
+        assert_eq!(settings.email().from(), Some("reports@example.com"));
+        assert_eq!(settings.email().reply_to_name(), "Test Support");
+        assert_eq!(
+            settings.email().sendgrid_api_key().unwrap().expose(),
+            "SG.test-api-key"
+        );
+        assert_eq!(
+            settings.logging().webhook_uri(),
+            Some("https://hooks.example.com/test")
+        );
+        assert_eq!(settings.logging().webhook_min_level(), LogLevel::Debug);
+    }
+}
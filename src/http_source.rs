@@ -0,0 +1,455 @@
+use crate::logger::jitter;
+use crate::{
+    Secret, Settings, SettingsError, DEFAULT_HTTP_CONNECT_TIMEOUT_SECONDS,
+    DEFAULT_HTTP_REQUEST_TIMEOUT_SECONDS,
+};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, Response};
+use std::fmt;
+use std::time::Duration;
+use url::Url;
+
+/// The largest blob [`Settings::from_url`] reads, in bytes.
+pub const MAX_REMOTE_BLOB_BYTES: usize = 64 * 1024;
+/// Retries after the first request when fetching a blob from a URL.
+pub const REMOTE_BLOB_MAX_RETRIES: u32 = 3;
+
+// Backoff before the first retry, doubling after each one
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// A bearer token for [`Settings::from_url`], sent as the `Authorization`
+/// header. Like a [`Secret`], its `Debug` is redacted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BearerToken(Secret);
+
+impl BearerToken {
+    pub fn new(token: impl Into<String>) -> BearerToken {
+        BearerToken(Secret::new(token))
+    }
+}
+
+impl From<String> for BearerToken {
+    fn from(token: String) -> BearerToken {
+        BearerToken::new(token)
+    }
+}
+
+impl From<&str> for BearerToken {
+    fn from(token: &str) -> BearerToken {
+        BearerToken::new(token)
+    }
+}
+
+/// Why a blob could not be fetched from a URL. None of them carries the
+/// bearer token or the response body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RemoteBlobError {
+    /// The server answered with a non-success status.
+    Status { status: u16 },
+    /// The body is larger than [`MAX_REMOTE_BLOB_BYTES`].
+    TooLarge { limit: usize },
+    /// The body is not a settings blob by its `Content-Type`, e.g. the HTML of
+    /// a login page.
+    ContentType(String),
+    /// The request did not get an answer, e.g. a connection failure.
+    Transport(String),
+    /// The server did not answer in time.
+    Timeout(String),
+}
+
+impl RemoteBlobError {
+    // A 401 or 403 won't get better by asking again
+    pub(crate) fn is_retryable(&self) -> bool {
+        match self {
+            RemoteBlobError::Status { status } => *status >= 500,
+            RemoteBlobError::Transport(_) | RemoteBlobError::Timeout(_) => true,
+            RemoteBlobError::TooLarge { .. } | RemoteBlobError::ContentType(_) => false,
+        }
+    }
+}
+
+impl std::error::Error for RemoteBlobError {}
+
+impl fmt::Display for RemoteBlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteBlobError::Status { status: 401 } => write!(f, "unauthorized (status 401)"),
+            RemoteBlobError::Status { status: 403 } => write!(f, "forbidden (status 403)"),
+            RemoteBlobError::Status { status } => write!(f, "server returned status {}", status),
+            RemoteBlobError::TooLarge { limit } => {
+                write!(f, "response is larger than {} bytes", limit)
+            }
+            RemoteBlobError::ContentType(content_type) => write!(
+                f,
+                "response has content type '{}', not a settings blob",
+                content_type
+            ),
+            RemoteBlobError::Transport(reason) => write!(f, "{}", reason),
+            RemoteBlobError::Timeout(reason) => write!(f, "timed out: {}", reason),
+        }
+    }
+}
+
+impl From<reqwest::Error> for RemoteBlobError {
+    // The URL is reported once, by `SettingsError::RemoteBlob`
+    fn from(e: reqwest::Error) -> RemoteBlobError {
+        if e.is_timeout() {
+            return RemoteBlobError::Timeout(e.without_url().to_string());
+        }
+        RemoteBlobError::Transport(e.without_url().to_string())
+    }
+}
+
+impl Settings {
+    /// Fetches the blob from `url` with a GET, authenticating with `auth` if
+    /// given, and parses it like `SecretBlob`.
+    ///
+    /// `url` must use https, or http to a loopback address. Server errors
+    /// (5xx) and failed requests are retried up to [`REMOTE_BLOB_MAX_RETRIES`]
+    /// times, backing off exponentially with jitter; other statuses, including
+    /// 401 and 403, fail at once. A body over [`MAX_REMOTE_BLOB_BYTES`] or
+    /// with a content type no blob has, like `text/html`, is refused. A
+    /// missing content type is fine.
+    ///
+    /// Connecting may take up to [`DEFAULT_HTTP_CONNECT_TIMEOUT_SECONDS`] and
+    /// each request up to [`DEFAULT_HTTP_REQUEST_TIMEOUT_SECONDS`]. Needs a
+    /// tokio runtime with the time driver enabled.
+    pub async fn from_url(url: &str, auth: Option<BearerToken>) -> Result<Settings, SettingsError> {
+        Settings::fetch_url(url, auth).await?.finish_load()
+    }
+
+    // Without the check for missing fields, which `load_async` makes once its
+    // overrides are in
+    pub(crate) async fn fetch_url(
+        url: &str,
+        auth: Option<BearerToken>,
+    ) -> Result<Settings, SettingsError> {
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(DEFAULT_HTTP_CONNECT_TIMEOUT_SECONDS))
+            .timeout(Duration::from_secs(DEFAULT_HTTP_REQUEST_TIMEOUT_SECONDS))
+            .build()
+            .map_err(|e| SettingsError::HttpClient(e.without_url().to_string()))?;
+        let blob = fetch(&client, url, auth.as_ref(), RETRY_BASE_DELAY).await?;
+        Settings::parse_blob(&blob)
+    }
+}
+
+async fn fetch(
+    client: &Client,
+    url: &str,
+    auth: Option<&BearerToken>,
+    retry_base_delay: Duration,
+) -> Result<String, SettingsError> {
+    let url = parse_url(url).map_err(|reason| SettingsError::InvalidUrl {
+        field: "url",
+        reason,
+    })?;
+    let mut backoff = retry_base_delay;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let error = match get(client, &url, auth).await {
+            Ok(blob) => return Ok(blob),
+            Err(error) => error,
+        };
+        if !error.is_retryable() || attempts > REMOTE_BLOB_MAX_RETRIES {
+            return Err(SettingsError::RemoteBlob {
+                url: display_url(&url),
+                attempts,
+                source: error,
+            });
+        }
+        tokio::time::sleep(jitter(backoff)).await;
+        backoff = backoff.saturating_mul(2);
+    }
+}
+
+async fn get(
+    client: &Client,
+    url: &Url,
+    auth: Option<&BearerToken>,
+) -> Result<String, RemoteBlobError> {
+    let mut request = client.get(url.clone());
+    if let Some(BearerToken(token)) = auth {
+        request = request.header(AUTHORIZATION, format!("Bearer {}", token.expose()));
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(RemoteBlobError::Status {
+            status: status.as_u16(),
+        });
+    }
+    check_content_type(&response)?;
+    let body = read_limited(response).await?;
+    // A blob is text; anything else fails to parse, as a blob in an env var would
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+// Loose on purpose: config services label blobs as JSON, TOML, YAML, plain
+// text or bytes, so only types that are plainly something else are refused
+fn check_content_type(response: &Response) -> Result<(), RemoteBlobError> {
+    let content_type = match response.headers().get(CONTENT_TYPE) {
+        Some(content_type) => String::from_utf8_lossy(content_type.as_bytes()).into_owned(),
+        None => return Ok(()),
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let blob_like = essence.is_empty()
+        || essence.contains("json")
+        || essence.contains("toml")
+        || essence.contains("yaml")
+        || essence == "application/octet-stream"
+        || (essence.starts_with("text/") && essence != "text/html");
+    if blob_like {
+        Ok(())
+    } else {
+        Err(RemoteBlobError::ContentType(content_type))
+    }
+}
+
+// Content-Length may be missing or wrong, so the body is counted as it arrives
+async fn read_limited(mut response: Response) -> Result<Vec<u8>, RemoteBlobError> {
+    let too_large = RemoteBlobError::TooLarge {
+        limit: MAX_REMOTE_BLOB_BYTES,
+    };
+    if response.content_length().unwrap_or_default() > MAX_REMOTE_BLOB_BYTES as u64 {
+        return Err(too_large);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_REMOTE_BLOB_BYTES {
+            return Err(too_large);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+// Plain http would send the token in the clear, except to this machine
+fn parse_url(raw: &str) -> Result<Url, String> {
+    let url = Url::parse(raw.trim()).map_err(|e| format!("is not a valid URL: {}", e))?;
+    let loopback = match url.host() {
+        Some(url::Host::Domain(host)) => host.eq_ignore_ascii_case("localhost"),
+        Some(url::Host::Ipv4(address)) => address.is_loopback(),
+        Some(url::Host::Ipv6(address)) => address.is_loopback(),
+        None => false,
+    };
+    match url.scheme() {
+        "https" => Ok(url),
+        "http" if loopback => Ok(url),
+        "http" => Err("must use https, except to a loopback address".to_string()),
+        scheme => Err(format!("must use https, not '{}'", scheme)),
+    }
+}
+
+// Credentials and query parameters may be secrets too, so errors show neither
+// `raw` as `display_url` shows it, for a URL already fetched from
+pub(crate) fn shown_url(raw: &str) -> String {
+    parse_url(raw)
+        .map(|url| display_url(&url))
+        .unwrap_or_default()
+}
+
+fn display_url(url: &Url) -> String {
+    let mut shown = url.clone();
+    let _ = shown.set_username("");
+    let _ = shown.set_password(None);
+    shown.set_query(None);
+    shown.set_fragment(None);
+    shown.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TOKEN: &str = "tok-8f3a9c";
+    const BLOB: &str = r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated"}"#;
+
+    async fn fetch_from(
+        server: &MockServer,
+        auth: Option<&BearerToken>,
+    ) -> Result<String, SettingsError> {
+        let url = format!("{}/settings", server.uri());
+        fetch(&Client::new(), &url, auth, Duration::from_millis(1)).await
+    }
+
+    #[tokio::test]
+    async fn test_fetches_and_parses_blob() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/settings"))
+            .and(header(
+                "authorization",
+                format!("Bearer {}", TOKEN).as_str(),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(BLOB, "application/json; charset=utf-8"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/settings", server.uri());
+        let settings = Settings::from_url(&url, Some(TOKEN.into())).await.unwrap();
+
+        assert_eq!(settings.database_server(), "sql01");
+        assert_eq!(settings.database_name(), "reports");
+    }
+
+    #[tokio::test]
+    async fn test_missing_ca_bundle_fails_the_load() {
+        let server = MockServer::start().await;
+        let blob = BLOB.replacen('{', r#"{"DatabaseCaCertPath": "/nonexistent/ca.pem", "#, 1);
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(blob, "application/json"))
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/settings", server.uri());
+        let err = Settings::from_url(&url, None).await.unwrap_err();
+
+        assert!(
+            err.to_string()
+                .contains("could not read '/nonexistent/ca.pem'"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auth_failure_is_not_retried_and_hides_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(401).set_body_string(TOKEN))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let err = fetch_from(&server, Some(&BearerToken::new(TOKEN)))
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(
+                err,
+                SettingsError::RemoteBlob {
+                    attempts: 1,
+                    source: RemoteBlobError::Status { status: 401 },
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Could not fetch settings blob from {}/settings: unauthorized (status 401)",
+                server.uri()
+            )
+        );
+        assert!(!format!("{:?}", err).contains(TOKEN));
+        assert!(!format!("{:?}", BearerToken::new(TOKEN)).contains(TOKEN));
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(BLOB))
+            .mount(&server)
+            .await;
+        let failing = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(u64::from(REMOTE_BLOB_MAX_RETRIES) + 1)
+            .mount(&failing)
+            .await;
+
+        assert_eq!(fetch_from(&server, None).await.unwrap(), BLOB);
+        let err = fetch_from(&failing, None).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("after 4 attempts: server returned status 500"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_refused() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string("x".repeat(MAX_REMOTE_BLOB_BYTES + 1)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let err = fetch_from(&server, None).await.unwrap_err();
+
+        assert!(
+            matches!(
+                err,
+                SettingsError::RemoteBlob {
+                    source: RemoteBlobError::TooLarge { limit: 65536 },
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_page_is_refused() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("<html></html>", "text/html"))
+            .mount(&server)
+            .await;
+
+        let err = fetch_from(&server, None).await.unwrap_err();
+
+        assert!(
+            err.to_string()
+                .ends_with("response has content type 'text/html', not a settings blob"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_url_must_be_https_or_loopback() {
+        assert!(parse_url("https://config.corp.example.com/blob").is_ok());
+        assert!(parse_url("http://127.0.0.1:8080/blob").is_ok());
+        assert!(parse_url("http://localhost/blob").is_ok());
+        assert_eq!(
+            parse_url("http://config.corp.example.com/blob").unwrap_err(),
+            "must use https, except to a loopback address"
+        );
+        assert_eq!(
+            parse_url("ftp://config/blob").unwrap_err(),
+            "must use https, not 'ftp'"
+        );
+        assert_eq!(
+            display_url(&Url::parse("https://svc:pw@config.example.com/blob?sig=abc").unwrap()),
+            "https://config.example.com/blob"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use crate::overrides::env_lookup;
+use crate::profile::Profile;
+use crate::{Settings, SettingsError, DEFAULT_BLOB_VAR};
+use serde_json::Value;
+
+impl Settings {
+    /// Like [`Settings::get_settings`], but first replaces every `${NAME}` in
+    /// the blob's string values with the value of the env variable `NAME`, e.g.
+    /// `"DatabasePassword": "${DB_PASSWORD}"`. `$${` stands for a literal `${`;
+    /// any other `$` is kept as it is. Values taken from variables are not
+    /// interpolated again, and `REPORTSETTINGS_<FIELD>` overrides are applied
+    /// afterwards as they are.
+    ///
+    /// Fails with [`SettingsError::MissingInterpolatedVar`] if a variable is
+    /// not set, and with [`SettingsError::InvalidInterpolation`] for a `${`
+    /// that is never closed or doesn't hold a variable name.
+    pub fn get_settings_interpolated() -> Result<Settings, SettingsError> {
+        let (settings, _) = Settings::load_blob(DEFAULT_BLOB_VAR, Profile::FromEnv)?;
+        let mut settings = settings.interpolated(env_lookup)?;
+        settings.apply_env_overrides()?;
+        settings.finish_load()
+    }
+
+    fn interpolated<F>(&self, lookup: F) -> Result<Settings, SettingsError>
+    where
+        F: Fn(&str) -> Result<Option<String>, SettingsError>,
+    {
+        let mut blob = serde_json::to_value(self)?;
+        interpolate_value(&mut blob, "", &lookup)?;
+        let settings: Settings = serde_json::from_value(blob)?;
+        Ok(settings.with_absent_fields(self.absent_fields.clone()))
+    }
+}
+
+// `path` names the field in errors, e.g. `Databases.warehouse.Password`
+fn interpolate_value<F>(value: &mut Value, path: &str, lookup: &F) -> Result<(), SettingsError>
+where
+    F: Fn(&str) -> Result<Option<String>, SettingsError>,
+{
+    match value {
+        Value::String(raw) if raw.contains('$') => *raw = interpolate(raw, path, lookup)?,
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                interpolate_value(value, &path, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate<F>(raw: &str, field: &str, lookup: &F) -> Result<String, SettingsError>
+where
+    F: Fn(&str) -> Result<Option<String>, SettingsError>,
+{
+    let mut interpolated = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(dollar) = rest.find('$') {
+        interpolated.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        if let Some(escaped) = after.strip_prefix("${") {
+            interpolated.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = after.strip_prefix('{') {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| invalid(field, "`${` is never closed".to_string()))?;
+            let name = &reference[..end];
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(invalid(
+                    field,
+                    format!("'{}' is not an env variable name", name),
+                ));
+            }
+            let value = lookup(name)?.ok_or_else(|| SettingsError::MissingInterpolatedVar {
+                name: name.to_string(),
+                field: field.to_string(),
+            })?;
+            interpolated.push_str(&value);
+            rest = &reference[end + 1..];
+        } else {
+            interpolated.push('$');
+            rest = after;
+        }
+    }
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+fn invalid(field: &str, reason: String) -> SettingsError {
+    SettingsError::InvalidInterpolation {
+        field: field.to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const BLOB: &str = r#"{
+        "DatabaseServer": "${DB_HOST},1433",
+        "DatabaseName": "reports",
+        "DatabaseUsername": "admin",
+        "DatabasePassword": "${DB_PASSWORD}",
+        "Databases": {
+            "warehouse": {
+                "Server": "wh-sql",
+                "Name": "warehouse",
+                "Username": "reader",
+                "Password": "${WH_PASSWORD}"
+            }
+        },
+        "EmailFromName": "Costs in $USD"
+    }"#;
+
+    fn interpolate_blob(blob: &str, vars: &[(&str, &str)]) -> Result<Settings, SettingsError> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        Settings::from_json_str(blob)
+            .unwrap()
+            .interpolated(|name| Ok(vars.get(name).map(|value| value.to_string())))
+    }
+
+    #[test]
+    fn test_replaces_references_in_nested_values() {
+        let settings = interpolate_blob(
+            BLOB,
+            &[
+                ("DB_HOST", "sql01"),
+                ("DB_PASSWORD", "rotated-password"),
+                ("WH_PASSWORD", "wh-password"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(settings.database_server(), "sql01,1433");
+        assert_eq!(settings.expose_database_password(), "rotated-password");
+        assert_eq!(
+            settings.databases["warehouse"].password.expose(),
+            "wh-password"
+        );
+        assert_eq!(settings.email_from_name(), Some("Costs in $USD"));
+    }
+
+    #[test]
+    fn test_values_are_not_interpolated_twice() {
+        let settings = interpolate_blob(
+            BLOB,
+            &[
+                ("DB_HOST", "sql01"),
+                ("DB_PASSWORD", "${WH_PASSWORD}"),
+                ("WH_PASSWORD", "wh-password"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(settings.expose_database_password(), "${WH_PASSWORD}");
+    }
+
+    #[test]
+    fn test_missing_var_is_named() {
+        let err =
+            interpolate_blob(BLOB, &[("DB_HOST", "sql01"), ("DB_PASSWORD", "x")]).unwrap_err();
+
+        assert!(matches!(
+            &err,
+            SettingsError::MissingInterpolatedVar { name, field }
+                if name == "WH_PASSWORD" && field == "Databases.warehouse.Password"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Env variable WH_PASSWORD is not set, but Databases.warehouse.Password refers to it as ${WH_PASSWORD}"
+        );
+    }
+
+    #[test]
+    fn test_escape_keeps_literal() {
+        let blob = r#"{"DatabaseServer": "sql01", "DatabaseName": "$${NOT_A_VAR}",
+            "DatabaseUsername": "admin", "DatabasePassword": "p$$w${DB_PASSWORD}$"}"#;
+
+        let settings = interpolate_blob(blob, &[("DB_PASSWORD", "rd")]).unwrap();
+
+        assert_eq!(settings.database_name(), "${NOT_A_VAR}");
+        assert_eq!(settings.expose_database_password(), "p$$wrd$");
+    }
+
+    #[test]
+    fn test_rejects_malformed_references() {
+        for (value, reason) in [
+            ("${DB_PASSWORD", "`${` is never closed"),
+            ("${}", "'' is not an env variable name"),
+            ("${A${B}}", "'A${B' is not an env variable name"),
+        ] {
+            let blob = format!(
+                r#"{{"DatabaseServer": "sql01", "DatabaseName": "reports",
+                    "DatabaseUsername": "admin", "DatabasePassword": "{}"}}"#,
+                value
+            );
+
+            let err = interpolate_blob(&blob, &[("A", "a"), ("B", "b")]).unwrap_err();
+
+            assert!(
+                matches!(
+                    &err,
+                    SettingsError::InvalidInterpolation { field, reason: r }
+                        if field == "DatabasePassword" && r == reason
+                ),
+                "{}",
+                err
+            );
+        }
+    }
+}
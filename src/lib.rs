@@ -1,17 +1,210 @@
 use ::serde::*;
-use sendgrid::v3::Email;
-use ssql::prelude::tiberius::{AuthMethod, Config, EncryptionLevel};
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+mod address;
+mod ado;
+mod analytics;
+mod audit;
+mod builder;
+mod cache;
+mod changes;
+mod cleanup;
+#[cfg(feature = "mssql")]
+mod connection;
+mod databases;
+mod dotenv;
+#[cfg(feature = "sendgrid")]
+mod email;
+mod encoded;
+mod encrypted;
+mod error;
+mod example;
+mod failover;
+#[cfg(feature = "figment")]
+mod figment_provider;
+mod fingerprint;
+mod format;
+#[cfg(any(feature = "log", feature = "tracing"))]
+mod forwarder;
+mod groups;
+mod http;
+#[cfg(feature = "remote")]
+mod http_source;
+mod interpolate;
+mod load;
+mod loader;
+#[cfg(feature = "remote")]
+mod log_buffer;
+mod log_event;
+#[cfg(any(feature = "log", feature = "tracing"))]
+mod log_filter;
+// The payloads are only posted with `remote`, but stay testable without it
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+mod log_format;
+#[cfg(feature = "log")]
+mod log_sink;
+mod logger;
+#[cfg(feature = "sendgrid")]
+mod message;
+mod migrate;
+mod nested;
+mod overrides;
+#[cfg(feature = "pool")]
+mod pool;
+mod prefixed;
+mod profile;
+#[cfg(feature = "sendgrid")]
+mod rate_limit;
+mod recipients;
+mod redacted;
+#[cfg(feature = "json5")]
+mod relaxed;
+#[cfg(feature = "reload")]
+mod reload;
+mod required;
+#[cfg(feature = "schema")]
+mod schema;
+mod secret;
+mod secret_store;
+mod secrets_dir;
+#[cfg(feature = "sendgrid")]
+mod send;
+mod sendgrid_host;
+mod serde_helpers;
+mod smtp;
+mod sql;
+#[cfg(feature = "sqlx")]
+mod sqlx_options;
+mod strict;
+mod summary;
+mod tenants;
+#[cfg(any(test, feature = "test-util"))]
+mod test_util;
+#[cfg(feature = "tracing")]
+mod tracing_layer;
+mod unsubscribe;
+mod validate;
+mod webhook;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+pub use address::EmailAddress;
+pub use analytics::{SENDGRID_MAX_CATEGORIES, SENDGRID_MAX_CATEGORY_BYTES};
+pub use audit::{AuditSink, LogAuditSink, SettingsEvent};
+pub use builder::SettingsBuilder;
+#[cfg(any(test, feature = "test-util"))]
+pub use cache::init_for_tests;
+pub use cache::{settings, try_init};
+pub use databases::{DatabaseTarget, DEFAULT_DATABASE};
+pub use dotenv::DOTENV_FILE;
+pub use encrypted::BLOB_KEY_LEN;
+pub use error::{FieldError, SettingsError};
+#[cfg(feature = "mssql")]
+pub use failover::DatabaseServerRole;
+#[cfg(feature = "figment")]
+pub use figment_provider::ReportSettingsProvider;
+pub use groups::{DatabaseSettings, EmailSettings, LoggingSettings};
+pub use http::{
+    DEFAULT_HTTP_CONNECT_TIMEOUT_SECONDS, DEFAULT_HTTP_REQUEST_TIMEOUT_SECONDS,
+    MAX_HTTP_TIMEOUT_SECONDS,
+};
+#[cfg(feature = "remote")]
+pub use http_source::{
+    BearerToken, RemoteBlobError, MAX_REMOTE_BLOB_BYTES, REMOTE_BLOB_MAX_RETRIES,
+};
+#[cfg(feature = "azure")]
+pub use load::BLOB_KEY_VAULT_VAR;
+#[cfg(feature = "aws")]
+pub use load::BLOB_SECRETS_MANAGER_REGION_VAR;
+#[cfg(any(feature = "azure", feature = "aws"))]
+pub use load::BLOB_SECRET_NAME_VAR;
+#[cfg(feature = "remote")]
+pub use load::{BLOB_TOKEN_VAR, BLOB_URL_VAR};
+pub use loader::{LoadedSettings, SettingsLoader, SettingsSource};
+#[cfg(feature = "remote")]
+pub use log_buffer::{BufferedWebhookLogger, DEFAULT_LOG_WEBHOOK_FLUSH_INTERVAL_SECONDS};
+pub use log_event::{LogEvent, LogEventBuilder};
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub use log_filter::WebhookFilter;
+pub use log_format::LogWebhookFormat;
+#[cfg(feature = "log")]
+pub use log_sink::WebhookLogSink;
+pub use logger::{
+    LogLevel, DEFAULT_LOG_WEBHOOK_BATCH_SIZE, DEFAULT_LOG_WEBHOOK_MAX_RETRIES,
+    DEFAULT_LOG_WEBHOOK_TIMEOUT_SECONDS,
+};
+#[cfg(feature = "remote")]
+pub use logger::{WebhookError, WebhookLogger};
+#[cfg(feature = "sendgrid")]
+pub use message::SENDGRID_MAX_MESSAGE_BYTES;
+pub use migrate::CURRENT_SCHEMA_VERSION;
+pub use overrides::ENV_OVERRIDE_PREFIX;
+#[cfg(feature = "pool")]
+pub use pool::{SqlClient, SqlConnectionManager, SqlPool, DEFAULT_DATABASE_POOL_MAX_SIZE};
+pub use profile::{DEFAULT_PROFILE, PROFILE_VAR};
+pub use recipients::{DEFAULT_EMAIL_RECIPIENT_SOFT_LIMIT, SENDGRID_MAX_RECIPIENTS};
+pub use redacted::RedactedSettings;
+#[cfg(feature = "refresh")]
+pub use reload::RefreshTask;
+#[cfg(feature = "reload")]
+pub use reload::SettingsHandle;
+#[cfg(feature = "schema")]
+pub use schema::SchemaViolation;
+pub use secret::Secret;
+#[cfg(feature = "azure")]
+pub use secret_store::KeyVaultStore;
+#[cfg(feature = "aws")]
+pub use secret_store::SecretsManagerStore;
+pub use secret_store::{SecretStore, SecretStoreError};
+#[cfg(feature = "sendgrid")]
+pub use send::{MailTransport, SendError, DEFAULT_EMAIL_MAX_RETRIES, DEFAULT_EMAIL_RETRY_BASE_MS};
+pub use sendgrid_host::DEFAULT_SENDGRID_API_HOST;
+#[cfg(feature = "smtp")]
+pub use smtp::{EmailTransport, SmtpError};
+pub use smtp::{DEFAULT_SMTP_PLAIN_PORT, DEFAULT_SMTP_PORT};
+pub use sql::{
+    DatabaseAuthMethod, DatabaseEncryption, DEFAULT_APPLICATION_NAME, DEFAULT_DATABASE_PORT,
+    MAX_DATABASE_TIMEOUT_SECONDS,
+};
+pub use tenants::TenantSettings;
+#[cfg(feature = "tracing")]
+pub use tracing_layer::{WebhookLayer, WebhookLayerHandle};
+pub use unsubscribe::SENDGRID_MAX_ASM_GROUPS_TO_DISPLAY;
+
+use format::{BlobFormat, FORMAT_VAR};
+use profile::Profile;
+
+/// The env variable `get_settings()` reads the blob from.
+pub const DEFAULT_BLOB_VAR: &str = "SecretBlob";
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base64::Engine;
+    use std::sync::{Mutex, MutexGuard};
 
-    // Mock the env variable used in the get_settings method for testing
-    fn mock_env_variable() {
-        env::set_var(
-            "SecretBlob",
-            r#"{
+    // Tests touching process-wide env variables must not run concurrently
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn lock_env() -> MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // The extension keeps the file from being sniffed, which would read
+    // `SecretBlobFormat`
+    fn write_blob_file(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        io::Write::write_all(&mut file, contents.as_bytes()).unwrap();
+        file
+    }
+
+    const TEST_BLOB: &str = r#"{
                 "DatabaseServer": "localhost",
                 "DatabaseName": "test_db",
                 "DatabaseUsername": "admin",
@@ -21,12 +214,28 @@ mod tests {
                 "EmailFromName": "Test",
                 "EmailFromAddress": "test@example.com",
                 "EmailToAddresses": "user1@example.com,user2@example.com"
-            }"#,
-        );
+            }"#;
+
+    const TEST_TOML_BLOB: &str = r#"
+            DatabaseServer = "localhost"
+            DatabaseName = "test_db"
+            DatabaseUsername = "admin"
+            DatabasePassword = "password123"
+            LogWebhookUri = "https://example.com"
+            SendgridApiKey = "sendgrid-api-key"
+            EmailFromName = "Test"
+            EmailFromAddress = "test@example.com"
+            EmailToAddresses = "user1@example.com,user2@example.com"
+        "#;
+
+    // Mock the env variable used in the get_settings method for testing
+    fn mock_env_variable() {
+        env::set_var("SecretBlob", TEST_BLOB);
     }
 
     #[test]
     fn test_get_settings_success() {
+        let _env = lock_env();
         mock_env_variable();
 
         let result = Settings::get_settings();
@@ -36,115 +245,1516 @@ mod tests {
         assert_eq!(settings.database_name, "test_db");
     }
 
+    #[test]
+    fn test_getters_reflect_blob() {
+        let settings: Settings = serde_json::from_str(
+            r#"{
+                "DatabaseServer": "localhost",
+                "DatabaseName": "test_db",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123",
+                "LogWebhookUri": "https://example.com/hook",
+                "SendgridApiKey": "sendgrid-api-key",
+                "EmailFromName": "Test",
+                "EmailFromAddress": "test@example.com",
+                "EmailToAddresses": "user1@example.com,user2@example.com"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.database_server(), "localhost");
+        assert_eq!(settings.database_name(), "test_db");
+        assert_eq!(settings.database_username(), "admin");
+        assert_eq!(settings.expose_database_password(), "password123");
+        assert_eq!(settings.log_webhook_uri(), Some("https://example.com/hook"));
+        assert_eq!(
+            settings.sendgrid_api_key().unwrap().expose(),
+            "sendgrid-api-key"
+        );
+        assert_eq!(settings.email_from_name(), Some("Test"));
+        assert_eq!(settings.email_from_address(), Some("test@example.com"));
+        assert_eq!(
+            settings.email_to_addresses(),
+            Some("user1@example.com,user2@example.com")
+        );
+    }
+
     #[test]
     fn test_get_settings_missing_env_var() {
+        let _env = lock_env();
         env::remove_var("SecretBlob");
+        env::remove_var("SecretBlobPath");
 
         let result = Settings::get_settings();
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Error getting env variable: environment variable not found");
+        let err = result.unwrap_err();
+        assert!(matches!(err, SettingsError::MissingEnvVar { ref name } if name == "SecretBlob"));
+        assert_eq!(
+            err.to_string(),
+            "Error getting env variable SecretBlob: environment variable not found"
+        );
+    }
+
+    #[test]
+    fn test_from_json_str_invalid_json() {
+        let result = Settings::from_json_str("invalid json");
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(matches!(err, SettingsError::InvalidJson(_)));
+        assert!(err
+            .to_string()
+            .contains("Could not deserialize settings blob: expected value at line 1 column 1"));
+    }
+
+    #[test]
+    fn test_from_reader_and_from_str_match_get_settings() {
+        let _env = lock_env();
+        mock_env_variable();
+        let expected = serde_json::to_value(Settings::get_settings().unwrap()).unwrap();
+
+        let from_reader = Settings::from_reader(TEST_BLOB.as_bytes()).unwrap();
+        let parsed: Settings = TEST_BLOB.parse().unwrap();
+
+        assert_eq!(serde_json::to_value(&from_reader).unwrap(), expected);
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), expected);
     }
 
     #[test]
-    fn test_get_settings_invalid_json() {
-        env::set_var("SecretBlob", "invalid json");
+    fn test_from_reader_reports_unreadable_input() {
+        let err = Settings::from_reader(&[b'{', 0xff, b'}'][..]).unwrap_err();
+
+        assert!(matches!(err, SettingsError::ReaderFailed(_)));
+        assert!(
+            err.to_string()
+                .starts_with("Could not read settings blob: "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_get_settings_from_file() {
+        let file = write_blob_file(TEST_BLOB);
+
+        let settings = Settings::get_settings_from_file(file.path()).unwrap();
+        assert_eq!(settings.database_server(), "localhost");
+    }
+
+    #[test]
+    fn test_get_settings_fails_on_missing_ca_bundle() {
+        let _env = lock_env();
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.pem");
+        let blob = TEST_BLOB.replacen(
+            '{',
+            &format!(
+                "{{\"DatabaseCaCertPath\": {:?},",
+                missing.display().to_string()
+            ),
+            1,
+        );
+        env::set_var("SecretBlob", &blob);
+        env::set_var("InvoiceReport_SecretBlob", &blob);
+
+        let err = Settings::get_settings().unwrap_err();
+        let others = [
+            Settings::get_settings_from_file(write_blob_file(&blob).path()).map(drop),
+            Settings::get_settings_with_prefix("InvoiceReport").map(drop),
+            Settings::get_settings_strict().map(drop),
+            Settings::get_settings_lenient().map(drop),
+            Settings::get_settings_interpolated().map(drop),
+            SettingsLoader::new()
+                .from_env_var("SecretBlob")
+                .load()
+                .map(drop),
+        ];
+        env::set_var("SecretBlob", format!("{{\"Default\": {}}}", blob));
+        let for_profile = Settings::get_settings_for_profile("Default").map(drop);
+        env::remove_var("InvoiceReport_SecretBlob");
+        mock_env_variable();
+
+        assert!(matches!(err, SettingsError::Validation(_)), "{}", err);
+        assert!(
+            err.to_string()
+                .contains(&format!("could not read '{}'", missing.display())),
+            "{}",
+            err
+        );
+        for other in others.into_iter().chain([for_profile]) {
+            assert_eq!(other.unwrap_err().to_string(), err.to_string());
+        }
+    }
+
+    #[test]
+    fn test_get_settings_from_file_not_found() {
+        let err = Settings::get_settings_from_file("/nonexistent/settings.json").unwrap_err();
+        assert!(matches!(err, SettingsError::FileNotFound { .. }));
+    }
+
+    #[test]
+    fn test_get_settings_from_file_unreadable() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = Settings::get_settings_from_file(dir.path()).unwrap_err();
+        assert!(matches!(err, SettingsError::FileUnreadable { .. }));
+    }
+
+    #[test]
+    fn test_get_settings_from_file_invalid_json() {
+        let file = write_blob_file("not json");
+
+        let err = Settings::get_settings_from_file(file.path()).unwrap_err();
+        assert!(matches!(err, SettingsError::InvalidJson(_)));
+    }
+
+    #[test]
+    fn test_get_settings_falls_back_to_path() {
+        let _env = lock_env();
+        let file = write_blob_file(TEST_BLOB);
+        env::remove_var("SecretBlob");
+        env::set_var("SecretBlobPath", file.path());
 
         let result = Settings::get_settings();
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Could not deserialize settings blob: expected value at line 1 column 1"));
+        env::remove_var("SecretBlobPath");
+
+        assert_eq!(result.unwrap().database_name(), "test_db");
     }
 
     #[test]
+    fn test_get_settings_decrypts_with_blob_key() {
+        use crate::encrypted::tests::{KEY, SEALED};
+
+        let _env = lock_env();
+        env::set_var("SecretBlob", SEALED);
+        env::set_var(
+            "SecretBlobKey",
+            base64::engine::general_purpose::STANDARD.encode(KEY),
+        );
+
+        let decrypted = Settings::get_settings();
+        env::set_var("SecretBlobKey", "not base64!");
+        let invalid_key = Settings::get_settings();
+        env::remove_var("SecretBlobKey");
+        mock_env_variable();
+
+        assert_eq!(decrypted.unwrap().database_name(), "reports");
+        assert!(matches!(
+            invalid_key,
+            Err(SettingsError::InvalidBlobKey(reason)) if reason == "SecretBlobKey is not valid base64"
+        ));
+    }
+
+    #[test]
+    fn test_get_settings_prefers_blob_over_path() {
+        let _env = lock_env();
+        let file = write_blob_file(&TEST_BLOB.replace("test_db", "file_db"));
+        mock_env_variable();
+        env::set_var("SecretBlobPath", file.path());
+
+        let result = Settings::get_settings();
+        env::remove_var("SecretBlobPath");
+
+        assert_eq!(result.unwrap().database_name(), "test_db");
+    }
+
+    #[test]
+    fn test_get_settings_from_var_loads_independently() {
+        let _env = lock_env();
+        env::set_var(
+            "InvoiceReportBlob",
+            TEST_BLOB.replace("test_db", "invoices"),
+        );
+        env::set_var("AuditReportBlob", TEST_BLOB.replace("test_db", "audit"));
+
+        let invoices = Settings::get_settings_from_var("InvoiceReportBlob");
+        let audit = Settings::get_settings_from_var("AuditReportBlob");
+        env::remove_var("InvoiceReportBlob");
+        env::remove_var("AuditReportBlob");
+
+        assert_eq!(invoices.unwrap().database_name(), "invoices");
+        assert_eq!(audit.unwrap().database_name(), "audit");
+    }
+
+    #[test]
+    fn test_get_settings_from_var_names_missing_var() {
+        let err = Settings::get_settings_from_var("NoSuchReportBlob").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Error getting env variable NoSuchReportBlob: environment variable not found"
+        );
+    }
+
+    #[test]
+    fn test_from_json_str_decodes_base64_blob() {
+        let blob = format!("{}\n", base64::prelude::BASE64_STANDARD.encode(TEST_BLOB));
+
+        let result = Settings::from_json_str(&blob);
+
+        assert_eq!(result.unwrap().database_name(), "test_db");
+    }
+
+    #[test]
+    fn test_from_json_str_reports_both_base64_and_json_errors() {
+        let blob = base64::prelude::BASE64_URL_SAFE.encode("{\"DatabaseServer\": 1}");
+
+        let err = Settings::from_json_str(&blob).unwrap_err();
+
+        assert!(matches!(err, SettingsError::InvalidJsonOrBase64 { .. }));
+        let message = err.to_string();
+        assert!(
+            message.contains("expected value at line 1 column 1"),
+            "{}",
+            message
+        );
+        assert!(message.contains("also tried it as base64"), "{}", message);
+        assert!(message.contains("invalid type: integer `1`"), "{}", message);
+    }
+
+    #[test]
+    fn test_validate_reports_field_errors() {
+        let settings = Settings::from_json_str(&TEST_BLOB.replace("localhost", "")).unwrap();
+
+        assert_eq!(
+            settings.validate(),
+            Err(vec![FieldError::new("DatabaseServer", "must not be empty")])
+        );
+    }
+
+    #[test]
+    fn test_incomplete_blob_lists_every_missing_field() {
+        let blob = r#"{"DatabaseUsername": "admin"}"#;
+        let file = write_blob_file(blob);
+
+        let err = Settings::from_json_str(blob).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Incomplete settings blob: missing fields `DatabaseServer`, `DatabaseName`, \
+             `DatabasePassword`"
+        );
+        assert_eq!(
+            Settings::get_settings_from_file(file.path())
+                .unwrap_err()
+                .to_string(),
+            err.to_string()
+        );
+        assert!(matches!(
+            Settings::from_json_str(r#"{"DatabaseUsername": "admin""#),
+            Err(SettingsError::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn test_tenant_settings_from_env_and_file() {
+        let _env = lock_env();
+        let blob = format!(
+            r#"{{"Tenants": {{"acme": {}, "globex": {}}}}}"#,
+            TEST_BLOB, TEST_BLOB
+        );
+        env::set_var("SecretBlob", &blob);
+
+        let tenants = TenantSettings::get_all().unwrap();
+        assert_eq!(tenants.len(), 2);
+        assert_eq!(tenants["acme"].database_name(), "test_db");
+
+        env::remove_var("SecretBlob");
+        let file = write_blob_file(&blob);
+        env::set_var("SecretBlobPath", file.path());
+        let globex = TenantSettings::get("globex");
+        env::remove_var("SecretBlobPath");
+        assert_eq!(globex.unwrap().database_server(), "localhost");
+        mock_env_variable();
+    }
+
+    #[test]
+    fn test_prefixed_settings_stay_apart() {
+        let _env = lock_env();
+        let invoice = TEST_BLOB.replace("test_db", "invoices");
+        env::set_var("InvoiceReport_SecretBlob", &invoice);
+        env::set_var("PayrollReport_SecretBlob", TEST_BLOB);
+        env::set_var("INVOICEREPORT_DATABASE_SERVER", "invoice-sql");
+        env::set_var("PAYROLLREPORT_APPLICATION_NAME", "Payroll");
+        env::set_var("REPORTSETTINGS_DATABASE_SERVER", "global-sql");
+
+        let invoices = Settings::get_settings_with_prefix("InvoiceReport");
+        let payroll = Settings::get_settings_with_prefix("PayrollReport_");
+        let invalid = Settings::get_settings_with_prefix("Invoice-Report");
+        env::remove_var("InvoiceReport_SecretBlob");
+        env::remove_var("PayrollReport_SecretBlob");
+        env::remove_var("INVOICEREPORT_DATABASE_SERVER");
+        env::remove_var("PAYROLLREPORT_APPLICATION_NAME");
+        env::remove_var("REPORTSETTINGS_DATABASE_SERVER");
+
+        let (invoices, payroll) = (invoices.unwrap(), payroll.unwrap());
+        assert_eq!(invoices.database_server(), "invoice-sql");
+        assert_eq!(invoices.database_name(), "invoices");
+        assert_eq!(invoices.application_name(), "InvoiceReport");
+        assert_eq!(payroll.database_server(), "localhost");
+        assert_eq!(payroll.database_name(), "test_db");
+        assert_eq!(payroll.application_name(), "Payroll");
+        assert_eq!(
+            invalid.unwrap_err().to_string(),
+            "Invalid settings prefix 'Invoice-Report': use ASCII letters, digits and underscores"
+        );
+        assert!(matches!(
+            Settings::get_settings_with_prefix("InvoiceReport"),
+            Err(SettingsError::MissingEnvVar { name }) if name == "InvoiceReport_SecretBlob"
+        ));
+    }
+
+    #[test]
+    fn test_get_settings_applies_env_overrides() {
+        let _env = lock_env();
+        mock_env_variable();
+        env::set_var("REPORTSETTINGS_DATABASE_SERVER", "staging-sql");
+
+        let result = Settings::get_settings();
+        env::remove_var("REPORTSETTINGS_DATABASE_SERVER");
+
+        let settings = result.unwrap();
+        assert_eq!(settings.database_server(), "staging-sql");
+        assert_eq!(settings.database_name(), "test_db");
+    }
+
+    #[test]
+    fn test_get_settings_picks_profile_from_environment() {
+        let _env = lock_env();
+        let blob = format!(
+            r#"{{"Default": {}, "Staging": {{"DatabaseName": "staging_db"}}}}"#,
+            TEST_BLOB
+        );
+        env::set_var("SecretBlob", &blob);
+
+        let default = Settings::get_settings();
+        env::set_var("Environment", "Staging");
+        let staging = Settings::get_settings();
+        env::set_var("Environment", "Nightly");
+        let unknown = Settings::get_settings();
+        env::remove_var("Environment");
+        let named = Settings::get_settings_for_profile("Staging");
+        mock_env_variable();
+        let flat = Settings::get_settings_for_profile("Staging");
+
+        assert_eq!(default.unwrap().database_name(), "test_db");
+        let staging = staging.unwrap();
+        assert_eq!(staging.database_name(), "staging_db");
+        assert_eq!(staging.database_server(), "localhost");
+        assert_eq!(
+            unknown.unwrap_err().to_string(),
+            "Unknown profile 'Nightly', expected one of: Default, Staging"
+        );
+        assert_eq!(named.unwrap().database_name(), "staging_db");
+        assert!(matches!(
+            flat,
+            Err(SettingsError::UnknownProfile { available, .. }) if available.is_empty()
+        ));
+    }
+
+    const FULL_BLOB: &str = r#"{
+                "DatabaseServer": "localhost",
+                "DatabaseName": "test_db",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123",
+                "DatabasePort": 14330,
+                "DatabaseFailoverServer": "localhost-mirror",
+                "DatabaseEncryption": "Required",
+                "DatabaseTrustCert": true,
+                "DatabaseCaCertPath": "/etc/ssl/certs/corp-sql-ca.pem",
+                "DatabaseAuthMethod": "SqlServer",
+                "DatabaseReadOnlyIntent": true,
+                "Databases": {
+                    "warehouse": {
+                        "Server": "wh-sql",
+                        "Name": "warehouse",
+                        "Username": "reader",
+                        "Password": "secret",
+                        "Port": 1434
+                    }
+                },
+                "ApplicationName": "nightly-report",
+                "DatabaseConnectTimeoutSeconds": 5,
+                "DatabaseCommandTimeoutSeconds": 60,
+                "DatabasePoolMaxSize": 4,
+                "DatabasePoolTimeoutSeconds": 15,
+                "DatabasePoolTestOnCheckout": true,
+                "LogWebhookUri": "https://example.com/hook",
+                "LogWebhookAuthHeader": "x-functions-key",
+                "LogWebhookAuthValue": "function-key",
+                "LogWebhookTimeoutSeconds": 3,
+                "LogWebhookMaxRetries": 2,
+                "LogWebhookBatchSize": 20,
+                "LogWebhookFormat": "Teams",
+                "LogWebhookMinLevel": "Error",
+                "LogWebhookModuleAllowList": "report, reportsettings_rust",
+                "SendgridApiKey": "sendgrid-api-key",
+                "EmailFromName": "Test",
+                "EmailFromAddress": "test@example.com",
+                "EmailToAddresses": "user1@example.com",
+                "EmailCcAddresses": "cc@example.com",
+                "EmailBccAddresses": "bcc@example.com",
+                "EmailReplyToAddress": "support@example.com",
+                "EmailReplyToName": "Support",
+                "EmailMaxRetries": 4,
+                "EmailRetryBaseMs": 100,
+                "EmailMaxPerHour": 200,
+                "EmailAllowEmptyRecipients": true,
+                "EmailRecipientSoftLimit": 50,
+                "SendgridTemplateId": "d-0123456789abcdef0123456789abcdef",
+                "EmailSandboxMode": true,
+                "EmailCategories": "staging, daily-costs",
+                "EmailCustomArgs": {"Environment": "staging"},
+                "EmailAsmGroupId": 12,
+                "EmailAsmGroupsToDisplay": "12, 14",
+                "SendgridApiHost": "https://api.eu.sendgrid.com",
+                "SmtpHost": "smtp.example.com",
+                "SmtpPort": 2525,
+                "SmtpUsername": "svc_mail",
+                "SmtpPassword": "smtp-password",
+                "SmtpUseStartTls": true,
+                "HttpProxyUri": "http://proxy.example.com:3128",
+                "HttpProxyUsername": "svc_report",
+                "HttpProxyPassword": "proxy-password",
+                "HttpConnectTimeoutSeconds": 5,
+                "HttpRequestTimeoutSeconds": 60,
+                "SchemaVersion": 1
+            }"#;
+
+    // Renames the keys of every object except the Databases map itself, whose
+    // keys are database names
+    fn respell(value: &serde_json::Value, respell_key: fn(&str) -> String) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => map
+                .iter()
+                .map(|(key, value)| {
+                    let value = match (key.as_str(), value) {
+                        ("Databases", serde_json::Value::Object(targets)) => targets
+                            .iter()
+                            .map(|(name, target)| (name.clone(), respell(target, respell_key)))
+                            .collect(),
+                        _ => value.clone(),
+                    };
+                    (respell_key(key), value)
+                })
+                .collect(),
+            value => value.clone(),
+        }
+    }
+
+    fn camel_case(key: &str) -> String {
+        let mut chars = key.chars();
+        chars
+            .next()
+            .map(|first| first.to_ascii_lowercase().to_string() + chars.as_str())
+            .unwrap_or_default()
+    }
+
+    fn snake_case(key: &str) -> String {
+        let mut snake = String::new();
+        for (i, c) in key.chars().enumerate() {
+            if c.is_ascii_uppercase() && i > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        }
+        snake
+    }
+
+    #[test]
+    fn test_accepts_pascal_camel_and_snake_case_keys() {
+        let blob: serde_json::Value = serde_json::from_str(FULL_BLOB).unwrap();
+        let pascal: Settings = serde_json::from_value(blob.clone()).unwrap();
+        let expected = serde_json::to_value(&pascal).unwrap();
+        assert_eq!(expected, blob);
+
+        for respell_key in [camel_case as fn(&str) -> String, snake_case] {
+            let respelled = respell(&blob, respell_key);
+            let settings: Settings = serde_json::from_value(respelled.clone())
+                .unwrap_or_else(|e| panic!("{}: {}", e, respelled));
+            assert_eq!(serde_json::to_value(&settings).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_rejects_field_given_under_two_spellings() {
+        let blob = TEST_BLOB.replacen("{", r#"{"databaseServer": "other","#, 1);
+        let err = Settings::from_json_str(&blob).unwrap_err();
+
+        assert!(
+            err.to_string().contains("duplicate field `DatabaseServer`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_toml_and_json_blobs_match() {
+        let from_toml = Settings::from_toml(TEST_TOML_BLOB).unwrap();
+        let from_json: Settings = serde_json::from_str(TEST_BLOB).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&from_toml).unwrap(),
+            serde_json::to_value(&from_json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_blob_without_format_is_sniffed_as_toml() {
+        let blob = TEST_TOML_BLOB.replace("test_db", "toml_db");
+
+        let (settings, _) = Settings::parse_blob_in_format(&blob, None, Profile::FromEnv).unwrap();
+
+        assert_eq!(settings.database_name(), "toml_db");
+    }
+
+    #[test]
+    fn test_get_settings_honours_format_var() {
+        let _env = lock_env();
+        mock_env_variable();
+        env::set_var("SecretBlobFormat", "toml");
+
+        let toml_result = Settings::get_settings();
+        env::set_var("SecretBlobFormat", "xml");
+        let unsupported = Settings::get_settings();
+        env::remove_var("SecretBlobFormat");
+
+        assert!(matches!(
+            toml_result.unwrap_err(),
+            SettingsError::InvalidToml(_)
+        ));
+        assert!(matches!(
+            unsupported.unwrap_err(),
+            SettingsError::UnsupportedFormat { .. }
+        ));
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("SG.abcdefghijklmnop.wxyz")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+
+        let debug = format!("{:?}", settings);
+
+        assert!(!debug.contains("password123"), "{}", debug);
+        assert!(!debug.contains("SG.abcdefghijklmnop"), "{}", debug);
+        assert!(debug.contains(r#"database_password: "***""#), "{}", debug);
+        assert!(
+            debug.contains(r#"sendgrid_api_key: Some("***wxyz")"#),
+            "{}",
+            debug
+        );
+        assert!(
+            debug.contains(r#"database_server: "localhost""#),
+            "{}",
+            debug
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
     fn test_get_sql_settings() {
-        let settings = Settings {
-            database_server: "localhost".to_string(),
-            database_name: "test_db".to_string(),
-            database_username: "admin".to_string(),
-            database_password: "password123".to_string(),
-            log_webhook_uri: "http://example.com".to_string(),
-            sendgrid_api_key: "sendgrid-api-key".to_string(),
-            email_from_name: "Test".to_string(),
-            email_from_address: "test@example.com".to_string(),
-            email_to_addresses: "user1@example.com".to_string(),
-        };
-    
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("http://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_name("Test")
+            .email_from_address("test@example.com")
+            .email_to_addresses("user1@example.com")
+            .build()
+            .unwrap();
+
         let sql_settings = settings.get_sql_settings();
-    
+
         assert_eq!(sql_settings.get_addr(), "localhost:1433");
     }
-    
+
     #[test]
+    #[cfg(feature = "sendgrid")]
+    #[allow(deprecated)]
     fn test_get_email_destinations() {
-        let settings = Settings {
-            database_server: "localhost".to_string(),
-            database_name: "test_db".to_string(),
-            database_username: "admin".to_string(),
-            database_password: "password123".to_string(),
-            log_webhook_uri: "https://example.com".to_string(),
-            sendgrid_api_key: "sendgrid-api-key".to_string(),
-            email_from_name: "Test".to_string(),
-            email_from_address: "test@example.com".to_string(),
-            email_to_addresses: "user1@example.com,user2@example.com".to_string(),
-        };
-    
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_name("Test")
+            .email_from_address("test@example.com")
+            .email_to_addresses("user1@example.com,user2@example.com")
+            .build()
+            .unwrap();
+
         let email_destinations = settings.get_email_destinations();
-    
+
         assert_eq!(email_destinations.len(), 2);
     }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    #[allow(deprecated)]
+    fn test_partial_blob_without_webhook_or_email() {
+        let settings = Settings::from_json_str(
+            r#"{
+                "DatabaseServer": "localhost",
+                "DatabaseName": "test_db",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.validate(), Ok(()));
+        assert_eq!(settings.log_webhook_uri(), None);
+        assert!(settings.sendgrid_api_key().is_none());
+        #[cfg(feature = "sendgrid")]
+        assert!(settings.get_email_from().is_none());
+        #[cfg(feature = "sendgrid")]
+        assert!(settings.get_email_destinations().is_empty());
+        assert!(matches!(
+            settings.log_webhook_url(),
+            Err(SettingsError::MissingField {
+                field: "LogWebhookUri"
+            })
+        ));
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Settings for a report service, usually loaded from the `SecretBlob` env var.
+///
+/// Only the database fields are needed by every service; the log webhook and
+/// email fields may be left out of the blob, and the operations that use them
+/// then fail with [`SettingsError::MissingField`]. A blob that leaves out
+/// required fields fails to load with [`SettingsError::MissingFields`], which
+/// names all of them in one error rather than one per deploy; required fields
+/// given but empty are reported by [`Settings::validate`].
+///
+/// Blob keys are PascalCase (`DatabaseServer`), but the camelCase
+/// (`databaseServer`) and snake_case (`database_server`) spellings are accepted
+/// too. Giving the same field twice under different spellings is an error.
+///
+/// `database_password`, `sendgrid_api_key`, `http_proxy_password`,
+/// `log_webhook_auth_value` and `smtp_password` are wiped from memory when the
+/// settings are dropped. Copies made elsewhere are not: the tiberius `Config`
+/// returned by [`Settings::get_sql_settings`] holds its own copy of the
+/// password, and the shared [`Settings::http_client`] one of the proxy
+/// password.
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(example = schema::example()))]
 #[serde(rename_all = "PascalCase")]
 pub struct Settings {
+    // Not needed with a connection string, or (username and password) with
+    // integrated auth; validate() requires them otherwise
+    /// SQL Server host, optionally followed by `\instance` or `,port`.
+    #[serde(default)]
+    #[serde(alias = "databaseServer", alias = "database_server")]
     database_server: String,
+    /// The database to connect to.
+    #[serde(default)]
+    #[serde(alias = "databaseName", alias = "database_name")]
     database_name: String,
+    /// SQL login name; not needed with integrated authentication.
+    #[serde(default)]
+    #[serde(alias = "databaseUsername", alias = "database_username")]
     database_username: String,
-    database_password: String,
-    pub log_webhook_uri: String,
-    pub sendgrid_api_key: String,
-    pub email_from_name: String,
-    pub email_from_address: String,
-    pub email_to_addresses: String,
+    /// SQL login password; not needed with integrated authentication.
+    #[serde(default)]
+    #[serde(alias = "databasePassword", alias = "database_password")]
+    database_password: Secret,
+    /// TCP port of the server, 1433 unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "databasePort", alias = "database_port")]
+    database_port: Option<u16>,
+    /// The mirror to connect to when `DatabaseServer` is down, in the same
+    /// forms; see [`Settings::connect_with_failover`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "databaseFailoverServer", alias = "database_failover_server")]
+    database_failover_server: Option<String>,
+    // Optional rather than defaulted, so that only fields actually given
+    // override a connection string
+    /// Off (the default), On or Required.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "databaseEncryption", alias = "database_encryption")]
+    database_encryption: Option<DatabaseEncryption>,
+    /// Whether to accept any server certificate; by default only with
+    /// encryption Off and no `DatabaseCaCertPath`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::flag")]
+    #[serde(alias = "databaseTrustCert", alias = "database_trust_cert")]
+    database_trust_cert: Option<bool>,
+    /// A `.pem` or `.crt` bundle with the CA the server certificate must chain to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "databaseCaCertPath", alias = "database_ca_cert_path")]
+    database_ca_cert_path: Option<String>,
+    /// SqlServer (the default) or Integrated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "databaseAuthMethod", alias = "database_auth_method")]
+    database_auth_method: Option<DatabaseAuthMethod>,
+    /// An ADO.NET connection string, used in place of or underneath the individual database fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        alias = "databaseConnectionString",
+        alias = "database_connection_string"
+    )]
+    database_connection_string: Option<Secret>,
+    /// Whether to connect with `ApplicationIntent=ReadOnly`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::flag")]
+    #[serde(alias = "databaseReadOnlyIntent", alias = "database_read_only_intent")]
+    database_read_only_intent: Option<bool>,
+    /// Further databases, by the name passed to `get_sql_settings_named`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[serde(alias = "databases")]
+    databases: BTreeMap<String, DatabaseTarget>,
+    /// Reported to SQL Server as the connection's `APP_NAME()`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "applicationName", alias = "application_name")]
+    application_name: Option<String>,
+    /// How long to wait for the database connection, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(
+        alias = "databaseConnectTimeoutSeconds",
+        alias = "database_connect_timeout_seconds"
+    )]
+    database_connect_timeout_seconds: Option<u64>,
+    /// How long a single query may run, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(
+        alias = "databaseCommandTimeoutSeconds",
+        alias = "database_command_timeout_seconds"
+    )]
+    database_command_timeout_seconds: Option<u64>,
+    /// Most connections `build_pool` keeps open, 10 unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "databasePoolMaxSize", alias = "database_pool_max_size")]
+    database_pool_max_size: Option<u32>,
+    /// How long to wait for a free pooled connection, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(
+        alias = "databasePoolTimeoutSeconds",
+        alias = "database_pool_timeout_seconds"
+    )]
+    database_pool_timeout_seconds: Option<u64>,
+    /// Whether pooled connections run `SELECT 1` before they are reused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::flag")]
+    #[serde(
+        alias = "databasePoolTestOnCheckout",
+        alias = "database_pool_test_on_checkout"
+    )]
+    database_pool_test_on_checkout: Option<bool>,
+    /// Webhook that log lines are posted to.
+    #[serde(alias = "logWebhookUri", alias = "log_webhook_uri")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(url))]
+    log_webhook_uri: Option<String>,
+    /// Header sent with every log post, e.g. `x-functions-key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "logWebhookAuthHeader", alias = "log_webhook_auth_header")]
+    log_webhook_auth_header: Option<String>,
+    /// Value of `LogWebhookAuthHeader`, e.g. `Bearer <token>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "logWebhookAuthValue", alias = "log_webhook_auth_value")]
+    log_webhook_auth_value: Option<Secret>,
+    /// Request timeout for the log webhook, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(
+        alias = "logWebhookTimeoutSeconds",
+        alias = "log_webhook_timeout_seconds"
+    )]
+    log_webhook_timeout_seconds: Option<u64>,
+    /// How often a failed log post is retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "logWebhookMaxRetries", alias = "log_webhook_max_retries")]
+    log_webhook_max_retries: Option<u32>,
+    /// How many events `WebhookLogger::post_batch` sends per request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "logWebhookBatchSize", alias = "log_webhook_batch_size")]
+    log_webhook_batch_size: Option<u32>,
+    /// Json (the default), Teams or Slack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "logWebhookFormat", alias = "log_webhook_format")]
+    log_webhook_format: Option<LogWebhookFormat>,
+    /// The least severe level posted: Debug, Info, Warning or Error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "logWebhookMinLevel", alias = "log_webhook_min_level")]
+    log_webhook_min_level: Option<LogLevel>,
+    /// Module path prefixes whose log records are posted, comma-separated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        alias = "logWebhookModuleAllowList",
+        alias = "log_webhook_module_allow_list"
+    )]
+    log_webhook_module_allow_list: Option<String>,
+    /// SendGrid API key for report emails.
+    #[serde(alias = "sendgridApiKey", alias = "sendgrid_api_key")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sendgrid_api_key: Option<Secret>,
+    /// Display name of the report email sender.
+    #[serde(alias = "emailFromName", alias = "email_from_name")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    email_from_name: Option<String>,
+    /// Sender address of report emails.
+    #[serde(alias = "emailFromAddress", alias = "email_from_address")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(email))]
+    email_from_address: Option<String>,
+    /// Report recipients, separated by commas or semicolons.
+    #[serde(alias = "emailToAddresses", alias = "email_to_addresses")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    email_to_addresses: Option<String>,
+    /// Cc recipients, separated by commas or semicolons.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "emailCcAddresses", alias = "email_cc_addresses")]
+    email_cc_addresses: Option<String>,
+    /// Bcc recipients, separated by commas or semicolons.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "emailBccAddresses", alias = "email_bcc_addresses")]
+    email_bcc_addresses: Option<String>,
+    /// Address replies to report emails go to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "emailReplyToAddress", alias = "email_reply_to_address")]
+    email_reply_to_address: Option<String>,
+    /// Display name for the reply-to address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "emailReplyToName", alias = "email_reply_to_name")]
+    email_reply_to_name: Option<String>,
+    /// How often a failed send is retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "emailMaxRetries", alias = "email_max_retries")]
+    email_max_retries: Option<u32>,
+    /// Delay before the first email retry, in milliseconds; it doubles after each retry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "emailRetryBaseMs", alias = "email_retry_base_ms")]
+    email_retry_base_ms: Option<u64>,
+    /// How many emails may be sent per hour; unlimited if absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "emailMaxPerHour", alias = "email_max_per_hour")]
+    email_max_per_hour: Option<u32>,
+    /// Whether a report with no recipients at all is skipped instead of failing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::flag")]
+    #[serde(
+        alias = "emailAllowEmptyRecipients",
+        alias = "email_allow_empty_recipients"
+    )]
+    email_allow_empty_recipients: Option<bool>,
+    /// Recipients across To, Cc and Bcc above which a warning is reported; 100 unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(
+        alias = "emailRecipientSoftLimit",
+        alias = "email_recipient_soft_limit"
+    )]
+    email_recipient_soft_limit: Option<u32>,
+    /// The SendGrid dynamic template (`d-...`) report emails are built from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "sendgridTemplateId", alias = "sendgrid_template_id")]
+    sendgrid_template_id: Option<String>,
+    /// Whether SendGrid only validates report emails instead of delivering them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::flag")]
+    #[serde(alias = "emailSandboxMode", alias = "email_sandbox_mode")]
+    email_sandbox_mode: Option<bool>,
+    /// Comma-separated SendGrid categories report emails are tagged with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "emailCategories", alias = "email_categories")]
+    email_categories: Option<String>,
+    /// SendGrid custom args for report emails, a JSON object of string values.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "emailCustomArgs", alias = "email_custom_args")]
+    #[cfg_attr(
+        feature = "schema",
+        schemars(with = "Option<BTreeMap<String, String>>")
+    )]
+    email_custom_args: Option<BTreeMap<String, serde_json::Value>>,
+    /// The SendGrid unsubscribe group report emails belong to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "emailAsmGroupId", alias = "email_asm_group_id")]
+    email_asm_group_id: Option<u32>,
+    /// Comma-separated unsubscribe group ids shown on the preferences page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        alias = "emailAsmGroupsToDisplay",
+        alias = "email_asm_groups_to_display"
+    )]
+    email_asm_groups_to_display: Option<String>,
+    /// The SendGrid API host, e.g. `https://api.eu.sendgrid.com` for EU data residency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "sendgridApiHost", alias = "sendgrid_api_host")]
+    sendgrid_api_host: Option<String>,
+    /// The SMTP relay report emails fall back to when SendGrid is down.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "smtpHost", alias = "smtp_host")]
+    smtp_host: Option<String>,
+    /// The SMTP relay's port; 587, or 25 with `SmtpUseStartTls` off, if absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "smtpPort", alias = "smtp_port")]
+    smtp_port: Option<u16>,
+    /// Username for the SMTP relay.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "smtpUsername", alias = "smtp_username")]
+    smtp_username: Option<String>,
+    /// Password for the SMTP relay.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "smtpPassword", alias = "smtp_password")]
+    smtp_password: Option<Secret>,
+    /// Whether the SMTP connection must be upgraded with STARTTLS; on if absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::flag")]
+    #[serde(alias = "smtpUseStartTls", alias = "smtp_use_start_tls")]
+    smtp_use_start_tls: Option<bool>,
+    /// The proxy outbound HTTP calls go through, e.g. `http://proxy.example.com:3128`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "httpProxyUri", alias = "http_proxy_uri")]
+    http_proxy_uri: Option<String>,
+    /// Username for the HTTP proxy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "httpProxyUsername", alias = "http_proxy_username")]
+    http_proxy_username: Option<String>,
+    /// Password for the HTTP proxy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "httpProxyPassword", alias = "http_proxy_password")]
+    http_proxy_password: Option<Secret>,
+    /// Connect timeout for outbound HTTP, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(
+        alias = "httpConnectTimeoutSeconds",
+        alias = "http_connect_timeout_seconds"
+    )]
+    http_connect_timeout_seconds: Option<u64>,
+    /// Request timeout for outbound HTTP, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(
+        alias = "httpRequestTimeoutSeconds",
+        alias = "http_request_timeout_seconds"
+    )]
+    http_request_timeout_seconds: Option<u64>,
+    /// The layout of the blob; 1 if absent. See [`Settings::schema_version`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "crate::serde_helpers::number")]
+    #[serde(alias = "schemaVersion", alias = "schema_version")]
+    schema_version: Option<u32>,
+    // Built by `http_client` on first use
+    #[cfg(feature = "remote")]
+    #[serde(skip)]
+    http_client: std::sync::OnceLock<reqwest::Client>,
+    // Shared by clones, and carried over by reloads that keep `EmailMaxPerHour`
+    #[cfg(feature = "sendgrid")]
+    #[serde(skip)]
+    email_rate_limiter: rate_limit::EmailRateLimiter,
+    // The required keys the parsed blob left out; see `Settings::require_fields`
+    #[serde(skip)]
+    absent_fields: Vec<&'static str>,
+}
+
+// The password is hidden entirely; the API key keeps its last four characters so
+// it can still be matched against the key listed in the SendGrid dashboard
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Settings")
+            .field("database_server", &self.database_server)
+            .field("database_name", &self.database_name)
+            .field("database_username", &self.database_username)
+            .field("database_password", &"***")
+            .field("database_port", &self.database_port)
+            .field("database_failover_server", &self.database_failover_server)
+            .field("database_encryption", &self.database_encryption)
+            .field("database_trust_cert", &self.database_trust_cert)
+            .field("database_ca_cert_path", &self.database_ca_cert_path)
+            .field("database_auth_method", &self.database_auth_method)
+            .field(
+                "database_connection_string",
+                &self.database_connection_string.as_ref().map(|_| "***"),
+            )
+            .field("database_read_only_intent", &self.database_read_only_intent)
+            .field("databases", &self.databases)
+            .field("application_name", &self.application_name)
+            .field(
+                "database_connect_timeout_seconds",
+                &self.database_connect_timeout_seconds,
+            )
+            .field(
+                "database_command_timeout_seconds",
+                &self.database_command_timeout_seconds,
+            )
+            .field("database_pool_max_size", &self.database_pool_max_size)
+            .field(
+                "database_pool_timeout_seconds",
+                &self.database_pool_timeout_seconds,
+            )
+            .field(
+                "database_pool_test_on_checkout",
+                &self.database_pool_test_on_checkout,
+            )
+            .field("log_webhook_uri", &self.log_webhook_uri)
+            .field("log_webhook_auth_header", &self.log_webhook_auth_header)
+            .field(
+                "log_webhook_auth_value",
+                &self.log_webhook_auth_value.as_ref().map(|_| "***"),
+            )
+            .field(
+                "log_webhook_timeout_seconds",
+                &self.log_webhook_timeout_seconds,
+            )
+            .field("log_webhook_max_retries", &self.log_webhook_max_retries)
+            .field("log_webhook_batch_size", &self.log_webhook_batch_size)
+            .field("log_webhook_format", &self.log_webhook_format)
+            .field("log_webhook_min_level", &self.log_webhook_min_level)
+            .field(
+                "log_webhook_module_allow_list",
+                &self.log_webhook_module_allow_list,
+            )
+            .field(
+                "sendgrid_api_key",
+                &self
+                    .sendgrid_api_key
+                    .as_ref()
+                    .map(|key| redact_tail(key.inspect())),
+            )
+            .field("email_from_name", &self.email_from_name)
+            .field("email_from_address", &self.email_from_address)
+            .field("email_to_addresses", &self.email_to_addresses)
+            .field("email_cc_addresses", &self.email_cc_addresses)
+            .field("email_bcc_addresses", &self.email_bcc_addresses)
+            .field("email_reply_to_address", &self.email_reply_to_address)
+            .field("email_reply_to_name", &self.email_reply_to_name)
+            .field("email_max_retries", &self.email_max_retries)
+            .field("email_retry_base_ms", &self.email_retry_base_ms)
+            .field("email_max_per_hour", &self.email_max_per_hour)
+            .field(
+                "email_allow_empty_recipients",
+                &self.email_allow_empty_recipients,
+            )
+            .field(
+                "email_recipient_soft_limit",
+                &self.email_recipient_soft_limit,
+            )
+            .field("sendgrid_template_id", &self.sendgrid_template_id)
+            .field("email_sandbox_mode", &self.email_sandbox_mode)
+            .field("email_categories", &self.email_categories)
+            .field("email_custom_args", &self.email_custom_args)
+            .field("email_asm_group_id", &self.email_asm_group_id)
+            .field(
+                "email_asm_groups_to_display",
+                &self.email_asm_groups_to_display,
+            )
+            .field("sendgrid_api_host", &self.sendgrid_api_host)
+            .field("smtp_host", &self.smtp_host)
+            .field("smtp_port", &self.smtp_port)
+            .field("smtp_username", &self.smtp_username)
+            .field("smtp_password", &self.smtp_password.as_ref().map(|_| "***"))
+            .field("smtp_use_start_tls", &self.smtp_use_start_tls)
+            .field("http_proxy_uri", &self.http_proxy_uri)
+            .field("http_proxy_username", &self.http_proxy_username)
+            .field(
+                "http_proxy_password",
+                &self.http_proxy_password.as_ref().map(|_| "***"),
+            )
+            .field(
+                "http_connect_timeout_seconds",
+                &self.http_connect_timeout_seconds,
+            )
+            .field(
+                "http_request_timeout_seconds",
+                &self.http_request_timeout_seconds,
+            )
+            .field("schema_version", &self.schema_version)
+            .finish()
+    }
+}
+
+/// Parses a JSON blob; the same as [`Settings::from_json_str`].
+impl FromStr for Settings {
+    type Err = SettingsError;
+
+    fn from_str(blob: &str) -> Result<Settings, SettingsError> {
+        Settings::from_json_str(blob)
+    }
+}
+
+// Where `Settings::load_blob` read the blob from
+pub(crate) fn blob_source(name: &str) -> SettingsSource {
+    match env::var_os(format!("{}Path", name)) {
+        Some(path) if env::var_os(name).is_none() => SettingsSource::File(path.into()),
+        _ => SettingsSource::EnvVar(name.to_string()),
+    }
+}
+
+// Short values would be mostly given away by their tail, so they are hidden fully
+pub(crate) fn redact_tail(value: &str) -> String {
+    let len = value.chars().count();
+    if len < 12 {
+        return "***".to_string();
+    }
+    let tail: String = value.chars().skip(len - 4).collect();
+    format!("***{}", tail)
 }
 
 impl Settings {
-    pub fn get_settings() -> Result<Settings, String> {
-        let secret_blob = match env::var("SecretBlob") {
-            Ok(s) => s,
-            Err(e) => return Err(format!("Error getting env variable: {}", e.to_string())),
-        };
+    pub fn builder() -> SettingsBuilder {
+        SettingsBuilder::new()
+    }
 
-        let sett: Settings = match serde_json::from_str(&secret_blob) {
+    pub fn database_server(&self) -> &str {
+        &self.database_server
+    }
+
+    pub fn database_name(&self) -> &str {
+        &self.database_name
+    }
+
+    pub fn database_username(&self) -> &str {
+        &self.database_username
+    }
+
+    /// `DatabasePort` as given in the blob. The port actually used may also come
+    /// from a `host,port` suffix on `DatabaseServer`; see [`Settings::get_sql_settings`].
+    pub fn database_port(&self) -> Option<u16> {
+        self.database_port
+    }
+
+    /// `DatabaseEncryption`, `Off` unless the blob says otherwise.
+    pub fn database_encryption(&self) -> DatabaseEncryption {
+        self.database_encryption.unwrap_or_default()
+    }
+
+    /// `DatabaseTrustCert`: whether to accept any server certificate. Unless
+    /// the blob says otherwise, only connections with encryption `Off`, which
+    /// only encrypt the login, do, and only without `DatabaseCaCertPath`.
+    pub fn database_trust_cert(&self) -> bool {
+        self.database_trust_cert.unwrap_or(
+            self.database_encryption() == DatabaseEncryption::Off
+                && self.database_ca_cert_path.is_none(),
+        )
+    }
+
+    /// `DatabaseCaCertPath`, the CA bundle the server certificate is checked
+    /// against; see [`Settings::get_sql_settings`].
+    pub fn database_ca_cert_path(&self) -> Option<&Path> {
+        self.database_ca_cert_path.as_deref().map(Path::new)
+    }
+
+    /// `DatabaseAuthMethod`, `SqlServer` unless the blob says otherwise.
+    pub fn database_auth_method(&self) -> DatabaseAuthMethod {
+        self.database_auth_method.unwrap_or_default()
+    }
+
+    /// `DatabaseConnectionString`, an ADO.NET connection string used in place of,
+    /// or underneath, the individual database fields.
+    pub fn database_connection_string(&self) -> Option<&Secret> {
+        self.database_connection_string.as_ref()
+    }
+
+    /// `DatabaseReadOnlyIntent`: whether to connect with `ApplicationIntent=ReadOnly`,
+    /// so an availability group listener routes to a readable secondary.
+    pub fn database_read_only_intent(&self) -> bool {
+        self.database_read_only_intent.unwrap_or_default()
+    }
+
+    /// `ApplicationName`, reported to SQL Server as the connection's `APP_NAME()`.
+    /// Defaults to [`DEFAULT_APPLICATION_NAME`].
+    pub fn application_name(&self) -> &str {
+        self.application_name
+            .as_deref()
+            .unwrap_or(DEFAULT_APPLICATION_NAME)
+    }
+
+    /// Replaces `ApplicationName`, for binaries that share one blob but should
+    /// show up separately in SQL Server monitoring.
+    pub fn with_application_name(mut self, application_name: impl Into<String>) -> Settings {
+        self.application_name = Some(application_name.into());
+        self
+    }
+
+    /// Returns the database password in clear text. Named deliberately so that
+    /// reading the secret is easy to spot in review and hard to do by accident.
+    /// Reported to the [`AuditSink`] like [`Secret::expose`].
+    #[track_caller]
+    pub fn expose_database_password(&self) -> &str {
+        self.database_password.expose()
+    }
+
+    pub fn log_webhook_uri(&self) -> Option<&str> {
+        self.log_webhook_uri.as_deref()
+    }
+
+    /// The SendGrid API key; call [`Secret::expose`] to read it.
+    pub fn sendgrid_api_key(&self) -> Option<&Secret> {
+        self.sendgrid_api_key.as_ref()
+    }
+
+    pub fn email_from_name(&self) -> Option<&str> {
+        self.email_from_name.as_deref()
+    }
+
+    pub fn email_from_address(&self) -> Option<&str> {
+        self.email_from_address.as_deref()
+    }
+
+    pub fn email_to_addresses(&self) -> Option<&str> {
+        self.email_to_addresses.as_deref()
+    }
+
+    /// `EmailCcAddresses` as given in the blob, or empty if unset.
+    pub fn email_cc_addresses(&self) -> &str {
+        self.email_cc_addresses.as_deref().unwrap_or_default()
+    }
+
+    /// `EmailBccAddresses` as given in the blob, or empty if unset.
+    pub fn email_bcc_addresses(&self) -> &str {
+        self.email_bcc_addresses.as_deref().unwrap_or_default()
+    }
+
+    /// `EmailReplyToAddress` as given in the blob, or empty if unset.
+    pub fn email_reply_to_address(&self) -> &str {
+        self.email_reply_to_address.as_deref().unwrap_or_default()
+    }
+
+    /// `EmailReplyToName` as given in the blob, or empty if unset.
+    pub fn email_reply_to_name(&self) -> &str {
+        self.email_reply_to_name.as_deref().unwrap_or_default()
+    }
+
+    /// `EmailAllowEmptyRecipients`: whether a report with no To, Cc or Bcc
+    /// recipients is skipped rather than failing with
+    /// [`SettingsError::NoRecipients`]. Off unless the blob says otherwise.
+    pub fn email_allow_empty_recipients(&self) -> bool {
+        self.email_allow_empty_recipients.unwrap_or_default()
+    }
+
+    /// `EmailRecipientSoftLimit`, or [`DEFAULT_EMAIL_RECIPIENT_SOFT_LIMIT`] if not set.
+    pub fn email_recipient_soft_limit(&self) -> u32 {
+        self.email_recipient_soft_limit
+            .unwrap_or(DEFAULT_EMAIL_RECIPIENT_SOFT_LIMIT)
+    }
+
+    pub fn sendgrid_template_id(&self) -> Option<&str> {
+        self.sendgrid_template_id.as_deref()
+    }
+
+    /// `EmailSandboxMode`: whether messages are built with SendGrid's sandbox
+    /// mode on, so they are checked but never delivered. Off unless set.
+    pub fn is_email_sandboxed(&self) -> bool {
+        self.email_sandbox_mode.unwrap_or_default()
+    }
+
+    /// Loads settings from the `SecretBlob` env var. See [`Settings::get_settings_from_var`].
+    ///
+    /// A blob with profiles (see [`Settings::get_settings_for_profile`]) loads
+    /// the profile named by the `Environment` env var, or `Default` if unset.
+    ///
+    /// With `SecretBlobKey` set, `SecretBlob` is decrypted with it first; see
+    /// [`Settings::get_settings_from_var`].
+    ///
+    /// In debug builds, when neither `SecretBlob` nor `SecretBlobPath` is set,
+    /// the fields are read from `./.env` instead (see [`Settings::from_dotenv`]),
+    /// with variables that are set taking precedence. Release builds only do
+    /// this with the `dotenv` feature.
+    pub fn get_settings() -> Result<Settings, SettingsError> {
+        match Settings::get_settings_from_var(DEFAULT_BLOB_VAR) {
+            Err(missing @ SettingsError::MissingEnvVar { .. }) => {
+                let settings = dotenv::dotenv_fallback(
+                    Path::new(DOTENV_FILE),
+                    missing,
+                    overrides::env_lookup,
+                )?;
+                settings.record_load(
+                    vec![SettingsSource::File(DOTENV_FILE.into())],
+                    BTreeMap::new(),
+                );
+                Ok(settings)
+            }
+            result => result,
+        }
+    }
+
+    /// Loads settings from the env var `name`. When that variable is absent and
+    /// `<name>Path` (e.g. `SecretBlobPath`) is set, the blob is read from that file instead.
+    ///
+    /// Precedence, highest first: `REPORTSETTINGS_<FIELD>` overrides (see
+    /// [`Settings::apply_env_overrides`]), then the blob variable, then the path variable.
+    ///
+    /// When `<name>Key` (e.g. `SecretBlobKey`) is set, it holds a base64
+    /// AES-256 key and the blob variable is taken to be encrypted with it, as
+    /// described at [`Settings::from_encrypted_blob`]. A blob read from the
+    /// path variable is never decrypted.
+    pub fn get_settings_from_var(name: &str) -> Result<Settings, SettingsError> {
+        let (mut settings, _) = Settings::load_blob(name, Profile::FromEnv)?;
+        settings.apply_env_overrides()?;
+        let settings = settings.finish_load()?;
+        settings.record_load(vec![blob_source(name)], BTreeMap::new());
+        Ok(settings)
+    }
+
+    // Also returns the keys that aren't settings fields
+    fn load_blob(name: &str, profile: Profile) -> Result<(Settings, Vec<String>), SettingsError> {
+        let secret_blob = match env::var(name) {
             Ok(s) => s,
-            Err(e) => {
-                return Err(format!(
-                    "Could not deserialize settings blob: {}",
-                    e.to_string()
-                ))
+            Err(env::VarError::NotPresent) => {
+                if let Some(path) = env::var_os(format!("{}Path", name)) {
+                    return Settings::read_blob_file(path.as_ref(), profile);
+                }
+                return Err(SettingsError::MissingEnvVar {
+                    name: name.to_string(),
+                });
+            }
+            Err(env::VarError::NotUnicode(_)) => {
+                return Err(SettingsError::InvalidEnvVar {
+                    name: name.to_string(),
+                })
             }
         };
 
-        Ok(sett)
+        match encrypted::blob_key(name)? {
+            Some(key) => Settings::parse_encrypted_blob(&secret_blob, &key, profile),
+            None => Settings::parse_blob_reporting_unknown(&secret_blob, profile),
+        }
     }
 
-    pub fn get_sql_settings(&self) -> Config {
-        let mut sql_settings = Config::new();
-        sql_settings.host(&self.database_server);
-        sql_settings.application_name("Login Checker");
-        sql_settings.database(&self.database_name);
-        sql_settings.authentication(AuthMethod::sql_server(
-            &self.database_username,
-            &self.database_password,
-        ));
-        sql_settings.encryption(EncryptionLevel::Off);
-        sql_settings.trust_cert();
-        sql_settings
+    pub fn get_settings_from_file(path: impl AsRef<Path>) -> Result<Settings, SettingsError> {
+        let settings = Settings::read_blob_file(path.as_ref(), Profile::FromEnv)?
+            .0
+            .finish_load()?;
+        settings.record_load(
+            vec![SettingsSource::File(path.as_ref().to_path_buf())],
+            BTreeMap::new(),
+        );
+        Ok(settings)
+    }
+
+    fn read_blob_file(
+        path: &Path,
+        profile: Profile,
+    ) -> Result<(Settings, Vec<String>), SettingsError> {
+        let contents = Settings::read_file(path)?;
+        match BlobFormat::from_path(path)? {
+            Some(format) => cleanup::parse_cleaned(&contents, |blob| {
+                format.parse_reporting_unknown(blob, profile)
+            }),
+            None => Settings::parse_blob_reporting_unknown(&contents, profile),
+        }
+    }
+
+    fn read_file(path: &Path) -> Result<String, SettingsError> {
+        fs::read_to_string(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => SettingsError::FileNotFound {
+                path: path.to_path_buf(),
+            },
+            _ => SettingsError::FileUnreadable {
+                path: path.to_path_buf(),
+                source: e,
+            },
+        })
+    }
+
+    /// Parses a blob in the PascalCase TOML layout used by the JSON blob.
+    pub fn from_toml(blob: &str) -> Result<Settings, SettingsError> {
+        BlobFormat::Toml.parse(blob)
+    }
+
+    /// Parses a blob in the PascalCase YAML layout used by the JSON blob. Anchors,
+    /// aliases and duplicate keys are rejected; `EmailToAddresses` may be a list.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(blob: &str) -> Result<Settings, SettingsError> {
+        BlobFormat::Yaml.parse(blob)
+    }
+
+    /// Parses a JSON blob exactly as [`Settings::get_settings`] parses the
+    /// `SecretBlob` variable, base64 encoding and profiles included, but
+    /// without touching the env var or applying `REPORTSETTINGS_<FIELD>`
+    /// overrides. Call [`Settings::validate`] to check the result.
+    ///
+    /// Only a blob with profiles reads the environment, for `Environment`.
+    pub fn from_json_str(blob: &str) -> Result<Settings, SettingsError> {
+        Settings::parse_json_reporting_unknown(blob, Profile::FromEnv)?
+            .0
+            .require_fields()
+    }
+
+    /// Like [`Settings::from_json_str`], reading the blob from `reader`.
+    pub fn from_reader(mut reader: impl io::Read) -> Result<Settings, SettingsError> {
+        let mut blob = String::new();
+        reader
+            .read_to_string(&mut blob)
+            .map_err(SettingsError::ReaderFailed)?;
+        Settings::from_json_str(&blob)
+    }
+
+    fn parse_blob(blob: &str) -> Result<Settings, SettingsError> {
+        Ok(Settings::parse_blob_reporting_unknown(blob, Profile::FromEnv)?.0)
+    }
+
+    fn parse_blob_reporting_unknown(
+        blob: &str,
+        profile: Profile,
+    ) -> Result<(Settings, Vec<String>), SettingsError> {
+        let format = env::var(FORMAT_VAR).ok();
+        Settings::parse_blob_in_format(blob, format.as_deref(), profile)
     }
 
-    pub fn get_email_destinations(&self) -> Vec<Email> {
-        self.email_to_addresses
-            .split(",")
-            .map(|x| Email::new(x))
-            .collect()
+    // `format` is the value of `SecretBlobFormat`, if set
+    fn parse_blob_in_format(
+        blob: &str,
+        format: Option<&str>,
+        profile: Profile,
+    ) -> Result<(Settings, Vec<String>), SettingsError> {
+        cleanup::parse_cleaned(blob, |blob| match BlobFormat::detect(blob, format)? {
+            BlobFormat::Json => Settings::parse_json_or_base64(blob, profile),
+            format => format.parse_reporting_unknown(blob, profile),
+        })
+    }
+
+    fn parse_json_reporting_unknown(
+        blob: &str,
+        profile: Profile,
+    ) -> Result<(Settings, Vec<String>), SettingsError> {
+        cleanup::parse_cleaned(blob, |blob| Settings::parse_json_or_base64(blob, profile))
+    }
+
+    // JSON that fails to parse but is made only of base64 characters is decoded
+    // and retried, so secret stores that mangle raw JSON can hold it encoded
+    fn parse_json_or_base64(
+        blob: &str,
+        profile: Profile,
+    ) -> Result<(Settings, Vec<String>), SettingsError> {
+        match BlobFormat::Json.parse_reporting_unknown(blob, profile) {
+            Err(SettingsError::InvalidJson(json)) if encoded::looks_like_base64(blob) => {
+                encoded::decode(blob)
+                    .and_then(|decoded| BlobFormat::Json.parse_reporting_unknown(&decoded, profile))
+                    .map_err(|e| SettingsError::InvalidJsonOrBase64 {
+                        json,
+                        base64: Box::new(e),
+                    })
+            }
+            result => result,
+        }
     }
 }
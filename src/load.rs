@@ -0,0 +1,286 @@
+#[cfg(feature = "remote")]
+use crate::http_source::{self, BearerToken};
+#[cfg(any(feature = "remote", feature = "azure", feature = "aws"))]
+use crate::overrides::env_lookup;
+#[cfg(any(feature = "azure", feature = "aws", test))]
+use crate::SecretStore;
+#[cfg(any(feature = "remote", feature = "azure", feature = "aws", test))]
+use crate::SettingsSource;
+use crate::{Settings, SettingsError};
+#[cfg(any(feature = "remote", feature = "azure", feature = "aws", test))]
+use std::collections::BTreeMap;
+
+/// The env variable [`Settings::load_async`] fetches the blob's URL from.
+#[cfg(feature = "remote")]
+pub const BLOB_URL_VAR: &str = "SecretBlobUrl";
+
+/// The env variable [`Settings::load_async`] reads the bearer token for
+/// [`BLOB_URL_VAR`] from, if set.
+#[cfg(feature = "remote")]
+pub const BLOB_TOKEN_VAR: &str = "SecretBlobToken";
+
+/// The env variable [`Settings::load_async`] reads the Azure Key Vault URL to
+/// fetch the blob from, e.g. `https://my-vault.vault.azure.net`.
+#[cfg(feature = "azure")]
+pub const BLOB_KEY_VAULT_VAR: &str = "SecretBlobKeyVault";
+
+/// The env variable [`Settings::load_async`] reads the AWS region of the
+/// Secrets Manager to fetch the blob from, e.g. `eu-west-1`.
+#[cfg(feature = "aws")]
+pub const BLOB_SECRETS_MANAGER_REGION_VAR: &str = "SecretBlobSecretsManagerRegion";
+
+/// The env variable [`Settings::load_async`] reads the name of the blob's
+/// secret from, when [`BLOB_KEY_VAULT_VAR`] or
+/// [`BLOB_SECRETS_MANAGER_REGION_VAR`] picks a secret store.
+#[cfg(any(feature = "azure", feature = "aws"))]
+pub const BLOB_SECRET_NAME_VAR: &str = "SecretBlobSecretName";
+
+impl Settings {
+    /// Loads and validates the settings without an async runtime: the blob in
+    /// `SecretBlob`, the file in `SecretBlobPath` or a `.env` file, as
+    /// [`Settings::get_settings`] reads them, overrides included.
+    ///
+    /// Never touches the network. It needs no runtime in any build, but only a
+    /// `--no-default-features` build leaves tokio out of the dependency tree:
+    /// the default `mssql`, `remote` and `reload` features each pull it in.
+    pub fn load() -> Result<Settings, SettingsError> {
+        validated(Settings::get_settings()?)
+    }
+
+    /// Like [`Settings::load`], but fetches the blob from wherever the env
+    /// picks, checked in this order:
+    ///
+    /// - [`BLOB_URL_VAR`], with the `remote` feature: the URL, as
+    ///   [`Settings::from_url`] fetches it, with the bearer token in
+    ///   [`BLOB_TOKEN_VAR`] if there is one.
+    /// - [`BLOB_KEY_VAULT_VAR`], with the `azure` feature: the secret named by
+    ///   [`BLOB_SECRET_NAME_VAR`] in that vault, as
+    ///   [`Settings::from_key_vault`] fetches it.
+    /// - [`BLOB_SECRETS_MANAGER_REGION_VAR`], with the `aws` feature: the
+    ///   secret named by [`BLOB_SECRET_NAME_VAR`] in that region, as
+    ///   [`Settings::from_secrets_manager`] fetches it.
+    ///
+    /// With none of them set it loads as [`Settings::load`] does. The
+    /// `REPORTSETTINGS_<FIELD>` overrides apply to a fetched blob too, and it
+    /// is validated the same way. A store picked without
+    /// [`BLOB_SECRET_NAME_VAR`] fails with [`SettingsError::MissingEnvVar`].
+    #[cfg(any(feature = "remote", feature = "azure", feature = "aws"))]
+    pub async fn load_async() -> Result<Settings, SettingsError> {
+        load_async_from(env_lookup).await
+    }
+}
+
+#[cfg(any(feature = "remote", feature = "azure", feature = "aws"))]
+async fn load_async_from<F>(lookup: F) -> Result<Settings, SettingsError>
+where
+    F: Fn(&str) -> Result<Option<String>, SettingsError>,
+{
+    #[cfg(feature = "remote")]
+    if let Some(url) = lookup(BLOB_URL_VAR)? {
+        let token = lookup(BLOB_TOKEN_VAR)?.map(BearerToken::new);
+        let settings = Settings::fetch_url(&url, token).await?;
+        let source = SettingsSource::Url(http_source::shown_url(&url));
+        return fetched(settings, source, &lookup);
+    }
+    #[cfg(feature = "azure")]
+    if let Some(vault_url) = lookup(BLOB_KEY_VAULT_VAR)? {
+        let store = crate::KeyVaultStore::new(&vault_url)?;
+        return load_from_store(&store, &secret_name(&lookup)?, &lookup).await;
+    }
+    #[cfg(feature = "aws")]
+    if let Some(region) = lookup(BLOB_SECRETS_MANAGER_REGION_VAR)? {
+        let store = crate::SecretsManagerStore::new(&region).await;
+        return load_from_store(&store, &secret_name(&lookup)?, &lookup).await;
+    }
+    Settings::load()
+}
+
+#[cfg(any(feature = "azure", feature = "aws"))]
+fn secret_name<F>(lookup: &F) -> Result<String, SettingsError>
+where
+    F: Fn(&str) -> Result<Option<String>, SettingsError>,
+{
+    lookup(BLOB_SECRET_NAME_VAR)?.ok_or_else(|| SettingsError::MissingEnvVar {
+        name: BLOB_SECRET_NAME_VAR.to_string(),
+    })
+}
+
+#[cfg(any(feature = "azure", feature = "aws", test))]
+async fn load_from_store<S, F>(store: &S, name: &str, lookup: &F) -> Result<Settings, SettingsError>
+where
+    S: SecretStore,
+    F: Fn(&str) -> Result<Option<String>, SettingsError>,
+{
+    let settings = Settings::fetch_secret(store, name).await?;
+    let source = SettingsSource::SecretStore {
+        store: store.store_name(),
+        name: name.to_string(),
+    };
+    fetched(settings, source, lookup)
+}
+
+// What every source of `load_async` does with the blob it fetched
+#[cfg(any(feature = "remote", feature = "azure", feature = "aws", test))]
+fn fetched<F>(
+    mut settings: Settings,
+    source: SettingsSource,
+    lookup: &F,
+) -> Result<Settings, SettingsError>
+where
+    F: Fn(&str) -> Result<Option<String>, SettingsError>,
+{
+    settings.apply_overrides_from(lookup)?;
+    let settings = settings.finish_load()?;
+    settings.record_load(vec![source], BTreeMap::new());
+    validated(settings)
+}
+
+fn validated(settings: Settings) -> Result<Settings, SettingsError> {
+    settings.validate().map_err(SettingsError::Validation)?;
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::tests::{recorded_here, Recorder};
+    use crate::{SecretStoreError, SettingsEvent};
+    use std::process::Command;
+
+    struct OneSecret(&'static str);
+
+    impl SecretStore for OneSecret {
+        fn store_name(&self) -> &'static str {
+            "mock store"
+        }
+
+        async fn get_secret(&self, _name: &str) -> Result<String, SecretStoreError> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    // The Key Vault and Secrets Manager sources of `load_async` both go
+    // through `load_from_store`
+    #[tokio::test]
+    async fn test_secret_store_blob_is_overridden_and_validated() {
+        Settings::set_audit_sink(Recorder);
+        let store = OneSecret(
+            r#"{"DatabaseServer": "sql01", "DatabaseUsername": "svc", "DatabasePassword": "pw"}"#,
+        );
+        let overrides = |name: &str| {
+            Ok((name == "REPORTSETTINGS_DATABASE_NAME").then(|| "reports".to_string()))
+        };
+
+        let settings = load_from_store(&store, "ReportSettings", &overrides)
+            .await
+            .unwrap();
+        assert_eq!(settings.database_name(), "reports");
+        assert!(matches!(
+            recorded_here().first(),
+            Some(SettingsEvent::Loaded { sources, .. })
+                if sources == &[SettingsSource::SecretStore {
+                    store: "mock store",
+                    name: "ReportSettings".to_string(),
+                }]
+        ));
+
+        let missing = load_from_store(&store, "ReportSettings", &|_: &str| Ok(None)).await;
+        assert_eq!(
+            missing.unwrap_err().to_string(),
+            "Incomplete settings blob: missing field `DatabaseName`"
+        );
+    }
+
+    // The sync half of the crate must build without an async runtime
+    #[test]
+    fn test_sync_features_pull_in_no_runtime() {
+        let output = Command::new(env!("CARGO"))
+            .args(["tree", "--no-default-features", "--edges", "normal"])
+            .args(["--prefix", "none"])
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let tree = String::from_utf8(output.stdout).unwrap();
+        for krate in ["tokio", "reqwest", "hyper"] {
+            assert!(
+                !tree
+                    .lines()
+                    .any(|line| line.split(' ').next() == Some(krate)),
+                "{} is in the sync-only tree:\n{}",
+                krate,
+                tree
+            );
+        }
+    }
+
+    #[cfg(feature = "remote")]
+    mod remote {
+        use super::super::*;
+        use std::collections::HashMap;
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        fn lookup(
+            vars: HashMap<&'static str, String>,
+        ) -> impl Fn(&str) -> Result<Option<String>, SettingsError> {
+            move |name| Ok(vars.get(name).cloned())
+        }
+
+        async fn serve(blob: &str) -> MockServer {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/settings.json"))
+                .and(header("authorization", "Bearer s3cret"))
+                .respond_with(ResponseTemplate::new(200).set_body_string(blob))
+                .expect(1)
+                .mount(&server)
+                .await;
+            server
+        }
+
+        #[tokio::test]
+        async fn test_load_async_fetches_and_overrides() {
+            let server = serve(
+                r#"{"DatabaseServer": "sql01", "DatabaseName": "reports",
+                    "DatabaseUsername": "reports", "DatabasePassword": "hunter2"}"#,
+            )
+            .await;
+            let vars = HashMap::from([
+                (BLOB_URL_VAR, format!("{}/settings.json", server.uri())),
+                (BLOB_TOKEN_VAR, "s3cret".to_string()),
+                (
+                    "REPORTSETTINGS_DATABASE_NAME",
+                    "reports_replica".to_string(),
+                ),
+            ]);
+
+            let settings = load_async_from(lookup(vars)).await.unwrap();
+
+            assert_eq!(settings.database_server(), "sql01");
+            assert_eq!(settings.database_name(), "reports_replica");
+        }
+
+        #[tokio::test]
+        async fn test_load_async_validates_fetched_blob() {
+            let server = serve(
+                r#"{"DatabaseServer": "sql01", "DatabaseName": "reports",
+                    "DatabaseUsername": "svc", "DatabasePassword": " "}"#,
+            )
+            .await;
+            let vars = HashMap::from([
+                (BLOB_URL_VAR, format!("{}/settings.json", server.uri())),
+                (BLOB_TOKEN_VAR, "s3cret".to_string()),
+            ]);
+
+            let err = load_async_from(lookup(vars)).await.unwrap_err();
+
+            assert!(matches!(err, SettingsError::Validation(_)), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use crate::format::FORMAT_VAR;
+use crate::overrides::env_lookup;
+use crate::profile::Profile;
+use crate::secrets_dir::read_secrets_dir;
+use crate::{DatabaseAuthMethod, FieldError, Settings, SettingsError, ENV_OVERRIDE_PREFIX};
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where a field in [`LoadedSettings`] got its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingsSource {
+    /// A blob in this env variable.
+    EnvVar(String),
+    /// A blob in this file.
+    File(PathBuf),
+    /// This `REPORTSETTINGS_<FIELD>` override variable.
+    FieldOverride(String),
+    /// A file in this secrets directory.
+    SecretsDir(PathBuf),
+    /// A blob fetched from this URL, shown without credentials or query.
+    #[cfg(feature = "remote")]
+    Url(String),
+    /// A blob in this secret of this store, such as Azure Key Vault.
+    SecretStore { store: &'static str, name: String },
+}
+
+impl fmt::Display for SettingsSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsSource::EnvVar(name) => write!(f, "env var {}", name),
+            SettingsSource::File(path) => write!(f, "file {}", path.display()),
+            SettingsSource::FieldOverride(name) => write!(f, "override {}", name),
+            SettingsSource::SecretsDir(dir) => write!(f, "secrets directory {}", dir.display()),
+            #[cfg(feature = "remote")]
+            SettingsSource::Url(url) => write!(f, "url {}", url),
+            SettingsSource::SecretStore { store, name } => write!(f, "{} secret {}", store, name),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    EnvVar(String),
+    File(PathBuf),
+    SecretsDir(PathBuf),
+    FieldEnvOverrides,
+}
+
+/// Loads settings from several sources, merged field by field: a field set by
+/// a source replaces the value of every source registered before it.
+///
+/// Sources that don't exist, such as an unset variable or a missing file, are
+/// skipped. An empty string counts as unset, so it never replaces an earlier value.
+///
+/// ```no_run
+/// use reportsettings_rust::SettingsLoader;
+///
+/// let loaded = SettingsLoader::new()
+///     .from_file("/etc/reports/settings.toml")
+///     .from_env_var("SecretBlob")
+///     .from_field_env_overrides()
+///     .load()?;
+/// for (field, source) in loaded.provenance() {
+///     println!("{} from {}", field, source);
+/// }
+/// # Ok::<(), reportsettings_rust::SettingsError>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct SettingsLoader {
+    sources: Vec<Source>,
+}
+
+/// Settings merged by [`SettingsLoader::load`], along with where each field came from.
+#[derive(Debug)]
+pub struct LoadedSettings {
+    settings: Settings,
+    provenance: BTreeMap<String, SettingsSource>,
+}
+
+impl LoadedSettings {
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn into_settings(self) -> Settings {
+        self.settings
+    }
+
+    /// The source that last set each field, keyed by PascalCase blob key. An
+    /// override that unset a field is listed too.
+    pub fn provenance(&self) -> &BTreeMap<String, SettingsSource> {
+        &self.provenance
+    }
+}
+
+// The method names mirror the sources, not conversions
+#[allow(clippy::wrong_self_convention)]
+impl SettingsLoader {
+    pub fn new() -> SettingsLoader {
+        SettingsLoader::default()
+    }
+
+    /// Adds a blob read from the env variable `name`, in any format `get_settings` accepts.
+    pub fn from_env_var(mut self, name: impl Into<String>) -> SettingsLoader {
+        self.sources.push(Source::EnvVar(name.into()));
+        self
+    }
+
+    /// Adds a blob read from a file, in the format its extension names.
+    pub fn from_file(mut self, path: impl AsRef<Path>) -> SettingsLoader {
+        self.sources.push(Source::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Adds the files of a secrets directory, as read by
+    /// [`Settings::from_secrets_dir`]. The directory need not have a file for
+    /// every required field, as long as some source sets it.
+    pub fn from_secrets_dir(mut self, dir: impl AsRef<Path>) -> SettingsLoader {
+        self.sources
+            .push(Source::SecretsDir(dir.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Adds the `REPORTSETTINGS_<FIELD>` overrides described at
+    /// [`Settings::apply_env_overrides`].
+    pub fn from_field_env_overrides(mut self) -> SettingsLoader {
+        self.sources.push(Source::FieldEnvOverrides);
+        self
+    }
+
+    /// Reads and merges every source. Fails on the first source that exists but
+    /// can't be parsed, and with [`SettingsError::Validation`] listing every
+    /// required field that no source set.
+    pub fn load(&self) -> Result<LoadedSettings, SettingsError> {
+        self.load_from(env_lookup)
+    }
+
+    fn load_from<F>(&self, lookup: F) -> Result<LoadedSettings, SettingsError>
+    where
+        F: Fn(&str) -> Result<Option<String>, SettingsError>,
+    {
+        let mut fields = Map::new();
+        let mut provenance = BTreeMap::new();
+        // The sources that set something, for the audit log
+        let mut read = Vec::new();
+        for source in &self.sources {
+            let before = provenance.clone();
+            match source {
+                Source::EnvVar(name) => {
+                    if let Some(blob) = lookup(name)? {
+                        let format = lookup(FORMAT_VAR)?;
+                        let (settings, _) = Settings::parse_blob_in_format(
+                            &blob,
+                            format.as_deref(),
+                            Profile::FromEnv,
+                        )?;
+                        for field in merge(&mut fields, &settings)? {
+                            provenance.insert(field, SettingsSource::EnvVar(name.clone()));
+                        }
+                    }
+                }
+                Source::File(path) => match Settings::read_blob_file(path, Profile::FromEnv) {
+                    Ok((settings, _)) => {
+                        for field in merge(&mut fields, &settings)? {
+                            provenance.insert(field, SettingsSource::File(path.clone()));
+                        }
+                    }
+                    Err(SettingsError::FileNotFound { .. }) => {}
+                    Err(e) => return Err(e),
+                },
+                Source::SecretsDir(dir) if dir.is_dir() => {
+                    for field in merge(&mut fields, &read_secrets_dir(dir)?)? {
+                        provenance.insert(field, SettingsSource::SecretsDir(dir.clone()));
+                    }
+                }
+                Source::SecretsDir(_) => {}
+                Source::FieldEnvOverrides => {
+                    let mut settings: Settings =
+                        serde_json::from_value(Value::Object(fields.clone()))?;
+                    let overridden = RefCell::new(Vec::new());
+                    settings.apply_overrides_from(|name| {
+                        let value = lookup(name)?;
+                        if value.is_some() {
+                            overridden.borrow_mut().push(name.to_string());
+                        }
+                        Ok(value)
+                    })?;
+
+                    let mut set = set_fields(&settings)?;
+                    for name in overridden.into_inner() {
+                        let field = pascal_case(&name[ENV_OVERRIDE_PREFIX.len()..]);
+                        match set.remove(&field) {
+                            Some(value) => fields.insert(field.clone(), value),
+                            None => fields.remove(&field),
+                        };
+                        provenance.insert(field, SettingsSource::FieldOverride(name));
+                    }
+                }
+            }
+            for source in provenance.values() {
+                if !before.values().any(|seen| seen == source) && !read.contains(source) {
+                    read.push(source.clone());
+                }
+            }
+        }
+
+        let settings: Settings = serde_json::from_value(Value::Object(fields))?;
+        let missing = missing_fields(&settings);
+        if !missing.is_empty() {
+            return Err(SettingsError::Validation(missing));
+        }
+        let settings = settings.finish_load()?;
+        settings.record_load(read, provenance.clone());
+        Ok(LoadedSettings {
+            settings,
+            provenance,
+        })
+    }
+}
+
+// Copies the fields a source set over the merged ones, returning their names
+fn merge(fields: &mut Map<String, Value>, source: &Settings) -> Result<Vec<String>, SettingsError> {
+    let set = set_fields(source)?;
+    let names = set.keys().cloned().collect();
+    fields.extend(set);
+    Ok(names)
+}
+
+// The fields a source set, by PascalCase blob key
+pub(crate) fn set_fields(settings: &Settings) -> Result<Map<String, Value>, SettingsError> {
+    let mut fields: Map<String, Value> = serde_json::from_value(serde_json::to_value(settings)?)?;
+    fields.retain(|_, value| value.as_str() != Some(""));
+    Ok(fields)
+}
+
+// `DATABASE_SERVER` to `DatabaseServer`
+pub(crate) fn pascal_case(screaming_snake: &str) -> String {
+    screaming_snake
+        .split('_')
+        .map(|word| {
+            let word = word.to_ascii_lowercase();
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+// Like SettingsBuilder::build, the database fields a connection needs
+pub(crate) fn missing_fields(settings: &Settings) -> Vec<FieldError> {
+    let fields = settings.database_connection_string.is_none();
+    let sql_login = fields && settings.database_auth_method() == DatabaseAuthMethod::SqlServer;
+
+    [
+        ("DatabaseServer", fields, settings.database_server.as_str()),
+        ("DatabaseName", fields, settings.database_name.as_str()),
+        (
+            "DatabaseUsername",
+            sql_login,
+            settings.database_username.as_str(),
+        ),
+        (
+            "DatabasePassword",
+            sql_login,
+            settings.database_password.inspect(),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, required, value)| *required && value.is_empty())
+    .map(|(field, _, _)| FieldError::new(field, "is required"))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    const FILE_BLOB: &str = r#"
+        DatabaseServer = "file-sql"
+        DatabaseName = "file_db"
+        DatabaseUsername = "admin"
+        DatabasePassword = "password123"
+        EmailFromName = "From File"
+        DatabasePort = 14330
+    "#;
+
+    fn blob_file(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    fn load(
+        loader: &SettingsLoader,
+        vars: &[(&str, &str)],
+    ) -> Result<LoadedSettings, SettingsError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        loader.load_from(|name| Ok(vars.get(name).cloned()))
+    }
+
+    #[test]
+    fn test_later_sources_override_earlier_ones() {
+        let file = blob_file(FILE_BLOB);
+        let loader = SettingsLoader::new()
+            .from_file(file.path())
+            .from_env_var("SecretBlob")
+            .from_field_env_overrides();
+
+        let loaded = load(
+            &loader,
+            &[
+                (
+                    "SecretBlob",
+                    r#"{"databaseName": "env_db", "EmailFromAddress": "env@example.com"}"#,
+                ),
+                ("REPORTSETTINGS_DATABASE_SERVER", "override-sql"),
+            ],
+        )
+        .unwrap();
+
+        let settings = loaded.settings();
+        assert_eq!(settings.database_server(), "override-sql");
+        assert_eq!(settings.database_name(), "env_db");
+        assert_eq!(settings.database_username(), "admin");
+        assert_eq!(settings.database_port(), Some(14330));
+        assert_eq!(settings.email_from_name(), Some("From File"));
+        assert_eq!(settings.email_from_address(), Some("env@example.com"));
+
+        let provenance = loaded.provenance();
+        let file_source = SettingsSource::File(file.path().to_path_buf());
+        let env_source = SettingsSource::EnvVar("SecretBlob".to_string());
+        assert_eq!(
+            provenance["DatabaseServer"],
+            SettingsSource::FieldOverride("REPORTSETTINGS_DATABASE_SERVER".to_string())
+        );
+        assert_eq!(provenance["DatabaseName"], env_source);
+        assert_eq!(provenance["EmailFromAddress"], env_source);
+        assert_eq!(provenance["DatabasePassword"], file_source);
+        assert_eq!(provenance["DatabasePort"], file_source);
+        assert_eq!(provenance.len(), 7);
+    }
+
+    #[test]
+    fn test_registration_order_decides() {
+        let file = blob_file(FILE_BLOB);
+        let loader = SettingsLoader::new()
+            .from_field_env_overrides()
+            .from_env_var("SecretBlob")
+            .from_file(file.path());
+
+        let loaded = load(
+            &loader,
+            &[
+                ("SecretBlob", r#"{"DatabaseServer": "env-sql"}"#),
+                ("REPORTSETTINGS_DATABASE_SERVER", "override-sql"),
+                ("REPORTSETTINGS_EMAIL_FROM_ADDRESS", "override@example.com"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(loaded.settings().database_server(), "file-sql");
+        assert_eq!(
+            loaded.settings().email_from_address(),
+            Some("override@example.com")
+        );
+        assert_eq!(
+            loaded.provenance()["DatabaseServer"].to_string(),
+            format!("file {}", file.path().display())
+        );
+    }
+
+    #[test]
+    fn test_empty_override_unsets_field() {
+        let file = blob_file(FILE_BLOB);
+        let loader = SettingsLoader::new()
+            .from_file(file.path())
+            .from_field_env_overrides();
+
+        let loaded = load(&loader, &[("REPORTSETTINGS_EMAIL_FROM_NAME", "")]).unwrap();
+
+        assert_eq!(loaded.settings().email_from_name(), None);
+        assert_eq!(
+            loaded.provenance()["EmailFromName"].to_string(),
+            "override REPORTSETTINGS_EMAIL_FROM_NAME"
+        );
+    }
+
+    #[test]
+    fn test_missing_sources_are_skipped_and_missing_fields_listed() {
+        let loader = SettingsLoader::new()
+            .from_file("/nonexistent/settings.toml")
+            .from_env_var("SecretBlob")
+            .from_env_var("SecretBlobOverlay")
+            .from_field_env_overrides();
+
+        let err = load(
+            &loader,
+            &[
+                ("SecretBlobOverlay", r#"{"DatabaseUsername": "admin"}"#),
+                ("REPORTSETTINGS_DATABASE_SERVER", "override-sql"),
+            ],
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            &err,
+            SettingsError::Validation(missing) if missing == &[
+                FieldError::new("DatabaseName", "is required"),
+                FieldError::new("DatabasePassword", "is required"),
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_secrets_dir_supplies_only_the_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("DatabasePassword"), "from-dir\r\n").unwrap();
+        let loader = SettingsLoader::new()
+            .from_env_var("SecretBlob")
+            .from_secrets_dir(dir.path())
+            .from_secrets_dir("/nonexistent/secrets");
+
+        let loaded = load(
+            &loader,
+            &[(
+                "SecretBlob",
+                r#"{"DatabaseServer": "env-sql", "DatabaseName": "env_db", "DatabaseUsername": "admin"}"#,
+            )],
+        )
+        .unwrap();
+
+        assert_eq!(loaded.settings().database_server(), "env-sql");
+        assert_eq!(loaded.settings().expose_database_password(), "from-dir");
+        assert_eq!(
+            loaded.provenance()["DatabasePassword"],
+            SettingsSource::SecretsDir(dir.path().to_path_buf())
+        );
+        assert_eq!(
+            loaded.provenance()["DatabaseServer"],
+            SettingsSource::EnvVar("SecretBlob".to_string())
+        );
+    }
+
+    #[test]
+    fn test_source_that_does_not_parse_fails() {
+        let loader = SettingsLoader::new().from_env_var("SecretBlob");
+
+        let err = load(&loader, &[("SecretBlob", "{ not json")]).unwrap_err();
+
+        assert!(matches!(err, SettingsError::InvalidJson(_)), "{:?}", err);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{LogEvent, SettingsError, WebhookLogger};
+use std::time::{Duration, Instant};
+
+/// How long a [`BufferedWebhookLogger`] holds an event before the next one
+/// logged flushes it, unless [`BufferedWebhookLogger::with_flush_interval`]
+/// says otherwise.
+pub const DEFAULT_LOG_WEBHOOK_FLUSH_INTERVAL_SECONDS: u64 = 5;
+
+/// Collects [`LogEvent`]s and posts them with [`WebhookLogger::post_batch`],
+/// for a report run that logs a few hundred progress lines.
+///
+/// [`BufferedWebhookLogger::log`] flushes once `LogWebhookBatchSize` events
+/// are waiting, or when the oldest has waited longer than the flush interval.
+/// There is no background timer: the interval is only checked when an event
+/// is logged. Call [`BufferedWebhookLogger::flush`] at the end of the job.
+///
+/// Dropping the logger can't post what is left, since that needs an await;
+/// the events still pending are written to stderr instead, so that they are
+/// not lost without a trace.
+#[derive(Debug)]
+pub struct BufferedWebhookLogger {
+    logger: WebhookLogger,
+    pending: Vec<LogEvent>,
+    flush_interval: Duration,
+    oldest: Option<Instant>,
+}
+
+impl BufferedWebhookLogger {
+    pub fn new(logger: WebhookLogger) -> BufferedWebhookLogger {
+        BufferedWebhookLogger {
+            logger,
+            pending: Vec::new(),
+            flush_interval: Duration::from_secs(DEFAULT_LOG_WEBHOOK_FLUSH_INTERVAL_SECONDS),
+            oldest: None,
+        }
+    }
+
+    /// Flushes events that have waited `interval` instead of
+    /// [`DEFAULT_LOG_WEBHOOK_FLUSH_INTERVAL_SECONDS`].
+    pub fn with_flush_interval(mut self, interval: Duration) -> BufferedWebhookLogger {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Buffers `event`, flushing if the batch is full or has waited long
+    /// enough. Fails like [`BufferedWebhookLogger::flush`].
+    pub async fn log(&mut self, event: LogEvent) -> Result<(), SettingsError> {
+        let oldest = *self.oldest.get_or_insert_with(Instant::now);
+        self.pending.push(event);
+        if self.pending.len() >= self.logger.batch_size().max(1) as usize
+            || oldest.elapsed() >= self.flush_interval
+        {
+            return self.flush().await;
+        }
+        Ok(())
+    }
+
+    /// Posts every pending event. The buffer is empty afterwards either way:
+    /// the events that were not delivered come back in the
+    /// [`SettingsError::LogWebhookBatch`].
+    pub async fn flush(&mut self) -> Result<(), SettingsError> {
+        let events = std::mem::take(&mut self.pending);
+        self.oldest = None;
+        self.logger.post_batch(&events).await
+    }
+
+    /// The events logged since the last flush.
+    pub fn pending(&self) -> &[LogEvent] {
+        &self.pending
+    }
+
+    // What dropping the logger writes to stderr, if anything
+    fn unflushed_report(&self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let lines: Vec<String> = self
+            .pending
+            .iter()
+            .map(|event| format!("[{}] {}", event.level(), event.message()))
+            .collect();
+        Some(format!(
+            "Log events never flushed to the log webhook ({}):\n{}",
+            self.pending.len(),
+            lines.join("\n")
+        ))
+    }
+}
+
+impl Drop for BufferedWebhookLogger {
+    fn drop(&mut self) {
+        if let Some(report) = self.unflushed_report() {
+            eprintln!("{}", report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LogLevel, Settings};
+    use serde_json::Value;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn logger(server: &MockServer, batch_size: u32) -> BufferedWebhookLogger {
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri(server.uri())
+            .log_webhook_batch_size(batch_size)
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+        BufferedWebhookLogger::new(settings.get_webhook_logger().unwrap())
+    }
+
+    async fn batch_sizes(server: &MockServer) -> Vec<usize> {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let body: Value = serde_json::from_slice(&request.body).unwrap();
+                body.as_array().unwrap().len()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_flushes_full_batches_and_on_demand() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(202))
+            .mount(&server)
+            .await;
+        let mut buffered = logger(&server, 2);
+
+        for line in ["one", "two", "three"] {
+            buffered
+                .log(LogEvent::new(LogLevel::Info, line))
+                .await
+                .unwrap();
+        }
+        assert_eq!(batch_sizes(&server).await, [2]);
+        assert_eq!(buffered.pending().len(), 1);
+
+        buffered.flush().await.unwrap();
+        assert_eq!(batch_sizes(&server).await, [2, 1]);
+        assert!(buffered.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flushes_after_interval() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(202))
+            .mount(&server)
+            .await;
+        let mut buffered = logger(&server, 50).with_flush_interval(Duration::ZERO);
+
+        buffered
+            .log(LogEvent::new(LogLevel::Info, "one"))
+            .await
+            .unwrap();
+
+        assert_eq!(batch_sizes(&server).await, [1]);
+    }
+
+    #[tokio::test]
+    async fn test_unflushed_events_are_reported() {
+        let server = MockServer::start().await;
+        let mut buffered = logger(&server, 50);
+
+        buffered
+            .log(LogEvent::new(LogLevel::Warning, "3 rows skipped"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            buffered.unflushed_report().unwrap(),
+            "Log events never flushed to the log webhook (1):\n[Warning] 3 rows skipped"
+        );
+        assert!(server.received_requests().await.unwrap().is_empty());
+        buffered.pending.clear();
+        assert_eq!(buffered.unflushed_report(), None);
+    }
+}
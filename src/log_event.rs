@@ -0,0 +1,183 @@
+use crate::LogLevel;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+/// One line for the log webhook, serialized as the `Json` format's
+/// `{ "Level", "Message", "Timestamp", "Source", "CorrelationId", "Details" }`,
+/// where the last two are left out when unset. Post it with
+/// [`crate::WebhookLogger::post_event`].
+///
+/// The timestamp is RFC 3339 in UTC with milliseconds, e.g.
+/// `2024-03-01T06:30:00.000Z`, whatever the host's time zone.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LogEvent {
+    level: LogLevel,
+    message: String,
+    #[serde(serialize_with = "rfc3339")]
+    timestamp: DateTime<Utc>,
+    source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<Value>,
+}
+
+/// Builds a [`LogEvent`]; see [`LogEvent::builder`].
+#[derive(Debug, Clone)]
+pub struct LogEventBuilder {
+    event: LogEvent,
+}
+
+impl LogEvent {
+    /// An event at `level`, timestamped now. The source is left empty, for
+    /// the logger to fill in with its application name.
+    pub fn new(level: LogLevel, message: impl Into<String>) -> LogEvent {
+        LogEvent {
+            level,
+            message: message.into(),
+            timestamp: Utc::now(),
+            source: String::new(),
+            correlation_id: None,
+            details: None,
+        }
+    }
+
+    /// Like [`LogEvent::new`], to set the other fields, e.g.
+    /// `LogEvent::builder(LogLevel::Error, "Query failed").correlation_id(run_id).build()`.
+    pub fn builder(level: LogLevel, message: impl Into<String>) -> LogEventBuilder {
+        LogEventBuilder {
+            event: LogEvent::new(level, message),
+        }
+    }
+
+    /// The severity, `Level` in the payload.
+    pub fn level(&self) -> LogLevel {
+        self.level
+    }
+
+    /// The log line, `Message` in the payload.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// When the event happened, in UTC.
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    /// Empty unless set with [`LogEventBuilder::source`].
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The id set with [`LogEventBuilder::correlation_id`], if any.
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// The data set with [`LogEventBuilder::details`], if any.
+    pub fn details(&self) -> Option<&Value> {
+        self.details.as_ref()
+    }
+
+    // This event with `source` if it has none of its own
+    #[cfg_attr(not(feature = "remote"), allow(dead_code))]
+    pub(crate) fn with_default_source(&self, source: &str) -> LogEvent {
+        let mut event = self.clone();
+        if event.source.is_empty() {
+            event.source = source.to_string();
+        }
+        event
+    }
+}
+
+impl LogEventBuilder {
+    /// When the event happened, instead of when it was built. Converted to UTC.
+    pub fn timestamp<Tz: TimeZone>(mut self, timestamp: DateTime<Tz>) -> Self {
+        self.event.timestamp = timestamp.with_timezone(&Utc);
+        self
+    }
+
+    /// The application the event is from, instead of the logger's
+    /// `ApplicationName`.
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.event.source = source.into();
+        self
+    }
+
+    /// An id tying the event to others from the same run or request.
+    pub fn correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.event.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    /// Structured data to go with the message, e.g. row counts.
+    pub fn details(mut self, details: Value) -> Self {
+        self.event.details = Some(details);
+        self
+    }
+
+    /// The event, timestamped when [`LogEvent::builder`] was called unless
+    /// [`LogEventBuilder::timestamp`] says otherwise.
+    pub fn build(self) -> LogEvent {
+        self.event
+    }
+}
+
+pub(crate) fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn rfc3339<S: Serializer>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_timestamp(*timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+    use serde_json::json;
+
+    // The shape the Logic App parses; changing it breaks the workflow
+    #[test]
+    fn test_serialization_snapshot() {
+        let timestamp = DateTime::parse_from_rfc3339("2024-03-01T08:30:00.250+02:00").unwrap();
+        let event = LogEvent::builder(LogLevel::Warning, "3 rows skipped")
+            .timestamp(timestamp)
+            .source("Nightly Sales")
+            .correlation_id("run-42")
+            .details(json!({"Skipped": 3}))
+            .build();
+
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"Level":"Warning","Message":"3 rows skipped","Timestamp":"2024-03-01T06:30:00.250Z","Source":"Nightly Sales","CorrelationId":"run-42","Details":{"Skipped":3}}"#
+        );
+    }
+
+    #[test]
+    fn test_optional_fields_are_left_out() {
+        let timestamp = FixedOffset::west_opt(5 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 3, 1, 1, 30, 0)
+            .unwrap();
+        let event = LogEvent::builder(LogLevel::Info, "Report sent")
+            .timestamp(timestamp)
+            .build()
+            .with_default_source("Nightly Sales");
+
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"Level":"Info","Message":"Report sent","Timestamp":"2024-03-01T06:30:00.000Z","Source":"Nightly Sales"}"#
+        );
+        let own = LogEvent::builder(LogLevel::Info, "Report sent")
+            .source("Invoices")
+            .build();
+        assert_eq!(
+            own.with_default_source("Nightly Sales").source(),
+            "Invoices"
+        );
+    }
+}
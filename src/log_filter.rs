@@ -0,0 +1,150 @@
+use crate::{LogLevel, Settings};
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Which records a [`crate::WebhookLogSink`] or [`crate::WebhookLayer`]
+/// forwards: those at or above `LogWebhookMinLevel`, from a module in
+/// `LogWebhookModuleAllowList` if that is set. Records are filtered before
+/// they are queued.
+///
+/// The filter is shared with the sink or layer, so that
+/// [`WebhookFilter::update`] applies to it while it is installed, e.g. after
+/// a [`crate::SettingsHandle`] reload.
+#[derive(Debug, Clone)]
+pub struct WebhookFilter {
+    rules: Arc<RwLock<Rules>>,
+}
+
+#[derive(Debug)]
+struct Rules {
+    min_level: LogLevel,
+    modules: Vec<String>,
+    // Whether this filter belongs to the installed `log` facade logger, whose
+    // max level has to follow `min_level`
+    #[cfg(feature = "log")]
+    log_facade: bool,
+}
+
+impl WebhookFilter {
+    pub(crate) fn new(settings: &Settings) -> WebhookFilter {
+        WebhookFilter {
+            rules: Arc::new(RwLock::new(Rules {
+                min_level: settings.log_webhook_min_level(),
+                modules: modules_of(settings),
+                #[cfg(feature = "log")]
+                log_facade: false,
+            })),
+        }
+    }
+
+    /// Takes `LogWebhookMinLevel` and `LogWebhookModuleAllowList` from
+    /// `settings`, for the records logged from now on.
+    pub fn update(&self, settings: &Settings) {
+        let mut rules = self.rules.write().unwrap_or_else(PoisonError::into_inner);
+        rules.min_level = settings.log_webhook_min_level();
+        rules.modules = modules_of(settings);
+        #[cfg(feature = "log")]
+        if rules.log_facade {
+            log::set_max_level(crate::log_sink::level_filter(rules.min_level));
+        }
+    }
+
+    /// The least severe level forwarded.
+    pub fn min_level(&self) -> LogLevel {
+        self.rules
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .min_level
+    }
+
+    /// Whether a record at `level` from `target`, a module path like
+    /// `report::load`, is forwarded.
+    pub(crate) fn allows(&self, level: LogLevel, target: &str) -> bool {
+        let rules = self.rules.read().unwrap_or_else(PoisonError::into_inner);
+        level >= rules.min_level
+            && (rules.modules.is_empty()
+                || rules.modules.iter().any(|module| in_module(target, module)))
+    }
+
+    #[cfg(feature = "log")]
+    pub(crate) fn set_log_facade(&self) {
+        self.rules
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .log_facade = true;
+    }
+}
+
+fn modules_of(settings: &Settings) -> Vec<String> {
+    settings
+        .log_webhook_module_allow_list()
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+// `report` covers `report::load` but not `reporting`
+fn in_module(target: &str, module: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_and_module_rules() {
+        let settings = Settings::for_tests()
+            .with_log_webhook_min_level(LogLevel::Info)
+            .with_log_webhook_module_allow_list("report, billing::export");
+        let filter = WebhookFilter::new(&settings);
+
+        for (level, target, allowed) in [
+            (LogLevel::Debug, "report", false),
+            (LogLevel::Info, "report", true),
+            (LogLevel::Warning, "report::load", true),
+            (LogLevel::Error, "reporting", false),
+            (LogLevel::Error, "billing::export::csv", true),
+            (LogLevel::Error, "billing", false),
+            (LogLevel::Error, "hyper::client", false),
+        ] {
+            assert_eq!(
+                filter.allows(level, target),
+                allowed,
+                "{} {}",
+                level,
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_applies_to_clones() {
+        let filter = WebhookFilter::new(&Settings::for_tests());
+        let installed = filter.clone();
+        assert!(installed.allows(LogLevel::Debug, "hyper::client"));
+
+        filter.update(
+            &Settings::for_tests()
+                .with_log_webhook_min_level(LogLevel::Warning)
+                .with_log_webhook_module_allow_list("report"),
+        );
+
+        assert_eq!(installed.min_level(), LogLevel::Warning);
+        assert!(!installed.allows(LogLevel::Error, "hyper::client"));
+        assert!(installed.allows(LogLevel::Warning, "report"));
+    }
+
+    #[test]
+    fn test_allow_list_entries_are_checked() {
+        let settings = Settings::for_tests().with_log_webhook_module_allow_list("report billing, ");
+
+        assert_eq!(settings.log_webhook_module_allow_list(), ["report billing"]);
+        assert_eq!(
+            settings.validate().unwrap_err()[0].to_string(),
+            "LogWebhookModuleAllowList: 'report billing' is not a module path; separate \
+             entries with commas"
+        );
+    }
+}
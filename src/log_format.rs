@@ -0,0 +1,285 @@
+use crate::log_event::format_timestamp;
+use crate::{LogEvent, LogLevel};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::str::FromStr;
+
+// Teams rejects cards over about 28 KB with a 413 that the webhook connector
+// doesn't always pass on; this leaves room for the rest of the card. Both
+// limits are on the JSON-escaped length, which is what gets posted
+const TEAMS_MAX_TEXT_BYTES: usize = 24 * 1024;
+// For the title and summary, which come from `ApplicationName`
+const TEAMS_MAX_TITLE_BYTES: usize = 1024;
+const TRUNCATED_SUFFIX: &str = "\n\n… (truncated)";
+const SHORTENED_SUFFIX: &str = "…";
+
+/// The payload posted to `LogWebhookUri`, read from `LogWebhookFormat`.
+///
+/// The blob value is matched case-insensitively.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum LogWebhookFormat {
+    /// `{ "Level", "Message", "Timestamp", "Source" }`, for endpoints of our own,
+    /// with `CorrelationId` and `Details` when the [`LogEvent`] has them.
+    #[default]
+    Json,
+    /// A MessageCard for a Microsoft Teams incoming webhook, with the
+    /// correlation id as a fact but no details.
+    Teams,
+    /// A message for a Slack incoming webhook, with the level as an emoji. The
+    /// correlation id and details are left out.
+    Slack,
+}
+
+impl LogWebhookFormat {
+    const NAMES: &'static str = "Json, Teams, Slack";
+
+    /// The body to post for one event.
+    pub(crate) fn payload(self, event: &LogEvent) -> Value {
+        match self {
+            LogWebhookFormat::Json => json!(event),
+            LogWebhookFormat::Teams => teams_card(event),
+            LogWebhookFormat::Slack => json!({
+                "text": slack_text(event.level(), event.message(), event.source()),
+            }),
+        }
+    }
+
+    /// The body to post for a batch of events, all with their source set.
+    pub(crate) fn batch_payload(self, events: &[LogEvent]) -> Value {
+        if self == LogWebhookFormat::Json {
+            return json!(events);
+        }
+        let Some(first) = events.first() else {
+            return Value::Null;
+        };
+        let level = events
+            .iter()
+            .map(LogEvent::level)
+            .max()
+            .unwrap_or(first.level());
+        let lines: Vec<&str> = events.iter().map(LogEvent::message).collect();
+        let combined = LogEvent::builder(level, lines.join("\n"))
+            .timestamp(first.timestamp())
+            .source(first.source())
+            .build();
+        self.payload(&combined)
+    }
+}
+
+fn teams_card(event: &LogEvent) -> Value {
+    let (level, source) = (event.level(), event.source());
+    let mut facts = vec![
+        json!({ "name": "Level", "value": level.to_string() }),
+        json!({ "name": "Time", "value": format_timestamp(event.timestamp()) }),
+    ];
+    if let Some(correlation_id) = event.correlation_id() {
+        facts.push(json!({ "name": "Correlation ID", "value": correlation_id }));
+    }
+    json!({
+        "@type": "MessageCard",
+        "@context": "https://schema.org/extensions",
+        "themeColor": theme_color(level),
+        "title": truncate(source, TEAMS_MAX_TITLE_BYTES, SHORTENED_SUFFIX),
+        "summary": truncate(
+            &format!("{}: {}", level, source),
+            TEAMS_MAX_TITLE_BYTES,
+            SHORTENED_SUFFIX,
+        ),
+        "text": truncate(event.message(), TEAMS_MAX_TEXT_BYTES, TRUNCATED_SUFFIX),
+        "sections": [{ "facts": facts }],
+    })
+}
+
+impl FromStr for LogWebhookFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogWebhookFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(LogWebhookFormat::Json),
+            "teams" => Ok(LogWebhookFormat::Teams),
+            "slack" => Ok(LogWebhookFormat::Slack),
+            _ => Err(format!(
+                "'{}' is not a valid log webhook format, expected one of: {}",
+                s,
+                LogWebhookFormat::NAMES
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogWebhookFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl<'de> Deserialize<'de> for LogWebhookFormat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// The `text` of a Slack message, e.g. `🔴 *Nightly Sales*: Query failed`.
+pub(crate) fn slack_text(level: LogLevel, message: &str, source: &str) -> String {
+    let emoji = match level {
+        LogLevel::Debug => "⚪",
+        LogLevel::Info => "🔵",
+        LogLevel::Warning => "🟡",
+        LogLevel::Error => "🔴",
+    };
+    format!("{} *{}*: {}", emoji, source, message)
+}
+
+fn theme_color(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Debug => "808080",
+        LogLevel::Info => "0078D7",
+        LogLevel::Warning => "FFB900",
+        LogLevel::Error => "D13438",
+    }
+}
+
+// Cuts on a character boundary, keeping the result within `max_bytes` once
+// escaped as a JSON string, quotes aside
+fn truncate(message: &str, max_bytes: usize, suffix: &str) -> String {
+    if escaped_len(message) <= max_bytes {
+        return message.to_string();
+    }
+    let budget = max_bytes - escaped_len(suffix);
+    let mut end = 0;
+    let mut len = 0;
+    for (i, c) in message.char_indices() {
+        len += escaped_len(c.encode_utf8(&mut [0; 4]));
+        if len > budget {
+            break;
+        }
+        end = i + c.len_utf8();
+    }
+    format!("{}{}", &message[..end], suffix)
+}
+
+fn escaped_len(s: &str) -> usize {
+    serde_json::to_string(s).map_or(s.len(), |escaped| escaped.len() - 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogEventBuilder;
+    use chrono::DateTime;
+
+    fn event(level: LogLevel, message: &str) -> LogEventBuilder {
+        LogEvent::builder(level, message)
+            .timestamp(DateTime::parse_from_rfc3339("2024-03-01T06:30:00Z").unwrap())
+            .source("Nightly Sales")
+    }
+
+    fn payload(format: LogWebhookFormat, level: LogLevel, message: &str) -> Value {
+        format.payload(&event(level, message).build())
+    }
+
+    #[test]
+    fn test_json_payload() {
+        assert_eq!(
+            payload(LogWebhookFormat::Json, LogLevel::Info, "Report sent"),
+            json!({
+                "Level": "Info",
+                "Message": "Report sent",
+                "Timestamp": "2024-03-01T06:30:00.000Z",
+                "Source": "Nightly Sales",
+            })
+        );
+    }
+
+    #[test]
+    fn test_teams_payload() {
+        assert_eq!(
+            payload(LogWebhookFormat::Teams, LogLevel::Error, "Query failed"),
+            json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "themeColor": "D13438",
+                "title": "Nightly Sales",
+                "summary": "Error: Nightly Sales",
+                "text": "Query failed",
+                "sections": [{
+                    "facts": [
+                        { "name": "Level", "value": "Error" },
+                        { "name": "Time", "value": "2024-03-01T06:30:00.000Z" },
+                    ],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_correlation_id_and_details() {
+        let event = event(LogLevel::Error, "Query failed")
+            .correlation_id("run-42")
+            .details(json!({"Rows": 0}))
+            .build();
+
+        let body = LogWebhookFormat::Json.payload(&event);
+        let card = LogWebhookFormat::Teams.payload(&event);
+        let slack = LogWebhookFormat::Slack.payload(&event);
+
+        assert_eq!(body["CorrelationId"], "run-42");
+        assert_eq!(body["Details"], json!({"Rows": 0}));
+        assert_eq!(
+            card["sections"][0]["facts"][2],
+            json!({ "name": "Correlation ID", "value": "run-42" })
+        );
+        assert_eq!(slack, json!({ "text": "🔴 *Nightly Sales*: Query failed" }));
+    }
+
+    #[test]
+    fn test_slack_payload() {
+        assert_eq!(
+            payload(LogWebhookFormat::Slack, LogLevel::Warning, "3 rows skipped"),
+            json!({ "text": "🟡 *Nightly Sales*: 3 rows skipped" })
+        );
+    }
+
+    #[test]
+    fn test_teams_truncates_long_messages() {
+        let message = "é".repeat(20 * 1024);
+
+        let card = payload(LogWebhookFormat::Teams, LogLevel::Info, &message);
+        let text = card["text"].as_str().unwrap();
+
+        assert!(text.len() <= TEAMS_MAX_TEXT_BYTES, "{}", text.len());
+        assert!(text.ends_with("(truncated)"));
+        assert!(serde_json::to_vec(&card).unwrap().len() < 28 * 1024);
+    }
+
+    #[test]
+    fn test_teams_limits_are_on_the_escaped_length() {
+        let message = "\"quoted\"\n".repeat(3 * 1024);
+        let source = "\"App\"\n".repeat(1024);
+        let event = LogEvent::builder(LogLevel::Error, message)
+            .timestamp(DateTime::parse_from_rfc3339("2024-03-01T06:30:00Z").unwrap())
+            .source(source)
+            .build();
+
+        let card = LogWebhookFormat::Teams.payload(&event);
+
+        assert!(card["text"].as_str().unwrap().ends_with("(truncated)"));
+        assert!(card["title"].as_str().unwrap().ends_with('…'));
+        assert!(card["summary"]
+            .as_str()
+            .unwrap()
+            .starts_with("Error: \"App\""));
+        assert!(serde_json::to_vec(&card).unwrap().len() < 28 * 1024);
+    }
+
+    #[test]
+    fn test_format_is_case_insensitive_and_named_on_error() {
+        assert_eq!("TEAMS".parse(), Ok(LogWebhookFormat::Teams));
+        assert_eq!(
+            "Discord".parse::<LogWebhookFormat>().unwrap_err(),
+            "'Discord' is not a valid log webhook format, expected one of: Json, Teams, Slack"
+        );
+    }
+}
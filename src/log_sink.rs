@@ -0,0 +1,197 @@
+use crate::forwarder::Forwarder;
+use crate::{LogLevel, Settings, SettingsError, WebhookFilter};
+
+/// A [`log::Log`] implementation that forwards records to the log webhook.
+///
+/// Records passing its [`WebhookFilter`], at or above `LogWebhookMinLevel` and
+/// from a module in `LogWebhookModuleAllowList` if set, are queued and posted from a
+/// background thread in batches, each batch as one webhook message at the level
+/// of its most severe record. Records logged while the queue is full are
+/// dropped (see [`WebhookLogSink::dropped_records`]), and lines that can't be delivered are written to stderr.
+/// [`log::Log::flush`] blocks until everything queued has been posted, so call
+/// `log::logger().flush()` before a short-lived job exits.
+pub struct WebhookLogSink {
+    filter: WebhookFilter,
+    forwarder: Forwarder,
+}
+
+impl WebhookLogSink {
+    /// The filter the sink applies, to change it once the sink is installed.
+    pub fn filter(&self) -> WebhookFilter {
+        self.filter.clone()
+    }
+
+    /// How many records were dropped because the queue was full.
+    pub fn dropped_records(&self) -> u64 {
+        self.forwarder.dropped()
+    }
+}
+
+fn level_of(level: log::Level) -> LogLevel {
+    match level {
+        log::Level::Error => LogLevel::Error,
+        log::Level::Warn => LogLevel::Warning,
+        log::Level::Info => LogLevel::Info,
+        log::Level::Debug | log::Level::Trace => LogLevel::Debug,
+    }
+}
+
+pub(crate) fn level_filter(level: LogLevel) -> log::LevelFilter {
+    match level {
+        LogLevel::Error => log::LevelFilter::Error,
+        LogLevel::Warning => log::LevelFilter::Warn,
+        LogLevel::Info => log::LevelFilter::Info,
+        LogLevel::Debug => log::LevelFilter::Trace,
+    }
+}
+
+impl log::Log for WebhookLogSink {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.filter
+            .allows(level_of(metadata.level()), metadata.target())
+            && !self.forwarder.on_worker()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let line = format!("{} {}: {}", record.level(), record.target(), record.args());
+            self.forwarder.send(level_of(record.level()), line);
+        }
+    }
+
+    fn flush(&self) {
+        self.forwarder.flush();
+    }
+}
+
+impl Settings {
+    /// A [`WebhookLogSink`] for `LogWebhookUri`, without installing it.
+    pub fn webhook_log_sink(&self) -> Result<WebhookLogSink, SettingsError> {
+        Ok(WebhookLogSink {
+            filter: WebhookFilter::new(self),
+            forwarder: Forwarder::spawn(self.get_webhook_logger()?)?,
+        })
+    }
+
+    /// Installs a [`WebhookLogSink`] as the `log` facade's logger, so that
+    /// `log::error!` and friends end up at the log webhook. The returned
+    /// filter changes what the installed sink forwards, the facade's max level
+    /// included.
+    pub fn init_webhook_logging(&self) -> Result<WebhookFilter, SettingsError> {
+        let sink = self.webhook_log_sink()?;
+        let filter = sink.filter();
+        log::set_boxed_logger(Box::new(sink)).map_err(|_| SettingsError::LoggerAlreadySet)?;
+        filter.set_log_facade();
+        log::set_max_level(level_filter(filter.min_level()));
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Log;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn log(sink: &WebhookLogSink, level: log::Level, message: &str) {
+        log_from(sink, "report", level, message);
+    }
+
+    fn log_from(sink: &WebhookLogSink, target: &str, level: log::Level, message: &str) {
+        sink.log(
+            &log::Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flush_posts_filtered_batch() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri(server.uri())
+            .log_webhook_min_level(LogLevel::Warning)
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+        let sink = settings.webhook_log_sink().unwrap();
+
+        log(&sink, log::Level::Info, "starting");
+        log(&sink, log::Level::Warn, "3 rows skipped");
+        log(&sink, log::Level::Error, "query failed");
+        sink.flush();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["Level"], "Error");
+        assert_eq!(
+            body["Message"],
+            "WARN report: 3 rows skipped\nERROR report: query failed"
+        );
+
+        // Nothing queued, nothing posted
+        sink.flush();
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+        assert_eq!(sink.dropped_records(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_module_allow_list_and_update() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let settings = Settings::for_tests()
+            .with_log_webhook_uri(server.uri())
+            .with_log_webhook_min_level(LogLevel::Info)
+            .with_log_webhook_module_allow_list("report, billing::export");
+        let sink = settings.webhook_log_sink().unwrap();
+
+        for level in [
+            log::Level::Trace,
+            log::Level::Debug,
+            log::Level::Info,
+            log::Level::Warn,
+            log::Level::Error,
+        ] {
+            log_from(&sink, "report::load", level, "load");
+        }
+        log_from(&sink, "billing::export::csv", log::Level::Error, "export");
+        log_from(&sink, "billing", log::Level::Error, "billing");
+        log_from(&sink, "hyper::client", log::Level::Error, "hyper");
+        sink.filter()
+            .update(&settings.with_log_webhook_min_level(LogLevel::Error));
+        log_from(&sink, "report", log::Level::Warn, "after update");
+        sink.flush();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(
+            body["Message"],
+            "INFO report::load: load\nWARN report::load: load\nERROR report::load: load\n\
+             ERROR billing::export::csv: export"
+        );
+    }
+
+    #[test]
+    fn test_min_level_parses_and_orders() {
+        assert_eq!("warn".parse(), Ok(LogLevel::Warning));
+        assert!(LogLevel::Error > LogLevel::Warning);
+        assert_eq!(level_filter(LogLevel::Info), log::LevelFilter::Info);
+    }
+}
@@ -0,0 +1,881 @@
+#[cfg(feature = "remote")]
+use crate::log_format::slack_text;
+use crate::{FieldError, LogWebhookFormat, Settings};
+#[cfg(feature = "remote")]
+use crate::{LogEvent, SettingsError};
+#[cfg(feature = "remote")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "remote")]
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use serde::{de, Deserialize, Deserializer, Serialize};
+#[cfg(feature = "remote")]
+use serde_json::{json, Value};
+#[cfg(feature = "remote")]
+use std::collections::hash_map::RandomState;
+use std::fmt;
+#[cfg(feature = "remote")]
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+use std::time::Duration;
+#[cfg(feature = "remote")]
+use url::Url;
+
+/// Request timeout for the log webhook when `LogWebhookTimeoutSeconds` is not set.
+pub const DEFAULT_LOG_WEBHOOK_TIMEOUT_SECONDS: u64 = 10;
+/// Retries after the first attempt when `LogWebhookMaxRetries` is not set.
+pub const DEFAULT_LOG_WEBHOOK_MAX_RETRIES: u32 = 3;
+/// Events per request from `WebhookLogger::post_batch` when `LogWebhookBatchSize`
+/// is not set.
+pub const DEFAULT_LOG_WEBHOOK_BATCH_SIZE: u32 = 50;
+
+// Backoff before the first retry, when the webhook doesn't send Retry-After
+#[cfg(feature = "remote")]
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+// A Retry-After beyond this is not waited out in full; logging should not
+// stall the report for minutes
+#[cfg(feature = "remote")]
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Severity of a line posted to the log webhook, ordered from least to most
+/// severe. Read from `LogWebhookMinLevel` case-insensitively, where `Warn` is
+/// accepted for `Warning`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Debug => "Debug",
+            LogLevel::Info => "Info",
+            LogLevel::Warning => "Warning",
+            LogLevel::Error => "Error",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogLevel, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warning" | "warn" => Ok(LogLevel::Warning),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(format!(
+                "'{}' is not a valid log level, expected one of: Debug, Info, Warning, Error",
+                s
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LogLevel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Why a log line could not be delivered to the webhook.
+#[cfg(feature = "remote")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WebhookError {
+    /// The webhook answered with a non-success status.
+    Status { status: u16, body: String },
+    /// The request did not get an answer, e.g. a connection failure.
+    Transport(String),
+    /// The webhook did not answer within `LogWebhookTimeoutSeconds`, or could
+    /// not be reached within `HttpConnectTimeoutSeconds`.
+    Timeout(String),
+}
+
+#[cfg(feature = "remote")]
+impl WebhookError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            WebhookError::Status { status, .. } => *status == 429 || *status >= 500,
+            WebhookError::Transport(_) | WebhookError::Timeout(_) => true,
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl std::error::Error for WebhookError {}
+
+#[cfg(feature = "remote")]
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookError::Status { status, body } => {
+                write!(f, "webhook returned status {}: {}", status, body)
+            }
+            WebhookError::Transport(reason) => write!(f, "{}", reason),
+            WebhookError::Timeout(reason) => write!(f, "timed out: {}", reason),
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl From<reqwest::Error> for WebhookError {
+    // Webhook URLs often carry their access token, so it is kept out of the message
+    fn from(e: reqwest::Error) -> WebhookError {
+        if e.is_timeout() {
+            return WebhookError::Timeout(e.without_url().to_string());
+        }
+        WebhookError::Transport(e.without_url().to_string())
+    }
+}
+
+/// Posts log lines to `LogWebhookUri`; see [`Settings::get_webhook_logger`].
+#[cfg(feature = "remote")]
+#[derive(Debug, Clone)]
+pub struct WebhookLogger {
+    client: reqwest::Client,
+    timeout: Duration,
+    url: Url,
+    auth: Option<(HeaderName, HeaderValue)>,
+    format: LogWebhookFormat,
+    source: String,
+    max_retries: u32,
+    batch_size: u32,
+    retry_base_delay: Duration,
+}
+
+#[cfg(feature = "remote")]
+impl WebhookLogger {
+    /// Posts `event` in the `LogWebhookFormat` layout; the default `Json`
+    /// format is the [`LogEvent`] serialized as is. An event without a source
+    /// gets the application name.
+    ///
+    /// Throttling (429), server errors (5xx) and failed requests are retried up
+    /// to `LogWebhookMaxRetries` times, waiting as long as the webhook's
+    /// `Retry-After` asks (up to 30 seconds), or else backing off exponentially
+    /// with jitter. If the line still isn't delivered, the error carries it.
+    ///
+    /// Needs a tokio runtime with the time driver enabled.
+    pub async fn post_event(&self, event: &LogEvent) -> Result<(), SettingsError> {
+        let payload = self
+            .format
+            .payload(&event.with_default_source(&self.source));
+        self.deliver(&payload, self.format == LogWebhookFormat::Slack)
+            .await
+            .map_err(|(source, attempts)| SettingsError::LogWebhook {
+                level: event.level(),
+                message: event.message().to_string(),
+                attempts,
+                source,
+            })
+    }
+
+    /// Posts `events` in batches of up to `LogWebhookBatchSize`, one request
+    /// each, in order. In the `Json` format a batch is an array of events; in
+    /// the others it is one message of all the batch's lines, at the most
+    /// severe level among them. Each batch is retried like
+    /// [`WebhookLogger::post_event`] retries an event.
+    ///
+    /// Posting stops at the first batch that can't be delivered, and the error,
+    /// a [`SettingsError::LogWebhookBatch`], hands back that batch's events and
+    /// those of the batches after it.
+    pub async fn post_batch(&self, events: &[LogEvent]) -> Result<(), SettingsError> {
+        let slack = self.format == LogWebhookFormat::Slack;
+        let batch_size = self.batch_size.max(1) as usize;
+        for (index, batch) in events.chunks(batch_size).enumerate() {
+            let batch: Vec<LogEvent> = batch
+                .iter()
+                .map(|event| event.with_default_source(&self.source))
+                .collect();
+            let payload = self.format.batch_payload(&batch);
+            if let Err((source, attempts)) = self.deliver(&payload, slack).await {
+                let delivered = index * batch_size;
+                return Err(SettingsError::LogWebhookBatch {
+                    delivered,
+                    undelivered: events[delivered..].to_vec(),
+                    attempts,
+                    source,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Posts a log line timestamped now, like [`WebhookLogger::post_event`]
+    /// posts a [`LogEvent::new`].
+    pub async fn post_log(&self, level: LogLevel, message: &str) -> Result<(), SettingsError> {
+        self.post_event(&LogEvent::new(level, message)).await
+    }
+
+    /// Posts a Slack Block Kit message, e.g. a multi-line report summary, with
+    /// `text` as the notification fallback. The Slack layout is used whatever
+    /// `LogWebhookFormat` says. Retries like [`WebhookLogger::post_event`].
+    pub async fn post_blocks(
+        &self,
+        level: LogLevel,
+        text: &str,
+        blocks: Vec<Value>,
+    ) -> Result<(), SettingsError> {
+        let payload = json!({
+            "text": slack_text(level, text, &self.source),
+            "blocks": blocks,
+        });
+        self.deliver(&payload, true)
+            .await
+            .map_err(|(source, attempts)| SettingsError::LogWebhook {
+                level,
+                message: text.to_string(),
+                attempts,
+                source,
+            })
+    }
+
+    pub(crate) fn batch_size(&self) -> u32 {
+        self.batch_size
+    }
+
+    // A failure comes with the number of attempts made
+    async fn deliver(&self, payload: &Value, slack: bool) -> Result<(), (WebhookError, u32)> {
+        let mut backoff = self.retry_base_delay;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let (error, retry_after) = match self.post(payload, slack).await {
+                Ok(()) => return Ok(()),
+                Err(failure) => failure,
+            };
+            if !error.is_retryable() || attempts > self.max_retries {
+                return Err((error, attempts));
+            }
+            let delay = retry_after.unwrap_or_else(|| jitter(backoff));
+            tokio::time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+            backoff = backoff.saturating_mul(2);
+        }
+    }
+
+    // A failure comes with the delay the webhook asked for, if any. Slack
+    // answers anything it accepts with a 200 and "ok"; other 200s carry an
+    // error code in the body instead
+    async fn post(
+        &self,
+        payload: &Value,
+        slack: bool,
+    ) -> Result<(), (WebhookError, Option<Duration>)> {
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .timeout(self.timeout)
+            .json(payload);
+        if let Some((name, value)) = &self.auth {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.map_err(|e| (e.into(), None))?;
+        let status = response.status();
+        if status.is_success() && !slack {
+            return Ok(());
+        }
+        let retry_after = retry_after(response.headers(), Utc::now());
+        let body = response.text().await.unwrap_or_default();
+        if status.is_success() && body.trim() == "ok" {
+            return Ok(());
+        }
+        let error = WebhookError::Status {
+            status: status.as_u16(),
+            body,
+        };
+        Err((error, retry_after))
+    }
+}
+
+// Retry-After is either a number of seconds or an HTTP date
+#[cfg(feature = "remote")]
+fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+// Somewhere between half and all of `delay`, so that loggers throttled together
+// don't retry in lockstep
+#[cfg(feature = "remote")]
+pub(crate) fn jitter(delay: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let fraction = 0.5 + (random % 1000) as f64 / 2000.0;
+    delay.mul_f64(fraction)
+}
+
+impl Settings {
+    /// `LogWebhookTimeoutSeconds`, or [`DEFAULT_LOG_WEBHOOK_TIMEOUT_SECONDS`] if unset.
+    pub fn log_webhook_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.log_webhook_timeout_seconds
+                .unwrap_or(DEFAULT_LOG_WEBHOOK_TIMEOUT_SECONDS),
+        )
+    }
+
+    /// `LogWebhookFormat`, defaulting to `Json`.
+    pub fn log_webhook_format(&self) -> LogWebhookFormat {
+        self.log_webhook_format.unwrap_or_default()
+    }
+
+    /// `LogWebhookMinLevel`, the least severe level forwarded by the `log`
+    /// facade sink; defaults to `Warning`.
+    pub fn log_webhook_min_level(&self) -> LogLevel {
+        self.log_webhook_min_level.unwrap_or(LogLevel::Warning)
+    }
+
+    /// `LogWebhookModuleAllowList`: the module path prefixes, like `report` or
+    /// `report::load`, whose records the `log` sink and `tracing` layer
+    /// forward. A prefix covers the module and those beneath it. Empty, so
+    /// that every module's records are forwarded, if unset.
+    pub fn log_webhook_module_allow_list(&self) -> Vec<&str> {
+        self.log_webhook_module_allow_list
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|module| !module.is_empty())
+            .collect()
+    }
+
+    // A space inside an entry is most likely a missing comma
+    pub(crate) fn log_webhook_module_errors(&self) -> Vec<FieldError> {
+        self.log_webhook_module_allow_list()
+            .into_iter()
+            .filter(|module| module.contains(char::is_whitespace))
+            .map(|module| {
+                FieldError::new(
+                    "LogWebhookModuleAllowList",
+                    format!(
+                        "'{}' is not a module path; separate entries with commas",
+                        module
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// `LogWebhookMaxRetries`, or [`DEFAULT_LOG_WEBHOOK_MAX_RETRIES`] if unset.
+    pub fn log_webhook_max_retries(&self) -> u32 {
+        self.log_webhook_max_retries
+            .unwrap_or(DEFAULT_LOG_WEBHOOK_MAX_RETRIES)
+    }
+
+    /// `LogWebhookBatchSize`, or [`DEFAULT_LOG_WEBHOOK_BATCH_SIZE`] if unset.
+    pub fn log_webhook_batch_size(&self) -> u32 {
+        self.log_webhook_batch_size
+            .unwrap_or(DEFAULT_LOG_WEBHOOK_BATCH_SIZE)
+    }
+
+    /// A client for `LogWebhookUri`, tagging lines with [`Settings::application_name`].
+    /// Each request goes through the shared [`Settings::http_client`] and may
+    /// take up to [`Settings::log_webhook_timeout`], which takes the place of
+    /// `HttpRequestTimeoutSeconds` for the webhook. Every post carries
+    /// `LogWebhookAuthHeader` if set, and the logger's `Debug` output hides
+    /// its value.
+    #[cfg(feature = "remote")]
+    pub fn get_webhook_logger(&self) -> Result<WebhookLogger, SettingsError> {
+        let url = self.log_webhook_url()?;
+        let errors = self.log_webhook_auth_errors();
+        if !errors.is_empty() {
+            return Err(SettingsError::Validation(errors));
+        }
+
+        Ok(WebhookLogger {
+            client: self.http_client()?,
+            timeout: self.log_webhook_timeout(),
+            url,
+            auth: self.log_webhook_auth(),
+            format: self.log_webhook_format(),
+            source: self.application_name().to_string(),
+            max_retries: self.log_webhook_max_retries(),
+            batch_size: self.log_webhook_batch_size(),
+            retry_base_delay: RETRY_BASE_DELAY,
+        })
+    }
+
+    // Both parse once `log_webhook_auth_errors` has passed them
+    #[cfg(feature = "remote")]
+    fn log_webhook_auth(&self) -> Option<(HeaderName, HeaderValue)> {
+        let name = HeaderName::from_bytes(self.log_webhook_auth_header()?.as_bytes()).ok()?;
+        let mut value = HeaderValue::from_str(self.log_webhook_auth_value()?.expose()).ok()?;
+        value.set_sensitive(true);
+        Some((name, value))
+    }
+}
+
+#[cfg(all(test, feature = "remote"))]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn settings(log_webhook_uri: &str) -> Settings {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri(log_webhook_uri)
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .application_name("Nightly Sales")
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_post_log_sends_json() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_partial_json(json!({
+                "Level": "Warning",
+                "Message": "3 rows skipped",
+                "Source": "Nightly Sales",
+            })))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let logger = settings(&format!("{}/hook", server.uri()))
+            .get_webhook_logger()
+            .unwrap();
+        logger
+            .post_log(LogLevel::Warning, "3 rows skipped")
+            .await
+            .unwrap();
+
+        let request = &server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let timestamp = body["Timestamp"].as_str().unwrap();
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(timestamp).is_ok(),
+            "{}",
+            timestamp
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_event_sends_all_fields() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_partial_json(json!({
+                "Level": "Error",
+                "Source": "Nightly Sales",
+                "CorrelationId": "run-42",
+                "Details": {"Rows": 0},
+            })))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let logger = settings(&format!("{}/hook", server.uri()))
+            .get_webhook_logger()
+            .unwrap();
+        let event = LogEvent::builder(LogLevel::Error, "Query failed")
+            .correlation_id("run-42")
+            .details(json!({"Rows": 0}))
+            .build();
+        logger.post_event(&event).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_post_batch_splits_into_batches() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(202))
+            .mount(&server)
+            .await;
+        let logger = WebhookLogger {
+            batch_size: 2,
+            ..settings(&server.uri()).get_webhook_logger().unwrap()
+        };
+        let events: Vec<LogEvent> = (1..=5)
+            .map(|n| LogEvent::new(LogLevel::Info, format!("line {}", n)))
+            .collect();
+
+        logger.post_batch(&events).await.unwrap();
+
+        let batches: Vec<Value> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| serde_json::from_slice(&request.body).unwrap())
+            .collect();
+        let messages: Vec<Vec<&str>> = batches
+            .iter()
+            .map(|batch| {
+                let batch = batch.as_array().unwrap();
+                batch
+                    .iter()
+                    .map(|e| e["Message"].as_str().unwrap())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                vec!["line 1", "line 2"],
+                vec!["line 3", "line 4"],
+                vec!["line 5"]
+            ]
+        );
+        assert_eq!(batches[0][0]["Source"], "Nightly Sales");
+    }
+
+    #[tokio::test]
+    async fn test_post_batch_hands_back_undelivered_events() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(202))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&server)
+            .await;
+        let logger = WebhookLogger {
+            batch_size: 2,
+            ..settings(&server.uri()).get_webhook_logger().unwrap()
+        };
+        let events: Vec<LogEvent> = (1..=5)
+            .map(|n| LogEvent::new(LogLevel::Info, format!("line {}", n)))
+            .collect();
+
+        let err = logger.post_batch(&events).await.unwrap_err();
+
+        let SettingsError::LogWebhookBatch {
+            delivered,
+            undelivered,
+            attempts,
+            ..
+        } = &err
+        else {
+            panic!("unexpected error: {}", err);
+        };
+        assert_eq!((*delivered, *attempts), (2, 1));
+        assert_eq!(undelivered, &events[2..]);
+        assert_eq!(
+            err.to_string(),
+            "Could not post 3 of 5 log events to log webhook: webhook returned status 400: "
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_batch_in_chat_formats_is_one_message() {
+        let events = [
+            LogEvent::builder(LogLevel::Info, "Started")
+                .source("Nightly Sales")
+                .build(),
+            LogEvent::builder(LogLevel::Warning, "3 rows skipped")
+                .source("Nightly Sales")
+                .build(),
+        ];
+
+        assert_eq!(
+            LogWebhookFormat::Slack.batch_payload(&events),
+            json!({ "text": "🟡 *Nightly Sales*: Started\n3 rows skipped" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auth_header_is_sent_and_hidden() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(wiremock::matchers::header(
+                "x-functions-key",
+                "function-key",
+            ))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let settings = Settings::for_tests()
+            .with_log_webhook_uri(server.uri())
+            .with_log_webhook_auth_header("x-functions-key")
+            .with_log_webhook_auth_value("function-key");
+
+        let logger = settings.get_webhook_logger().unwrap();
+        logger.post_log(LogLevel::Info, "hello").await.unwrap();
+
+        let debug = format!("{:?} {:?}", logger, settings);
+        assert!(!debug.contains("function-key"), "{}", debug);
+        let err = settings
+            .with_log_webhook_auth_header("x functions key")
+            .get_webhook_logger()
+            .unwrap_err();
+        assert!(matches!(err, SettingsError::Validation(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_post_log_reports_status() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+            .mount(&server)
+            .await;
+
+        let err = fast_logger(&server)
+            .post_log(LogLevel::Error, "failed")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            SettingsError::LogWebhook {
+                level: LogLevel::Error,
+                ref message,
+                attempts: 4,
+                source: WebhookError::Status { status: 500, .. },
+            } if message == "failed"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Could not post to log webhook after 4 attempts: webhook returned status 500: boom"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transport_error_hides_url() {
+        // Nothing listens on port 1
+        let logger = WebhookLogger {
+            max_retries: 0,
+            ..settings("http://127.0.0.1:1/hook?token=secret-token")
+                .get_webhook_logger()
+                .unwrap()
+        };
+
+        let err = logger.post_log(LogLevel::Info, "hello").await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            SettingsError::LogWebhook {
+                attempts: 1,
+                source: WebhookError::Transport(_),
+                ..
+            }
+        ));
+        assert!(!err.to_string().contains("secret-token"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_slow_webhook_times_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(3)))
+            .mount(&server)
+            .await;
+        let logger = WebhookLogger {
+            max_retries: 0,
+            timeout: Duration::from_secs(1),
+            ..settings(&server.uri()).get_webhook_logger().unwrap()
+        };
+
+        let err = logger.post_log(LogLevel::Info, "hello").await.unwrap_err();
+
+        assert!(err.is_timeout(), "{}", err);
+        assert!(matches!(
+            err,
+            SettingsError::LogWebhook {
+                source: WebhookError::Timeout(_),
+                ..
+            }
+        ));
+    }
+
+    // Retries without the real backoff delay
+    fn fast_logger(server: &MockServer) -> WebhookLogger {
+        WebhookLogger {
+            retry_base_delay: Duration::from_millis(1),
+            ..settings(&server.uri()).get_webhook_logger().unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_throttled_posts() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        fast_logger(&server)
+            .post_log(LogLevel::Info, "hello")
+            .await
+            .unwrap();
+
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&server)
+            .await;
+
+        let err = fast_logger(&server)
+            .post_log(LogLevel::Info, "hello")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, SettingsError::LogWebhook { attempts: 1, .. }));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    fn slack_logger(server: &MockServer) -> WebhookLogger {
+        WebhookLogger {
+            format: LogWebhookFormat::Slack,
+            ..fast_logger(server)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slack_checks_response_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("invalid_token"))
+            .mount(&server)
+            .await;
+        let logger = slack_logger(&server);
+
+        logger.post_log(LogLevel::Info, "hello").await.unwrap();
+        let err = logger.post_log(LogLevel::Info, "hello").await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            SettingsError::LogWebhook {
+                attempts: 1,
+                source: WebhookError::Status { status: 200, ref body },
+                ..
+            } if body == "invalid_token"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_slack_invalid_payload_is_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_payload"))
+            .mount(&server)
+            .await;
+
+        let err = slack_logger(&server)
+            .post_blocks(
+                LogLevel::Info,
+                "Summary",
+                vec![json!({ "type": "divider" })],
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Could not post to log webhook: webhook returned status 400: invalid_payload"
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_post_blocks_sends_fallback_text() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "text": "🔵 *Nightly Sales*: 12 reports sent",
+                "blocks": [{ "type": "section", "text": { "type": "mrkdwn", "text": "*12* sent" } }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        fast_logger(&server)
+            .post_blocks(
+                LogLevel::Info,
+                "12 reports sent",
+                vec![
+                    json!({ "type": "section", "text": { "type": "mrkdwn", "text": "*12* sent" } }),
+                ],
+            )
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_retry_after_seconds_and_date() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let header = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_static(value));
+            headers
+        };
+
+        assert_eq!(retry_after(&header("7"), now), Some(Duration::from_secs(7)));
+        assert_eq!(
+            retry_after(&header("Wed, 21 Oct 2015 07:28:05 GMT"), now),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            retry_after(&header("Wed, 21 Oct 2015 07:27:00 GMT"), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&header("soon"), now), None);
+        assert_eq!(retry_after(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_range() {
+        for _ in 0..100 {
+            let delay = jitter(Duration::from_millis(1000));
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1000));
+        }
+    }
+
+    #[test]
+    fn test_timeout_default_and_override() {
+        let default = settings("https://example.com");
+        assert_eq!(default.log_webhook_timeout(), Duration::from_secs(10));
+
+        let custom = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .log_webhook_timeout_seconds(3)
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+        assert_eq!(custom.log_webhook_timeout(), Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,586 @@
+use crate::{Settings, SettingsError, SENDGRID_MAX_RECIPIENTS};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sendgrid::v3::message::{MailSettings, SandboxMode};
+use sendgrid::v3::{Attachment, Content, Message, Personalization, Sender, ASM};
+use serde_json::Value;
+
+/// The largest message SendGrid accepts, in bytes of the JSON request body,
+/// which holds the attachments base64-encoded.
+pub const SENDGRID_MAX_MESSAGE_BYTES: usize = 30_000_000;
+
+impl Settings {
+    /// A SendGrid sender using `SendgridApiKey`, posting to
+    /// [`Settings::sendgrid_mail_send_url`]: the `/v3/mail/send` endpoint on
+    /// `SendgridApiHost`, or on `https://api.sendgrid.com` if that is not set.
+    /// Without a key, SendGrid rejects every message.
+    ///
+    /// The sender keeps its own copy of the key, which is not wiped on drop and
+    /// shows up in its `Debug` output.
+    ///
+    /// It uses the shared [`Settings::http_client`], or reqwest's default
+    /// client if that can't be set up, e.g. with an invalid `HttpProxyUri`;
+    /// [`Settings::try_get_sendgrid_sender`] reports that instead.
+    pub fn get_sendgrid_sender(&self) -> Sender {
+        self.sendgrid_sender(self.http_client().ok())
+    }
+
+    /// Like [`Settings::get_sendgrid_sender`], but fails if the shared
+    /// [`Settings::http_client`] can't be set up rather than bypassing the
+    /// proxy.
+    pub fn try_get_sendgrid_sender(&self) -> Result<Sender, SettingsError> {
+        Ok(self.sendgrid_sender(Some(self.http_client()?)))
+    }
+
+    fn sendgrid_sender(&self, client: Option<reqwest::Client>) -> Sender {
+        let key = self.sendgrid_api_key().map(|key| key.expose());
+        let mut sender = Sender::new(key.unwrap_or_default().to_string(), client);
+        sender.set_host(self.sendgrid_mail_send_url());
+        sender
+    }
+
+    /// Like [`Settings::get_sendgrid_sender`], but posting to `host`, the full
+    /// mail send URL, whatever `SendgridApiHost` says, e.g. a local test server.
+    pub fn get_sendgrid_sender_with_host(&self, host: &str) -> Sender {
+        let mut sender = self.get_sendgrid_sender();
+        sender.set_host(host);
+        sender
+    }
+
+    /// A message from [`Settings::get_email_from`] to the To, CC and BCC
+    /// recipients, with the reply-to address if one is set, ready to send. The
+    /// plain-text body, if given, is attached ahead of the HTML one, the order
+    /// SendGrid requires. With `EmailSandboxMode` on, SendGrid accepts the
+    /// message without delivering it.
+    ///
+    /// The message is tagged with `EmailCategories`, carries `EmailCustomArgs`,
+    /// and belongs to the `EmailAsmGroupId` unsubscribe group if one is set.
+    ///
+    /// Fails if `EmailFromAddress` is not set, if a recipient field holds an
+    /// invalid entry, if there are no To recipients, if there are more than
+    /// [`SENDGRID_MAX_RECIPIENTS`] recipients in all, or with
+    /// [`SettingsError::Validation`] if the categories, custom args or
+    /// unsubscribe groups break SendGrid's rules.
+    pub fn build_message(
+        &self,
+        subject: &str,
+        html_body: &str,
+        plain_body: Option<&str>,
+    ) -> Result<Message, SettingsError> {
+        let mut message = self.addressed_message(None)?.set_subject(subject);
+        if let Some(plain_body) = plain_body {
+            message = message.add_content(
+                Content::new()
+                    .set_content_type("text/plain")
+                    .set_value(plain_body),
+            );
+        }
+        Ok(message.add_content(
+            Content::new()
+                .set_content_type("text/html")
+                .set_value(html_body),
+        ))
+    }
+
+    /// [`Settings::build_message`] with files attached, each given as
+    /// `(filename, content_type, bytes)`, e.g.
+    /// `("costs.csv", "text/csv", &csv)`. The bytes are sent base64-encoded.
+    ///
+    /// Also fails if a filename is empty, or if the whole message comes to more
+    /// than [`SENDGRID_MAX_MESSAGE_BYTES`], which is checked before anything is
+    /// sent.
+    pub fn build_message_with_attachments(
+        &self,
+        subject: &str,
+        html_body: &str,
+        plain_body: Option<&str>,
+        attachments: &[(&str, &str, &[u8])],
+    ) -> Result<Message, SettingsError> {
+        if let Some(position) = attachments
+            .iter()
+            .position(|(filename, _, _)| filename.trim().is_empty())
+        {
+            return Err(SettingsError::EmptyAttachmentName {
+                position: position + 1,
+            });
+        }
+
+        let mut message = self.build_message(subject, html_body, plain_body)?;
+        for (filename, content_type, bytes) in attachments {
+            message = message.add_attachment(
+                Attachment::new()
+                    .set_filename(*filename)
+                    .set_mime_type(*content_type)
+                    .set_base64_content(STANDARD.encode(bytes)),
+            );
+        }
+
+        let size = serde_json::to_vec(&message)?.len();
+        if size > SENDGRID_MAX_MESSAGE_BYTES {
+            return Err(SettingsError::MessageTooLarge { size });
+        }
+        Ok(message)
+    }
+
+    /// A message rendered by SendGrid from the `SendgridTemplateId` dynamic
+    /// template, addressed like [`Settings::build_message`], with `data` as the
+    /// template's dynamic data. The template supplies the subject and body.
+    ///
+    /// Fails like `build_message`, and also if `SendgridTemplateId` is not set
+    /// or `data` is not a JSON object.
+    pub fn build_template_message(&self, data: &Value) -> Result<Message, SettingsError> {
+        let template_id = self
+            .sendgrid_template_id()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .ok_or(SettingsError::MissingField {
+                field: "SendgridTemplateId",
+            })?;
+        if !data.is_object() {
+            return Err(SettingsError::InvalidTemplateData);
+        }
+        Ok(self
+            .addressed_message(Some(data))?
+            .set_template_id(template_id))
+    }
+
+    // From, recipients and reply-to, with the template data on the personalization
+    fn addressed_message(&self, template_data: Option<&Value>) -> Result<Message, SettingsError> {
+        let from = self.get_email_from().ok_or(SettingsError::MissingField {
+            field: "EmailFromAddress",
+        })?;
+        let to = self.try_get_email_destinations()?;
+        if to.is_empty() {
+            return Err(SettingsError::NoRecipients);
+        }
+
+        let cc = self.get_email_cc()?;
+        let bcc = self.get_email_bcc()?;
+        let count = to.len() + cc.len() + bcc.len();
+        if count > SENDGRID_MAX_RECIPIENTS {
+            return Err(SettingsError::TooManyRecipients { count });
+        }
+
+        let mut personalization = Personalization::new_many(to);
+        for cc in cc {
+            personalization = personalization.add_cc(cc);
+        }
+        for bcc in bcc {
+            personalization = personalization.add_bcc(bcc);
+        }
+
+        if let Some(data) = template_data {
+            personalization = personalization
+                .add_dynamic_template_data_json(data)
+                .map_err(|_| SettingsError::InvalidTemplateData)?;
+        }
+
+        let mut errors = self.analytics_errors();
+        errors.extend(self.unsubscribe_errors());
+        if !errors.is_empty() {
+            return Err(SettingsError::Validation(errors));
+        }
+        if let Some(args) = self.email_custom_args() {
+            personalization = personalization.add_custom_args(
+                args.iter()
+                    .filter_map(|(name, value)| Some((name.clone(), value.as_str()?.to_string())))
+                    .collect(),
+            );
+        }
+
+        let mut message = Message::new(from).add_personalization(personalization);
+        let categories = self.email_categories();
+        if !categories.is_empty() {
+            let categories: Vec<String> = categories.into_iter().map(String::from).collect();
+            message = message.add_categories(&categories);
+        }
+        if let Some(group_id) = self.email_asm_group_id() {
+            let asm = ASM::new()
+                .set_group_id(group_id)
+                .set_groups_to_display(self.email_asm_groups_to_display().into_iter().collect())
+                .expect("validated to at most 25 groups");
+            message = message.set_asm(asm);
+        }
+        if let Some(reply_to) = self.get_email_reply_to() {
+            message = message.set_reply_to(reply_to);
+        }
+        if self.is_email_sandboxed() {
+            message = message.set_mail_settings(
+                MailSettings::new().set_sandbox_mode(SandboxMode::new().set_enable(true)),
+            );
+        }
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SettingsBuilder;
+    use serde_json::json;
+
+    fn settings() -> SettingsBuilder {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_name("Reports")
+            .email_from_address("reports@example.com")
+            .email_to_addresses("ops@example.com, Jane <jane@example.com>")
+    }
+
+    #[test]
+    fn test_build_message_json() {
+        let settings = settings()
+            .email_cc_addresses("finance@example.com")
+            .email_bcc_addresses("audit@example.com")
+            .build()
+            .unwrap();
+
+        let message = settings
+            .build_message("Daily report", "<p>Done</p>", Some("Done"))
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            json!({
+                "from": { "email": "reports@example.com", "name": "Reports" },
+                "subject": "Daily report",
+                "personalizations": [{
+                    "to": [
+                        { "email": "ops@example.com" },
+                        { "email": "jane@example.com", "name": "Jane" },
+                    ],
+                    "cc": [{ "email": "finance@example.com" }],
+                    "bcc": [{ "email": "audit@example.com" }],
+                }],
+                "content": [
+                    { "type": "text/plain", "value": "Done" },
+                    { "type": "text/html", "value": "<p>Done</p>" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_message_html_only() {
+        let message = settings()
+            .build()
+            .unwrap()
+            .build_message("Daily report", "<p>Done</p>", None)
+            .unwrap();
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            json["content"],
+            json!([{ "type": "text/html", "value": "<p>Done</p>" }])
+        );
+        assert!(json["personalizations"][0].get("cc").is_none());
+        assert!(json.get("reply_to").is_none());
+    }
+
+    #[test]
+    fn test_build_message_sets_reply_to() {
+        let message = settings()
+            .email_reply_to_address("support@example.com")
+            .build()
+            .unwrap()
+            .build_message("Daily report", "<p>Done</p>", None)
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap()["reply_to"],
+            json!({ "email": "support@example.com" })
+        );
+    }
+
+    #[test]
+    fn test_categories_and_custom_args_json() {
+        let tagged = settings()
+            .email_categories("staging, daily-costs")
+            .email_custom_arg("Environment", "staging")
+            .email_custom_arg("Report", "costs")
+            .build()
+            .unwrap();
+
+        let message = serde_json::to_value(
+            tagged
+                .build_message("Daily report", "<p>Done</p>", None)
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(message["categories"], json!(["staging", "daily-costs"]));
+        assert_eq!(
+            message["personalizations"][0]["custom_args"],
+            json!({ "Environment": "staging", "Report": "costs" })
+        );
+        let untagged = serde_json::to_value(
+            settings()
+                .build()
+                .unwrap()
+                .build_message("r", "b", None)
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(untagged.get("categories").is_none());
+        assert!(untagged["personalizations"][0].get("custom_args").is_none());
+    }
+
+    #[test]
+    fn test_non_string_custom_arg_is_refused() {
+        let settings = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailFromAddress": "reports@example.com", "EmailToAddresses": "ops@example.com",
+                "EmailCustomArgs": {"RunId": 42}}"#,
+        )
+        .unwrap();
+
+        let Err(err) = settings.build_message("Daily report", "<p>Done</p>", None) else {
+            panic!("built a message with a numeric custom arg");
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid settings: EmailCustomArgs: 'RunId' must be a string, not a number"
+        );
+    }
+
+    #[test]
+    fn test_asm_block_json() {
+        let grouped = settings()
+            .email_asm_group_id(12)
+            .email_asm_groups_to_display("12, 14, 15")
+            .build()
+            .unwrap();
+        let group_only = settings().email_asm_group_id(12).build().unwrap();
+
+        let mut message = serde_json::to_value(
+            grouped
+                .build_message("Daily report", "<p>Done</p>", None)
+                .unwrap(),
+        )
+        .unwrap();
+        // The sendgrid crate keeps the groups in a HashSet
+        message["asm"]["groups_to_display"]
+            .as_array_mut()
+            .unwrap()
+            .sort_by_key(|id| id.as_u64());
+        let group_only = serde_json::to_value(
+            group_only
+                .build_message("Daily report", "<p>Done</p>", None)
+                .unwrap(),
+        )
+        .unwrap();
+        let ungrouped = serde_json::to_value(
+            settings()
+                .build()
+                .unwrap()
+                .build_message("Daily report", "<p>Done</p>", None)
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message["asm"],
+            json!({ "group_id": 12, "groups_to_display": [12, 14, 15] })
+        );
+        assert_eq!(
+            group_only["asm"],
+            json!({ "group_id": 12, "groups_to_display": [] })
+        );
+        assert!(ungrouped.get("asm").is_none());
+    }
+
+    #[test]
+    fn test_sandbox_mode_only_when_enabled() {
+        let sandboxed = settings().email_sandbox_mode(true).build().unwrap();
+        let live = settings().email_sandbox_mode(false).build().unwrap();
+
+        let sandboxed = serde_json::to_value(
+            sandboxed
+                .build_message("Daily report", "<p>Done</p>", None)
+                .unwrap(),
+        )
+        .unwrap();
+        let live = serde_json::to_value(
+            live.build_message("Daily report", "<p>Done</p>", None)
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            sandboxed["mail_settings"],
+            json!({ "sandbox_mode": { "enable": true } })
+        );
+        assert!(live.get("mail_settings").is_none(), "{}", live);
+    }
+
+    #[test]
+    fn test_attachments_round_trip() {
+        let settings = settings().build().unwrap();
+        let xlsx: &[u8] = &[0x50, 0x4b, 0x03, 0x04, 0x00, 0xff, 0x80, 0x7f];
+
+        let message = settings
+            .build_message_with_attachments(
+                "Daily report",
+                "<p>Done</p>",
+                None,
+                &[
+                    ("costs.csv", "text/csv", b"region,total\nNorth,1250\n"),
+                    (
+                        "costs.xlsx",
+                        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                        xlsx,
+                    ),
+                ],
+            )
+            .unwrap();
+
+        let json = serde_json::to_value(&message).unwrap();
+        let attachments = json["attachments"].as_array().unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0]["filename"], "costs.csv");
+        assert_eq!(attachments[0]["type"], "text/csv");
+        let decoded: Vec<Vec<u8>> = attachments
+            .iter()
+            .map(|a| STANDARD.decode(a["content"].as_str().unwrap()).unwrap())
+            .collect();
+        assert_eq!(decoded, [&b"region,total\nNorth,1250\n"[..], xlsx]);
+    }
+
+    #[test]
+    fn test_attachment_errors() {
+        let settings = settings().build().unwrap();
+        let large = vec![0u8; 22_600_000];
+
+        let Err(unnamed) = settings.build_message_with_attachments(
+            "Daily report",
+            "<p>Done</p>",
+            None,
+            &[("costs.csv", "text/csv", b"a"), (" ", "text/csv", b"b")],
+        ) else {
+            panic!("attached a file without a name");
+        };
+        let Err(too_large) = settings.build_message_with_attachments(
+            "Daily report",
+            "<p>Done</p>",
+            None,
+            &[("dump.bin", "application/octet-stream", &large)],
+        ) else {
+            panic!("built a message over the size limit");
+        };
+
+        assert_eq!(unnamed.to_string(), "Attachment 2 has an empty filename");
+        assert!(
+            matches!(too_large, SettingsError::MessageTooLarge { size } if size > 30_000_000),
+            "{}",
+            too_large
+        );
+    }
+
+    #[test]
+    fn test_build_template_message_json() {
+        let settings = settings()
+            .email_cc_addresses("finance@example.com")
+            .email_bcc_addresses("audit@example.com")
+            .email_reply_to_address("support@example.com")
+            .sendgrid_template_id("d-0123456789abcdef")
+            .build()
+            .unwrap();
+
+        let message = settings
+            .build_template_message(&json!({ "total": 1250, "rows": [{ "name": "North" }] }))
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            json!({
+                "from": { "email": "reports@example.com", "name": "Reports" },
+                "subject": "",
+                "personalizations": [{
+                    "to": [
+                        { "email": "ops@example.com" },
+                        { "email": "jane@example.com", "name": "Jane" },
+                    ],
+                    "cc": [{ "email": "finance@example.com" }],
+                    "bcc": [{ "email": "audit@example.com" }],
+                    "dynamic_template_data": { "total": 1250, "rows": [{ "name": "North" }] },
+                }],
+                "reply_to": { "email": "support@example.com" },
+                "template_id": "d-0123456789abcdef",
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_template_message_errors() {
+        let untemplated = settings().build().unwrap();
+        let templated = settings()
+            .sendgrid_template_id("d-0123456789abcdef")
+            .build()
+            .unwrap();
+
+        let Err(missing) = untemplated.build_template_message(&json!({})) else {
+            panic!("built a message without a template");
+        };
+        let Err(not_object) = templated.build_template_message(&json!([1, 2])) else {
+            panic!("built a message with array template data");
+        };
+
+        assert_eq!(missing.to_string(), "SendgridTemplateId: not set");
+        assert!(matches!(not_object, SettingsError::InvalidTemplateData));
+    }
+
+    #[test]
+    fn test_sendgrid_sender_host() {
+        let settings = settings().build().unwrap();
+
+        let default = format!("{:?}", settings.get_sendgrid_sender());
+        assert!(
+            default.contains("https://api.sendgrid.com/v3/mail/send"),
+            "{}",
+            default
+        );
+
+        let eu = settings.get_sendgrid_sender_with_host("https://api.eu.sendgrid.com/v3/mail/send");
+        assert!(format!("{:?}", eu).contains("api.eu.sendgrid.com"));
+    }
+
+    #[test]
+    fn test_build_message_needs_recipients() {
+        let settings = settings()
+            .email_to_addresses(" , ")
+            .email_cc_addresses("finance@example.com")
+            .build()
+            .unwrap();
+
+        let result = settings.build_message("Daily report", "<p>Done</p>", None);
+        assert!(matches!(result, Err(SettingsError::NoRecipients)));
+    }
+
+    #[test]
+    fn test_build_message_limits_recipients() {
+        let bcc: Vec<String> = (0..999).map(|i| format!("user{}@example.com", i)).collect();
+        let settings = settings()
+            .email_bcc_addresses(bcc.join(", "))
+            .build()
+            .unwrap();
+
+        let Err(err) = settings.build_message("Daily report", "<p>Done</p>", None) else {
+            panic!("built a message for too many recipients");
+        };
+
+        assert!(matches!(
+            err,
+            SettingsError::TooManyRecipients { count: 1001 }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "1001 recipients across To, Cc and Bcc, but SendGrid accepts at most 1000"
+        );
+    }
+}
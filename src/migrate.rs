@@ -0,0 +1,169 @@
+use crate::{nested, Settings, SettingsError};
+use serde_json::{Map, Value};
+
+/// The newest `SchemaVersion` this crate reads. A blob without one is
+/// version 1.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+// The spellings of `SchemaVersion` the field accepts
+const VERSION_KEYS: [&str; 3] = ["SchemaVersion", "schemaVersion", "schema_version"];
+
+// Version 1: the flat keys `Settings` deserializes from. Blobs written before
+// versions existed may group their fields as version 2 does, so a version 1
+// blob is flattened too
+struct SettingsV1(Map<String, Value>);
+
+// Version 2: the `Database*`, `Email*` and `Log*` fields grouped into objects,
+// as `Settings::to_nested_json` writes them
+struct SettingsV2(Map<String, Value>);
+
+impl From<SettingsV1> for SettingsV2 {
+    fn from(v1: SettingsV1) -> SettingsV2 {
+        SettingsV2(nested::nest(v1.0))
+    }
+}
+
+impl TryFrom<SettingsV2> for SettingsV1 {
+    type Error = SettingsError;
+
+    fn try_from(v2: SettingsV2) -> Result<SettingsV1, SettingsError> {
+        let mut blob = Value::Object(v2.0);
+        nested::flatten(&mut blob)?;
+        match blob {
+            Value::Object(flat) => Ok(SettingsV1(flat)),
+            _ => unreachable!("flattening keeps an object"),
+        }
+    }
+}
+
+impl Settings {
+    /// `SchemaVersion`, the layout the blob was written in: 1 for flat keys,
+    /// the default, and 2 for the groups of [`Settings::to_nested_json`].
+    /// Blobs of every version up to [`CURRENT_SCHEMA_VERSION`] load into the
+    /// same settings.
+    pub fn schema_version(&self) -> u32 {
+        self.schema_version.unwrap_or(1)
+    }
+}
+
+/// The `SchemaVersion` of a parsed blob, failing with
+/// [`SettingsError::UnsupportedSchemaVersion`] if it is newer than
+/// [`CURRENT_SCHEMA_VERSION`]. A version that isn't a number is left for
+/// deserialization to report.
+pub(crate) fn schema_version(blob: &Value) -> Result<u32, SettingsError> {
+    let version =
+        VERSION_KEYS
+            .iter()
+            .find_map(|key| blob.get(key))
+            .and_then(|version| match version {
+                Value::String(version) => version.trim().parse().ok(),
+                version => version.as_u64(),
+            });
+    check_version(version)
+}
+
+/// Like [`schema_version`], for a TOML blob.
+pub(crate) fn toml_schema_version(blob: &toml::Table) -> Result<u32, SettingsError> {
+    let version = VERSION_KEYS
+        .iter()
+        .find_map(|key| blob.get(*key))
+        .and_then(|version| match version {
+            toml::Value::String(version) => version.trim().parse().ok(),
+            version => version
+                .as_integer()
+                .and_then(|version| u64::try_from(version).ok()),
+        });
+    check_version(version)
+}
+
+fn check_version(version: Option<u64>) -> Result<u32, SettingsError> {
+    match version {
+        Some(version) if version > u64::from(CURRENT_SCHEMA_VERSION) => {
+            Err(SettingsError::UnsupportedSchemaVersion { version })
+        }
+        // 0 is let through for `Settings::validate` to report
+        Some(version) => Ok(version as u32),
+        None => Ok(1),
+    }
+}
+
+/// Migrates a parsed blob of any supported version to the flat keys of the
+/// current settings, leaving anything that isn't an object for
+/// deserialization to report.
+pub(crate) fn migrate(blob: &mut Value) -> Result<(), SettingsError> {
+    schema_version(blob)?;
+    let fields = match blob {
+        Value::Object(fields) => std::mem::take(fields),
+        _ => return Ok(()),
+    };
+    // A version 1 blob may already be grouped, so it takes the version 2
+    // migration as well; a later version will add its step before this one
+    let v1 = SettingsV1::try_from(SettingsV2(fields))?;
+    *blob = Value::Object(v1.0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_round_trip() {
+        let settings = Settings::for_tests();
+        let v1 = serde_json::to_string(&settings).unwrap();
+
+        let loaded = Settings::from_json_str(&v1).unwrap();
+
+        assert_eq!(loaded, settings);
+        assert_eq!(loaded.schema_version(), 1);
+    }
+
+    #[test]
+    fn test_v2_round_trip() {
+        let settings = Settings::for_tests().with_schema_version(2);
+        let Value::Object(flat) = serde_json::to_value(&settings).unwrap() else {
+            panic!("not an object");
+        };
+
+        let v2 = SettingsV2::from(SettingsV1(flat.clone()));
+        assert_eq!(
+            v2.0.get("Database").and_then(|group| group.get("Server")),
+            flat.get("DatabaseServer")
+        );
+        assert!(!v2.0.contains_key("DatabaseServer"));
+        let blob = Value::Object(v2.0.clone()).to_string();
+        assert_eq!(SettingsV1::try_from(v2).unwrap().0, flat);
+
+        for loaded in [
+            Settings::from_json_str(&blob).unwrap(),
+            Settings::from_json_str(&settings.to_nested_json()).unwrap(),
+        ] {
+            assert_eq!(loaded, settings);
+            assert_eq!(loaded.schema_version(), 2);
+        }
+    }
+
+    #[test]
+    fn test_newer_version_is_refused() {
+        let blobs = [
+            r#"{"SchemaVersion": 3, "DatabaseServer": "sql01"}"#,
+            r#"{"SchemaVersion": 3, "Database": {"Server": "sql01"}}"#,
+        ];
+
+        for blob in blobs {
+            let err = Settings::from_json_str(blob).unwrap_err();
+            assert!(
+                matches!(err, SettingsError::UnsupportedSchemaVersion { version: 3 }),
+                "{}",
+                err
+            );
+            assert!(err
+                .to_string()
+                .contains("blob schema version 3 is newer than this crate supports"));
+        }
+        assert!(matches!(
+            Settings::from_toml("SchemaVersion = 3\nDatabaseServer = \"sql01\""),
+            Err(SettingsError::UnsupportedSchemaVersion { version: 3 })
+        ));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{Settings, SettingsError};
+use serde_json::{Map, Value};
+
+// The groups of the nested blob shape, with the prefix their fields carry in
+// the flat shape: `{"Logging": {"WebhookUri": ..}}` is `LogWebhookUri`
+const GROUPS: &[(&str, &str)] = &[
+    ("Database", "Database"),
+    ("Email", "Email"),
+    ("Logging", "Log"),
+];
+
+// Keep their flat names inside the `Email` group
+const SENDGRID_FIELDS: &[&str] = &["SendgridApiKey", "SendgridTemplateId", "SendgridApiHost"];
+
+impl Settings {
+    /// The settings as a JSON blob in the nested shape, with the `Database*`,
+    /// `Sendgrid*` and `Email*`, and `LogWebhook*` fields moved into
+    /// `Database`, `Email` and `Logging` objects without their prefix, e.g.
+    /// `{"Database": {"Server": "sql01", ...}, "Email": {"FromAddress": ...}}`.
+    /// `Databases`, `ApplicationName` and the `Http*` fields stay at the top
+    /// level.
+    ///
+    /// Every blob parser accepts this shape as well as the flat one. Like the
+    /// `Serialize` impl, it holds the secrets in clear text.
+    pub fn to_nested_json(&self) -> String {
+        let flat = match serde_json::to_value(self).expect("settings serialize") {
+            Value::Object(flat) => flat,
+            _ => unreachable!("settings serialize as an object"),
+        };
+        Value::Object(nest(flat)).to_string()
+    }
+}
+
+/// Moves the fields of a flat blob into their groups; the reverse of
+/// [`flatten`].
+pub(crate) fn nest(flat: Map<String, Value>) -> Map<String, Value> {
+    let mut nested = Map::new();
+    for (key, value) in flat {
+        match group_of(&key) {
+            Some((group, field)) => {
+                let group = nested
+                    .entry(group)
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(group) = group {
+                    group.insert(field.to_string(), value);
+                }
+            }
+            None => {
+                nested.insert(key, value);
+            }
+        }
+    }
+    nested
+}
+
+// The group and unprefixed name of a flat key, or `None` for top-level keys
+fn group_of(key: &str) -> Option<(&'static str, &str)> {
+    if SENDGRID_FIELDS.contains(&key) {
+        return Some(("Email", key));
+    }
+    GROUPS.iter().find_map(|(group, prefix)| {
+        key.strip_prefix(prefix)
+            // `Databases` is not in the `Database` group
+            .filter(|field| field.starts_with(|c: char| c.is_ascii_uppercase()))
+            .map(|field| (*group, field))
+    })
+}
+
+/// The keys of the groups in the nested shape.
+pub(crate) fn groups() -> impl Iterator<Item = &'static str> {
+    GROUPS.iter().map(|(group, _)| *group)
+}
+
+/// Whether a parsed blob uses the nested shape for any group.
+pub(crate) fn is_nested(blob: &Value) -> bool {
+    blob.as_object()
+        .is_some_and(|fields| groups().any(|group| fields.get(group).is_some_and(Value::is_object)))
+}
+
+/// Moves the fields of the nested groups in `blob` to their flat keys, leaving
+/// flat blobs as they are. A field given both ways is an error.
+pub(crate) fn flatten(blob: &mut Value) -> Result<(), SettingsError> {
+    let fields = match blob {
+        Value::Object(fields) => fields,
+        _ => return Ok(()),
+    };
+    for (group, prefix) in GROUPS {
+        let grouped = match fields.get(*group) {
+            Some(Value::Object(_)) => fields.remove(*group),
+            _ => continue,
+        };
+        if let Some(Value::Object(grouped)) = grouped {
+            for (field, value) in grouped {
+                let key = if *group == "Email" && SENDGRID_FIELDS.contains(&field.as_str()) {
+                    field.clone()
+                } else {
+                    format!("{}{}", prefix, field)
+                };
+                if fields.contains_key(&key) {
+                    return Err(SettingsError::DuplicateField {
+                        field: key,
+                        nested: format!("{}.{}", group, field),
+                    });
+                }
+                fields.insert(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NESTED: &str = r#"{
+        "Database": {
+            "Server": "sql01",
+            "Name": "reports",
+            "Username": "svc_report",
+            "Password": "hunter2",
+            "Port": 14330,
+            "PoolMaxSize": 4
+        },
+        "Databases": {
+            "warehouse": {"Server": "wh-sql", "Name": "warehouse", "Username": "reader", "Password": "secret"}
+        },
+        "Email": {
+            "SendgridApiKey": "SG.abcdefghijklmnop.wxyz",
+            "FromAddress": "reports@example.com",
+            "ToAddresses": "ops@example.com"
+        },
+        "Logging": {"WebhookUri": "https://hooks.example.com/x", "WebhookMinLevel": "Error"},
+        "ApplicationName": "costs"
+    }"#;
+
+    #[test]
+    fn test_nested_blob_parses_like_flat() {
+        let nested = Settings::from_json_str(NESTED).unwrap();
+        let flat = Settings::from_json_str(
+            r#"{
+                "DatabaseServer": "sql01",
+                "DatabaseName": "reports",
+                "DatabaseUsername": "svc_report",
+                "DatabasePassword": "hunter2",
+                "DatabasePort": 14330,
+                "DatabasePoolMaxSize": 4,
+                "Databases": {
+                    "warehouse": {"Server": "wh-sql", "Name": "warehouse", "Username": "reader", "Password": "secret"}
+                },
+                "SendgridApiKey": "SG.abcdefghijklmnop.wxyz",
+                "EmailFromAddress": "reports@example.com",
+                "EmailToAddresses": "ops@example.com",
+                "LogWebhookUri": "https://hooks.example.com/x",
+                "LogWebhookMinLevel": "Error",
+                "ApplicationName": "costs"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(nested, flat);
+        assert_eq!(nested.database().port(), Some(14330));
+    }
+
+    #[test]
+    fn test_both_shapes_round_trip() {
+        let settings = Settings::for_tests();
+
+        let flat = Settings::from_json_str(&serde_json::to_string(&settings).unwrap()).unwrap();
+        let nested = Settings::from_json_str(&settings.to_nested_json()).unwrap();
+
+        assert_eq!(flat, settings);
+        assert_eq!(nested, settings);
+        let Value::Object(shape) = serde_json::from_str(&settings.to_nested_json()).unwrap() else {
+            panic!("not an object");
+        };
+        let mut keys: Vec<&str> = shape.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "ApplicationName",
+                "Database",
+                "Databases",
+                "Email",
+                "HttpConnectTimeoutSeconds",
+                "HttpRequestTimeoutSeconds",
+                "Logging",
+                "SchemaVersion"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_toml_and_unknown_keys() {
+        let settings = Settings::from_toml(
+            "[Database]\nServer = \"sql01\"\nName = \"reports\"\nAuthMethod = \"Integrated\"\n\n[Logging]\nWebhookUri = \"https://hooks.example.com/x\"\n",
+        )
+        .unwrap();
+        let (_, unknown) = Settings::parse_json_reporting_unknown(
+            r#"{"Database": {"Sever": "sql01"}}"#,
+            crate::profile::Profile::FromEnv,
+        )
+        .unwrap();
+
+        assert_eq!(settings.database().server(), "sql01");
+        assert_eq!(
+            settings.logging().webhook_uri(),
+            Some("https://hooks.example.com/x")
+        );
+        assert_eq!(unknown, ["DatabaseSever"]);
+    }
+
+    #[test]
+    fn test_field_given_both_ways() {
+        let err =
+            Settings::from_json_str(r#"{"DatabaseServer": "a", "Database": {"Server": "b"}}"#)
+                .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "DatabaseServer is given both at the top level and as Database.Server"
+        );
+    }
+}
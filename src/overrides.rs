@@ -0,0 +1,395 @@
+use crate::serde_helpers::parse_bool;
+use crate::{Secret, Settings, SettingsError};
+use std::env;
+use zeroize::Zeroize;
+
+/// Prefix of the per-field override variables, e.g. `REPORTSETTINGS_DATABASE_SERVER`.
+pub const ENV_OVERRIDE_PREFIX: &str = "REPORTSETTINGS_";
+
+impl Settings {
+    /// Replaces fields with the values of `REPORTSETTINGS_<FIELD>` env variables,
+    /// where `<FIELD>` is the blob key in SCREAMING_SNAKE_CASE.
+    ///
+    /// Overrides take precedence over everything in the blob. A variable that is
+    /// set to an empty string sets the field to empty; only unset variables leave
+    /// the field alone.
+    pub fn apply_env_overrides(&mut self) -> Result<(), SettingsError> {
+        self.apply_overrides_from(env_lookup)
+    }
+
+    pub(crate) fn apply_overrides_from<F>(&mut self, lookup: F) -> Result<(), SettingsError>
+    where
+        F: Fn(&str) -> Result<Option<String>, SettingsError>,
+    {
+        self.apply_overrides_with_prefix(ENV_OVERRIDE_PREFIX, lookup)
+    }
+
+    // Like `apply_overrides_from`, for override variables named `<prefix><FIELD>`
+    pub(crate) fn apply_overrides_with_prefix<F>(
+        &mut self,
+        prefix: &str,
+        lookup: F,
+    ) -> Result<(), SettingsError>
+    where
+        F: Fn(&str) -> Result<Option<String>, SettingsError>,
+    {
+        // The client may have been built from the proxy fields being replaced
+        #[cfg(feature = "remote")]
+        {
+            self.http_client = Default::default();
+        }
+        let fields: [(&str, &mut String); 4] = [
+            ("DATABASE_SERVER", &mut self.database_server),
+            ("DATABASE_NAME", &mut self.database_name),
+            ("DATABASE_USERNAME", &mut self.database_username),
+            ("DATABASE_PASSWORD", self.database_password.expose_mut()),
+        ];
+
+        for (suffix, field) in fields {
+            if let Some(value) = lookup(&format!("{}{}", prefix, suffix))? {
+                // Plain assignment would free the old secret without wiping it
+                field.zeroize();
+                *field = value;
+            }
+        }
+
+        // Typed fields: an empty override unsets the field, like it empties
+        // string fields
+        if let Some(port) = typed_override(prefix, &lookup, "DATABASE_PORT", |value| {
+            optional(value, |port| {
+                port.parse()
+                    .map_err(|_| format!("'{}' is not a valid port", port))
+            })
+        })? {
+            self.database_port = port;
+        }
+        if let Some(encryption) = typed_override(prefix, &lookup, "DATABASE_ENCRYPTION", |value| {
+            optional(value, str::parse)
+        })? {
+            self.database_encryption = encryption;
+        }
+        if let Some(trust_cert) = typed_override(prefix, &lookup, "DATABASE_TRUST_CERT", |value| {
+            optional(value, parse_bool)
+        })? {
+            self.database_trust_cert = trust_cert;
+        }
+        if let Some(read_only) =
+            typed_override(prefix, &lookup, "DATABASE_READ_ONLY_INTENT", |value| {
+                optional(value, parse_bool)
+            })?
+        {
+            self.database_read_only_intent = read_only;
+        }
+        if let Some(test_on_checkout) =
+            typed_override(prefix, &lookup, "DATABASE_POOL_TEST_ON_CHECKOUT", |value| {
+                optional(value, parse_bool)
+            })?
+        {
+            self.database_pool_test_on_checkout = test_on_checkout;
+        }
+        if let Some(allow) =
+            typed_override(prefix, &lookup, "EMAIL_ALLOW_EMPTY_RECIPIENTS", |value| {
+                optional(value, parse_bool)
+            })?
+        {
+            self.email_allow_empty_recipients = allow;
+        }
+        if let Some(sandboxed) = typed_override(prefix, &lookup, "EMAIL_SANDBOX_MODE", |value| {
+            optional(value, parse_bool)
+        })? {
+            self.email_sandbox_mode = sandboxed;
+        }
+        if let Some(port) = typed_override(prefix, &lookup, "SMTP_PORT", |value| {
+            optional(value, |port| {
+                port.parse()
+                    .map_err(|_| format!("'{}' is not a valid port", port))
+            })
+        })? {
+            self.smtp_port = port;
+        }
+        if let Some(start_tls) = typed_override(prefix, &lookup, "SMTP_USE_START_TLS", |value| {
+            optional(value, parse_bool)
+        })? {
+            self.smtp_use_start_tls = start_tls;
+        }
+        if let Some(args) = typed_override(prefix, &lookup, "EMAIL_CUSTOM_ARGS", |value| {
+            optional(value, |value| {
+                serde_json::from_str(value)
+                    .map_err(|e| format!("'{}' is not a JSON object: {}", value, e))
+            })
+        })? {
+            self.email_custom_args = args;
+        }
+        if let Some(format) = typed_override(prefix, &lookup, "LOG_WEBHOOK_FORMAT", |value| {
+            optional(value, str::parse)
+        })? {
+            self.log_webhook_format = format;
+        }
+        if let Some(level) = typed_override(prefix, &lookup, "LOG_WEBHOOK_MIN_LEVEL", |value| {
+            optional(value, str::parse)
+        })? {
+            self.log_webhook_min_level = level;
+        }
+        if let Some(auth_method) =
+            typed_override(prefix, &lookup, "DATABASE_AUTH_METHOD", |value| {
+                optional(value, str::parse)
+            })?
+        {
+            self.database_auth_method = auth_method;
+        }
+        for (suffix, field) in [
+            (
+                "DATABASE_CONNECTION_STRING",
+                &mut self.database_connection_string,
+            ),
+            ("SENDGRID_API_KEY", &mut self.sendgrid_api_key),
+            ("HTTP_PROXY_PASSWORD", &mut self.http_proxy_password),
+            ("SMTP_PASSWORD", &mut self.smtp_password),
+            ("LOG_WEBHOOK_AUTH_VALUE", &mut self.log_webhook_auth_value),
+        ] {
+            if let Some(secret) = typed_override(prefix, &lookup, suffix, |value| {
+                optional(value, |value| Ok(Secret::new(value)))
+            })? {
+                *field = secret;
+            }
+        }
+        for (suffix, field) in [
+            (
+                "DATABASE_CONNECT_TIMEOUT_SECONDS",
+                &mut self.database_connect_timeout_seconds,
+            ),
+            (
+                "DATABASE_COMMAND_TIMEOUT_SECONDS",
+                &mut self.database_command_timeout_seconds,
+            ),
+            (
+                "DATABASE_POOL_TIMEOUT_SECONDS",
+                &mut self.database_pool_timeout_seconds,
+            ),
+            (
+                "LOG_WEBHOOK_TIMEOUT_SECONDS",
+                &mut self.log_webhook_timeout_seconds,
+            ),
+            (
+                "HTTP_CONNECT_TIMEOUT_SECONDS",
+                &mut self.http_connect_timeout_seconds,
+            ),
+            (
+                "HTTP_REQUEST_TIMEOUT_SECONDS",
+                &mut self.http_request_timeout_seconds,
+            ),
+        ] {
+            if let Some(seconds) = typed_override(prefix, &lookup, suffix, |value| {
+                optional(value, |seconds| {
+                    seconds
+                        .parse()
+                        .map_err(|_| format!("'{}' is not a whole number of seconds", seconds))
+                })
+            })? {
+                *field = seconds;
+            }
+        }
+        for (suffix, field) in [
+            ("DATABASE_POOL_MAX_SIZE", &mut self.database_pool_max_size),
+            ("EMAIL_MAX_RETRIES", &mut self.email_max_retries),
+            ("EMAIL_MAX_PER_HOUR", &mut self.email_max_per_hour),
+            ("LOG_WEBHOOK_MAX_RETRIES", &mut self.log_webhook_max_retries),
+            ("LOG_WEBHOOK_BATCH_SIZE", &mut self.log_webhook_batch_size),
+            (
+                "EMAIL_RECIPIENT_SOFT_LIMIT",
+                &mut self.email_recipient_soft_limit,
+            ),
+            ("EMAIL_ASM_GROUP_ID", &mut self.email_asm_group_id),
+        ] {
+            if let Some(retries) = typed_override(prefix, &lookup, suffix, |value| {
+                optional(value, |retries| {
+                    retries
+                        .parse()
+                        .map_err(|_| format!("'{}' is not a whole number", retries))
+                })
+            })? {
+                *field = retries;
+            }
+        }
+        if let Some(milliseconds) =
+            typed_override(prefix, &lookup, "EMAIL_RETRY_BASE_MS", |value| {
+                optional(value, |milliseconds| {
+                    milliseconds.parse().map_err(|_| {
+                        format!("'{}' is not a whole number of milliseconds", milliseconds)
+                    })
+                })
+            })?
+        {
+            self.email_retry_base_ms = milliseconds;
+        }
+        for (suffix, field) in [
+            (
+                "DATABASE_FAILOVER_SERVER",
+                &mut self.database_failover_server,
+            ),
+            ("DATABASE_CA_CERT_PATH", &mut self.database_ca_cert_path),
+            ("LOG_WEBHOOK_URI", &mut self.log_webhook_uri),
+            ("LOG_WEBHOOK_AUTH_HEADER", &mut self.log_webhook_auth_header),
+            (
+                "LOG_WEBHOOK_MODULE_ALLOW_LIST",
+                &mut self.log_webhook_module_allow_list,
+            ),
+            ("EMAIL_FROM_NAME", &mut self.email_from_name),
+            ("EMAIL_FROM_ADDRESS", &mut self.email_from_address),
+            ("EMAIL_TO_ADDRESSES", &mut self.email_to_addresses),
+            ("APPLICATION_NAME", &mut self.application_name),
+            ("EMAIL_CC_ADDRESSES", &mut self.email_cc_addresses),
+            ("EMAIL_BCC_ADDRESSES", &mut self.email_bcc_addresses),
+            ("EMAIL_REPLY_TO_ADDRESS", &mut self.email_reply_to_address),
+            ("EMAIL_REPLY_TO_NAME", &mut self.email_reply_to_name),
+            ("SENDGRID_TEMPLATE_ID", &mut self.sendgrid_template_id),
+            ("SENDGRID_API_HOST", &mut self.sendgrid_api_host),
+            ("SMTP_HOST", &mut self.smtp_host),
+            ("SMTP_USERNAME", &mut self.smtp_username),
+            ("HTTP_PROXY_URI", &mut self.http_proxy_uri),
+            ("HTTP_PROXY_USERNAME", &mut self.http_proxy_username),
+            ("EMAIL_CATEGORIES", &mut self.email_categories),
+            (
+                "EMAIL_ASM_GROUPS_TO_DISPLAY",
+                &mut self.email_asm_groups_to_display,
+            ),
+        ] {
+            if let Some(value) = typed_override(prefix, &lookup, suffix, |value| {
+                optional(value, |value| Ok(value.to_string()))
+            })? {
+                *field = value;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads an env variable, treating an unset one as `None`.
+pub(crate) fn env_lookup(name: &str) -> Result<Option<String>, SettingsError> {
+    match env::var(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(SettingsError::InvalidEnvVar {
+            name: name.to_string(),
+        }),
+    }
+}
+
+fn typed_override<F, T>(
+    prefix: &str,
+    lookup: &F,
+    suffix: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, SettingsError>
+where
+    F: Fn(&str) -> Result<Option<String>, SettingsError>,
+{
+    let name = format!("{}{}", prefix, suffix);
+    match lookup(&name)? {
+        Some(value) => parse(value.trim())
+            .map(Some)
+            .map_err(|reason| SettingsError::InvalidOverride { name, reason }),
+        None => Ok(None),
+    }
+}
+
+fn optional<T>(
+    value: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    match value {
+        "" => Ok(None),
+        value => parse(value).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DatabaseEncryption;
+    use std::collections::HashMap;
+
+    fn settings() -> Settings {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_name("Test")
+            .email_from_address("test@example.com")
+            .email_to_addresses("user1@example.com")
+            .build()
+            .unwrap()
+    }
+
+    fn apply(settings: &mut Settings, vars: &[(&str, &str)]) {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        settings
+            .apply_overrides_from(|name| Ok(vars.get(name).cloned()))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_override_replaces_field() {
+        let mut settings = settings();
+        apply(
+            &mut settings,
+            &[("REPORTSETTINGS_DATABASE_SERVER", "staging-sql")],
+        );
+
+        assert_eq!(settings.database_server(), "staging-sql");
+        assert_eq!(settings.database_name(), "test_db");
+    }
+
+    #[test]
+    fn test_empty_override_sets_empty() {
+        let mut settings = settings();
+        apply(&mut settings, &[("REPORTSETTINGS_EMAIL_FROM_NAME", "")]);
+
+        assert_eq!(settings.email_from_name(), None);
+    }
+
+    #[test]
+    fn test_port_override_is_parsed() {
+        let mut settings = settings();
+        apply(&mut settings, &[("REPORTSETTINGS_DATABASE_PORT", "14330")]);
+        assert_eq!(settings.database_port(), Some(14330));
+
+        let err = settings
+            .apply_overrides_from(|_| Ok(Some("fourteen".to_string())))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid override REPORTSETTINGS_DATABASE_PORT: 'fourteen' is not a valid port"
+        );
+    }
+
+    #[test]
+    fn test_encryption_overrides_are_parsed() {
+        let mut settings = settings();
+        apply(
+            &mut settings,
+            &[
+                ("REPORTSETTINGS_DATABASE_ENCRYPTION", "required"),
+                ("REPORTSETTINGS_DATABASE_TRUST_CERT", "TRUE"),
+            ],
+        );
+
+        assert_eq!(settings.database_encryption(), DatabaseEncryption::Required);
+        assert!(settings.database_trust_cert());
+    }
+
+    #[test]
+    fn test_no_overrides_leaves_settings_alone() {
+        let mut settings = settings();
+        apply(&mut settings, &[("DATABASE_SERVER", "not-prefixed")]);
+
+        assert_eq!(settings.database_server(), "localhost");
+    }
+}
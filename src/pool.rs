@@ -0,0 +1,207 @@
+use crate::connection::connect;
+use crate::{Settings, SettingsError};
+use deadpool::managed::{Manager, Metrics, Pool, RecycleResult};
+use deadpool::Runtime;
+use ssql::prelude::tiberius::{error::Error, Client, Config};
+use ssql::prelude::{Compat, TcpStream};
+use std::time::Duration;
+
+/// Pool size when `DatabasePoolMaxSize` is not set.
+pub const DEFAULT_DATABASE_POOL_MAX_SIZE: u32 = 10;
+
+/// A tiberius client as handed out by [`SqlPool`].
+pub type SqlClient = Client<Compat<TcpStream>>;
+
+/// A deadpool pool of tiberius clients, built by [`Settings::build_pool`].
+pub type SqlPool = Pool<SqlConnectionManager>;
+
+/// Opens the connections of a [`SqlPool`]. With `DatabasePoolTestOnCheckout`
+/// set, an idle connection runs `SELECT 1` before it is handed out again, and
+/// is replaced by a new one if that fails.
+pub struct SqlConnectionManager {
+    config: Config,
+    test_on_checkout: bool,
+}
+
+impl SqlConnectionManager {
+    pub fn new(config: Config, test_on_checkout: bool) -> SqlConnectionManager {
+        SqlConnectionManager {
+            config,
+            test_on_checkout,
+        }
+    }
+
+    pub fn test_on_checkout(&self) -> bool {
+        self.test_on_checkout
+    }
+}
+
+impl Manager for SqlConnectionManager {
+    type Type = SqlClient;
+    type Error = Error;
+
+    async fn create(&self) -> Result<SqlClient, Error> {
+        connect(self.config.clone()).await
+    }
+
+    async fn recycle(&self, client: &mut SqlClient, _: &Metrics) -> RecycleResult<Error> {
+        if self.test_on_checkout {
+            client.simple_query("SELECT 1").await?.into_row().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Settings {
+    /// `DatabasePoolMaxSize`, or [`DEFAULT_DATABASE_POOL_MAX_SIZE`] if unset.
+    pub fn database_pool_max_size(&self) -> u32 {
+        self.database_pool_max_size
+            .unwrap_or(DEFAULT_DATABASE_POOL_MAX_SIZE)
+    }
+
+    /// How long [`SqlPool::get`] waits for a free connection when the pool is
+    /// at its maximum size, or `None` to wait until one is returned.
+    pub fn database_pool_timeout(&self) -> Option<Duration> {
+        self.database_pool_timeout_seconds.map(Duration::from_secs)
+    }
+
+    /// `DatabasePoolTestOnCheckout`: whether reused connections are checked
+    /// with `SELECT 1` first.
+    pub fn database_pool_test_on_checkout(&self) -> bool {
+        self.database_pool_test_on_checkout.unwrap_or_default()
+    }
+
+    /// Builds a pool of connections to the default database. Connections are
+    /// opened on demand, so this does not touch the network; a bad server or
+    /// login shows up as an error from [`SqlPool::get`].
+    ///
+    /// `DatabaseConnectTimeoutSeconds` bounds opening a connection and
+    /// `DatabaseCommandTimeoutSeconds` the checkout query. The pool needs a
+    /// tokio runtime with the time driver enabled.
+    pub fn build_pool(&self) -> Result<SqlPool, SettingsError> {
+        let manager = SqlConnectionManager::new(
+            self.try_get_sql_settings()?,
+            self.database_pool_test_on_checkout(),
+        );
+
+        Ok(Pool::builder(manager)
+            .max_size(self.database_pool_max_size() as usize)
+            .wait_timeout(self.database_pool_timeout())
+            .create_timeout(self.connect_timeout())
+            .recycle_timeout(self.command_timeout())
+            .runtime(Runtime::Tokio1)
+            .build()
+            .expect("timeouts only need a runtime"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FieldError, SettingsBuilder};
+    use std::env;
+
+    fn settings() -> SettingsBuilder {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+    }
+
+    #[test]
+    fn test_default_pool_config() {
+        let pool = settings().build().unwrap().build_pool().unwrap();
+
+        assert_eq!(pool.status().max_size, 10);
+        assert_eq!(pool.status().size, 0);
+        let timeouts = pool.timeouts();
+        assert_eq!(timeouts.wait, None);
+        assert_eq!(timeouts.create, None);
+        assert_eq!(timeouts.recycle, None);
+        assert!(!pool.manager().test_on_checkout());
+    }
+
+    #[test]
+    fn test_pool_config_from_settings() {
+        let settings = settings()
+            .database_pool_max_size(4)
+            .database_pool_timeout_seconds(15)
+            .database_pool_test_on_checkout(true)
+            .database_connect_timeout_seconds(5)
+            .database_command_timeout_seconds(30)
+            .build()
+            .unwrap();
+
+        let pool = settings.build_pool().unwrap();
+
+        assert_eq!(pool.status().max_size, 4);
+        let timeouts = pool.timeouts();
+        assert_eq!(timeouts.wait, Some(Duration::from_secs(15)));
+        assert_eq!(timeouts.create, Some(Duration::from_secs(5)));
+        assert_eq!(timeouts.recycle, Some(Duration::from_secs(30)));
+        assert!(pool.manager().test_on_checkout());
+    }
+
+    #[test]
+    fn test_pool_fields_are_validated() {
+        let settings = settings()
+            .database_pool_max_size(0)
+            .database_pool_timeout_seconds(0)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            settings.validate(),
+            Err(vec![
+                FieldError::new(
+                    "DatabasePoolTimeoutSeconds",
+                    "must be between 1 and 600 seconds, got 0"
+                ),
+                FieldError::new("DatabasePoolMaxSize", "must be at least 1 connection"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_invalid_settings_fail_before_building() {
+        let settings = settings()
+            .database_server("localhost,14330")
+            .database_port(1433)
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            settings.build_pool(),
+            Err(SettingsError::Validation(_))
+        ));
+    }
+
+    // Set REPORTSETTINGS_TEST_SQL_BLOB to a settings blob for a reachable
+    // server to run this against it
+    #[tokio::test]
+    async fn test_live_pool_reuses_checked_connection() {
+        let blob = match env::var("REPORTSETTINGS_TEST_SQL_BLOB") {
+            Ok(blob) => blob,
+            Err(_) => return,
+        };
+        let mut settings = Settings::from_json_str(&blob).unwrap();
+        settings.database_pool_max_size = Some(1);
+        settings.database_pool_test_on_checkout = Some(true);
+        let pool = settings.build_pool().unwrap();
+
+        for _ in 0..2 {
+            let mut client = pool.get().await.unwrap();
+            let row = client
+                .simple_query("SELECT 1")
+                .await
+                .unwrap()
+                .into_row()
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(row.get::<i32, _>(0), Some(1));
+        }
+        assert_eq!(pool.status().size, 1);
+    }
+}
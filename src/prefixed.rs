@@ -0,0 +1,37 @@
+use crate::overrides::env_lookup;
+use crate::profile::Profile;
+use crate::{blob_source, Settings, SettingsError, DEFAULT_BLOB_VAR};
+use std::collections::BTreeMap;
+
+impl Settings {
+    /// Like [`Settings::get_settings`], for one of several jobs sharing a
+    /// process, each with its own settings. For the prefix `InvoiceReport`
+    /// (`InvoiceReport_` works too), the blob is read from
+    /// `InvoiceReport_SecretBlob`, or the file in `InvoiceReport_SecretBlobPath`,
+    /// and the per-field overrides from `INVOICEREPORT_DATABASE_SERVER` and the
+    /// like. The unprefixed `SecretBlob` and `REPORTSETTINGS_<FIELD>`
+    /// variables are not read, so one job's settings never leak into another's.
+    ///
+    /// Unless the blob or an override sets `ApplicationName`, it is the prefix,
+    /// so that DBAs can tell the jobs' connections apart. The prefix may only
+    /// hold ASCII letters, digits and underscores.
+    pub fn get_settings_with_prefix(prefix: &str) -> Result<Settings, SettingsError> {
+        let name = prefix.strip_suffix('_').unwrap_or(prefix);
+        if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+            return Err(SettingsError::InvalidSettingsPrefix {
+                prefix: prefix.to_string(),
+            });
+        }
+
+        let var = format!("{}_{}", name, DEFAULT_BLOB_VAR);
+        let (mut settings, _) = Settings::load_blob(&var, Profile::FromEnv)?;
+        let overrides = format!("{}_", name.to_ascii_uppercase());
+        settings.apply_overrides_with_prefix(&overrides, env_lookup)?;
+        if settings.application_name.is_none() {
+            settings.application_name = Some(name.to_string());
+        }
+        let settings = settings.finish_load()?;
+        settings.record_load(vec![blob_source(&var)], BTreeMap::new());
+        Ok(settings)
+    }
+}
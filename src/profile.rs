@@ -0,0 +1,216 @@
+use crate::{Settings, SettingsError, DEFAULT_BLOB_VAR};
+use serde_json::Value;
+use std::env;
+
+/// The env variable naming the profile to load from a blob with profiles.
+pub const PROFILE_VAR: &str = "Environment";
+
+/// The profile every other profile is merged over, and the one loaded when
+/// `Environment` is not set.
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Which profile to load from a blob with profiles.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Profile<'a> {
+    /// The profile named by `Environment`; blobs without profiles load as they are.
+    FromEnv,
+    /// This profile; blobs without profiles are an error.
+    Named(&'a str),
+}
+
+impl Settings {
+    /// Like [`Settings::get_settings`], but loads the profile `name` instead of
+    /// the one named by `Environment`.
+    ///
+    /// A JSON blob has profiles when its top level has a `Default` key, e.g.
+    /// `{"Default": {...}, "Staging": {...}}`. The chosen profile is merged over
+    /// `Default`, so it only needs the values that differ; in nested objects
+    /// such as `Databases` the merge goes key by key. Fails with
+    /// [`SettingsError::UnknownProfile`] if the blob has no such profile, or no
+    /// profiles at all.
+    pub fn get_settings_for_profile(name: &str) -> Result<Settings, SettingsError> {
+        let (mut settings, _) = Settings::load_blob(DEFAULT_BLOB_VAR, Profile::Named(name))?;
+        settings.apply_env_overrides()?;
+        settings.finish_load()
+    }
+}
+
+/// Given a parsed JSON blob with profiles, returns the chosen profile merged
+/// over `Default`. Returns `None` for blobs without profiles (and blobs that
+/// didn't parse), which are then loaded as they are.
+pub(crate) fn select(
+    blob: Option<Value>,
+    profile: Profile,
+) -> Result<Option<Value>, SettingsError> {
+    let profiles = match blob {
+        Some(Value::Object(profiles)) if profiles.contains_key(DEFAULT_PROFILE) => profiles,
+        Some(_) => {
+            without_profiles(profile)?;
+            return Ok(None);
+        }
+        None => return Ok(None),
+    };
+
+    let name = profile_name(profile)?;
+    let mut merged = profiles[DEFAULT_PROFILE].clone();
+    match profiles.get(&name) {
+        Some(_) if name == DEFAULT_PROFILE => {}
+        Some(overrides) => merge(&mut merged, overrides.clone()),
+        None => return Err(unknown_profile(name, profiles.keys().cloned().collect())),
+    }
+    Ok(Some(merged))
+}
+
+/// Blobs without profiles load as they are, unless a profile was asked for by name.
+pub(crate) fn without_profiles(profile: Profile) -> Result<(), SettingsError> {
+    match profile {
+        Profile::FromEnv => Ok(()),
+        Profile::Named(name) => Err(unknown_profile(name.to_string(), Vec::new())),
+    }
+}
+
+fn profile_name(profile: Profile) -> Result<String, SettingsError> {
+    match profile {
+        Profile::Named(name) => Ok(name.to_string()),
+        Profile::FromEnv => match env::var(PROFILE_VAR) {
+            Ok(name) if !name.trim().is_empty() => Ok(name.trim().to_string()),
+            Ok(_) | Err(env::VarError::NotPresent) => Ok(DEFAULT_PROFILE.to_string()),
+            Err(env::VarError::NotUnicode(_)) => Err(SettingsError::InvalidEnvVar {
+                name: PROFILE_VAR.to_string(),
+            }),
+        },
+    }
+}
+
+fn unknown_profile(name: String, available: Vec<String>) -> SettingsError {
+    SettingsError::UnknownProfile { name, available }
+}
+
+// Objects are merged key by key; any other profile value, null included,
+// replaces the default one
+pub(crate) fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::BlobFormat;
+
+    const BLOB: &str = r#"{
+        "Default": {
+            "DatabaseServer": "localhost",
+            "DatabaseName": "reports",
+            "DatabaseUsername": "admin",
+            "DatabasePassword": "password123",
+            "EmailFromAddress": "reports@example.com",
+            "Databases": {
+                "warehouse": {
+                    "Server": "wh-dev",
+                    "Name": "warehouse",
+                    "Username": "reader",
+                    "Password": "secret"
+                }
+            }
+        },
+        "Staging": {
+            "DatabaseServer": "staging-sql",
+            "Databases": {
+                "warehouse": { "Server": "wh-staging" }
+            }
+        },
+        "Production": {
+            "DatabaseServer": "prod-sql",
+            "EmailFromAddress": null
+        }
+    }"#;
+
+    fn parse(profile: Profile) -> Result<Settings, SettingsError> {
+        Ok(BlobFormat::Json.parse_reporting_unknown(BLOB, profile)?.0)
+    }
+
+    #[test]
+    fn test_profile_value_wins() {
+        let settings = parse(Profile::Named("Staging")).unwrap();
+
+        assert_eq!(settings.database_server(), "staging-sql");
+        assert_eq!(settings.databases["warehouse"].server, "wh-staging");
+
+        let production = parse(Profile::Named("Production")).unwrap();
+        assert_eq!(production.database_server(), "prod-sql");
+        assert_eq!(production.email_from_address(), None);
+    }
+
+    #[test]
+    fn test_missing_profile_key_falls_through() {
+        let settings = parse(Profile::Named("Staging")).unwrap();
+
+        assert_eq!(settings.database_name(), "reports");
+        assert_eq!(settings.database_username(), "admin");
+        assert_eq!(settings.email_from_address(), Some("reports@example.com"));
+        assert_eq!(settings.databases["warehouse"].name, "warehouse");
+    }
+
+    #[test]
+    fn test_default_profile_alone() {
+        let settings = parse(Profile::Named("Default")).unwrap();
+
+        assert_eq!(settings.database_server(), "localhost");
+        assert_eq!(settings.databases["warehouse"].server, "wh-dev");
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_available() {
+        let err = parse(Profile::Named("Prod")).unwrap_err();
+
+        assert!(matches!(
+            &err,
+            SettingsError::UnknownProfile { name, available }
+                if name == "Prod" && available == &["Default", "Production", "Staging"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Unknown profile 'Prod', expected one of: Default, Production, Staging"
+        );
+    }
+
+    #[test]
+    fn test_blob_without_profiles() {
+        let flat = r#"{"DatabaseServer": "localhost", "DatabaseName": "reports"}"#;
+
+        let (settings, _) = BlobFormat::Json
+            .parse_reporting_unknown(flat, Profile::FromEnv)
+            .unwrap();
+        assert_eq!(settings.database_server(), "localhost");
+
+        let err = BlobFormat::Json
+            .parse_reporting_unknown(flat, Profile::Named("Staging"))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown profile 'Staging': the blob has no profiles"
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_are_reported_within_the_profile() {
+        let blob = BLOB.replace(r#""prod-sql","#, r#""prod-sql", "DatabaseSever": "x","#);
+
+        let (_, unknown) = BlobFormat::Json
+            .parse_reporting_unknown(&blob, Profile::Named("Production"))
+            .unwrap();
+        assert_eq!(unknown, ["DatabaseSever"]);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{Settings, SettingsError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+/// The token bucket behind `EmailMaxPerHour`. Clones share the bucket, so
+/// every clone of the settings draws from the same hourly allowance.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EmailRateLimiter {
+    bucket: Arc<Mutex<Option<Bucket>>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    per_hour: u32,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl EmailRateLimiter {
+    // Takes one send from a bucket that starts full and refills at `per_hour`
+    // an hour, or says how long until the next one is allowed. A different
+    // limit, e.g. from an env override, starts a new bucket
+    fn acquire(&self, per_hour: u32, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        if bucket
+            .as_ref()
+            .is_some_and(|bucket| bucket.per_hour != per_hour)
+        {
+            *bucket = None;
+        }
+        let bucket = bucket.get_or_insert_with(|| Bucket {
+            per_hour,
+            tokens: f64::from(per_hour),
+            refilled: now,
+        });
+        let rate = f64::from(per_hour) / HOUR.as_secs_f64();
+        let elapsed = now.saturating_duration_since(bucket.refilled);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(f64::from(per_hour));
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+impl Settings {
+    /// `EmailMaxPerHour`, or `None` if sends are not limited.
+    pub fn email_max_per_hour(&self) -> Option<u32> {
+        self.email_max_per_hour
+    }
+
+    // Counts one email against `EmailMaxPerHour`; 0 is rejected by `validate`
+    // and treated as unset here
+    pub(crate) fn take_email_send(&self) -> Result<(), SettingsError> {
+        let Some(per_hour) = self.email_max_per_hour.filter(|&limit| limit > 0) else {
+            return Ok(());
+        };
+        self.email_rate_limiter
+            .acquire(per_hour, Instant::now())
+            .map_err(|retry_after| SettingsError::RateLimited { retry_after })
+    }
+
+    // These settings drawing from `previous`'s allowance, if the limit is the
+    // same, so that a reload doesn't hand out a fresh hour's worth of sends
+    #[cfg(feature = "reload")]
+    pub(crate) fn keeping_email_rate_limiter(mut self, previous: &Settings) -> Settings {
+        if self.email_max_per_hour == previous.email_max_per_hour {
+            self.email_rate_limiter = previous.email_rate_limiter.clone();
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_over_the_hour() {
+        let limiter = EmailRateLimiter::default();
+        let start = Instant::now();
+
+        assert_eq!(limiter.acquire(4, start), Ok(()));
+        assert_eq!(limiter.acquire(4, start), Ok(()));
+        assert_eq!(limiter.acquire(4, start), Ok(()));
+        assert_eq!(limiter.acquire(4, start), Ok(()));
+        assert_eq!(limiter.acquire(4, start), Err(Duration::from_secs(900)));
+        // A quarter of an hour refills one send
+        assert_eq!(limiter.acquire(4, start + Duration::from_secs(900)), Ok(()));
+        assert!(limiter
+            .acquire(4, start + Duration::from_secs(900))
+            .is_err());
+        // A new limit starts full
+        assert_eq!(limiter.acquire(1, start + Duration::from_secs(900)), Ok(()));
+    }
+
+    #[test]
+    fn test_zero_limit_is_rejected() {
+        let settings = Settings::for_tests().with_email_max_per_hour(0);
+
+        assert_eq!(
+            settings.validate().unwrap_err()[0].to_string(),
+            "EmailMaxPerHour: must be at least 1 email"
+        );
+        assert!(settings.take_email_send().is_ok());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::address::{parse_address_list, split_address_list, EmailAddress};
+use crate::{FieldError, Settings, SettingsError};
+use std::collections::HashSet;
+
+/// The most recipients SendGrid accepts on one message, across To, Cc and Bcc.
+pub const SENDGRID_MAX_RECIPIENTS: usize = 1000;
+
+/// The recipient count above which [`Settings::warnings`] reports a message,
+/// unless `EmailRecipientSoftLimit` sets another.
+pub const DEFAULT_EMAIL_RECIPIENT_SOFT_LIMIT: u32 = 100;
+
+impl Settings {
+    /// The `EmailToAddresses` recipients, each a bare address or `Name <address>`.
+    /// Entries are trimmed and empty ones are skipped; an invalid entry fails the
+    /// whole list, naming the entry. Empty if `EmailToAddresses` is not set.
+    pub fn email_to_recipients(&self) -> Result<Vec<EmailAddress>, SettingsError> {
+        parse_address_list(
+            "EmailToAddresses",
+            self.email_to_addresses().unwrap_or_default(),
+        )
+    }
+
+    /// The `EmailCcAddresses` recipients, parsed like `EmailToAddresses`. Addresses
+    /// already in `EmailToAddresses` are left out, as SendGrid rejects a message
+    /// that lists an address twice.
+    pub fn email_cc_recipients(&self) -> Result<Vec<EmailAddress>, SettingsError> {
+        recipients_except(
+            "EmailCcAddresses",
+            self.email_cc_addresses(),
+            &[self.email_to_addresses().unwrap_or_default()],
+        )
+    }
+
+    /// The `EmailBccAddresses` recipients, leaving out addresses already in
+    /// `EmailToAddresses` or `EmailCcAddresses`.
+    pub fn email_bcc_recipients(&self) -> Result<Vec<EmailAddress>, SettingsError> {
+        recipients_except(
+            "EmailBccAddresses",
+            self.email_bcc_addresses(),
+            &[
+                self.email_to_addresses().unwrap_or_default(),
+                self.email_cc_addresses(),
+            ],
+        )
+    }
+
+    /// The number of distinct valid addresses across `EmailToAddresses`,
+    /// `EmailCcAddresses` and `EmailBccAddresses`, which is what a message
+    /// built from these settings is sent to.
+    pub fn email_recipient_count(&self) -> usize {
+        self.recipient_fields()
+            .iter()
+            .flat_map(|(_, raw)| split_address_list(raw))
+            .filter_map(|(_, entry)| EmailAddress::parse(entry))
+            .map(|address| address.address().to_lowercase())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    // Invalid entries are reported by `validate` itself
+    pub(crate) fn recipient_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        let count = self.email_recipient_count();
+        if count > SENDGRID_MAX_RECIPIENTS {
+            errors.push(FieldError::new(
+                "EmailToAddresses",
+                format!(
+                    "{} recipients across To, Cc and Bcc, but SendGrid accepts at most {}",
+                    count, SENDGRID_MAX_RECIPIENTS
+                ),
+            ));
+        }
+
+        let to: HashSet<String> = split_address_list(self.email_to_addresses().unwrap_or_default())
+            .filter_map(|(_, entry)| EmailAddress::parse(entry))
+            .map(|address| address.address().to_lowercase())
+            .collect();
+        for (position, entry) in split_address_list(self.email_cc_addresses()) {
+            if let Some(address) = EmailAddress::parse(entry) {
+                if to.contains(&address.address().to_lowercase()) {
+                    errors.push(FieldError::new(
+                        "EmailCcAddresses",
+                        format!(
+                            "'{}' (entry {}) is already in EmailToAddresses",
+                            entry, position
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if self.email_recipient_soft_limit == Some(0) {
+            errors.push(FieldError::new(
+                "EmailRecipientSoftLimit",
+                "must be at least 1 recipient",
+            ));
+        }
+        errors
+    }
+
+    pub(crate) fn recipient_warnings(&self) -> Vec<FieldError> {
+        let count = self.email_recipient_count();
+        let limit = self.email_recipient_soft_limit();
+        // Past the hard limit, `validate` reports an error instead
+        if count > limit as usize && count <= SENDGRID_MAX_RECIPIENTS {
+            vec![FieldError::new(
+                "EmailToAddresses",
+                format!(
+                    "{} recipients across To, Cc and Bcc is more than EmailRecipientSoftLimit ({})",
+                    count, limit
+                ),
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub(crate) fn recipient_fields(&self) -> [(&'static str, &str); 3] {
+        [
+            (
+                "EmailToAddresses",
+                self.email_to_addresses().unwrap_or_default(),
+            ),
+            ("EmailCcAddresses", self.email_cc_addresses()),
+            ("EmailBccAddresses", self.email_bcc_addresses()),
+        ]
+    }
+}
+
+// Addresses compare case-insensitively, like duplicates within a field.
+// Invalid entries in the earlier fields are reported by their own getters
+fn recipients_except(
+    field: &'static str,
+    raw: &str,
+    earlier: &[&str],
+) -> Result<Vec<EmailAddress>, SettingsError> {
+    let taken: HashSet<String> = earlier
+        .iter()
+        .flat_map(|raw| split_address_list(raw))
+        .filter_map(|(_, entry)| EmailAddress::parse(entry))
+        .map(|address| address.address().to_lowercase())
+        .collect();
+
+    Ok(parse_address_list(field, raw)?
+        .into_iter()
+        .filter(|address| !taken.contains(&address.address().to_lowercase()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipients_without_sendgrid() {
+        let settings = Settings::from_json_str(
+            r#"{
+                "DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailToAddresses": "ops@corp.com, Jane <jane@corp.com>",
+                "EmailCcAddresses": "finance@corp.com; JANE@corp.com",
+                "EmailBccAddresses": "audit@corp.com, ops@corp.com"
+            }"#,
+        )
+        .unwrap();
+
+        let to: Vec<String> = settings
+            .email_to_recipients()
+            .unwrap()
+            .iter()
+            .map(EmailAddress::to_string)
+            .collect();
+        let cc = settings.email_cc_recipients().unwrap();
+        let bcc = settings.email_bcc_recipients().unwrap();
+
+        assert_eq!(to, ["ops@corp.com", "Jane <jane@corp.com>"]);
+        assert_eq!(cc, ["finance@corp.com".parse().unwrap()]);
+        assert_eq!(bcc, ["audit@corp.com".parse().unwrap()]);
+    }
+
+    #[test]
+    fn test_invalid_recipient_fails_the_list() {
+        let settings = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailToAddresses": "ops@corp.com", "EmailCcAddresses": "ok@corp.com, nope"}"#,
+        )
+        .unwrap();
+
+        let err = settings.email_cc_recipients().unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "EmailCcAddresses: 'nope' (entry 2) is not a valid email address"
+        );
+    }
+
+    fn builder() -> crate::SettingsBuilder {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+    }
+
+    fn addresses(range: std::ops::Range<usize>) -> String {
+        range
+            .map(|i| format!("user{}@example.com", i))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    #[test]
+    fn test_recipient_count_limits() {
+        let at_limit = builder()
+            .email_to_addresses(addresses(0..600))
+            .email_cc_addresses(addresses(600..900))
+            .email_bcc_addresses(format!("{}, USER0@example.com", addresses(900..1000)))
+            .build()
+            .unwrap();
+        let over = builder()
+            .email_to_addresses(addresses(0..600))
+            .email_bcc_addresses(addresses(600..1001))
+            .build()
+            .unwrap();
+
+        assert_eq!(at_limit.email_recipient_count(), 1000);
+        assert_eq!(at_limit.recipient_errors(), []);
+        assert_eq!(over.email_recipient_count(), 1001);
+        assert_eq!(
+            over.recipient_errors(),
+            [FieldError::new(
+                "EmailToAddresses",
+                "1001 recipients across To, Cc and Bcc, but SendGrid accepts at most 1000"
+            )]
+        );
+        assert!(over.recipient_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_soft_limit_warns() {
+        let settings = builder()
+            .email_to_addresses(addresses(0..101))
+            .build()
+            .unwrap();
+        let raised = builder()
+            .email_to_addresses(addresses(0..101))
+            .email_recipient_soft_limit(200)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            settings.recipient_warnings(),
+            [FieldError::new(
+                "EmailToAddresses",
+                "101 recipients across To, Cc and Bcc is more than EmailRecipientSoftLimit (100)"
+            )]
+        );
+        assert!(settings.recipient_errors().is_empty());
+        assert!(raised.recipient_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_address_in_to_and_cc_is_rejected() {
+        let settings = Settings::from_json_str(
+            r#"{
+                "DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailToAddresses": "ops@corp.com, jane@corp.com",
+                "EmailCcAddresses": "finance@corp.com, Jane <JANE@corp.com>",
+                "EmailRecipientSoftLimit": 0
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings.recipient_errors(),
+            [
+                FieldError::new(
+                    "EmailCcAddresses",
+                    "'Jane <JANE@corp.com>' (entry 2) is already in EmailToAddresses"
+                ),
+                FieldError::new("EmailRecipientSoftLimit", "must be at least 1 recipient"),
+            ]
+        );
+    }
+}
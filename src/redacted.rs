@@ -0,0 +1,177 @@
+use crate::{redact_tail, Settings};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use zeroize::Zeroize;
+
+/// The effective settings with the secrets masked, for diagnostics endpoints.
+/// Built by [`Settings::redacted`].
+///
+/// It serializes like the blob, with PascalCase keys and unset fields left
+/// out, except that `DatabasePassword`, `DatabaseConnectionString`,
+/// `HttpProxyPassword`, `LogWebhookAuthValue`, `SmtpPassword` and the `Databases` passwords
+/// become `"***"` and `SendgridApiKey` keeps only its last four characters,
+/// as in the settings' `Debug` output. With the `mssql` feature it also has the resolved
+/// `SqlAddress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RedactedSettings {
+    #[serde(flatten)]
+    fields: Map<String, Value>,
+    /// The `host:port` the default database is reached at; see
+    /// [`Settings::get_sql_settings`].
+    #[cfg(feature = "mssql")]
+    sql_address: String,
+}
+
+impl RedactedSettings {
+    /// A field as it is serialized, by its PascalCase blob key.
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.fields.get(field)
+    }
+
+    /// The `host:port` the default database is reached at.
+    #[cfg(feature = "mssql")]
+    pub fn sql_address(&self) -> &str {
+        &self.sql_address
+    }
+}
+
+impl Settings {
+    /// These settings with the secrets masked; see [`RedactedSettings`].
+    pub fn redacted(&self) -> RedactedSettings {
+        let mut fields = match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields,
+            _ => unreachable!("settings serialize to a JSON object"),
+        };
+
+        if let Some(password) = fields.get_mut("DatabasePassword") {
+            mask(password, "***".to_string());
+        }
+        if let Some(connection_string) = fields.get_mut("DatabaseConnectionString") {
+            mask(connection_string, "***".to_string());
+        }
+        for field in ["HttpProxyPassword", "LogWebhookAuthValue", "SmtpPassword"] {
+            if let Some(secret) = fields.get_mut(field) {
+                mask(secret, "***".to_string());
+            }
+        }
+        if let (Some(api_key), Some(secret)) = (
+            fields.get_mut("SendgridApiKey"),
+            self.sendgrid_api_key.as_ref(),
+        ) {
+            mask(api_key, redact_tail(secret.inspect()));
+        }
+        if let Some(Value::Object(targets)) = fields.get_mut("Databases") {
+            for target in targets.values_mut() {
+                if let Some(password) = target.get_mut("Password") {
+                    mask(password, "***".to_string());
+                }
+            }
+        }
+
+        RedactedSettings {
+            fields,
+            #[cfg(feature = "mssql")]
+            sql_address: self.get_sql_settings().get_addr().to_string(),
+        }
+    }
+
+    /// [`Settings::redacted`] as a JSON string.
+    pub fn to_redacted_json(&self) -> String {
+        serde_json::to_string(&self.redacted()).expect("redacted settings serialize")
+    }
+}
+
+// The serialized copy of a secret isn't wiped on drop like the `Secret` itself,
+// so it is wiped before being replaced
+pub(crate) fn mask(value: &mut Value, masked: String) {
+    if let Value::String(secret) = value {
+        secret.zeroize();
+    }
+    *value = Value::String(masked);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOB: &str = r#"{
+        "DatabaseServer": "sql.example.com,14330",
+        "DatabaseName": "reports",
+        "DatabaseUsername": "admin",
+        "DatabasePassword": "hunter2-password",
+        "Databases": {
+            "warehouse": {
+                "Server": "wh-sql",
+                "Name": "warehouse",
+                "Username": "reader",
+                "Password": "warehouse-password"
+            }
+        },
+        "SendgridApiKey": "SG.abcdefghijklmnop.wxyz",
+        "EmailFromAddress": "reports@example.com",
+        "LogWebhookAuthHeader": "x-functions-key",
+        "LogWebhookAuthValue": "function-key-value",
+        "SmtpHost": "smtp.example.com",
+        "SmtpUsername": "svc_mail",
+        "SmtpPassword": "smtp-relay-password"
+    }"#;
+
+    #[test]
+    fn test_secrets_do_not_appear() {
+        let settings = Settings::from_json_str(BLOB).unwrap();
+
+        let json = settings.to_redacted_json();
+
+        for secret in [
+            "hunter2-password",
+            "warehouse-password",
+            "SG.abcdefghijklmnop",
+            "function-key-value",
+            "smtp-relay-password",
+        ] {
+            assert!(!json.contains(secret), "{}", json);
+        }
+        let redacted = settings.redacted();
+        assert_eq!(redacted.get("DatabasePassword").unwrap(), "***");
+        assert_eq!(redacted.get("SendgridApiKey").unwrap(), "***wxyz");
+        assert_eq!(redacted.get("LogWebhookAuthValue").unwrap(), "***");
+        assert_eq!(redacted.get("SmtpPassword").unwrap(), "***");
+        assert_eq!(
+            redacted.get("Databases").unwrap()["warehouse"]["Password"],
+            "***"
+        );
+    }
+
+    #[test]
+    fn test_other_fields_pass_through() {
+        let settings = Settings::from_json_str(BLOB).unwrap();
+
+        let redacted: Value = serde_json::from_str(&settings.to_redacted_json()).unwrap();
+
+        assert_eq!(redacted["DatabaseServer"], "sql.example.com,14330");
+        assert_eq!(redacted["EmailFromAddress"], "reports@example.com");
+        assert_eq!(redacted["Databases"]["warehouse"]["Username"], "reader");
+        assert!(redacted.get("LogWebhookUri").is_none());
+        #[cfg(feature = "mssql")]
+        assert_eq!(redacted["SqlAddress"], "sql.example.com:14330");
+    }
+
+    #[test]
+    fn test_connection_string_is_masked() {
+        let settings = Settings::from_json_str(
+            r#"{"DatabaseConnectionString": "Server=sql;Database=reports;User Id=admin;Password=hunter2-password"}"#,
+        )
+        .unwrap();
+
+        let json = settings.to_redacted_json();
+
+        assert!(!json.contains("hunter2-password"), "{}", json);
+        assert_eq!(
+            settings.redacted().get("DatabaseConnectionString").unwrap(),
+            "***"
+        );
+        #[cfg(feature = "mssql")]
+        assert_eq!(settings.redacted().sql_address(), "sql:1433");
+    }
+}
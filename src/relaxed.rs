@@ -0,0 +1,109 @@
+use crate::audit::{self, SettingsEvent};
+use crate::format::{self, BlobFormat};
+use crate::profile::Profile;
+use crate::{Settings, SettingsError};
+use serde_json::Value;
+
+impl Settings {
+    /// Parses a JSON5 blob, JSON with the comments, trailing commas, unquoted
+    /// keys and single-quoted strings people tend to add when editing a blob
+    /// by hand. Otherwise it is read like [`Settings::from_json_str`] reads
+    /// one, profiles and nested groups included.
+    ///
+    /// `get_settings` reads blobs this way when `SecretBlobFormat` is `json5`,
+    /// as does a [`crate::SettingsLoader`] for a `.json5` file. Strict JSON is
+    /// tried first either way, and a blob that needed JSON5 is reported to the
+    /// [`crate::AuditSink`] as a [`SettingsEvent::ParsedAsJson5`].
+    pub fn from_json5(blob: &str) -> Result<Settings, SettingsError> {
+        BlobFormat::Json5.parse(blob)
+    }
+}
+
+pub(crate) fn parse(
+    blob: &str,
+    profile: Profile,
+) -> Result<(Settings, Vec<String>), SettingsError> {
+    let json = match BlobFormat::Json.parse_reporting_unknown(blob, profile) {
+        Err(SettingsError::InvalidJson(json)) if json.is_syntax() || json.is_eof() => json,
+        result => return result,
+    };
+    let relaxed: Value = match ::json5::from_str(blob) {
+        Ok(relaxed) => relaxed,
+        Err(json5) => {
+            return Err(SettingsError::InvalidJson5 {
+                json,
+                json5: json5.to_string(),
+            })
+        }
+    };
+    audit::record(|| SettingsEvent::ParsedAsJson5 {
+        reason: json.to_string(),
+    });
+    format::parse_json_value(relaxed, profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::tests::{recorded_here, Recorder};
+
+    const RELAXED: &str = r#"{
+        // The reporting replica
+        DatabaseServer: "sql01",
+        "DatabaseName": 'reports',
+        DatabaseAuthMethod: "Integrated",
+        "Email": {
+            "ToAddresses": "ops@example.com", /* for now */
+        },
+    }"#;
+
+    #[test]
+    fn test_comments_trailing_commas_and_unquoted_keys() {
+        Settings::set_audit_sink(Recorder);
+
+        let settings = Settings::from_json5(RELAXED).unwrap();
+        let strict = Settings::from_json5(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.database_server(), "sql01");
+        assert_eq!(settings.database_name(), "reports");
+        assert_eq!(settings.email_to_addresses(), Some("ops@example.com"));
+        assert_eq!(strict.database_server(), "sql01");
+        let relaxed: Vec<SettingsEvent> = recorded_here()
+            .into_iter()
+            .filter(|event| matches!(event, SettingsEvent::ParsedAsJson5 { .. }))
+            .collect();
+        assert_eq!(relaxed.len(), 1, "{:?}", relaxed);
+        assert!(relaxed[0]
+            .to_string()
+            .starts_with("Settings blob is not strict JSON (key must be a string at line 2"));
+    }
+
+    #[test]
+    fn test_both_errors_are_reported() {
+        let err = Settings::from_json5("{DatabaseServer: }").unwrap_err();
+
+        assert!(matches!(err, SettingsError::InvalidJson5 { .. }), "{}", err);
+        let message = err.to_string();
+        assert!(message.contains("key must be a string"), "{}", message);
+        assert!(message.contains("also tried it as JSON5"), "{}", message);
+        assert!(matches!(
+            Settings::from_json5(r#"{"DatabasePort": "lots",}"#),
+            Err(SettingsError::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_var_selects_json5() {
+        let (settings, _) =
+            Settings::parse_blob_in_format(RELAXED, Some("JSON5"), Profile::FromEnv).unwrap();
+
+        assert_eq!(settings.database_name(), "reports");
+        assert!(matches!(
+            Settings::parse_blob_in_format(RELAXED, None, Profile::FromEnv),
+            Err(SettingsError::InvalidJson(_))
+        ));
+    }
+}
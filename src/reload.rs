@@ -0,0 +1,297 @@
+use crate::{audit, Settings, SettingsError, SettingsEvent};
+use arc_swap::ArcSwap;
+use std::fmt;
+use std::sync::Arc;
+#[cfg(feature = "refresh")]
+use std::time::Duration;
+use tokio::sync::watch;
+#[cfg(feature = "refresh")]
+use tokio::task::JoinHandle;
+#[cfg(feature = "refresh")]
+use tokio::time::MissedTickBehavior;
+
+type Loader = Box<dyn Fn() -> Result<Settings, SettingsError> + Send + Sync>;
+
+/// Settings that can be reloaded while they are in use, e.g. to pick up a
+/// rotated database password without restarting.
+///
+/// Readers take a snapshot with [`SettingsHandle::current`] and keep seeing the
+/// same values for as long as they hold it; [`SettingsHandle::reload`] swaps in
+/// a new snapshot for later readers. Components that hold on to something built
+/// from the settings, like a database client, can [`SettingsHandle::subscribe`]
+/// to rebuild it when they change.
+pub struct SettingsHandle {
+    current: ArcSwap<Settings>,
+    loader: Loader,
+    sender: watch::Sender<Arc<Settings>>,
+}
+
+impl fmt::Debug for SettingsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SettingsHandle")
+            .field("current", &self.current.load())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SettingsHandle {
+    /// Loads with [`Settings::get_settings`], now and on every reload.
+    pub fn new() -> Result<SettingsHandle, SettingsError> {
+        SettingsHandle::with_loader(Settings::get_settings)
+    }
+
+    /// Loads with `loader`, now and on every reload. The first load must
+    /// validate, like every later one.
+    pub fn with_loader<F>(loader: F) -> Result<SettingsHandle, SettingsError>
+    where
+        F: Fn() -> Result<Settings, SettingsError> + Send + Sync + 'static,
+    {
+        let settings = Arc::new(load_validated(&loader)?);
+        Ok(SettingsHandle {
+            current: ArcSwap::new(settings.clone()),
+            loader: Box::new(loader),
+            sender: watch::Sender::new(settings),
+        })
+    }
+
+    /// The latest snapshot.
+    pub fn current(&self) -> Arc<Settings> {
+        self.current.load_full()
+    }
+
+    /// Runs the loader again and, if the new settings validate, makes them the
+    /// current snapshot and notifies subscribers. On error the current snapshot
+    /// is kept. A successful reload is reported to the [`crate::AuditSink`]
+    /// with the fields it changed and the new [`Settings::fingerprint`].
+    pub fn reload(&self) -> Result<(), SettingsError> {
+        self.swap(load_validated(&self.loader)?);
+        Ok(())
+    }
+
+    /// A receiver that is marked changed after every successful reload.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Settings>> {
+        self.sender.subscribe()
+    }
+
+    /// Reloads every `interval` on a tokio task, like
+    /// [`SettingsHandle::reload`] but swapping in the new settings only if
+    /// their [`Settings::fingerprint`] differs from the current snapshot's.
+    /// The first reload is one `interval` from now.
+    ///
+    /// A reload that fails keeps the current snapshot and is reported to the
+    /// [`crate::AuditSink`] as a [`SettingsEvent::RefreshFailed`], which
+    /// [`crate::LogAuditSink`] logs as a warning for the log webhook to pick
+    /// up. The task carries on and tries again at the next tick. It runs until
+    /// [`RefreshTask::stop`] is called or the [`RefreshTask`] is dropped.
+    ///
+    /// The loader runs on the task itself, so it should be quick, as reading
+    /// a file or an env variable is. Needs a tokio runtime with the time
+    /// driver enabled.
+    #[cfg(feature = "refresh")]
+    pub fn spawn_refresh(self: &Arc<Self>, interval: Duration) -> RefreshTask {
+        let handle = Arc::clone(self);
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick is immediate, and the settings were just loaded
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                if let Err(error) = handle.refresh() {
+                    audit::record(|| SettingsEvent::RefreshFailed {
+                        error: error.to_string(),
+                    });
+                }
+            }
+        });
+        RefreshTask { task }
+    }
+
+    #[cfg(feature = "refresh")]
+    fn refresh(&self) -> Result<(), SettingsError> {
+        let settings = load_validated(&self.loader)?;
+        if settings.fingerprint() != self.current.load().fingerprint() {
+            self.swap(settings);
+        }
+        Ok(())
+    }
+
+    fn swap(&self, settings: Settings) {
+        #[cfg(feature = "sendgrid")]
+        let settings = settings.keeping_email_rate_limiter(&self.current.load());
+        let settings = Arc::new(settings);
+        let previous = self.current.swap(settings.clone());
+        audit::record(|| SettingsEvent::Reloaded {
+            changed: previous.changed_fields(&settings),
+            fingerprint: settings.fingerprint(),
+        });
+        self.sender.send_replace(settings);
+    }
+}
+
+/// The task started by [`SettingsHandle::spawn_refresh`]. Dropping it stops
+/// the task too.
+#[cfg(feature = "refresh")]
+#[derive(Debug)]
+pub struct RefreshTask {
+    task: JoinHandle<()>,
+}
+
+#[cfg(feature = "refresh")]
+impl RefreshTask {
+    /// Stops the task, waiting for it to finish a reload it is in the middle of.
+    pub async fn stop(mut self) {
+        self.task.abort();
+        let _ = (&mut self.task).await;
+    }
+}
+
+#[cfg(feature = "refresh")]
+impl Drop for RefreshTask {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn load_validated(
+    loader: &dyn Fn() -> Result<Settings, SettingsError>,
+) -> Result<Settings, SettingsError> {
+    let settings = loader()?;
+    settings.validate().map_err(SettingsError::Validation)?;
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // A loader that hands out the queued passwords in turn, an empty one
+    // failing validation
+    fn handle(passwords: &[&'static str]) -> SettingsHandle {
+        let passwords = Mutex::new(passwords.to_vec());
+        SettingsHandle::with_loader(move || {
+            let password = passwords.lock().unwrap().remove(0);
+            Settings::builder()
+                .database_server("localhost")
+                .database_name("test_db")
+                .database_username("admin")
+                .database_password(password)
+                .log_webhook_uri("https://example.com")
+                .sendgrid_api_key("sendgrid-api-key")
+                .email_from_address("test@example.com")
+                .build()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_old_snapshots_survive_reload() {
+        let handle = handle(&["old-password", "new-password"]);
+        let old = handle.current();
+
+        handle.reload().unwrap();
+
+        assert_eq!(old.expose_database_password(), "old-password");
+        assert_eq!(old.database_username(), "admin");
+        assert_eq!(handle.current().expose_database_password(), "new-password");
+    }
+
+    #[test]
+    fn test_invalid_reload_keeps_current() {
+        let handle = handle(&["old-password", ""]);
+        let mut changes = handle.subscribe();
+
+        let err = handle.reload().unwrap_err();
+
+        assert!(matches!(err, SettingsError::Validation(_)));
+        assert_eq!(handle.current().expose_database_password(), "old-password");
+        assert!(!changes.has_changed().unwrap());
+        assert_eq!(
+            changes.borrow_and_update().expose_database_password(),
+            "old-password"
+        );
+    }
+
+    #[test]
+    fn test_subscribers_see_reloads() {
+        let handle = handle(&["old-password", "new-password"]);
+        let mut changes = handle.subscribe();
+
+        handle.reload().unwrap();
+
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(
+            changes.borrow_and_update().expose_database_password(),
+            "new-password"
+        );
+    }
+
+    #[cfg(feature = "sendgrid")]
+    #[test]
+    fn test_email_allowance_survives_reload_with_same_limit() {
+        let limits = Mutex::new(vec![1, 1, 2]);
+        let handle = SettingsHandle::with_loader(move || {
+            let limit = limits.lock().unwrap().remove(0);
+            Ok(Settings::for_tests().with_email_max_per_hour(limit))
+        })
+        .unwrap();
+        handle.current().take_email_send().unwrap();
+
+        handle.reload().unwrap();
+        let err = handle.current().take_email_send().unwrap_err();
+        assert!(matches!(err, SettingsError::RateLimited { .. }), "{}", err);
+
+        handle.reload().unwrap();
+        handle.current().take_email_send().unwrap();
+    }
+
+    #[cfg(feature = "refresh")]
+    #[tokio::test]
+    async fn test_refresh_follows_file_changes() {
+        use crate::audit::tests::{recorded_here, Recorder};
+        use std::time::Duration;
+
+        Settings::set_audit_sink(Recorder);
+        let file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        let write = |password: &str| {
+            let blob = Settings::for_tests().with_database_password(password);
+            std::fs::write(file.path(), serde_json::to_string(&blob).unwrap()).unwrap();
+        };
+        write("old-password");
+        let path = file.path().to_path_buf();
+        let handle = Arc::new(
+            SettingsHandle::with_loader(move || Settings::get_settings_from_file(&path)).unwrap(),
+        );
+        let mut changes = handle.subscribe();
+        let refresh = handle.spawn_refresh(Duration::from_millis(10));
+
+        // Unchanged settings are not swapped in
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!changes.has_changed().unwrap());
+
+        write("new-password");
+        tokio::time::timeout(Duration::from_secs(5), changes.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            changes.borrow_and_update().expose_database_password(),
+            "new-password"
+        );
+
+        write("");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(handle.current().expose_database_password(), "new-password");
+        assert!(!changes.has_changed().unwrap());
+        assert!(recorded_here().iter().any(|event| matches!(
+            event,
+            SettingsEvent::RefreshFailed { error } if error.contains("DatabasePassword")
+        )));
+
+        refresh.stop().await;
+        write("newest-password");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(handle.current().expose_database_password(), "new-password");
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{DatabaseAuthMethod, Settings, SettingsError};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::Value;
+
+// Which of the fields `validate` may require the blob gives at all, whatever
+// their values; the aliases are those of `Settings`
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Present {
+    #[serde(alias = "databaseServer", alias = "database_server")]
+    database_server: Option<IgnoredAny>,
+    #[serde(alias = "databaseName", alias = "database_name")]
+    database_name: Option<IgnoredAny>,
+    #[serde(alias = "databaseUsername", alias = "database_username")]
+    database_username: Option<IgnoredAny>,
+    #[serde(alias = "databasePassword", alias = "database_password")]
+    database_password: Option<IgnoredAny>,
+}
+
+/// The required keys `blob` leaves out, in declaration order. `blob` is the
+/// flat, migrated blob the settings were deserialized from.
+pub(crate) fn absent_fields(blob: &Value) -> Vec<&'static str> {
+    let present = match Present::deserialize(blob) {
+        Ok(present) => present,
+        Err(_) => return Vec::new(),
+    };
+    [
+        ("DatabaseServer", present.database_server.is_some()),
+        ("DatabaseName", present.database_name.is_some()),
+        ("DatabaseUsername", present.database_username.is_some()),
+        ("DatabasePassword", present.database_password.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, present)| !present)
+    .map(|(field, _)| field)
+    .collect()
+}
+
+impl Settings {
+    // The required fields that are still empty, in declaration order: the
+    // database server and name unless there is a connection string, and the
+    // login too with SqlServer auth
+    pub(crate) fn empty_required_fields(&self) -> Vec<&'static str> {
+        let mut required = Vec::new();
+        if self.database_connection_string.is_none() {
+            required.push(("DatabaseServer", self.database_server.as_str()));
+            required.push(("DatabaseName", self.database_name.as_str()));
+        }
+        if self.database_connection_string.is_none()
+            && self.database_auth_method() == DatabaseAuthMethod::SqlServer
+        {
+            required.push(("DatabaseUsername", self.database_username.as_str()));
+            required.push(("DatabasePassword", self.database_password.inspect()));
+        }
+        required
+            .into_iter()
+            .filter(|(_, value)| value.trim().is_empty())
+            .map(|(field, _)| field)
+            .collect()
+    }
+
+    pub(crate) fn with_absent_fields(mut self, fields: Vec<&'static str>) -> Settings {
+        self.absent_fields = fields;
+        self
+    }
+
+    /// Fails with [`SettingsError::MissingFields`], listing all of them at
+    /// once, if the blob left out required fields that nothing has set since.
+    /// A field given but empty is left to [`Settings::validate`], and so is a
+    /// field no longer required, such as the login once a connection string
+    /// or integrated auth is set.
+    pub(crate) fn require_fields(mut self) -> Result<Settings, SettingsError> {
+        let empty = self.empty_required_fields();
+        let fields: Vec<&'static str> = std::mem::take(&mut self.absent_fields)
+            .into_iter()
+            .filter(|field| empty.contains(field))
+            .collect();
+        if fields.is_empty() {
+            Ok(self)
+        } else {
+            Err(SettingsError::MissingFields { fields })
+        }
+    }
+
+    /// What every loader does last, after its overrides: fails if required
+    /// fields are missing, or if `DatabaseCaCertPath` names a bundle that
+    /// can't be used. The parse-only entry points such as
+    /// [`Settings::from_json_str`] stop at [`Settings::require_fields`] and
+    /// leave the bundle to [`Settings::validate`].
+    pub(crate) fn finish_load(self) -> Result<Settings, SettingsError> {
+        let settings = self.require_fields()?;
+        settings.check_ca_cert()?;
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absent_fields_accepts_every_spelling() {
+        let blob = serde_json::json!({
+            "databaseServer": "sql01",
+            "database_name": "reports",
+            "DatabasePassword": "",
+        });
+
+        assert_eq!(absent_fields(&blob), vec!["DatabaseUsername"]);
+    }
+
+    #[test]
+    fn test_missing_fields_are_listed_in_declaration_order() {
+        let err = Settings::from_json_str(r#"{"DatabaseName": "reports"}"#).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Incomplete settings blob: missing fields `DatabaseServer`, \
+             `DatabaseUsername`, `DatabasePassword`"
+        );
+        assert!(matches!(
+            err,
+            SettingsError::MissingFields { ref fields } if fields.len() == 3
+        ));
+    }
+
+    #[test]
+    fn test_only_fields_still_required_are_missing() {
+        let integrated = r#"{"DatabaseServer": "sql01", "DatabaseName": "reports",
+            "DatabaseAuthMethod": "Integrated"}"#;
+        assert!(Settings::from_json_str(integrated).is_ok());
+
+        let from_string = r#"{"DatabaseConnectionString": "Server=sql01;Database=reports"}"#;
+        assert!(Settings::from_json_str(from_string).is_ok());
+
+        // Given but empty is for validate() to report
+        let empty = r#"{"DatabaseServer": "", "DatabaseName": "reports",
+            "DatabaseUsername": "svc", "DatabasePassword": "pw"}"#;
+        assert!(Settings::from_json_str(empty).is_ok());
+
+        let err = Settings::from_json_str(
+            r#"{"DatabaseName": "reports", "DatabaseAuthMethod": "Integrated"}"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Incomplete settings blob: missing field `DatabaseServer`"
+        );
+    }
+}
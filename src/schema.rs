@@ -0,0 +1,270 @@
+use crate::{
+    DatabaseAuthMethod, DatabaseEncryption, LogLevel, LogWebhookFormat, Secret, Settings,
+    SettingsError,
+};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::fmt;
+
+/// A value in a blob that doesn't match [`Settings::json_schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the value, e.g. `/Databases/warehouse/Port`; empty for
+    /// the blob as a whole.
+    pub path: String,
+    /// What is wrong with it. The value itself is left out, so that secrets
+    /// don't end up in deploy logs.
+    pub reason: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.reason)
+        } else {
+            write!(f, "{}: {}", self.path, self.reason)
+        }
+    }
+}
+
+impl Settings {
+    /// A JSON Schema (draft 2020-12) for the JSON blob, with a description of
+    /// each field and an example blob, for editors and deploy pipelines.
+    ///
+    /// Only the PascalCase keys are described; the camelCase and snake_case
+    /// spellings are accepted when loading but not checked by the schema. The
+    /// database fields are required unless `DatabaseConnectionString` is given.
+    /// Numbers and flags are described by their JSON types, although loading
+    /// also accepts them written as strings, such as `"1433"` or `"true"`.
+    pub fn json_schema() -> Value {
+        let mut schema = schemars::schema_for!(Settings);
+        schema.insert(
+            "anyOf".to_string(),
+            json!([
+                { "required": ["DatabaseConnectionString"] },
+                { "required": ["DatabaseServer", "DatabaseName"] },
+            ]),
+        );
+        schema.to_value()
+    }
+
+    /// Checks a candidate JSON blob against [`Settings::json_schema`] without
+    /// loading it, e.g. as a deploy preflight. Fails with
+    /// [`SettingsError::SchemaViolations`] listing every mismatch, or with
+    /// [`SettingsError::InvalidJson`] if the blob isn't JSON at all.
+    ///
+    /// The schema only covers types and formats; [`Settings::validate`] still
+    /// catches problems such as conflicting ports. A blob in the nested shape
+    /// (see [`Settings::to_nested_json`]) is checked in its flat form, so paths
+    /// name the flat keys.
+    pub fn validate_against_schema(blob: &str) -> Result<(), SettingsError> {
+        let mut blob: Value = serde_json::from_str(blob)?;
+        crate::migrate::migrate(&mut blob)?;
+        let validator = jsonschema::options()
+            .should_validate_formats(true)
+            .build(&Settings::json_schema())
+            .expect("the generated schema is valid");
+
+        let violations: Vec<SchemaViolation> = validator
+            .iter_errors(&blob)
+            .map(|error| SchemaViolation {
+                path: error.instance_path().to_string(),
+                reason: error.masked().to_string(),
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::SchemaViolations(violations))
+        }
+    }
+}
+
+/// The example blob embedded in the schema.
+pub(crate) fn example() -> Value {
+    json!({
+        "DatabaseServer": "sql.example.com,1433",
+        "DatabaseName": "reports",
+        "DatabaseUsername": "report_service",
+        "DatabasePassword": "<database password>",
+        "DatabaseEncryption": "Required",
+        "Databases": {
+            "warehouse": {
+                "Server": "warehouse-sql.example.com",
+                "Name": "warehouse",
+                "Username": "report_reader",
+                "Password": "<warehouse password>"
+            }
+        },
+        "ApplicationName": "nightly-report",
+        "DatabaseConnectTimeoutSeconds": 15,
+        "LogWebhookUri": "https://hooks.example.com/reports",
+        "LogWebhookFormat": "Teams",
+        "LogWebhookMinLevel": "Warning",
+        "SendgridApiKey": "<sendgrid api key>",
+        "EmailFromName": "Report Service",
+        "EmailFromAddress": "reports@example.com",
+        "EmailToAddresses": "finance@example.com; Ops Team <ops@example.com>"
+    })
+}
+
+// The enums are read case-insensitively, which `enum` can't express; a pattern
+// of per-letter classes can, and works in every regex dialect
+fn names_schema(names: &[&str]) -> Schema {
+    let alternatives: Vec<String> = names
+        .iter()
+        .map(|name| {
+            name.chars()
+                .map(|c| format!("[{}{}]", c.to_ascii_uppercase(), c.to_ascii_lowercase()))
+                .collect()
+        })
+        .collect();
+    json_schema!({
+        "type": "string",
+        "pattern": format!("^({})$", alternatives.join("|")),
+        "examples": names,
+    })
+}
+
+impl JsonSchema for Secret {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "Secret".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        String::json_schema(generator)
+    }
+}
+
+impl JsonSchema for DatabaseEncryption {
+    fn schema_name() -> Cow<'static, str> {
+        "DatabaseEncryption".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        names_schema(&["Off", "On", "Required"])
+    }
+}
+
+impl JsonSchema for DatabaseAuthMethod {
+    fn schema_name() -> Cow<'static, str> {
+        "DatabaseAuthMethod".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        names_schema(&["SqlServer", "Integrated"])
+    }
+}
+
+impl JsonSchema for LogWebhookFormat {
+    fn schema_name() -> Cow<'static, str> {
+        "LogWebhookFormat".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        names_schema(&["Json", "Teams", "Slack"])
+    }
+}
+
+impl JsonSchema for LogLevel {
+    fn schema_name() -> Cow<'static, str> {
+        "LogLevel".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        names_schema(&["Debug", "Info", "Warning", "Warn", "Error"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_example_deserializes_and_validates() {
+        let schema = Settings::json_schema();
+        let example = &schema["examples"][0];
+
+        let settings: Settings = serde_json::from_value(example.clone()).unwrap();
+        assert_eq!(settings.validate(), Ok(()));
+        Settings::validate_against_schema(&example.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_schema_describes_fields() {
+        let schema = Settings::json_schema();
+        let properties = &schema["properties"];
+
+        assert_eq!(
+            properties["DatabaseServer"]["description"],
+            "SQL Server host, optionally followed by `\\instance` or `,port`."
+        );
+        assert_eq!(properties["LogWebhookUri"]["format"], "uri");
+        assert_eq!(properties["EmailFromAddress"]["format"], "email");
+        assert!(properties.get("database_server").is_none());
+        assert!(schema.get("required").is_none());
+        assert_eq!(
+            schema["anyOf"][1],
+            json!({ "required": ["DatabaseServer", "DatabaseName"] })
+        );
+    }
+
+    #[test]
+    fn test_enums_match_case_insensitively() {
+        for encryption in ["Required", "required", "OFF"] {
+            let blob = json!({
+                "DatabaseServer": "localhost",
+                "DatabaseName": "reports",
+                "DatabaseEncryption": encryption,
+            });
+            Settings::validate_against_schema(&blob.to_string()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reports_every_violation() {
+        let blob = json!({
+            "DatabaseName": "reports",
+            "DatabasePort": "1433",
+            "DatabaseEncryption": "Mandatory",
+            "LogWebhookUri": "not a url",
+            "EmailFromAddress": "nobody",
+            "Databases": { "warehouse": { "Server": "wh-sql", "Port": -1 } },
+        });
+
+        let err = Settings::validate_against_schema(&blob.to_string()).unwrap_err();
+        let violations = match err {
+            SettingsError::SchemaViolations(violations) => violations,
+            err => panic!("{:?}", err),
+        };
+        let mut paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        paths.sort();
+        paths.dedup();
+        assert_eq!(
+            paths,
+            [
+                "",
+                "/DatabaseEncryption",
+                "/DatabasePort",
+                "/Databases/warehouse",
+                "/Databases/warehouse/Port",
+                "/EmailFromAddress",
+                "/LogWebhookUri",
+            ]
+        );
+        assert!(violations.iter().all(|v| !v.reason.contains("Mandatory")));
+    }
+
+    #[test]
+    fn test_rejects_non_json() {
+        assert!(matches!(
+            Settings::validate_against_schema("DatabaseServer = 'x'"),
+            Err(SettingsError::InvalidJson(_))
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{audit, SettingsEvent};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::panic::Location;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// A secret string, such as a password or API key.
+///
+/// It deserializes from a plain string, but has no `Display` and a redacted
+/// `Debug`, so the only way to read it is [`Secret::expose`]. The value is wiped
+/// from memory on drop. Comparisons take the same time however much of two
+/// equally long secrets matches.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Secret {
+        Secret(Zeroizing::new(value.into()))
+    }
+
+    /// Returns the secret in clear text. Each call is reported to the
+    /// [`crate::AuditSink`] as a [`SettingsEvent::SecretExposed`] naming the
+    /// caller.
+    #[track_caller]
+    pub fn expose(&self) -> &str {
+        let location = Location::caller();
+        audit::record(|| SettingsEvent::SecretExposed { location });
+        &self.0
+    }
+
+    // For checks that never hand the value on, like emptiness checks and
+    // masking, so they aren't reported as reads
+    pub(crate) fn inspect(&self) -> &str {
+        &self.0
+    }
+
+    // Env overrides replace the value in place; callers must wipe it first
+    pub(crate) fn expose_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Secret {
+        Secret::new(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Secret {
+        Secret::new(value)
+    }
+}
+
+impl PartialEq for Secret {
+    fn eq(&self, other: &Secret) -> bool {
+        self.inspect()
+            .as_bytes()
+            .ct_eq(other.inspect().as_bytes())
+            .into()
+    }
+}
+
+impl Eq for Secret {}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserializes_from_plain_string() {
+        let secret: Secret = serde_json::from_str(r#""hunter2""#).unwrap();
+
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), r#""hunter2""#);
+    }
+
+    #[test]
+    fn test_compares_values() {
+        assert_eq!(Secret::new("hunter2"), Secret::from("hunter2".to_string()));
+        assert_ne!(Secret::new("hunter2"), Secret::new("hunter3"));
+        assert_ne!(Secret::new("hunter2"), Secret::new("hunter22"));
+        assert_eq!(Secret::default(), Secret::new(""));
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        assert_eq!(format!("{:?}", Secret::new("hunter2")), "Secret(***)");
+    }
+}
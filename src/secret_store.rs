@@ -0,0 +1,355 @@
+use crate::{Settings, SettingsError};
+use std::fmt;
+use std::future::Future;
+
+/// Why a secret store could not hand out a secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SecretStoreError {
+    /// The caller could not authenticate, or is not allowed to read the secret.
+    Auth(String),
+    /// The store has no secret by that name.
+    NotFound,
+    /// The store rejected the request because of rate limiting; retrying later may succeed.
+    Throttled(String),
+    /// Anything else, e.g. a network failure or an unexpected response.
+    Other(String),
+}
+
+impl std::error::Error for SecretStoreError {}
+
+impl fmt::Display for SecretStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretStoreError::Auth(reason) => write!(f, "authentication failed: {}", reason),
+            SecretStoreError::NotFound => write!(f, "secret not found"),
+            SecretStoreError::Throttled(reason) => write!(f, "request throttled: {}", reason),
+            SecretStoreError::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// A client that fetches a single secret by name.
+///
+/// The Azure Key Vault client behind the `azure` feature implements this; tests
+/// and other stores can provide their own implementation.
+pub trait SecretStore {
+    /// A short human-readable name for the store, used in error messages.
+    fn store_name(&self) -> &'static str;
+
+    fn get_secret(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<String, SecretStoreError>> + Send;
+}
+
+impl Settings {
+    /// Fetches the blob from a secret store and parses it like `SecretBlob`.
+    pub async fn from_secret_store<S: SecretStore>(
+        store: &S,
+        secret_name: &str,
+    ) -> Result<Settings, SettingsError> {
+        Settings::fetch_secret(store, secret_name)
+            .await?
+            .finish_load()
+    }
+
+    // Without the check for missing fields, which `load_async` makes once its
+    // overrides are in
+    pub(crate) async fn fetch_secret<S: SecretStore>(
+        store: &S,
+        secret_name: &str,
+    ) -> Result<Settings, SettingsError> {
+        let blob =
+            store
+                .get_secret(secret_name)
+                .await
+                .map_err(|source| SettingsError::SecretStore {
+                    store: store.store_name(),
+                    name: secret_name.to_string(),
+                    source,
+                })?;
+
+        Settings::parse_blob(&blob)
+    }
+}
+
+#[cfg(feature = "azure")]
+pub use self::azure::KeyVaultStore;
+
+#[cfg(feature = "azure")]
+mod azure {
+    use super::{SecretStore, SecretStoreError};
+    use crate::{Settings, SettingsError};
+    use azure_core::error::ErrorKind;
+    use azure_core::StatusCode;
+    use azure_security_keyvault::SecretClient;
+
+    /// Azure Key Vault secrets, authenticated with `DefaultAzureCredential`.
+    pub struct KeyVaultStore {
+        client: SecretClient,
+    }
+
+    impl KeyVaultStore {
+        pub fn new(vault_url: &str) -> Result<KeyVaultStore, SettingsError> {
+            let wrap = |source| SettingsError::SecretStore {
+                store: "Azure Key Vault",
+                name: vault_url.to_string(),
+                source,
+            };
+            let credential = azure_identity::create_default_credential()
+                .map_err(|e| wrap(SecretStoreError::Auth(e.to_string())))?;
+            let client = SecretClient::new(vault_url, credential)
+                .map_err(|e| wrap(SecretStoreError::Other(e.to_string())))?;
+
+            Ok(KeyVaultStore { client })
+        }
+    }
+
+    impl SecretStore for KeyVaultStore {
+        fn store_name(&self) -> &'static str {
+            "Azure Key Vault"
+        }
+
+        async fn get_secret(&self, name: &str) -> Result<String, SecretStoreError> {
+            match self.client.get(name).await {
+                Ok(secret) => Ok(secret.value),
+                Err(e) => Err(match e.kind() {
+                    ErrorKind::Credential => SecretStoreError::Auth(e.to_string()),
+                    ErrorKind::HttpResponse { status, .. } => match status {
+                        StatusCode::NotFound => SecretStoreError::NotFound,
+                        StatusCode::Unauthorized | StatusCode::Forbidden => {
+                            SecretStoreError::Auth(e.to_string())
+                        }
+                        StatusCode::TooManyRequests => SecretStoreError::Throttled(e.to_string()),
+                        _ => SecretStoreError::Other(e.to_string()),
+                    },
+                    _ => SecretStoreError::Other(e.to_string()),
+                }),
+            }
+        }
+    }
+
+    impl Settings {
+        /// Loads the blob from an Azure Key Vault secret, e.g.
+        /// `from_key_vault("https://my-vault.vault.azure.net", "ReportSettings")`.
+        pub async fn from_key_vault(
+            vault_url: &str,
+            secret_name: &str,
+        ) -> Result<Settings, SettingsError> {
+            let store = KeyVaultStore::new(vault_url)?;
+            Settings::from_secret_store(&store, secret_name).await
+        }
+    }
+}
+
+#[cfg(feature = "aws")]
+pub use self::aws::SecretsManagerStore;
+
+#[cfg(feature = "aws")]
+mod aws {
+    use super::{SecretStore, SecretStoreError};
+    use crate::{Settings, SettingsError};
+    use aws_config::{BehaviorVersion, Region};
+    use aws_sdk_secretsmanager::error::{DisplayErrorContext, ProvideErrorMetadata};
+    use aws_sdk_secretsmanager::operation::get_secret_value::GetSecretValueError;
+    use aws_sdk_secretsmanager::Client;
+
+    const AUTH_ERROR_CODES: &[&str] = &[
+        "AccessDeniedException",
+        "UnrecognizedClientException",
+        "InvalidSignatureException",
+        "ExpiredTokenException",
+    ];
+
+    /// AWS Secrets Manager, using the default credential provider chain.
+    pub struct SecretsManagerStore {
+        client: Client,
+    }
+
+    impl SecretsManagerStore {
+        pub async fn new(region: &str) -> SecretsManagerStore {
+            let config = aws_config::defaults(BehaviorVersion::latest())
+                .region(Region::new(region.to_string()))
+                .load()
+                .await;
+
+            SecretsManagerStore::from_client(Client::new(&config))
+        }
+
+        pub fn from_client(client: Client) -> SecretsManagerStore {
+            SecretsManagerStore { client }
+        }
+    }
+
+    impl SecretStore for SecretsManagerStore {
+        fn store_name(&self) -> &'static str {
+            "AWS Secrets Manager"
+        }
+
+        async fn get_secret(&self, name: &str) -> Result<String, SecretStoreError> {
+            let output = match self.client.get_secret_value().secret_id(name).send().await {
+                Ok(output) => output,
+                Err(e) => {
+                    let reason = DisplayErrorContext(&e).to_string();
+                    return Err(match e.code() {
+                        Some("ThrottlingException") => SecretStoreError::Throttled(reason),
+                        Some(code) if AUTH_ERROR_CODES.contains(&code) => {
+                            SecretStoreError::Auth(reason)
+                        }
+                        _ => match e.into_service_error() {
+                            GetSecretValueError::ResourceNotFoundException(_) => {
+                                SecretStoreError::NotFound
+                            }
+                            _ => SecretStoreError::Other(reason),
+                        },
+                    });
+                }
+            };
+
+            // The SDK has already undone the wire-level base64 of SecretBinary
+            if let Some(secret) = output.secret_string() {
+                Ok(secret.to_string())
+            } else if let Some(binary) = output.secret_binary() {
+                String::from_utf8(binary.as_ref().to_vec()).map_err(|_| {
+                    SecretStoreError::Other("SecretBinary is not valid UTF-8".to_string())
+                })
+            } else {
+                Err(SecretStoreError::Other(
+                    "response contained neither SecretString nor SecretBinary".to_string(),
+                ))
+            }
+        }
+    }
+
+    impl Settings {
+        /// Loads the blob from an AWS Secrets Manager secret in the given region.
+        pub async fn from_secrets_manager(
+            secret_id: &str,
+            region: &str,
+        ) -> Result<Settings, SettingsError> {
+            let store = SecretsManagerStore::new(region).await;
+            Settings::from_secret_store(&store, secret_id).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct MockStore(HashMap<&'static str, Result<&'static str, SecretStoreError>>);
+
+    impl SecretStore for MockStore {
+        fn store_name(&self) -> &'static str {
+            "mock store"
+        }
+
+        async fn get_secret(&self, name: &str) -> Result<String, SecretStoreError> {
+            match self.0.get(name) {
+                Some(Ok(blob)) => Ok(blob.to_string()),
+                Some(Err(e)) => Err(e.clone()),
+                None => Err(SecretStoreError::NotFound),
+            }
+        }
+    }
+
+    fn store() -> MockStore {
+        MockStore(HashMap::from([
+            (
+                "good",
+                Ok(r#"{
+                    "DatabaseServer": "localhost",
+                    "DatabaseName": "vault_db",
+                    "DatabaseUsername": "admin",
+                    "DatabasePassword": "password123",
+                    "LogWebhookUri": "https://example.com",
+                    "SendgridApiKey": "sendgrid-api-key",
+                    "EmailFromName": "Test",
+                    "EmailFromAddress": "test@example.com",
+                    "EmailToAddresses": "user1@example.com"
+                }"#),
+            ),
+            ("malformed", Ok("{ not json")),
+            ("forbidden", Err(SecretStoreError::Auth("403".to_string()))),
+            ("busy", Err(SecretStoreError::Throttled("429".to_string()))),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn test_loads_settings_from_store() {
+        let settings = Settings::from_secret_store(&store(), "good").await.unwrap();
+        assert_eq!(settings.database_name(), "vault_db");
+    }
+
+    #[tokio::test]
+    async fn test_distinguishes_store_errors() {
+        let store = store();
+
+        let missing = Settings::from_secret_store(&store, "missing")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            missing,
+            SettingsError::SecretStore { source: SecretStoreError::NotFound, ref name, .. }
+                if name == "missing"
+        ));
+
+        let forbidden = Settings::from_secret_store(&store, "forbidden")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            forbidden,
+            SettingsError::SecretStore {
+                source: SecretStoreError::Auth(_),
+                ..
+            }
+        ));
+
+        let malformed = Settings::from_secret_store(&store, "malformed")
+            .await
+            .unwrap_err();
+        assert!(matches!(malformed, SettingsError::InvalidJson(_)));
+    }
+
+    #[tokio::test]
+    async fn test_only_throttling_is_retryable() {
+        let store = store();
+
+        let busy = Settings::from_secret_store(&store, "busy")
+            .await
+            .unwrap_err();
+        assert!(busy.is_retryable(), "{}", busy);
+
+        for name in ["missing", "forbidden", "malformed"] {
+            let err = Settings::from_secret_store(&store, name).await.unwrap_err();
+            assert!(!err.is_retryable(), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_ca_bundle_fails_the_load() {
+        let store = MockStore(HashMap::from([(
+            "pinned",
+            Ok(r#"{
+                "DatabaseServer": "localhost",
+                "DatabaseName": "vault_db",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123",
+                "DatabaseCaCertPath": "/nonexistent/ca.pem"
+            }"#),
+        )]));
+
+        let err = Settings::from_secret_store(&store, "pinned")
+            .await
+            .unwrap_err();
+
+        assert!(
+            err.to_string()
+                .contains("could not read '/nonexistent/ca.pem'"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use crate::loader::{missing_fields, pascal_case};
+use crate::{Settings, SettingsError, ENV_OVERRIDE_PREFIX};
+use serde_json::{Map, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+impl Settings {
+    /// Loads settings from a directory with one file per field, named by its
+    /// PascalCase blob key, as Docker and Kubernetes mount secrets: e.g.
+    /// `/run/secrets/reportsettings/DatabasePassword`. A single trailing `\n`
+    /// or `\r\n` is dropped from each file; other files in the directory are
+    /// ignored. `Databases` can't be given this way.
+    ///
+    /// Fails with [`SettingsError::MissingSecretFiles`] listing the required
+    /// database fields that have no file. To take only some fields from the
+    /// directory and the rest from a blob, use
+    /// [`crate::SettingsLoader::from_secrets_dir`].
+    pub fn from_secrets_dir(dir: impl AsRef<Path>) -> Result<Settings, SettingsError> {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Err(SettingsError::FileNotFound {
+                path: dir.to_path_buf(),
+            });
+        }
+
+        let settings = read_secrets_dir(dir)?;
+        let missing = missing_fields(&settings);
+        if missing.is_empty() {
+            settings.finish_load()
+        } else {
+            Err(SettingsError::MissingSecretFiles {
+                dir: dir.to_path_buf(),
+                files: missing
+                    .iter()
+                    .map(|error| error.field.to_string())
+                    .collect(),
+            })
+        }
+    }
+}
+
+/// The fields given by files in `dir`, with every other field unset.
+pub(crate) fn read_secrets_dir(dir: &Path) -> Result<Settings, SettingsError> {
+    let mut settings: Settings = serde_json::from_value(Value::Object(Map::new()))?;
+    // Files are read through the override machinery, which knows how to parse
+    // each field from a string
+    settings
+        .apply_overrides_from(|name| read_field_file(dir, name))
+        .map_err(|error| match error {
+            SettingsError::InvalidOverride { name, reason } => SettingsError::InvalidOverride {
+                name: field_path(dir, &name),
+                reason,
+            },
+            error => error,
+        })?;
+    Ok(settings)
+}
+
+fn read_field_file(dir: &Path, name: &str) -> Result<Option<String>, SettingsError> {
+    let path = dir.join(pascal_case(&name[ENV_OVERRIDE_PREFIX.len()..]));
+    match fs::read_to_string(&path) {
+        Ok(mut contents) => {
+            if contents.ends_with('\n') {
+                contents.pop();
+                if contents.ends_with('\r') {
+                    contents.pop();
+                }
+            }
+            Ok(Some(contents))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(SettingsError::FileUnreadable { path, source: e }),
+    }
+}
+
+// Errors about a file name the file rather than the override variable
+fn field_path(dir: &Path, name: &str) -> String {
+    let field = match name.strip_prefix(ENV_OVERRIDE_PREFIX) {
+        Some(suffix) => pascal_case(suffix),
+        None => name.to_string(),
+    };
+    dir.join(field).display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets_dir(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, contents) in files {
+            fs::write(dir.path().join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_reads_one_file_per_field() {
+        let dir = secrets_dir(&[
+            ("DatabaseServer", "sql01"),
+            ("DatabaseName", "reports\n"),
+            ("DatabaseUsername", "svc_report\r\n"),
+            ("DatabasePassword", "line one\nline two\n\n"),
+            ("DatabasePort", "14330\n"),
+            ("SendgridApiKey", "SG.key\n"),
+            ("README", "not a field"),
+        ]);
+
+        let settings = Settings::from_secrets_dir(dir.path()).unwrap();
+
+        assert_eq!(settings.database_server(), "sql01");
+        assert_eq!(settings.database_name(), "reports");
+        assert_eq!(settings.database_username(), "svc_report");
+        assert_eq!(settings.expose_database_password(), "line one\nline two\n");
+        assert_eq!(settings.database_port(), Some(14330));
+        assert_eq!(settings.sendgrid_api_key().unwrap().expose(), "SG.key");
+        assert_eq!(settings.email_from_address(), None);
+    }
+
+    #[test]
+    fn test_lists_missing_files() {
+        let dir = secrets_dir(&[("DatabaseServer", "sql01\n")]);
+
+        let err = Settings::from_secrets_dir(dir.path()).unwrap_err();
+
+        assert!(matches!(
+            &err,
+            SettingsError::MissingSecretFiles { files, .. }
+                if files == &["DatabaseName", "DatabaseUsername", "DatabasePassword"]
+        ));
+        assert!(
+            err.to_string()
+                .ends_with("is missing files: DatabaseName, DatabaseUsername, DatabasePassword"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_missing_ca_bundle_fails_the_load() {
+        let missing = tempfile::tempdir().unwrap().path().join("missing.pem");
+        let dir = secrets_dir(&[
+            ("DatabaseServer", "sql01"),
+            ("DatabaseName", "reports"),
+            ("DatabaseUsername", "svc_report"),
+            ("DatabasePassword", "hunter2"),
+            ("DatabaseCaCertPath", &missing.display().to_string()),
+        ]);
+
+        let err = Settings::from_secrets_dir(dir.path()).unwrap_err();
+
+        assert!(
+            err.to_string()
+                .contains(&format!("could not read '{}'", missing.display())),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_invalid_value_names_the_file() {
+        let dir = secrets_dir(&[("DatabasePort", "lots\n")]);
+
+        let err = Settings::from_secrets_dir(dir.path()).unwrap_err();
+
+        match err {
+            SettingsError::InvalidOverride { name, reason } => {
+                assert_eq!(name, dir.path().join("DatabasePort").display().to_string());
+                assert_eq!(reason, "'lots' is not a valid port");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_missing_dir() {
+        let err = Settings::from_secrets_dir("/nonexistent/secrets").unwrap_err();
+
+        assert!(matches!(err, SettingsError::FileNotFound { .. }));
+    }
+}
@@ -0,0 +1,683 @@
+use crate::address::split_address_list;
+use crate::{LogLevel, Secret, Settings, SettingsError};
+use sendgrid::v3::{Message, Sender};
+use sendgrid::SendgridError;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+/// Retries after the first attempt when `EmailMaxRetries` is not set.
+pub const DEFAULT_EMAIL_MAX_RETRIES: u32 = 3;
+/// Delay before the first retry when `EmailRetryBaseMs` is not set; each
+/// further retry waits twice as long as the one before.
+pub const DEFAULT_EMAIL_RETRY_BASE_MS: u64 = 500;
+
+/// Why a message could not be handed to SendGrid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SendError {
+    /// SendGrid answered with a non-success status.
+    Status { status: u16, body: String },
+    /// The request did not get an answer, e.g. a DNS or connection failure.
+    Transport(String),
+    /// SendGrid did not answer within `HttpRequestTimeoutSeconds`, or could not
+    /// be reached within `HttpConnectTimeoutSeconds`.
+    Timeout(String),
+}
+
+impl SendError {
+    // Rate limiting and server errors may pass; anything else SendGrid rejects
+    // (bad key, malformed message) will be rejected again
+    fn is_retryable(&self) -> bool {
+        match self {
+            SendError::Status { status, .. } => *status == 429 || *status >= 500,
+            SendError::Transport(_) | SendError::Timeout(_) => true,
+        }
+    }
+
+    // SendGrid itself is in trouble, rather than the message
+    #[cfg(feature = "smtp")]
+    fn is_outage(&self) -> bool {
+        match self {
+            SendError::Status { status, .. } => *status >= 500,
+            SendError::Timeout(_) => true,
+            SendError::Transport(_) => false,
+        }
+    }
+
+    fn redact(self, secret: &str) -> SendError {
+        if secret.is_empty() {
+            return self;
+        }
+        match self {
+            SendError::Status { status, body } => SendError::Status {
+                status,
+                body: body.replace(secret, "***"),
+            },
+            SendError::Transport(reason) => SendError::Transport(reason.replace(secret, "***")),
+            SendError::Timeout(reason) => SendError::Timeout(reason.replace(secret, "***")),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Status { status, body } => {
+                write!(f, "SendGrid returned status {}: {}", status, body)
+            }
+            SendError::Transport(reason) => write!(f, "{}", reason),
+            SendError::Timeout(reason) => write!(f, "timed out: {}", reason),
+        }
+    }
+}
+
+/// Something that delivers a SendGrid message, one attempt per call.
+///
+/// [`Sender`] implements this; tests can provide their own implementation to
+/// exercise the retry logic without network calls.
+pub trait MailTransport {
+    fn send(&self, message: &Message) -> impl Future<Output = Result<(), SendError>> + Send;
+}
+
+impl MailTransport for Sender {
+    async fn send(&self, message: &Message) -> Result<(), SendError> {
+        match Sender::send(self, message).await {
+            Ok(_) => Ok(()),
+            Err(SendgridError::RequestNotSuccessful(response)) => Err(SendError::Status {
+                status: response.status.as_u16(),
+                body: response.body,
+            }),
+            Err(SendgridError::ReqwestError(e)) if e.is_timeout() => {
+                Err(SendError::Timeout(e.to_string()))
+            }
+            Err(e) => Err(SendError::Transport(e.to_string())),
+        }
+    }
+}
+
+impl Settings {
+    /// `EmailMaxRetries`, or [`DEFAULT_EMAIL_MAX_RETRIES`] if unset.
+    pub fn email_max_retries(&self) -> u32 {
+        self.email_max_retries.unwrap_or(DEFAULT_EMAIL_MAX_RETRIES)
+    }
+
+    /// `EmailRetryBaseMs` as a duration, or [`DEFAULT_EMAIL_RETRY_BASE_MS`] if unset.
+    pub fn email_retry_base_delay(&self) -> Duration {
+        Duration::from_millis(
+            self.email_retry_base_ms
+                .unwrap_or(DEFAULT_EMAIL_RETRY_BASE_MS),
+        )
+    }
+
+    /// Builds the report message (see [`Settings::build_message`]) and sends it
+    /// through SendGrid, retrying as described in [`Settings::send_message_with`].
+    /// Fails without sending if `SendgridApiKey` is not set, or with
+    /// [`SettingsError::NoRecipients`] if there are no To recipients.
+    ///
+    /// With `EmailAllowEmptyRecipients` set and no To, Cc or Bcc recipients at
+    /// all, nothing is sent and this succeeds, for environments whose reports
+    /// only go to the log webhook.
+    ///
+    /// With `EmailSandboxMode` on, SendGrid checks the message but doesn't
+    /// deliver it, and a `Warning` line saying so is posted to `LogWebhookUri`
+    /// if one is set.
+    ///
+    /// With the `smtp` feature and `SmtpHost` set, a message SendGrid still
+    /// fails with a server error (5xx) or a timeout after its retries is sent
+    /// through that relay instead, and settings without `SendgridApiKey` send
+    /// through the relay alone; see [`Settings::preferred_email_transport`].
+    /// If both fail, the [`SettingsError::EmailFallback`] says why each did.
+    pub async fn send_report_email(
+        &self,
+        subject: &str,
+        html_body: &str,
+    ) -> Result<(), SettingsError> {
+        if self.email_allow_empty_recipients() && !self.has_recipients() {
+            return Ok(());
+        }
+        #[cfg(feature = "smtp")]
+        if self.preferred_email_transport() == Some(crate::EmailTransport::Smtp) {
+            let message = self.build_message(subject, html_body, None)?;
+            self.take_email_send()?;
+            return self
+                .send_smtp(&message)
+                .await
+                .map_err(|source| SettingsError::SmtpSend {
+                    host: self.smtp_host().unwrap_or_default().to_string(),
+                    source,
+                });
+        }
+        if self.sendgrid_api_key().is_none() {
+            return Err(SettingsError::MissingField {
+                field: "SendgridApiKey",
+            });
+        }
+        let message = self.build_message(subject, html_body, None)?;
+        match self
+            .send_message_with(&self.try_get_sendgrid_sender()?, &message)
+            .await
+        {
+            Ok(()) => {}
+            // Counted against `EmailMaxPerHour` once already
+            #[cfg(feature = "smtp")]
+            Err(SettingsError::EmailSend { attempts, source })
+                if source.is_outage() && self.smtp_configured() =>
+            {
+                self.send_smtp(&message)
+                    .await
+                    .map_err(|smtp| SettingsError::EmailFallback {
+                        sendgrid_attempts: attempts,
+                        sendgrid: source,
+                        host: self.smtp_host().unwrap_or_default().to_string(),
+                        smtp,
+                    })?;
+            }
+            Err(error) => return Err(error),
+        }
+        if self.is_email_sandboxed() {
+            self.log_sandboxed_send(subject).await;
+        }
+        Ok(())
+    }
+
+    // The email went through, so a webhook that can't be reached only loses
+    // the notice
+    async fn log_sandboxed_send(&self, subject: &str) {
+        if self.log_webhook_min_level() > LogLevel::Warning {
+            return;
+        }
+        if let Ok(logger) = self.get_webhook_logger() {
+            let _ = logger
+                .post_log(
+                    LogLevel::Warning,
+                    &format!(
+                        "Report email '{}' was sent in SendGrid sandbox mode and not delivered",
+                        subject
+                    ),
+                )
+                .await;
+        }
+    }
+
+    // Invalid entries count, so that they are reported rather than skipped
+    fn has_recipients(&self) -> bool {
+        [
+            self.email_to_addresses().unwrap_or_default(),
+            self.email_cc_addresses(),
+            self.email_bcc_addresses(),
+        ]
+        .into_iter()
+        .any(|raw| split_address_list(raw).next().is_some())
+    }
+
+    /// Sends `message` through `transport`. Rate limiting (429), server errors
+    /// (5xx) and failed requests are retried up to `EmailMaxRetries` times,
+    /// waiting `EmailRetryBaseMs` and doubling the wait after each retry; other
+    /// rejections fail at once.
+    ///
+    /// With `EmailMaxPerHour` set, each message counts once against it, however
+    /// many attempts it takes. Once the limit is used up, this fails with
+    /// [`SettingsError::RateLimited`] without sending. The allowance is shared
+    /// by clones of these settings and kept across [`crate::SettingsHandle`]
+    /// reloads that don't change the limit.
+    ///
+    /// Needs a tokio runtime with the time driver enabled.
+    pub async fn send_message_with<T: MailTransport>(
+        &self,
+        transport: &T,
+        message: &Message,
+    ) -> Result<(), SettingsError> {
+        self.take_email_send()?;
+        let mut delay = self.email_retry_base_delay();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match transport.send(message).await {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            };
+            if !error.is_retryable() || attempts > self.email_max_retries() {
+                return Err(SettingsError::EmailSend {
+                    attempts,
+                    source: error.redact(
+                        self.sendgrid_api_key()
+                            .map(Secret::inspect)
+                            .unwrap_or_default(),
+                    ),
+                });
+            }
+            tokio::time::sleep(delay).await;
+            delay = delay.saturating_mul(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Answers each call with the next scripted result, then with success
+    struct ScriptedTransport(Mutex<Vec<Result<(), SendError>>>);
+
+    impl ScriptedTransport {
+        fn new(mut results: Vec<Result<(), SendError>>) -> ScriptedTransport {
+            results.reverse();
+            ScriptedTransport(Mutex::new(results))
+        }
+
+        fn remaining(&self) -> usize {
+            self.0.lock().unwrap().len()
+        }
+    }
+
+    impl MailTransport for ScriptedTransport {
+        async fn send(&self, _message: &Message) -> Result<(), SendError> {
+            self.0.lock().unwrap().pop().unwrap_or(Ok(()))
+        }
+    }
+
+    fn status(status: u16, body: &str) -> Result<(), SendError> {
+        Err(SendError::Status {
+            status,
+            body: body.to_string(),
+        })
+    }
+
+    fn settings() -> Settings {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("SG.secret-api-key")
+            .email_from_address("reports@example.com")
+            .email_to_addresses("ops@example.com")
+            .email_max_retries(2)
+            .email_retry_base_ms(1)
+            .build()
+            .unwrap()
+    }
+
+    async fn send(transport: &ScriptedTransport) -> Result<(), SettingsError> {
+        let settings = settings();
+        let message = settings.build_message("Report", "<p>Done</p>", None)?;
+        settings.send_message_with(transport, &message).await
+    }
+
+    #[tokio::test]
+    async fn test_sandboxed_send_is_logged() {
+        use wiremock::matchers::{body_partial_json, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "Level": "Warning",
+                "Message": "Report email 'Report' was sent in SendGrid sandbox mode and not delivered",
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let sandboxed = settings()
+            .with_email_sandbox_mode(true)
+            .with_log_webhook_uri(server.uri());
+
+        sandboxed.log_sandboxed_send("Report").await;
+        // Not posted below the minimum level, and no webhook is no error
+        sandboxed
+            .clone()
+            .with_log_webhook_min_level(LogLevel::Error)
+            .log_sandboxed_send("Report")
+            .await;
+        settings().log_sandboxed_send("Report").await;
+    }
+
+    #[tokio::test]
+    async fn test_report_email_goes_to_configured_host() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v3/mail/send"))
+            .and(header("authorization", "Bearer SG.secret-api-key"))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let settings = settings()
+            .with_sendgrid_api_key("SG.secret-api-key")
+            .with_sendgrid_api_host(format!("{}/", server.uri()));
+
+        settings
+            .send_report_email("Report", "<p>Done</p>")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            settings.sendgrid_mail_send_url(),
+            format!("{}/v3/mail/send", server.uri())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_sendgrid_times_out() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(202).set_delay(Duration::from_secs(3)))
+            .mount(&server)
+            .await;
+        let slow = settings()
+            .with_sendgrid_api_host(server.uri())
+            .with_http_request_timeout_seconds(1)
+            .with_email_max_retries(0);
+
+        let err = slow
+            .send_report_email("Report", "<p>Done</p>")
+            .await
+            .unwrap_err();
+
+        assert!(err.is_timeout(), "{}", err);
+        assert!(matches!(
+            err,
+            SettingsError::EmailSend {
+                attempts: 1,
+                source: SendError::Timeout(_),
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_no_recipients_fails_unless_allowed() {
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .email_from_address("reports@example.com")
+            .email_to_addresses(" ")
+            .build()
+            .unwrap();
+
+        let refused = settings
+            .clone()
+            .with_sendgrid_api_key("SG.secret-api-key")
+            .send_report_email("Report", "<p>Done</p>")
+            .await;
+        // Skipped before the missing API key or the network come into play
+        let skipped = settings
+            .with_email_allow_empty_recipients(true)
+            .send_report_email("Report", "<p>Done</p>")
+            .await;
+
+        assert!(matches!(refused, Err(SettingsError::NoRecipients)));
+        assert!(skipped.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_retries_rate_limits_and_server_errors() {
+        let transport = ScriptedTransport::new(vec![
+            status(429, "slow down"),
+            Err(SendError::Transport("connection reset".to_string())),
+        ]);
+        send(&transport).await.unwrap();
+
+        let transport = ScriptedTransport::new(vec![
+            status(503, "down"),
+            status(500, "down"),
+            status(502, "still down"),
+        ]);
+        let err = send(&transport).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Could not send email after 3 attempts: SendGrid returned status 502: still down"
+        );
+        assert_eq!(transport.remaining(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_fail_at_once() {
+        let transport = ScriptedTransport::new(vec![
+            status(401, "bad key SG.secret-api-key"),
+            status(500, "never sent"),
+        ]);
+
+        let err = send(&transport).await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            SettingsError::EmailSend {
+                attempts: 1,
+                source: SendError::Status { status: 401, .. }
+            }
+        ));
+        assert!(!err.to_string().contains("SG.secret-api-key"), "{}", err);
+        assert_eq!(transport.remaining(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sends_beyond_email_max_per_hour_are_rejected() {
+        let settings = settings().with_email_max_per_hour(2);
+        let message = settings
+            .build_message("Report", "<p>Done</p>", None)
+            .unwrap();
+        let transport = ScriptedTransport::new(vec![status(503, "down")]);
+
+        // The retried send counts once
+        settings
+            .send_message_with(&transport, &message)
+            .await
+            .unwrap();
+        settings
+            .clone()
+            .send_message_with(&transport, &message)
+            .await
+            .unwrap();
+        let err = settings
+            .send_message_with(&transport, &message)
+            .await
+            .unwrap_err();
+
+        let SettingsError::RateLimited { retry_after } = err else {
+            panic!("unexpected error: {}", err);
+        };
+        // Two an hour refill one every half hour
+        assert!(
+            retry_after <= Duration::from_secs(1800),
+            "{:?}",
+            retry_after
+        );
+        assert!(retry_after > Duration::from_secs(1790), "{:?}", retry_after);
+        assert_eq!(
+            SettingsError::RateLimited { retry_after }.to_string(),
+            "Email not sent: EmailMaxPerHour reached, try again in 1800s"
+        );
+    }
+
+    // A relay on a local port taking one connection, refusing the sender with
+    // `refusal` if given; the task returns the lines the client sent
+    #[cfg(feature = "smtp")]
+    async fn relay(refusal: Option<&'static str>) -> (u16, tokio::task::JoinHandle<Vec<String>>) {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 relay ready\r\n").await.unwrap();
+            let mut transcript = Vec::new();
+            let mut in_data = false;
+            while let Ok(Some(line)) = lines.next_line().await {
+                transcript.push(line.clone());
+                let reply = if in_data {
+                    if line != "." {
+                        continue;
+                    }
+                    in_data = false;
+                    "250 queued"
+                } else if line.starts_with("MAIL FROM") {
+                    refusal.unwrap_or("250 ok")
+                } else if line == "DATA" {
+                    in_data = true;
+                    "354 go ahead"
+                } else if line == "QUIT" {
+                    "221 bye"
+                } else {
+                    "250 ok"
+                };
+                write
+                    .write_all(format!("{}\r\n", reply).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            transcript
+        });
+        (port, task)
+    }
+
+    #[cfg(feature = "smtp")]
+    async fn failing_sendgrid(status: u16) -> wiremock::MockServer {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(status).set_body_string("unavailable"))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[cfg(feature = "smtp")]
+    fn with_relay(settings: Settings, server: &wiremock::MockServer, port: u16) -> Settings {
+        settings
+            .with_sendgrid_api_host(server.uri())
+            .with_email_max_retries(0)
+            .with_email_cc_addresses("audit@example.com")
+            .with_email_bcc_addresses("archive@example.com")
+            .with_smtp_host("127.0.0.1")
+            .with_smtp_port(port)
+            .with_smtp_use_start_tls(false)
+    }
+
+    #[cfg(feature = "smtp")]
+    #[tokio::test]
+    async fn test_sendgrid_outage_falls_back_to_smtp() {
+        let server = failing_sendgrid(503).await;
+        let (port, relay) = relay(None).await;
+
+        with_relay(settings(), &server, port)
+            .send_report_email("Report", "<p>Done</p>")
+            .await
+            .unwrap();
+
+        let transcript = relay.await.unwrap();
+        for expected in [
+            "MAIL FROM:<reports@example.com>",
+            "RCPT TO:<ops@example.com>",
+            "RCPT TO:<audit@example.com>",
+            "RCPT TO:<archive@example.com>",
+            "Subject: Report",
+            "<p>Done</p>",
+        ] {
+            assert!(
+                transcript.iter().any(|line| line == expected),
+                "{}\n{:#?}",
+                expected,
+                transcript
+            );
+        }
+    }
+
+    #[cfg(feature = "smtp")]
+    #[tokio::test]
+    async fn test_failed_fallback_names_both_transports() {
+        let server = failing_sendgrid(503).await;
+        let (port, relay) = relay(Some("554 relay access denied")).await;
+
+        let err = with_relay(settings(), &server, port)
+            .send_report_email("Report", "<p>Done</p>")
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(err, SettingsError::EmailFallback { .. }),
+            "{:?}",
+            err
+        );
+        let message = err.to_string();
+        assert!(
+            message.starts_with(
+                "Could not send email through SendGrid or the SMTP fallback; SendGrid after 1 \
+                 attempt: SendGrid returned status 503: unavailable; SMTP relay 127.0.0.1: the \
+                 relay rejected the message: "
+            ),
+            "{}",
+            message
+        );
+        assert!(message.contains("relay access denied"), "{}", message);
+        relay.abort();
+    }
+
+    #[cfg(feature = "smtp")]
+    #[tokio::test]
+    async fn test_rejected_message_is_not_sent_over_smtp() {
+        let server = failing_sendgrid(400).await;
+        let (port, relay) = relay(None).await;
+
+        let err = with_relay(settings(), &server, port)
+            .send_report_email("Report", "<p>Done</p>")
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(
+                err,
+                SettingsError::EmailSend {
+                    source: SendError::Status { status: 400, .. },
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+        assert!(!relay.is_finished());
+        relay.abort();
+    }
+
+    #[test]
+    fn test_retry_defaults() {
+        let settings = Settings::from_json_str(
+            r#"{
+                "DatabaseServer": "localhost",
+                "DatabaseName": "test_db",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123",
+                "LogWebhookUri": "https://example.com",
+                "SendgridApiKey": "sendgrid-api-key",
+                "EmailFromName": "Test",
+                "EmailFromAddress": "test@example.com",
+                "EmailToAddresses": "user1@example.com"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.email_max_retries(), DEFAULT_EMAIL_MAX_RETRIES);
+        assert_eq!(
+            settings.email_retry_base_delay(),
+            Duration::from_millis(DEFAULT_EMAIL_RETRY_BASE_MS)
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{FieldError, Settings};
+use url::{Host, Url};
+
+/// The SendGrid API host used when `SendgridApiHost` is not set.
+pub const DEFAULT_SENDGRID_API_HOST: &str = "https://api.sendgrid.com";
+
+impl Settings {
+    /// `SendgridApiHost`, e.g. `https://api.eu.sendgrid.com` for EU data
+    /// residency, or [`DEFAULT_SENDGRID_API_HOST`] if not set.
+    pub fn sendgrid_api_host(&self) -> &str {
+        self.sendgrid_api_host
+            .as_deref()
+            .map(str::trim)
+            .unwrap_or(DEFAULT_SENDGRID_API_HOST)
+    }
+
+    /// The mail send endpoint on [`Settings::sendgrid_api_host`], which
+    /// [`Settings::get_sendgrid_sender`] posts to.
+    pub fn sendgrid_mail_send_url(&self) -> String {
+        format!(
+            "{}/v3/mail/send",
+            self.sendgrid_api_host().trim_end_matches('/')
+        )
+    }
+
+    pub(crate) fn sendgrid_host_errors(&self) -> Vec<FieldError> {
+        match self.sendgrid_api_host.as_deref().map(parse_api_host) {
+            Some(Err(reason)) => vec![FieldError::new("SendgridApiHost", reason)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Plain http is only allowed to this machine, for local test servers; the API
+// key must not cross the network in the clear
+fn parse_api_host(raw: &str) -> Result<Url, String> {
+    let url = Url::parse(raw.trim()).map_err(|e| format!("is not a valid URL: {}", e))?;
+    let loopback = match url.host() {
+        Some(Host::Domain(domain)) => domain == "localhost",
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    };
+    match url.scheme() {
+        "https" => {}
+        "http" if loopback => {}
+        scheme => return Err(format!("must use https, not '{}'", scheme)),
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err("must be a host without a query or fragment".to_string());
+    }
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_and_configured_host() {
+        let default = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated"}"#,
+        )
+        .unwrap();
+        let eu = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "SendgridApiHost": "https://api.eu.sendgrid.com/"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            default.sendgrid_mail_send_url(),
+            "https://api.sendgrid.com/v3/mail/send"
+        );
+        assert_eq!(
+            eu.sendgrid_mail_send_url(),
+            "https://api.eu.sendgrid.com/v3/mail/send"
+        );
+        assert!(default.sendgrid_host_errors().is_empty());
+        assert!(eu.sendgrid_host_errors().is_empty());
+    }
+
+    #[test]
+    fn test_host_must_be_https() {
+        assert_eq!(
+            parse_api_host("http://api.eu.sendgrid.com").unwrap_err(),
+            "must use https, not 'http'"
+        );
+        assert_eq!(
+            parse_api_host("api.eu.sendgrid.com").unwrap_err(),
+            "is not a valid URL: relative URL without a base"
+        );
+        assert_eq!(
+            parse_api_host("https://api.eu.sendgrid.com?region=eu").unwrap_err(),
+            "must be a host without a query or fragment"
+        );
+        assert!(parse_api_host("http://127.0.0.1:8025").is_ok());
+        assert!(parse_api_host("http://localhost:8025").is_ok());
+    }
+}
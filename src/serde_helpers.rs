@@ -0,0 +1,222 @@
+use serde::de::{self, Deserializer, Unexpected, Visitor};
+use std::fmt;
+use std::marker::PhantomData;
+
+// Deserializers for the number and flag fields that also take their string
+// forms, e.g. `"1433"` or `" true "`, since some provisioning tools write every
+// value as a string. An empty string or null leaves the field unset.
+
+/// The unsigned integer types of the blob's number fields.
+pub(crate) trait Integer: Sized + TryFrom<u64> {
+    const MAX: u64;
+}
+
+impl Integer for u16 {
+    const MAX: u64 = u16::MAX as u64;
+}
+
+impl Integer for u32 {
+    const MAX: u64 = u32::MAX as u64;
+}
+
+impl Integer for u64 {
+    const MAX: u64 = u64::MAX;
+}
+
+/// For an `Option<u16>`, `Option<u32>` or `Option<u64>` field.
+pub(crate) fn number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Integer,
+{
+    deserializer.deserialize_any(NumberVisitor(PhantomData))
+}
+
+/// For an `Option<bool>` field.
+pub(crate) fn flag<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(FlagVisitor)
+}
+
+/// `true` or `false` in any case, as the blob and the env overrides spell them.
+pub(crate) fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "false" => Ok(false),
+        "true" => Ok(true),
+        _ => Err(format!("'{}' is not true or false", value)),
+    }
+}
+
+struct NumberVisitor<T>(PhantomData<T>);
+
+impl<T: Integer> NumberVisitor<T> {
+    fn in_range<E: de::Error>(&self, value: u64, unexpected: Unexpected) -> Result<Option<T>, E> {
+        T::try_from(value)
+            .map(Some)
+            .map_err(|_| E::invalid_value(unexpected, self))
+    }
+}
+
+impl<'de, T: Integer> Visitor<'de> for NumberVisitor<T> {
+    type Value = Option<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a whole number from 0 to {}", T::MAX)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Option<T>, E> {
+        self.in_range(value, Unexpected::Unsigned(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Option<T>, E> {
+        match u64::try_from(value) {
+            Ok(unsigned) => self.in_range(unsigned, Unexpected::Signed(value)),
+            Err(_) => Err(E::invalid_value(Unexpected::Signed(value), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Option<T>, E> {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            return Ok(None);
+        }
+        // Only digits, so that neither `+5` nor `14,33` passes for a number
+        if !trimmed.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(E::invalid_value(Unexpected::Str(value), &self));
+        }
+        match trimmed.parse::<u64>() {
+            Ok(parsed) => self.in_range(parsed, Unexpected::Str(value)),
+            Err(_) => Err(E::invalid_value(Unexpected::Str(value), &self)),
+        }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(None)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<T>, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+struct FlagVisitor;
+
+impl<'de> Visitor<'de> for FlagVisitor {
+    type Value = Option<bool>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("true or false")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Option<bool>, E> {
+        Ok(Some(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Option<bool>, E> {
+        match value.trim() {
+            "" => Ok(None),
+            trimmed => parse_bool(trimmed)
+                .map(Some)
+                .map_err(|_| E::invalid_value(Unexpected::Str(value), &self)),
+        }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<bool>, E> {
+        Ok(None)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<bool>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<bool>, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Settings;
+
+    #[test]
+    fn test_numbers_and_flags_as_strings() {
+        let native = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "DatabasePort": 1433, "DatabaseTrustCert": true, "EmailMaxRetries": 2}"#,
+        )
+        .unwrap();
+        let strings = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "DatabasePort": " 1433 ", "DatabaseTrustCert": "TRUE", "EmailMaxRetries": "2",
+                "Databases": {"warehouse": {
+                    "Server": "wh-sql", "Name": "warehouse", "Username": "reader",
+                    "Password": "secret", "Port": "14330"
+                }}}"#,
+        )
+        .unwrap();
+        let blank = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "DatabasePort": "", "DatabaseTrustCert": ""}"#,
+        )
+        .unwrap();
+
+        assert_eq!(strings.database_port(), Some(1433));
+        assert_eq!(strings.database_port(), native.database_port());
+        assert_eq!(strings.database_trust_cert, native.database_trust_cert);
+        assert_eq!(strings.email_max_retries, native.email_max_retries);
+        assert_eq!(strings.databases["warehouse"].port, Some(14330));
+        assert_eq!(blank.database_port(), None);
+        assert_eq!(blank.database_trust_cert, None);
+        assert_eq!(
+            Settings::from_toml(
+                "DatabaseServer = \"sql01\"\nDatabaseName = \"reports\"\n\
+                 DatabaseAuthMethod = \"Integrated\"\n\
+                 DatabasePort = \"1433\"\nDatabaseTrustCert = \"false\""
+            )
+            .unwrap()
+            .database_trust_cert,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_malformed_strings_are_precise_errors() {
+        let cases = [
+            (
+                r#"{"DatabaseTrustCert": "yes"}"#,
+                "DatabaseTrustCert: invalid value: string \"yes\", expected true or false",
+            ),
+            (
+                r#"{"DatabasePort": "14,33"}"#,
+                "DatabasePort: invalid value: string \"14,33\", expected a whole number from 0 \
+                 to 65535",
+            ),
+            (
+                r#"{"DatabasePort": 70000}"#,
+                "DatabasePort: invalid value: integer `70000`, expected a whole number from 0 \
+                 to 65535",
+            ),
+            (
+                r#"{"EmailMaxRetries": "-1"}"#,
+                "EmailMaxRetries: invalid value: string \"-1\", expected a whole number from 0 \
+                 to 4294967295",
+            ),
+            (
+                r#"{"DatabasePort": 14.5}"#,
+                "DatabasePort: invalid type: floating point `14.5`, expected a whole number \
+                 from 0 to 65535",
+            ),
+        ];
+
+        for (blob, message) in cases {
+            let err = Settings::from_json_str(blob).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use crate::{FieldError, Secret, Settings};
+#[cfg(feature = "smtp")]
+use lettre::message::header::ContentType;
+#[cfg(feature = "smtp")]
+use lettre::message::{Mailbox, MultiPart};
+#[cfg(feature = "smtp")]
+use lettre::transport::smtp::authentication::Credentials;
+#[cfg(feature = "smtp")]
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+#[cfg(feature = "smtp")]
+use serde::Deserialize;
+#[cfg(feature = "smtp")]
+use std::fmt;
+
+/// The SMTP port when `SmtpPort` is not set and `SmtpUseStartTls` is on.
+pub const DEFAULT_SMTP_PORT: u16 = 587;
+
+/// The SMTP port when `SmtpPort` is not set and `SmtpUseStartTls` is off.
+pub const DEFAULT_SMTP_PLAIN_PORT: u16 = 25;
+
+/// Which service [`Settings::send_report_email`] hands a message to.
+#[cfg(feature = "smtp")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTransport {
+    /// The SendGrid API, with `SendgridApiKey`.
+    SendGrid,
+    /// The SMTP relay in `SmtpHost`.
+    Smtp,
+}
+
+#[cfg(feature = "smtp")]
+impl fmt::Display for EmailTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmailTransport::SendGrid => write!(f, "SendGrid"),
+            EmailTransport::Smtp => write!(f, "SMTP"),
+        }
+    }
+}
+
+/// Why the SMTP relay did not take a message.
+#[cfg(feature = "smtp")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SmtpError {
+    /// The relay answered with an error code, e.g. 550 for an unknown mailbox.
+    Rejected(String),
+    /// The relay could not be reached, or the TLS upgrade or login failed.
+    Transport(String),
+    /// The relay stopped answering.
+    Timeout(String),
+    /// The message can't be sent over SMTP, e.g. a SendGrid template message.
+    InvalidMessage(String),
+}
+
+#[cfg(feature = "smtp")]
+impl std::error::Error for SmtpError {}
+
+#[cfg(feature = "smtp")]
+impl fmt::Display for SmtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmtpError::Rejected(reason) => write!(f, "the relay rejected the message: {}", reason),
+            SmtpError::Transport(reason) => write!(f, "{}", reason),
+            SmtpError::Timeout(reason) => write!(f, "timed out: {}", reason),
+            SmtpError::InvalidMessage(reason) => write!(f, "invalid message: {}", reason),
+        }
+    }
+}
+
+#[cfg(feature = "smtp")]
+impl From<lettre::transport::smtp::Error> for SmtpError {
+    fn from(e: lettre::transport::smtp::Error) -> SmtpError {
+        if e.is_timeout() {
+            SmtpError::Timeout(e.to_string())
+        } else if e.is_transient() || e.is_permanent() {
+            SmtpError::Rejected(e.to_string())
+        } else {
+            SmtpError::Transport(e.to_string())
+        }
+    }
+}
+
+impl Settings {
+    /// `SmtpHost`, the relay report emails fall back to, e.g.
+    /// `smtp.corp.example.com`.
+    pub fn smtp_host(&self) -> Option<&str> {
+        self.smtp_host.as_deref()
+    }
+
+    /// `SmtpPort`, or [`DEFAULT_SMTP_PORT`] if unset, or
+    /// [`DEFAULT_SMTP_PLAIN_PORT`] with `SmtpUseStartTls` off.
+    pub fn smtp_port(&self) -> u16 {
+        match self.smtp_port {
+            Some(port) => port,
+            None if self.smtp_use_start_tls() => DEFAULT_SMTP_PORT,
+            None => DEFAULT_SMTP_PLAIN_PORT,
+        }
+    }
+
+    pub fn smtp_username(&self) -> Option<&str> {
+        self.smtp_username.as_deref()
+    }
+
+    /// The relay password; call [`Secret::expose`] to read it.
+    pub fn smtp_password(&self) -> Option<&Secret> {
+        self.smtp_password.as_ref()
+    }
+
+    /// `SmtpUseStartTls`, on unless set to false: the connection is upgraded
+    /// with STARTTLS before the login and the message, and the send fails if
+    /// the relay doesn't offer it.
+    pub fn smtp_use_start_tls(&self) -> bool {
+        self.smtp_use_start_tls.unwrap_or(true)
+    }
+
+    pub(crate) fn smtp_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        match self.smtp_host().map(str::trim) {
+            Some("") => errors.push(FieldError::new("SmtpHost", "must not be empty")),
+            Some(host) if host.contains("://") => errors.push(FieldError::new(
+                "SmtpHost",
+                "must be a host name, not a URL",
+            )),
+            Some(host) if host.contains(char::is_whitespace) => errors.push(FieldError::new(
+                "SmtpHost",
+                format!("'{}' is not a host name", host),
+            )),
+            _ => {}
+        }
+        if self.smtp_port == Some(0) {
+            errors.push(FieldError::new("SmtpPort", "must not be 0"));
+        }
+        if self.smtp_password.is_some() && self.smtp_username.is_none() {
+            errors.push(FieldError::new(
+                "SmtpPassword",
+                "is set but SmtpUsername is not",
+            ));
+        }
+        if self.smtp_username.is_some() && self.smtp_password.is_none() {
+            errors.push(FieldError::new(
+                "SmtpPassword",
+                "must be set to use SmtpUsername",
+            ));
+        }
+        if self.smtp_host.is_none()
+            && (self.smtp_username.is_some() || self.smtp_password.is_some())
+        {
+            errors.push(FieldError::new(
+                "SmtpHost",
+                "must be set to use SmtpUsername and SmtpPassword",
+            ));
+        }
+        errors
+    }
+
+    pub(crate) fn smtp_warnings(&self) -> Vec<FieldError> {
+        if self.smtp_password.is_some() && !self.smtp_use_start_tls() {
+            vec![FieldError::new(
+                "SmtpUseStartTls",
+                "is off, so SmtpPassword is sent to the relay unencrypted",
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(feature = "smtp")]
+impl Settings {
+    /// The transport [`Settings::send_report_email`] tries first: SendGrid if
+    /// `SendgridApiKey` is set, the SMTP relay if only `SmtpHost` is, or
+    /// `None` if neither is.
+    ///
+    /// SMTP has no sandbox, so it is never used with `EmailSandboxMode` on.
+    pub fn preferred_email_transport(&self) -> Option<EmailTransport> {
+        if self.sendgrid_api_key().is_some() {
+            Some(EmailTransport::SendGrid)
+        } else if self.smtp_configured() {
+            Some(EmailTransport::Smtp)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn smtp_configured(&self) -> bool {
+        self.smtp_host().is_some() && !self.is_email_sandboxed()
+    }
+
+    /// Sends `message`, as built by [`Settings::build_message`], through the
+    /// `SmtpHost` relay: the same sender, reply-to, recipients, subject and
+    /// bodies, with the plain-text and HTML bodies as alternatives. Bcc
+    /// recipients get the message without being listed in its headers.
+    /// Categories, custom args and the unsubscribe group only mean something
+    /// to SendGrid and are left out; template messages and attachments are
+    /// refused.
+    ///
+    /// The transport keeps its own copy of `SmtpPassword` for the send, which
+    /// is not wiped.
+    pub(crate) async fn send_smtp(&self, message: &sendgrid::v3::Message) -> Result<(), SmtpError> {
+        let email = smtp_message(message)?;
+        let host = self.smtp_host().unwrap_or_default().trim();
+        let mut transport = if self.smtp_use_start_tls() {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        }
+        .port(self.smtp_port());
+        if let (Some(username), Some(password)) = (self.smtp_username(), self.smtp_password()) {
+            transport = transport.credentials(Credentials::new(
+                username.to_string(),
+                password.expose().to_string(),
+            ));
+        }
+        transport.build().send(email).await?;
+        Ok(())
+    }
+}
+
+// The parts of a SendGrid message's JSON body that SMTP has room for
+#[cfg(feature = "smtp")]
+#[derive(Deserialize)]
+struct Outgoing {
+    from: Address,
+    #[serde(default)]
+    subject: String,
+    personalizations: Vec<Recipients>,
+    reply_to: Option<Address>,
+    #[serde(default)]
+    content: Vec<Body>,
+    #[serde(default)]
+    attachments: Vec<serde_json::Value>,
+    template_id: Option<String>,
+}
+
+#[cfg(feature = "smtp")]
+#[derive(Deserialize)]
+struct Address {
+    email: String,
+    name: Option<String>,
+}
+
+#[cfg(feature = "smtp")]
+#[derive(Deserialize)]
+struct Recipients {
+    to: Vec<Address>,
+    #[serde(default)]
+    cc: Vec<Address>,
+    #[serde(default)]
+    bcc: Vec<Address>,
+}
+
+#[cfg(feature = "smtp")]
+#[derive(Deserialize)]
+struct Body {
+    #[serde(rename = "type")]
+    content_type: String,
+    value: String,
+}
+
+// Read back from the JSON SendGrid would get, so that both transports send
+// the very same message
+#[cfg(feature = "smtp")]
+fn smtp_message(message: &sendgrid::v3::Message) -> Result<lettre::Message, SmtpError> {
+    let invalid = |reason: String| SmtpError::InvalidMessage(reason);
+    let outgoing: Outgoing = serde_json::to_value(message)
+        .and_then(serde_json::from_value)
+        .map_err(|e| invalid(e.to_string()))?;
+    if outgoing.template_id.is_some() {
+        return Err(invalid(
+            "SendGrid templates can't be sent over SMTP".to_string(),
+        ));
+    }
+    if !outgoing.attachments.is_empty() {
+        return Err(invalid("attachments can't be sent over SMTP".to_string()));
+    }
+
+    let mut builder = lettre::Message::builder()
+        .from(mailbox(&outgoing.from)?)
+        .subject(outgoing.subject);
+    if let Some(reply_to) = &outgoing.reply_to {
+        builder = builder.reply_to(mailbox(reply_to)?);
+    }
+    for recipients in &outgoing.personalizations {
+        for to in &recipients.to {
+            builder = builder.to(mailbox(to)?);
+        }
+        for cc in &recipients.cc {
+            builder = builder.cc(mailbox(cc)?);
+        }
+        for bcc in &recipients.bcc {
+            builder = builder.bcc(mailbox(bcc)?);
+        }
+    }
+
+    let body = |content_type: &str| {
+        outgoing
+            .content
+            .iter()
+            .find(|body| body.content_type == content_type)
+            .map(|body| body.value.clone())
+    };
+    match (body("text/plain"), body("text/html")) {
+        (Some(plain), Some(html)) => {
+            builder.multipart(MultiPart::alternative_plain_html(plain, html))
+        }
+        (None, Some(html)) => builder.header(ContentType::TEXT_HTML).body(html),
+        (Some(plain), None) => builder.header(ContentType::TEXT_PLAIN).body(plain),
+        (None, None) => return Err(invalid("the message has no body".to_string())),
+    }
+    .map_err(|e| invalid(e.to_string()))
+}
+
+#[cfg(feature = "smtp")]
+fn mailbox(address: &Address) -> Result<Mailbox, SmtpError> {
+    let email = address.email.parse().map_err(|_| {
+        SmtpError::InvalidMessage(format!("'{}' is not a valid email address", address.email))
+    })?;
+    Ok(Mailbox::new(address.name.clone(), email))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smtp_fields_are_checked() {
+        let settings = Settings::for_tests()
+            .with_smtp_host("smtp://relay.example.com")
+            .with_smtp_port(0)
+            .with_smtp_username("svc_mail");
+
+        assert_eq!(
+            settings.validate(),
+            Err(vec![
+                FieldError::new("SmtpHost", "must be a host name, not a URL"),
+                FieldError::new("SmtpPort", "must not be 0"),
+                FieldError::new("SmtpPassword", "must be set to use SmtpUsername"),
+            ])
+        );
+        let orphan = Settings::for_tests().with_smtp_password("hunter2");
+        assert_eq!(
+            orphan.smtp_errors(),
+            [
+                FieldError::new("SmtpPassword", "is set but SmtpUsername is not"),
+                FieldError::new(
+                    "SmtpHost",
+                    "must be set to use SmtpUsername and SmtpPassword"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_port_follows_start_tls() {
+        let relay = Settings::for_tests().with_smtp_host("relay.example.com");
+
+        assert!(relay.smtp_use_start_tls());
+        assert_eq!(relay.smtp_port(), DEFAULT_SMTP_PORT);
+        let plain = relay.clone().with_smtp_use_start_tls(false);
+        assert_eq!(plain.smtp_port(), DEFAULT_SMTP_PLAIN_PORT);
+        assert_eq!(plain.clone().with_smtp_port(2525).smtp_port(), 2525);
+        assert!(plain.warnings().is_empty());
+        assert_eq!(
+            plain
+                .with_smtp_username("svc_mail")
+                .with_smtp_password("hunter2")
+                .warnings(),
+            [FieldError::new(
+                "SmtpUseStartTls",
+                "is off, so SmtpPassword is sent to the relay unencrypted"
+            )]
+        );
+    }
+
+    #[cfg(feature = "smtp")]
+    #[test]
+    fn test_preferred_transport() {
+        let settings = Settings::for_tests().with_smtp_host("relay.example.com");
+        let smtp_only = Settings::from_json_str(
+            r#"{
+                "DatabaseServer": "localhost",
+                "DatabaseName": "test_db",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123",
+                "EmailFromAddress": "reports@example.com",
+                "SmtpHost": "relay.example.com"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings.preferred_email_transport(),
+            Some(EmailTransport::SendGrid)
+        );
+        assert_eq!(
+            smtp_only.preferred_email_transport(),
+            Some(EmailTransport::Smtp)
+        );
+        assert_eq!(
+            smtp_only
+                .clone()
+                .with_email_sandbox_mode(true)
+                .preferred_email_transport(),
+            None
+        );
+        assert_eq!(
+            Settings::for_tests().preferred_email_transport(),
+            Some(EmailTransport::SendGrid)
+        );
+    }
+
+    #[cfg(feature = "smtp")]
+    #[test]
+    fn test_message_matches_sendgrid_one() {
+        let settings = Settings::for_tests();
+        let message = settings
+            .build_message("Nightly sales", "<p>Done</p>", Some("Done"))
+            .unwrap();
+
+        let email = smtp_message(&message).unwrap();
+
+        let recipients: Vec<String> = email
+            .envelope()
+            .to()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            recipients,
+            ["to@example.com", "cc@example.com", "bcc@example.com"]
+        );
+        let formatted = String::from_utf8(email.formatted()).unwrap();
+        for expected in [
+            "From: \"Test Reports\" <reports@example.com>",
+            "Reply-To: \"Test Support\" <reply@example.com>",
+            "To: to@example.com",
+            "Cc: cc@example.com",
+            "Subject: Nightly sales",
+            "Content-Type: multipart/alternative",
+            "Content-Type: text/plain; charset=utf-8",
+            "Content-Type: text/html; charset=utf-8",
+            "<p>Done</p>",
+        ] {
+            assert!(formatted.contains(expected), "{}\n{}", expected, formatted);
+        }
+        assert!(!formatted.contains("bcc@example.com"), "{}", formatted);
+        let template = settings
+            .build_template_message(&serde_json::json!({"Total": 3}))
+            .unwrap();
+        assert!(matches!(
+            smtp_message(&template),
+            Err(SmtpError::InvalidMessage(_))
+        ));
+    }
+}
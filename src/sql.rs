@@ -0,0 +1,950 @@
+use crate::{FieldError, Settings, SettingsError};
+use serde::{de, Deserialize, Deserializer, Serialize};
+#[cfg(feature = "mssql")]
+use ssql::prelude::tiberius::{AuthMethod, Config, EncryptionLevel};
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The port SQL Server listens on unless configured otherwise.
+pub const DEFAULT_DATABASE_PORT: u16 = 1433;
+
+/// The longest connect, command or pool checkout timeout [`Settings::validate`] accepts.
+pub const MAX_DATABASE_TIMEOUT_SECONDS: u64 = 600;
+
+/// The application name reported to SQL Server (as `APP_NAME()`) unless
+/// `ApplicationName` says otherwise.
+pub const DEFAULT_APPLICATION_NAME: &str = "Report Service";
+
+/// How the connection to SQL Server is encrypted, read from `DatabaseEncryption`.
+///
+/// The blob value is matched case-insensitively.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum DatabaseEncryption {
+    /// Only the login packet is encrypted.
+    #[default]
+    Off,
+    /// Encrypt everything if the server supports it.
+    On,
+    /// Encrypt everything, and fail if the server does not support it.
+    Required,
+}
+
+impl DatabaseEncryption {
+    const NAMES: &'static str = "Off, On, Required";
+
+    #[cfg(feature = "mssql")]
+    fn level(self) -> EncryptionLevel {
+        match self {
+            DatabaseEncryption::Off => EncryptionLevel::Off,
+            DatabaseEncryption::On => EncryptionLevel::On,
+            DatabaseEncryption::Required => EncryptionLevel::Required,
+        }
+    }
+}
+
+impl FromStr for DatabaseEncryption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DatabaseEncryption, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(DatabaseEncryption::Off),
+            "on" => Ok(DatabaseEncryption::On),
+            "required" => Ok(DatabaseEncryption::Required),
+            _ => Err(format!(
+                "'{}' is not a valid encryption level, expected one of: {}",
+                s,
+                DatabaseEncryption::NAMES
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DatabaseEncryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl<'de> Deserialize<'de> for DatabaseEncryption {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// How to log in to SQL Server, read from `DatabaseAuthMethod`.
+///
+/// The blob value is matched case-insensitively.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum DatabaseAuthMethod {
+    /// A SQL Server login, using `DatabaseUsername` and `DatabasePassword`.
+    #[default]
+    SqlServer,
+    /// The Windows identity the process runs as. Only available on Windows
+    /// builds; the username and password fields are not needed.
+    Integrated,
+}
+
+impl DatabaseAuthMethod {
+    const NAMES: &'static str = "SqlServer, Integrated";
+}
+
+impl FromStr for DatabaseAuthMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DatabaseAuthMethod, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "sqlserver" => Ok(DatabaseAuthMethod::SqlServer),
+            "integrated" => Ok(DatabaseAuthMethod::Integrated),
+            _ => Err(format!(
+                "'{}' is not a valid authentication method, expected one of: {}",
+                s,
+                DatabaseAuthMethod::NAMES
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DatabaseAuthMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl<'de> Deserialize<'de> for DatabaseAuthMethod {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// `DatabaseServer` split into host, named instance and port suffix.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ServerAddress<'a> {
+    pub host: &'a str,
+    pub instance: Option<&'a str>,
+    pub port: Option<u16>,
+}
+
+/// Accepts `host`, `host\instance`, `host,port` (the SQL Server convention) and
+/// `host:port`. A value with more than one colon is taken to be a bare IPv6
+/// address. A doubled backslash, as left behind by over-eager escaping, counts
+/// as a single one.
+pub(crate) fn parse_server(raw: &str) -> Result<ServerAddress<'_>, String> {
+    let raw = raw.trim();
+    let split = raw.rsplit_once(',').or_else(|| match raw.split_once(':') {
+        Some((host, port)) if !port.contains(':') => Some((host, port)),
+        _ => None,
+    });
+
+    let (host, port) = match split {
+        None => (raw, None),
+        Some((host, port)) => {
+            let port = port.trim();
+            match port.parse::<u16>() {
+                Ok(number) if number != 0 => (host.trim(), Some(number)),
+                _ => return Err(format!("'{}' is not a valid port", port)),
+            }
+        }
+    };
+
+    let (host, instance) = match host.split_once('\\') {
+        None => (host, None),
+        Some((host, instance)) => {
+            let instance = instance.trim_start_matches('\\');
+            if host.is_empty() || instance.is_empty() || instance.contains('\\') {
+                return Err(format!("'{}' is not a valid host\\instance name", raw));
+            }
+            (host, Some(instance))
+        }
+    };
+
+    Ok(ServerAddress {
+        host,
+        instance,
+        port,
+    })
+}
+
+/// The parts of a connection that differ between the flat database fields and
+/// the entries of `Databases`.
+// Only the address and auth method are checked without a config to build
+#[cfg_attr(not(feature = "mssql"), allow(dead_code))]
+pub(crate) struct Target<'a> {
+    pub server: &'a str,
+    pub port: Option<u16>,
+    pub name: &'a str,
+    pub username: &'a str,
+    pub password: &'a str,
+    pub auth_method: DatabaseAuthMethod,
+}
+
+/// Combines a server with an explicit port; the port is the explicit one, else
+/// the server's suffix, else unset. Errors name the flat fields.
+pub(crate) fn resolve_address<'a>(
+    server: &'a str,
+    port: Option<u16>,
+    errors: &mut Vec<FieldError>,
+) -> ServerAddress<'a> {
+    let address = parse_server(server).unwrap_or_else(|reason| {
+        errors.push(FieldError::new("DatabaseServer", reason));
+        ServerAddress {
+            host: server.trim(),
+            instance: None,
+            port: None,
+        }
+    });
+    if let (Some(suffix), Some(port)) = (address.port, port) {
+        if suffix != port {
+            errors.push(FieldError::new(
+                "DatabasePort",
+                format!(
+                    "is {} but DatabaseServer '{}' specifies port {}",
+                    port, server, suffix
+                ),
+            ));
+        }
+    }
+    let port = port.or(address.port);
+    if let (Some(instance), Some(port)) = (address.instance, port) {
+        errors.push(FieldError::new(
+            "DatabaseServer",
+            format!(
+                "names instance '{}' but port {} is also set; give one or the other",
+                instance, port
+            ),
+        ));
+    }
+
+    ServerAddress { port, ..address }
+}
+
+impl Settings {
+    /// The tiberius configuration for the report database.
+    ///
+    /// With `DatabaseConnectionString` set, the config is parsed from it by
+    /// tiberius, and any individual database field that is also set wins over
+    /// the matching part of the string; see [`Settings::warnings`].
+    ///
+    /// The server certificate is accepted as-is if [`Settings::database_trust_cert`]
+    /// says so. Otherwise it must chain to the system roots or, with
+    /// `DatabaseCaCertPath` set, to the CA in that bundle; tiberius' native-tls
+    /// backend adds the CA to the system roots rather than replacing them.
+    /// The bundle is read when the settings are validated, and again by
+    /// tiberius when connecting.
+    ///
+    /// This is always the primary server, even with `DatabaseFailoverServer`
+    /// set; see [`Settings::get_sql_settings_failover`] and
+    /// [`Settings::connect_with_failover`].
+    ///
+    /// This assumes the settings pass [`Settings::validate`]; where fields
+    /// conflict, e.g. a `DatabaseServer` port suffix that disagrees with
+    /// `DatabasePort`, the explicit field wins. Use
+    /// [`Settings::try_get_sql_settings`] to have conflicts reported instead.
+    #[cfg(feature = "mssql")]
+    pub fn get_sql_settings(&self) -> Config {
+        self.sql_config().0
+    }
+
+    /// Like [`Settings::get_sql_settings`], but fails on conflicting or malformed
+    /// database fields.
+    #[cfg(feature = "mssql")]
+    pub fn try_get_sql_settings(&self) -> Result<Config, SettingsError> {
+        let (config, errors) = self.sql_config();
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(SettingsError::Validation(errors))
+        }
+    }
+
+    /// `DatabaseConnectTimeoutSeconds` as given in the blob.
+    pub fn database_connect_timeout_seconds(&self) -> Option<u64> {
+        self.database_connect_timeout_seconds
+    }
+
+    /// `DatabaseCommandTimeoutSeconds` as given in the blob.
+    pub fn database_command_timeout_seconds(&self) -> Option<u64> {
+        self.database_command_timeout_seconds
+    }
+
+    /// How long to wait for the connection to the database, or `None` to wait
+    /// as long as the platform does.
+    ///
+    /// tiberius has no timeout of its own, so callers apply it themselves, e.g.
+    /// by wrapping both `TcpStream::connect(config.get_addr())` and
+    /// `Client::connect` in one `tokio::time::timeout`.
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.database_connect_timeout_seconds
+            .map(Duration::from_secs)
+    }
+
+    /// How long a single query may run, or `None` for no limit. Like
+    /// [`Settings::connect_timeout`], this has to be applied by the caller around
+    /// each query future.
+    pub fn command_timeout(&self) -> Option<Duration> {
+        self.database_command_timeout_seconds
+            .map(Duration::from_secs)
+    }
+
+    pub(crate) fn timeout_errors(&self) -> Vec<FieldError> {
+        let timeouts = [
+            (
+                "DatabaseConnectTimeoutSeconds",
+                self.database_connect_timeout_seconds,
+            ),
+            (
+                "DatabaseCommandTimeoutSeconds",
+                self.database_command_timeout_seconds,
+            ),
+            (
+                "DatabasePoolTimeoutSeconds",
+                self.database_pool_timeout_seconds,
+            ),
+        ];
+        timeouts
+            .into_iter()
+            .filter_map(|(field, seconds)| match seconds {
+                Some(seconds) if seconds == 0 || seconds > MAX_DATABASE_TIMEOUT_SECONDS => {
+                    Some(FieldError::new(
+                        field,
+                        format!(
+                            "must be between 1 and {} seconds, got {}",
+                            MAX_DATABASE_TIMEOUT_SECONDS, seconds
+                        ),
+                    ))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The problems with the database fields that [`Settings::validate`] reports.
+    /// Without the `mssql` feature a connection string is not test-parsed by
+    /// tiberius, but everything else is checked the same way.
+    pub(crate) fn sql_errors(&self) -> Vec<FieldError> {
+        #[cfg(feature = "mssql")]
+        return self.sql_config().1;
+
+        #[cfg(not(feature = "mssql"))]
+        {
+            let mut errors = Vec::new();
+            match self.database_connection_string {
+                Some(_) => {
+                    self.connection_string_pairs(&mut errors);
+                }
+                None => {
+                    self.check_target(&self.flat_target(), &mut errors);
+                }
+            }
+            errors.extend(self.ca_cert_errors());
+            errors
+        }
+    }
+
+    // Builds the best config it can, collecting every problem on the way, so
+    // the lenient and strict getters and validate() can't drift apart
+    #[cfg(feature = "mssql")]
+    pub(crate) fn sql_config(&self) -> (Config, Vec<FieldError>) {
+        let mut errors = Vec::new();
+        let config = match self.database_connection_string {
+            Some(_) => self.connection_string_config(&mut errors),
+            None => self.target_config(&self.flat_target(), &mut errors),
+        };
+        errors.extend(self.ca_cert_errors());
+        (config, errors)
+    }
+
+    /// Fails with [`SettingsError::Validation`] if `DatabaseCaCertPath` is set
+    /// but can't be used; see [`Settings::finish_load`].
+    pub(crate) fn check_ca_cert(&self) -> Result<(), SettingsError> {
+        let errors = self.ca_cert_errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::Validation(errors))
+        }
+    }
+
+    // Read when loading rather than when connecting, so that a missing bundle
+    // fails the load naming the path instead of a connection with
+    // tiberius' "Could not read provided CA certificate!"
+    fn ca_cert_errors(&self) -> Vec<FieldError> {
+        let Some(path) = self.database_ca_cert_path() else {
+            return Vec::new();
+        };
+        let mut errors = Vec::new();
+        if self.database_trust_cert == Some(true) {
+            errors.push(FieldError::new(
+                "DatabaseTrustCert",
+                "must not be true with DatabaseCaCertPath set",
+            ));
+        }
+        // The extensions tiberius reads as PEM
+        let pem_extension = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pem") || ext.eq_ignore_ascii_case("crt"));
+        if !pem_extension {
+            errors.push(FieldError::new(
+                "DatabaseCaCertPath",
+                format!("'{}' must be a .pem or .crt file", path.display()),
+            ));
+        }
+        match fs::read(path) {
+            Err(e) => errors.push(FieldError::new(
+                "DatabaseCaCertPath",
+                format!("could not read '{}': {}", path.display(), e),
+            )),
+            Ok(pem) if !String::from_utf8_lossy(&pem).contains("-----BEGIN CERTIFICATE-----") => {
+                errors.push(FieldError::new(
+                    "DatabaseCaCertPath",
+                    format!("'{}' holds no PEM certificate", path.display()),
+                ))
+            }
+            Ok(_) => {}
+        }
+        errors
+    }
+
+    pub(crate) fn flat_target(&self) -> Target<'_> {
+        Target {
+            server: &self.database_server,
+            port: self.database_port,
+            name: &self.database_name,
+            username: &self.database_username,
+            password: self.expose_database_password(),
+            auth_method: self.database_auth_method(),
+        }
+    }
+
+    /// Collects every problem [`Settings::target_config`] would run into, and
+    /// resolves the address it connects to.
+    pub(crate) fn check_target<'a>(
+        &self,
+        target: &Target<'a>,
+        errors: &mut Vec<FieldError>,
+    ) -> ServerAddress<'a> {
+        let address = resolve_address(target.server, target.port, errors);
+        #[cfg(not(windows))]
+        if target.auth_method == DatabaseAuthMethod::Integrated {
+            errors.push(FieldError::new(
+                "DatabaseAuthMethod",
+                "Integrated authentication is only supported on Windows builds",
+            ));
+        }
+        address
+    }
+
+    // Everything but the target itself (encryption, application name and so
+    // on) is shared by the flat fields and every entry of `Databases`
+    #[cfg(feature = "mssql")]
+    pub(crate) fn target_config(
+        &self,
+        target: &Target<'_>,
+        errors: &mut Vec<FieldError>,
+    ) -> Config {
+        let address = self.check_target(target, errors);
+        self.config_at(&address, target)
+    }
+
+    // The config for `target` at `address`, which the failover server replaces
+    // the target's own with
+    #[cfg(feature = "mssql")]
+    pub(crate) fn config_at(&self, address: &ServerAddress<'_>, target: &Target<'_>) -> Config {
+        let mut config = Config::new();
+        config.host(address.host);
+        match (address.instance, address.port) {
+            // With no port, tiberius asks SQL Browser where the instance listens
+            (Some(instance), None) => config.instance_name(instance),
+            (Some(instance), Some(port)) => {
+                config.instance_name(instance);
+                config.port(port);
+            }
+            (None, port) => config.port(port.unwrap_or(DEFAULT_DATABASE_PORT)),
+        }
+        config.application_name(self.application_name());
+        config.readonly(self.database_read_only_intent());
+        config.database(target.name);
+        match target.auth_method {
+            DatabaseAuthMethod::SqlServer => {
+                config.authentication(AuthMethod::sql_server(target.username, target.password))
+            }
+            #[cfg(windows)]
+            DatabaseAuthMethod::Integrated => config.authentication(AuthMethod::Integrated),
+            // Leaving the config without credentials makes the login fail
+            // loudly rather than fall back to a SQL login; check_target has
+            // reported it
+            #[cfg(not(windows))]
+            DatabaseAuthMethod::Integrated => {}
+        }
+        config.encryption(self.database_encryption().level());
+        if let Some(path) = self.database_ca_cert_path() {
+            config.trust_cert_ca(path.display());
+        } else if self.trusts_cert() {
+            config.trust_cert();
+        }
+
+        config
+    }
+
+    /// `DatabaseServer` combined with `DatabasePort`; see [`resolve_address`].
+    pub(crate) fn sql_address(&self, errors: &mut Vec<FieldError>) -> ServerAddress<'_> {
+        resolve_address(&self.database_server, self.database_port, errors)
+    }
+
+    // A CA bundle wins over `DatabaseTrustCert`, which validate() rejects next
+    // to it; tiberius panics if asked for both
+    pub(crate) fn trusts_cert(&self) -> bool {
+        self.database_ca_cert_path.is_none() && self.database_trust_cert()
+    }
+}
+
+/// Same as [`Settings::get_sql_settings`], so connection helpers can take
+/// `impl Into<Config>`.
+///
+/// There is no separate `TryFrom<&Settings>`: std already derives an infallible
+/// one from this impl, and a second would conflict with it. Use
+/// [`Settings::try_get_sql_settings`] when conflicts should be reported.
+#[cfg(feature = "mssql")]
+impl From<&Settings> for Config {
+    fn from(settings: &Settings) -> Config {
+        settings.get_sql_settings()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SettingsBuilder;
+
+    fn settings() -> SettingsBuilder {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+    }
+
+    #[cfg(feature = "mssql")]
+    fn settings_with_server(server: &str) -> Settings {
+        settings().database_server(server).build().unwrap()
+    }
+
+    #[test]
+    fn test_parse_server_spellings() {
+        let cases = [
+            ("localhost", "localhost", None, None),
+            ("replica,14330", "replica", None, Some(14330)),
+            ("replica:14330", "replica", None, Some(14330)),
+            (" replica , 14330 ", "replica", None, Some(14330)),
+            ("::1", "::1", None, None),
+            ("SQLPROD01\\REPORTS", "SQLPROD01", Some("REPORTS"), None),
+            ("SQLPROD01\\\\REPORTS", "SQLPROD01", Some("REPORTS"), None),
+            (
+                "SQLPROD01\\REPORTS,14330",
+                "SQLPROD01",
+                Some("REPORTS"),
+                Some(14330),
+            ),
+        ];
+        for (raw, host, instance, port) in cases {
+            assert_eq!(
+                parse_server(raw),
+                Ok(ServerAddress {
+                    host,
+                    instance,
+                    port
+                }),
+                "{}",
+                raw
+            );
+        }
+        assert!(parse_server("SQLPROD01\\").is_err());
+        assert_eq!(
+            parse_server("replica,port").unwrap_err(),
+            "'port' is not a valid port"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_get_addr_reflects_custom_port() {
+        for builder in [
+            settings().database_port(14330),
+            settings().database_server("localhost,14330"),
+            settings().database_server("localhost:14330"),
+            settings()
+                .database_server("localhost,14330")
+                .database_port(14330),
+        ] {
+            let config = builder.build().unwrap().try_get_sql_settings().unwrap();
+            assert_eq!(config.get_addr(), "localhost:14330");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_into_config_matches_get_sql_settings() {
+        for settings in [
+            settings().build().unwrap(),
+            settings().database_port(14330).build().unwrap(),
+            settings_with_server(r"sql01\REPORTS"),
+        ] {
+            let config: Config = (&settings).into();
+            assert_eq!(config.get_addr(), settings.get_sql_settings().get_addr());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_port_is_read_from_blob() {
+        let settings: Settings = serde_json::from_str(
+            r#"{
+                "DatabaseServer": "replica",
+                "DatabaseName": "test_db",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123",
+                "DatabasePort": 14330,
+                "LogWebhookUri": "https://example.com",
+                "SendgridApiKey": "sendgrid-api-key",
+                "EmailFromName": "Test",
+                "EmailFromAddress": "test@example.com",
+                "EmailToAddresses": "user1@example.com"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.get_sql_settings().get_addr(), "replica:14330");
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_encryption_level_and_trust_cert() {
+        let debug =
+            |builder: SettingsBuilder| format!("{:?}", builder.build().unwrap().get_sql_settings());
+
+        let off = debug(settings());
+        assert!(off.contains("encryption: Off"), "{}", off);
+        assert!(off.contains("trust: TrustAll"), "{}", off);
+
+        let required = debug(settings().database_encryption(DatabaseEncryption::Required));
+        assert!(required.contains("encryption: Required"), "{}", required);
+        assert!(required.contains("trust: Default"), "{}", required);
+
+        let trusted = debug(
+            settings()
+                .database_encryption(DatabaseEncryption::On)
+                .database_trust_cert(true),
+        );
+        assert!(trusted.contains("encryption: On"), "{}", trusted);
+        assert!(trusted.contains("trust: TrustAll"), "{}", trusted);
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_trust_all_ca_bundle_and_system_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("corp-sql-ca.pem");
+        fs::write(
+            &bundle,
+            "-----BEGIN CERTIFICATE-----\nMIIBszCCAVmgAwIBAgIU\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        let encrypted = || settings().database_encryption(DatabaseEncryption::Required);
+        let trust = |builder: SettingsBuilder| {
+            let settings = builder.build().unwrap();
+            assert_eq!(settings.validate(), Ok(()));
+            let debug = format!("{:?}", settings.try_get_sql_settings().unwrap());
+            debug.split("trust: ").nth(1).unwrap().to_string()
+        };
+
+        let trust_all = trust(encrypted().database_trust_cert(true));
+        assert!(trust_all.starts_with("TrustAll"), "{}", trust_all);
+
+        for builder in [
+            encrypted().database_ca_cert_path(bundle.display().to_string()),
+            // A bundle also stops unencrypted logins trusting any certificate
+            settings().database_ca_cert_path(bundle.display().to_string()),
+        ] {
+            let ca = trust(builder);
+            assert!(
+                ca.starts_with(&format!("CaCertificateLocation({:?})", bundle)),
+                "{}",
+                ca
+            );
+        }
+
+        for builder in [
+            encrypted(),
+            encrypted().database_trust_cert(false),
+            settings().database_trust_cert(false),
+        ] {
+            let strict = trust(builder);
+            assert!(strict.starts_with("Default"), "{}", strict);
+        }
+    }
+
+    #[test]
+    fn test_ca_bundle_is_checked_by_validate() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.pem");
+        let not_pem = dir.path().join("ca.crt");
+        fs::write(&not_pem, [0x30, 0x82, 0x01, 0xb3]).unwrap();
+        let blob = |path: &std::path::Path, trust: bool| {
+            serde_json::json!({
+                "DatabaseServer": "sql01",
+                "DatabaseName": "reports",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123",
+                "DatabaseEncryption": "Required",
+                "DatabaseTrustCert": trust,
+                "DatabaseCaCertPath": path,
+            })
+            .to_string()
+        };
+
+        let messages = |blob: String| {
+            let settings = Settings::from_json_str(&blob).unwrap();
+            settings
+                .validate()
+                .unwrap_err()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let missing_errors = messages(blob(&missing, false));
+        assert_eq!(missing_errors.len(), 1, "{:?}", missing_errors);
+        assert!(
+            missing_errors[0].starts_with(&format!(
+                "DatabaseCaCertPath: could not read '{}': ",
+                missing.display()
+            )),
+            "{:?}",
+            missing_errors
+        );
+        assert_eq!(
+            messages(blob(&not_pem, true)),
+            [
+                "DatabaseTrustCert: must not be true with DatabaseCaCertPath set".to_string(),
+                format!(
+                    "DatabaseCaCertPath: '{}' holds no PEM certificate",
+                    not_pem.display()
+                ),
+            ]
+        );
+        assert_eq!(
+            messages(blob(&dir.path().join("ca.der"), false))[0],
+            format!(
+                "DatabaseCaCertPath: '{}' must be a .pem or .crt file",
+                dir.path().join("ca.der").display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_encryption_is_case_insensitive_and_named_on_error() {
+        let parse = |value: &str| serde_json::from_value::<DatabaseEncryption>(value.into());
+
+        assert_eq!(parse("required").unwrap(), DatabaseEncryption::Required);
+        assert_eq!(parse("ON").unwrap(), DatabaseEncryption::On);
+        assert_eq!(
+            parse("Strict").unwrap_err().to_string(),
+            "'Strict' is not a valid encryption level, expected one of: Off, On, Required"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_integrated_auth_needs_no_credentials() {
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_auth_method(DatabaseAuthMethod::Integrated)
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+
+        #[cfg(windows)]
+        {
+            assert_eq!(settings.validate(), Ok(()));
+            let debug = format!("{:?}", settings.get_sql_settings());
+            assert!(debug.contains("auth: Integrated"), "{}", debug);
+        }
+        #[cfg(not(windows))]
+        assert_eq!(
+            settings.validate(),
+            Err(vec![FieldError::new(
+                "DatabaseAuthMethod",
+                "Integrated authentication is only supported on Windows builds"
+            )])
+        );
+    }
+
+    #[test]
+    fn test_sql_server_auth_still_needs_credentials() {
+        let err = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_auth_method(DatabaseAuthMethod::SqlServer)
+            .log_webhook_uri("https://example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid settings: DatabaseUsername: is required; DatabasePassword: is required"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_named_instance_from_json_blob() {
+        let settings: Settings = serde_json::from_str(
+            r#"{
+                "DatabaseServer": "SQLPROD01\\REPORTS",
+                "DatabaseName": "test_db",
+                "DatabaseUsername": "admin",
+                "DatabasePassword": "password123",
+                "LogWebhookUri": "https://example.com",
+                "SendgridApiKey": "sendgrid-api-key",
+                "EmailFromName": "Test",
+                "EmailFromAddress": "test@example.com",
+                "EmailToAddresses": "user1@example.com"
+            }"#,
+        )
+        .unwrap();
+
+        let config = settings.try_get_sql_settings().unwrap();
+        let debug = format!("{:?}", config);
+        assert!(
+            debug.contains(r#"instance_name: Some("REPORTS")"#),
+            "{}",
+            debug
+        );
+        assert_eq!(config.get_addr(), "SQLPROD01:1434");
+
+        let plain = settings_with_server("SQLPROD01").get_sql_settings();
+        assert!(format!("{:?}", plain).contains("instance_name: None"));
+        assert_eq!(plain.get_addr(), "SQLPROD01:1433");
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_instance_and_port_conflict() {
+        let err = settings_with_server("SQLPROD01\\REPORTS,14330")
+            .try_get_sql_settings()
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid settings: DatabaseServer: names instance 'REPORTS' but port 14330 is also set; give one or the other"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_application_name_default_and_override() {
+        let app_name = |settings: &Settings| {
+            let debug = format!("{:?}", settings.get_sql_settings());
+            debug.contains(&format!(
+                "application_name: Some({:?})",
+                settings.application_name()
+            ))
+        };
+
+        let default = settings().build().unwrap();
+        assert_eq!(default.application_name(), "Report Service");
+        assert!(app_name(&default));
+
+        let from_blob = settings()
+            .application_name("Nightly Sales")
+            .build()
+            .unwrap();
+        assert_eq!(from_blob.application_name(), "Nightly Sales");
+        assert!(app_name(&from_blob));
+
+        let overridden = from_blob.with_application_name("Sales Backfill");
+        assert_eq!(overridden.application_name(), "Sales Backfill");
+        assert!(app_name(&overridden));
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_read_only_intent() {
+        let default = format!("{:?}", settings().build().unwrap().get_sql_settings());
+        assert!(default.contains("readonly: false"), "{}", default);
+
+        let read_only = settings().database_read_only_intent(true).build().unwrap();
+        let debug = format!("{:?}", read_only.get_sql_settings());
+        assert!(debug.contains("readonly: true"), "{}", debug);
+        assert!(read_only
+            .to_ado_connection_string()
+            .ends_with(";ApplicationIntent=ReadOnly"));
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let unset = settings().build().unwrap();
+        assert_eq!(unset.connect_timeout(), None);
+        assert_eq!(unset.command_timeout(), None);
+
+        let set = settings()
+            .database_connect_timeout_seconds(15)
+            .database_command_timeout_seconds(600)
+            .build()
+            .unwrap();
+        assert_eq!(set.connect_timeout(), Some(Duration::from_secs(15)));
+        assert_eq!(set.command_timeout(), Some(Duration::from_secs(600)));
+        assert_eq!(set.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_timeouts_out_of_range_are_rejected() {
+        let settings = settings()
+            .database_connect_timeout_seconds(0)
+            .database_command_timeout_seconds(3600)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            settings.validate(),
+            Err(vec![
+                FieldError::new(
+                    "DatabaseConnectTimeoutSeconds",
+                    "must be between 1 and 600 seconds, got 0"
+                ),
+                FieldError::new(
+                    "DatabaseCommandTimeoutSeconds",
+                    "must be between 1 and 600 seconds, got 3600"
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mssql")]
+    fn test_conflicting_ports_are_rejected() {
+        let settings = settings()
+            .database_server("localhost,14330")
+            .database_port(1433)
+            .build()
+            .unwrap();
+
+        let err = settings.try_get_sql_settings().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid settings: DatabasePort: is 1433 but DatabaseServer 'localhost,14330' specifies port 14330"
+        );
+        assert_eq!(settings.get_sql_settings().get_addr(), "localhost:1433");
+    }
+}
@@ -0,0 +1,113 @@
+use crate::profile::Profile;
+use crate::{Settings, SettingsError, DEFAULT_BLOB_VAR};
+
+impl Settings {
+    /// Like [`Settings::get_settings`], but fails with
+    /// [`SettingsError::UnknownFields`] listing every key in the blob that isn't a
+    /// settings field, such as a misspelled `EmailToAddreses`. Meant as a
+    /// preflight check for deploy pipelines.
+    pub fn get_settings_strict() -> Result<Settings, SettingsError> {
+        reject_unknown(Settings::get_settings_lenient()?)
+    }
+
+    /// Like [`Settings::get_settings`], but also returns the keys in the blob that
+    /// aren't settings fields, as paths like `Databases.warehouse.Sever`, so they
+    /// can be logged as warnings.
+    pub fn get_settings_lenient() -> Result<(Settings, Vec<String>), SettingsError> {
+        let (mut settings, unknown) = Settings::load_blob(DEFAULT_BLOB_VAR, Profile::FromEnv)?;
+        settings.apply_env_overrides()?;
+        Ok((settings.finish_load()?, unknown))
+    }
+}
+
+fn reject_unknown((settings, unknown): (Settings, Vec<String>)) -> Result<Settings, SettingsError> {
+    if unknown.is_empty() {
+        Ok(settings)
+    } else {
+        Err(SettingsError::UnknownFields { keys: unknown })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::BlobFormat;
+
+    // `EmailToAddreses` is a typo; `Owner` is simply not a field
+    const BLOB: &str = r#"{
+        "DatabaseServer": "localhost",
+        "DatabaseName": "test_db",
+        "DatabaseUsername": "admin",
+        "DatabasePassword": "password123",
+        "LogWebhookUri": "https://example.com",
+        "SendgridApiKey": "sendgrid-api-key",
+        "EmailFromName": "Test",
+        "EmailFromAddress": "test@example.com",
+        "EmailToAddresses": "user1@example.com",
+        "EmailToAddreses": "user2@example.com",
+        "Owner": "reporting team"
+    }"#;
+
+    #[test]
+    fn test_lenient_returns_unknown_keys() {
+        let (settings, unknown) =
+            Settings::parse_json_reporting_unknown(BLOB, Profile::FromEnv).unwrap();
+
+        assert_eq!(settings.email_to_addresses(), Some("user1@example.com"));
+        assert_eq!(unknown, ["EmailToAddreses", "Owner"]);
+    }
+
+    #[test]
+    fn test_strict_lists_every_unknown_key() {
+        let err =
+            reject_unknown(Settings::parse_json_reporting_unknown(BLOB, Profile::FromEnv).unwrap())
+                .unwrap_err();
+
+        assert!(matches!(&err, SettingsError::UnknownFields { keys } if keys.len() == 2));
+        assert_eq!(
+            err.to_string(),
+            "Unknown fields in settings blob: EmailToAddreses, Owner"
+        );
+    }
+
+    #[test]
+    fn test_reports_nested_and_toml_keys() {
+        let blob = r#"
+            DatabaseServer = "localhost"
+            LogWebhookUri = "https://example.com"
+            SendgridApiKey = "sendgrid-api-key"
+            EmailFromName = "Test"
+            EmailFromAddress = "test@example.com"
+            EmailToAddresses = "user1@example.com"
+
+            [Databases.warehouse]
+            Sever = "wh-sql"
+            Name = "warehouse"
+            Username = "reader"
+            Password = "secret"
+            Server = "wh-sql"
+        "#;
+
+        let (_, unknown) = BlobFormat::Toml
+            .parse_reporting_unknown(blob, Profile::FromEnv)
+            .unwrap();
+        assert_eq!(unknown, ["Databases.warehouse.Sever"]);
+    }
+
+    #[test]
+    fn test_known_aliases_are_not_unknown() {
+        let blob = BLOB
+            .replace(r#""EmailToAddreses": "user2@example.com","#, "")
+            .replace(
+                r#""Owner": "reporting team""#,
+                r#""databaseName": "other_db""#,
+            )
+            .replace(r#""DatabaseName": "test_db","#, "");
+
+        let settings = reject_unknown(
+            Settings::parse_json_reporting_unknown(&blob, Profile::FromEnv).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(settings.database_name(), "other_db");
+    }
+}
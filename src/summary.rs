@@ -0,0 +1,150 @@
+use crate::address::split_address_list;
+use crate::sql::ServerAddress;
+use crate::webhook::parse_webhook_url;
+use crate::{Settings, DEFAULT_DATABASE_PORT};
+use std::fmt;
+
+/// A one-line summary for startup logs, such as
+/// `Settings[db=SQLPROD01:1433/reports failover=SQLPROD02:1433 user=svc_report webhook=https://hooks.example.com recipients=4]`.
+///
+/// It has no secrets, and of the webhook only the scheme, host and port, since
+/// the path of a webhook URL is often a token. `user` and `webhook` are left
+/// out when unset, and so is `failover`, the `DatabaseFailoverServer` address
+/// shown after the database; `recipients` counts the To, Cc and Bcc entries, and
+/// `fingerprint` is [`Settings::fingerprint`]. The format is meant for people
+/// reading logs and is not a stable interface: don't parse it.
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Settings[db=")?;
+        if self.database_server.trim().is_empty() && self.database_connection_string.is_some() {
+            write!(f, "(connection string)")?;
+        } else {
+            write_address(f, &self.sql_address(&mut Vec::new()))?;
+        }
+        write!(f, "/{}", self.database_name)?;
+        if let Some(address) = self.failover_address(&mut Vec::new()) {
+            write!(f, " failover=")?;
+            write_address(f, &address)?;
+        }
+
+        if !self.database_username.is_empty() {
+            write!(f, " user={}", self.database_username)?;
+        }
+        if let Some(raw) = self.log_webhook_uri() {
+            match parse_webhook_url(raw) {
+                Ok(url) => write!(f, " webhook={}", url.origin().ascii_serialization())?,
+                Err(_) => write!(f, " webhook=(invalid)")?,
+            }
+        }
+
+        let recipients: usize = [
+            self.email_to_addresses.as_deref(),
+            self.email_cc_addresses.as_deref(),
+            self.email_bcc_addresses.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|raw| split_address_list(raw).count())
+        .sum();
+        write!(
+            f,
+            " recipients={} fingerprint={}]",
+            recipients,
+            self.fingerprint()
+        )
+    }
+}
+
+fn write_address(f: &mut fmt::Formatter<'_>, address: &ServerAddress<'_>) -> fmt::Result {
+    match address.instance {
+        Some(instance) => write!(f, "{}\\{}", address.host, instance),
+        None => write!(
+            f,
+            "{}:{}",
+            address.host,
+            address.port.unwrap_or(DEFAULT_DATABASE_PORT)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_of_full_settings() {
+        let settings = Settings::builder()
+            .database_server("SQLPROD01")
+            .database_failover_server("SQLPROD02")
+            .database_name("reports")
+            .database_username("svc_report")
+            .database_password("password123")
+            .log_webhook_uri("https://hooks.example.com/services/T000/B000/secret-token")
+            .sendgrid_api_key("SG.abcdefghijklmnop.wxyz")
+            .email_to_addresses("a@example.com; \"Ops, Night\" <ops@example.com>")
+            .email_cc_addresses("c@example.com")
+            .email_bcc_addresses("d@example.com,")
+            .build()
+            .unwrap();
+
+        let summary = settings.to_string();
+
+        assert_eq!(
+            summary,
+            format!(
+                "Settings[db=SQLPROD01:1433/reports failover=SQLPROD02:1433 user=svc_report webhook=https://hooks.example.com recipients=4 fingerprint={}]",
+                settings.fingerprint()
+            )
+        );
+        for secret in ["password123", "secret-token", "SG."] {
+            assert!(!summary.contains(secret), "{}", summary);
+        }
+    }
+
+    #[test]
+    fn test_summary_of_minimal_settings() {
+        let settings = Settings::builder()
+            .database_server("sql01\\reporting")
+            .database_failover_server("sql02\\reporting")
+            .database_name("reports")
+            .database_auth_method(crate::DatabaseAuthMethod::Integrated)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            settings.to_string(),
+            format!(
+                "Settings[db=sql01\\reporting/reports failover=sql02\\reporting recipients=0 fingerprint={}]",
+                settings.fingerprint()
+            )
+        );
+    }
+
+    #[test]
+    fn test_summary_with_port_and_connection_string() {
+        let with_port = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01,14330", "DatabaseName": "reports", "DatabaseUsername": "svc",
+                "DatabasePassword": "pw", "LogWebhookUri": "not a url"}"#,
+        )
+        .unwrap();
+        let from_string = Settings::from_json_str(
+            r#"{"DatabaseConnectionString": "Server=sql01;Password=hunter2", "DatabaseName": "reports"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            with_port.to_string(),
+            format!(
+                "Settings[db=sql01:14330/reports user=svc webhook=(invalid) recipients=0 fingerprint={}]",
+                with_port.fingerprint()
+            )
+        );
+        assert_eq!(
+            from_string.to_string(),
+            format!(
+                "Settings[db=(connection string)/reports recipients=0 fingerprint={}]",
+                from_string.fingerprint()
+            )
+        );
+    }
+}
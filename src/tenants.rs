@@ -0,0 +1,252 @@
+use crate::cleanup;
+use crate::format;
+use crate::profile::{self, Profile};
+use crate::{Settings, SettingsError, DEFAULT_BLOB_VAR};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::env;
+
+const TENANTS_KEY: &str = "Tenants";
+const SHARED_KEY: &str = "Shared";
+
+/// Settings for several tenants from one blob, for a binary that runs the
+/// same reports for more than one customer.
+///
+/// The blob is JSON of the form `{"Tenants": {"acme": {...}, "globex": {...}},
+/// "Shared": {...}}`. Each tenant's settings are merged over `Shared` the way
+/// a profile is merged over `Default`, so a tenant only needs the values that
+/// differ, and are then loaded and validated like a standalone blob; a
+/// tenant's settings may have profiles of their own. `Shared` is optional.
+///
+/// Tenant names must not be empty, nor differ from each other only by case.
+/// The `REPORTSETTINGS_<FIELD>` env overrides are not applied, since they
+/// would apply to every tenant alike.
+#[derive(Debug, Clone, Copy)]
+pub struct TenantSettings;
+
+impl TenantSettings {
+    /// The settings of every tenant in the `SecretBlob` variable, or the file
+    /// named by `SecretBlobPath` if that variable is not set, by tenant name.
+    /// Fails if any tenant's settings do.
+    pub fn get_all() -> Result<HashMap<String, Settings>, SettingsError> {
+        TenantSettings::from_json_str(&read_blob()?)
+    }
+
+    /// The settings of tenant `name`, read like [`TenantSettings::get_all`]
+    /// reads them; the other tenants' settings are not loaded. Fails with
+    /// [`SettingsError::UnknownTenant`] if the blob has no such tenant.
+    pub fn get(name: &str) -> Result<Settings, SettingsError> {
+        TenantSettings::tenant_from_json_str(&read_blob()?, name)
+    }
+
+    /// Like [`TenantSettings::get_all`], parsing `blob`.
+    pub fn from_json_str(blob: &str) -> Result<HashMap<String, Settings>, SettingsError> {
+        let (tenants, shared) = split(blob)?;
+        tenants
+            .into_iter()
+            .map(|(name, overrides)| {
+                let settings = load(&name, &shared, overrides)?;
+                Ok((name, settings))
+            })
+            .collect()
+    }
+
+    /// Like [`TenantSettings::get`], parsing `blob`.
+    pub fn tenant_from_json_str(blob: &str, name: &str) -> Result<Settings, SettingsError> {
+        let (mut tenants, shared) = split(blob)?;
+        match tenants.remove(name) {
+            Some(overrides) => load(name, &shared, overrides),
+            None => Err(SettingsError::UnknownTenant {
+                name: name.to_string(),
+                available: tenants.keys().cloned().collect(),
+            }),
+        }
+    }
+}
+
+fn read_blob() -> Result<String, SettingsError> {
+    match env::var(DEFAULT_BLOB_VAR) {
+        Ok(blob) => Ok(blob),
+        Err(env::VarError::NotPresent) => match env::var_os(format!("{}Path", DEFAULT_BLOB_VAR)) {
+            Some(path) => Settings::read_file(path.as_ref()),
+            None => Err(SettingsError::MissingEnvVar {
+                name: DEFAULT_BLOB_VAR.to_string(),
+            }),
+        },
+        Err(env::VarError::NotUnicode(_)) => Err(SettingsError::InvalidEnvVar {
+            name: DEFAULT_BLOB_VAR.to_string(),
+        }),
+    }
+}
+
+// The tenants, sorted by name, and the shared settings
+fn split(blob: &str) -> Result<(Map<String, Value>, Value), SettingsError> {
+    let blob: Value = cleanup::parse_cleaned(blob, |blob| Ok(serde_json::from_str(blob)?))?;
+    let Value::Object(mut blob) = blob else {
+        return Err(invalid("the blob is not a JSON object"));
+    };
+    if let Some(key) = blob
+        .keys()
+        .find(|key| *key != TENANTS_KEY && *key != SHARED_KEY)
+    {
+        return Err(invalid(format!(
+            "unexpected key '{}' next to Tenants and Shared",
+            key
+        )));
+    }
+    let tenants = match blob.remove(TENANTS_KEY) {
+        Some(Value::Object(tenants)) if !tenants.is_empty() => tenants,
+        Some(Value::Object(_)) => return Err(invalid("Tenants is empty")),
+        Some(_) => return Err(invalid("Tenants must be an object")),
+        None => return Err(invalid("the blob has no Tenants object")),
+    };
+    let shared = match blob.remove(SHARED_KEY) {
+        Some(shared @ Value::Object(_)) => shared,
+        Some(_) => return Err(invalid("Shared must be an object")),
+        None => Value::Object(Map::new()),
+    };
+    check_names(&tenants)?;
+    Ok((tenants, shared))
+}
+
+// Names that differ only by case would be told apart by `get` but not by the
+// people configuring them
+fn check_names(tenants: &Map<String, Value>) -> Result<(), SettingsError> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for name in tenants.keys() {
+        if name.trim().is_empty() {
+            return Err(invalid("tenant names must not be empty"));
+        }
+        if let Some(other) = seen.insert(name.to_lowercase(), name) {
+            return Err(invalid(format!(
+                "tenants '{}' and '{}' differ only by case",
+                other, name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn load(name: &str, shared: &Value, overrides: Value) -> Result<Settings, SettingsError> {
+    let in_tenant = |source| SettingsError::Tenant {
+        name: name.to_string(),
+        source: Box::new(source),
+    };
+    if !overrides.is_object() {
+        return Err(in_tenant(invalid(
+            "the tenant's settings must be an object",
+        )));
+    }
+    let mut merged = shared.clone();
+    profile::merge(&mut merged, overrides);
+    let (settings, _) = format::parse_json_value(merged, Profile::FromEnv).map_err(in_tenant)?;
+    let settings = settings.require_fields().map_err(in_tenant)?;
+    settings
+        .validate()
+        .map_err(|errors| in_tenant(SettingsError::Validation(errors)))?;
+    Ok(settings)
+}
+
+fn invalid(reason: impl Into<String>) -> SettingsError {
+    SettingsError::InvalidTenants(reason.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOB: &str = r#"{
+        "Shared": {
+            "DatabaseServer": "sql01",
+            "DatabaseUsername": "reports",
+            "DatabasePassword": "shared-password",
+            "EmailFromAddress": "reports@example.com"
+        },
+        "Tenants": {
+            "acme": {"DatabaseName": "acme_reports"},
+            "globex": {
+                "DatabaseServer": "globex-sql",
+                "DatabaseName": "globex_reports",
+                "DatabasePassword": "globex-password"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_shared_values_are_merged_under_each_tenant() {
+        let tenants = TenantSettings::from_json_str(BLOB).unwrap();
+
+        let mut names: Vec<&String> = tenants.keys().collect();
+        names.sort();
+        assert_eq!(names, ["acme", "globex"]);
+        let acme = &tenants["acme"];
+        assert_eq!(acme.database_server(), "sql01");
+        assert_eq!(acme.database_name(), "acme_reports");
+        assert_eq!(acme.expose_database_password(), "shared-password");
+        let globex = &tenants["globex"];
+        assert_eq!(globex.database_server(), "globex-sql");
+        assert_eq!(globex.database_username(), "reports");
+        assert_eq!(globex.expose_database_password(), "globex-password");
+        assert_eq!(
+            TenantSettings::tenant_from_json_str(BLOB, "globex").unwrap(),
+            *globex
+        );
+    }
+
+    #[test]
+    fn test_unknown_tenant_lists_available() {
+        let err = TenantSettings::tenant_from_json_str(BLOB, "initech").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Unknown tenant 'initech', expected one of: acme, globex"
+        );
+    }
+
+    #[test]
+    fn test_tenant_names_are_checked() {
+        let cases = [
+            (
+                r#"{"Tenants": {"Acme": {}, "acme": {}}}"#,
+                "Invalid tenants blob: tenants 'Acme' and 'acme' differ only by case",
+            ),
+            (
+                r#"{"Tenants": {" ": {}}}"#,
+                "Invalid tenants blob: tenant names must not be empty",
+            ),
+            (
+                r#"{"Tenants": {}}"#,
+                "Invalid tenants blob: Tenants is empty",
+            ),
+            (
+                r#"{"DatabaseServer": "sql01"}"#,
+                "Invalid tenants blob: unexpected key 'DatabaseServer' next to Tenants and Shared",
+            ),
+        ];
+
+        for (blob, message) in cases {
+            let err = TenantSettings::from_json_str(blob).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_each_tenant_is_validated() {
+        let blob = BLOB.replace(
+            r#""DatabaseName": "acme_reports""#,
+            r#""DatabaseName": "", "DatabasePort": 0"#,
+        );
+
+        let err = TenantSettings::from_json_str(&blob).unwrap_err();
+
+        let SettingsError::Tenant { name, source } = &err else {
+            panic!("unexpected error: {}", err);
+        };
+        assert_eq!(name, "acme");
+        assert!(matches!(**source, SettingsError::Validation(_)));
+        assert!(err
+            .to_string()
+            .starts_with("Tenant 'acme': Invalid settings: DatabaseName: must not be empty"));
+        assert!(TenantSettings::tenant_from_json_str(&blob, "globex").is_ok());
+    }
+}
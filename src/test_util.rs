@@ -0,0 +1,245 @@
+use crate::{
+    DatabaseAuthMethod, DatabaseEncryption, DatabaseTarget, LogLevel, LogWebhookFormat, Settings,
+    SettingsBuilder, DEFAULT_LOG_WEBHOOK_BATCH_SIZE,
+};
+
+// Each `with_*` method rebuilds the settings with one builder setter applied;
+// `with_application_name` is always available, so it isn't generated here
+macro_rules! with_setters {
+    ($($with:ident => $setter:ident($value:ident: $ty:ty);)*) => {
+        impl Settings {
+            $(
+                #[doc = concat!("These settings with [`SettingsBuilder::", stringify!($setter), "`] applied.")]
+                pub fn $with(self, $value: $ty) -> Settings {
+                    SettingsBuilder::from_settings(self)
+                        .$setter($value)
+                        .build()
+                        .expect("the settings already had every required field")
+                }
+            )*
+        }
+    };
+}
+
+with_setters! {
+    with_database_server => database_server(database_server: impl Into<String>);
+    with_database_name => database_name(database_name: impl Into<String>);
+    with_database_username => database_username(database_username: impl Into<String>);
+    with_database_password => database_password(database_password: impl Into<String>);
+    with_database_port => database_port(database_port: u16);
+    with_database_failover_server => database_failover_server(server: impl Into<String>);
+    with_database_encryption => database_encryption(database_encryption: DatabaseEncryption);
+    with_database_trust_cert => database_trust_cert(database_trust_cert: bool);
+    with_database_ca_cert_path => database_ca_cert_path(path: impl Into<String>);
+    with_database_auth_method => database_auth_method(database_auth_method: DatabaseAuthMethod);
+    with_database_connection_string => database_connection_string(database_connection_string: impl Into<String>);
+    with_database_read_only_intent => database_read_only_intent(read_only: bool);
+    with_database_connect_timeout_seconds => database_connect_timeout_seconds(seconds: u64);
+    with_database_command_timeout_seconds => database_command_timeout_seconds(seconds: u64);
+    with_database_pool_max_size => database_pool_max_size(max_size: u32);
+    with_database_pool_timeout_seconds => database_pool_timeout_seconds(seconds: u64);
+    with_database_pool_test_on_checkout => database_pool_test_on_checkout(test_on_checkout: bool);
+    with_log_webhook_uri => log_webhook_uri(log_webhook_uri: impl Into<String>);
+    with_log_webhook_auth_header => log_webhook_auth_header(header: impl Into<String>);
+    with_log_webhook_auth_value => log_webhook_auth_value(value: impl Into<String>);
+    with_log_webhook_timeout_seconds => log_webhook_timeout_seconds(seconds: u64);
+    with_log_webhook_max_retries => log_webhook_max_retries(retries: u32);
+    with_log_webhook_batch_size => log_webhook_batch_size(size: u32);
+    with_log_webhook_format => log_webhook_format(format: LogWebhookFormat);
+    with_log_webhook_min_level => log_webhook_min_level(level: LogLevel);
+    with_log_webhook_module_allow_list => log_webhook_module_allow_list(modules: impl Into<String>);
+    with_sendgrid_api_key => sendgrid_api_key(sendgrid_api_key: impl Into<String>);
+    with_email_from_name => email_from_name(email_from_name: impl Into<String>);
+    with_email_from_address => email_from_address(email_from_address: impl Into<String>);
+    with_email_to_addresses => email_to_addresses(email_to_addresses: impl Into<String>);
+    with_email_cc_addresses => email_cc_addresses(email_cc_addresses: impl Into<String>);
+    with_email_bcc_addresses => email_bcc_addresses(email_bcc_addresses: impl Into<String>);
+    with_email_reply_to_address => email_reply_to_address(email_reply_to_address: impl Into<String>);
+    with_email_reply_to_name => email_reply_to_name(email_reply_to_name: impl Into<String>);
+    with_email_max_retries => email_max_retries(retries: u32);
+    with_email_retry_base_ms => email_retry_base_ms(milliseconds: u64);
+    with_email_max_per_hour => email_max_per_hour(limit: u32);
+    with_email_allow_empty_recipients => email_allow_empty_recipients(allow: bool);
+    with_email_recipient_soft_limit => email_recipient_soft_limit(recipients: u32);
+    with_sendgrid_template_id => sendgrid_template_id(sendgrid_template_id: impl Into<String>);
+    with_email_sandbox_mode => email_sandbox_mode(sandboxed: bool);
+    with_email_categories => email_categories(email_categories: impl Into<String>);
+    with_email_asm_group_id => email_asm_group_id(group_id: u32);
+    with_email_asm_groups_to_display => email_asm_groups_to_display(email_asm_groups_to_display: impl Into<String>);
+    with_sendgrid_api_host => sendgrid_api_host(sendgrid_api_host: impl Into<String>);
+    with_smtp_host => smtp_host(smtp_host: impl Into<String>);
+    with_smtp_port => smtp_port(smtp_port: u16);
+    with_smtp_username => smtp_username(smtp_username: impl Into<String>);
+    with_smtp_password => smtp_password(smtp_password: impl Into<String>);
+    with_smtp_use_start_tls => smtp_use_start_tls(use_start_tls: bool);
+    with_http_proxy_uri => http_proxy_uri(http_proxy_uri: impl Into<String>);
+    with_http_proxy_username => http_proxy_username(http_proxy_username: impl Into<String>);
+    with_http_proxy_password => http_proxy_password(http_proxy_password: impl Into<String>);
+    with_http_connect_timeout_seconds => http_connect_timeout_seconds(seconds: u64);
+    with_http_request_timeout_seconds => http_request_timeout_seconds(seconds: u64);
+    with_schema_version => schema_version(version: u32);
+}
+
+impl Settings {
+    /// Obviously fake settings for tests, with every field but
+    /// `DatabaseConnectionString` set: a `localhost` database, `example.com`
+    /// webhook and email addresses, placeholder secrets and a `warehouse` entry
+    /// in `Databases`. They pass [`Settings::validate`].
+    ///
+    /// Change single fields with the `with_*` methods, e.g.
+    /// `Settings::for_tests().with_database_name("invoices")`. Only built for
+    /// this crate's tests and with the `test-util` feature, which other crates
+    /// can enable as a dev-dependency.
+    pub fn for_tests() -> Settings {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("test_user")
+            .database_password("test-password")
+            .database_port(1433)
+            .database_encryption(DatabaseEncryption::Off)
+            .database_trust_cert(false)
+            .database_auth_method(DatabaseAuthMethod::SqlServer)
+            .database_read_only_intent(false)
+            .database(
+                "warehouse",
+                DatabaseTarget {
+                    server: "localhost".to_string(),
+                    name: "test_warehouse".to_string(),
+                    username: "test_user".to_string(),
+                    password: "test-password".into(),
+                    port: None,
+                },
+            )
+            .application_name("test-report")
+            .database_connect_timeout_seconds(5)
+            .database_command_timeout_seconds(30)
+            .database_pool_max_size(2)
+            .database_pool_timeout_seconds(5)
+            .database_pool_test_on_checkout(false)
+            .log_webhook_uri("https://hooks.example.com/test")
+            .log_webhook_auth_header("x-functions-key")
+            .log_webhook_auth_value("test-function-key")
+            .log_webhook_timeout_seconds(5)
+            .log_webhook_max_retries(0)
+            .log_webhook_batch_size(DEFAULT_LOG_WEBHOOK_BATCH_SIZE)
+            .log_webhook_format(LogWebhookFormat::Json)
+            .log_webhook_min_level(LogLevel::Debug)
+            .sendgrid_api_key("SG.test-api-key")
+            .email_from_name("Test Reports")
+            .email_from_address("reports@example.com")
+            .email_to_addresses("to@example.com")
+            .email_cc_addresses("cc@example.com")
+            .email_bcc_addresses("bcc@example.com")
+            .email_reply_to_address("reply@example.com")
+            .email_reply_to_name("Test Support")
+            .email_max_retries(0)
+            .email_retry_base_ms(1)
+            .email_allow_empty_recipients(false)
+            .email_recipient_soft_limit(100)
+            .sendgrid_template_id("d-test-template")
+            .email_sandbox_mode(false)
+            .email_categories("tests")
+            .email_custom_arg("Environment", "test")
+            .email_asm_group_id(1)
+            .email_asm_groups_to_display("1")
+            .sendgrid_api_host("https://api.sendgrid.com")
+            .http_connect_timeout_seconds(5)
+            .http_request_timeout_seconds(10)
+            .schema_version(1)
+            .build()
+            .expect("the fixture has every required field")
+    }
+
+    /// These settings with `name` added to, or replaced in, `EmailCustomArgs`.
+    pub fn with_email_custom_arg(
+        self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Settings {
+        SettingsBuilder::from_settings(self)
+            .email_custom_arg(name, value)
+            .build()
+            .expect("the settings already had every required field")
+    }
+
+    /// These settings with `target` added to, or replacing `name` in, the
+    /// `Databases` map.
+    pub fn with_database(self, name: impl Into<String>, target: DatabaseTarget) -> Settings {
+        SettingsBuilder::from_settings(self)
+            .database(name, target)
+            .build()
+            .expect("the settings already had every required field")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_is_valid_and_complete() {
+        let settings = Settings::for_tests();
+
+        assert_eq!(settings.validate(), Ok(()));
+        assert!(settings.warnings().is_empty(), "{:?}", settings.warnings());
+        let fields = serde_json::to_value(&settings).unwrap();
+        let annotated = Settings::example_blob_annotated();
+        let unset: Vec<&str> = annotated
+            .iter()
+            .map(|(field, _)| *field)
+            .filter(|field| fields.get(field).is_none())
+            .collect();
+        // A failover server would be dialled by every test whose database is
+        // down, a CA bundle would have to exist on every machine running the
+        // tests, a proxy in the fixture would reroute every test's webhook calls, an
+        // allow list would filter out the tests' own log lines, a send limit
+        // would fail tests depending on how many sent before them, and a relay
+        // would be dialled by every test whose SendGrid server fails
+        assert_eq!(
+            unset,
+            [
+                "DatabaseFailoverServer",
+                "DatabaseCaCertPath",
+                "DatabaseConnectionString",
+                "LogWebhookModuleAllowList",
+                "EmailMaxPerHour",
+                "SmtpHost",
+                "SmtpPort",
+                "SmtpUsername",
+                "SmtpPassword",
+                "SmtpUseStartTls",
+                "HttpProxyUri",
+                "HttpProxyUsername",
+                "HttpProxyPassword"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_changes_one_field() {
+        let settings = Settings::for_tests()
+            .with_database_name("invoices")
+            .with_email_to_addresses("a@example.com, b@example.com");
+
+        assert_eq!(
+            Settings::for_tests().changed_fields(&settings),
+            ["DatabaseName", "EmailToAddresses"]
+        );
+        assert_eq!(settings.database_name(), "invoices");
+    }
+
+    #[test]
+    fn test_with_can_clear_required_fields() {
+        let settings = Settings::for_tests()
+            .with_database_password("")
+            .with_database("archive", DatabaseTarget::default());
+
+        assert_eq!(settings.expose_database_password(), "");
+        assert_eq!(
+            settings.database_names(),
+            ["default", "archive", "warehouse"]
+        );
+        assert!(settings.validate().is_err());
+    }
+}
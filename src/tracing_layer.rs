@@ -0,0 +1,275 @@
+use crate::forwarder::Forwarder;
+use crate::{LogLevel, Settings, SettingsError, WebhookFilter};
+use std::fmt::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// A [`tracing_subscriber::Layer`] that forwards events to the log webhook.
+///
+/// Events passing its [`WebhookFilter`], at or above `LogWebhookMinLevel` and
+/// from a module in `LogWebhookModuleAllowList` if set, become lines like
+/// `WARN report::load import:batch: 3 rows skipped file="a.csv"`, naming the
+/// target, the spans the event happened in, the message and the event's other
+/// fields. Lines are queued without blocking and posted in batches from a
+/// background thread, in the `LogWebhookFormat` layout; when the queue is full
+/// they are dropped and counted, see [`WebhookLayerHandle::dropped_events`].
+///
+/// ```no_run
+/// use tracing_subscriber::prelude::*;
+///
+/// # fn main() -> Result<(), reportsettings_rust::SettingsError> {
+/// let settings = reportsettings_rust::Settings::get_settings()?;
+/// let layer = settings.webhook_layer()?;
+/// let handle = layer.handle();
+/// tracing_subscriber::registry().with(layer).init();
+///
+/// tracing::warn!(rows = 3, "rows skipped");
+///
+/// // Before exiting, so the last lines aren't lost
+/// handle.flush();
+/// # Ok(())
+/// # }
+/// ```
+pub struct WebhookLayer {
+    filter: WebhookFilter,
+    fields: Option<Vec<&'static str>>,
+    forwarder: Forwarder,
+}
+
+/// Flushes and inspects a [`WebhookLayer`] after it has been given to a subscriber.
+#[derive(Clone)]
+pub struct WebhookLayerHandle {
+    filter: WebhookFilter,
+    forwarder: Forwarder,
+}
+
+impl WebhookLayerHandle {
+    /// The filter the layer applies; updating it needs no new subscriber.
+    pub fn filter(&self) -> WebhookFilter {
+        self.filter.clone()
+    }
+
+    /// Blocks until every event queued so far has been posted.
+    pub fn flush(&self) {
+        self.forwarder.flush();
+    }
+
+    /// How many events were dropped because the queue was full.
+    pub fn dropped_events(&self) -> u64 {
+        self.forwarder.dropped()
+    }
+}
+
+impl WebhookLayer {
+    pub fn handle(&self) -> WebhookLayerHandle {
+        WebhookLayerHandle {
+            filter: self.filter.clone(),
+            forwarder: self.forwarder.clone(),
+        }
+    }
+
+    /// Includes only the named fields in forwarded lines, instead of all of them.
+    /// The message is always included.
+    pub fn with_fields(mut self, fields: &[&'static str]) -> WebhookLayer {
+        self.fields = Some(fields.to_vec());
+        self
+    }
+}
+
+fn level_of(level: Level) -> LogLevel {
+    match level {
+        Level::ERROR => LogLevel::Error,
+        Level::WARN => LogLevel::Warning,
+        Level::INFO => LogLevel::Info,
+        _ => LogLevel::Debug,
+    }
+}
+
+struct LineVisitor<'a> {
+    message: String,
+    fields: String,
+    only: Option<&'a [&'static str]>,
+}
+
+impl LineVisitor<'_> {
+    fn wanted(&self, field: &Field) -> bool {
+        self.only.is_none_or(|only| only.contains(&field.name()))
+    }
+}
+
+impl Visit for LineVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else if self.wanted(field) {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else if self.wanted(field) {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+impl<S> Layer<S> for WebhookLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = level_of(*metadata.level());
+        if !self.filter.allows(level, metadata.target()) || self.forwarder.on_worker() {
+            return;
+        }
+
+        let mut visitor = LineVisitor {
+            message: String::new(),
+            fields: String::new(),
+            only: self.fields.as_deref(),
+        };
+        event.record(&mut visitor);
+
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                let names: Vec<&str> = scope.from_root().map(|span| span.name()).collect();
+                format!(" {}", names.join(":"))
+            })
+            .unwrap_or_default();
+        let line = format!(
+            "{} {}{}: {}{}",
+            metadata.level(),
+            metadata.target(),
+            spans,
+            visitor.message,
+            visitor.fields
+        );
+        self.forwarder.send(level, line);
+    }
+}
+
+impl Settings {
+    /// A [`WebhookLayer`] for `LogWebhookUri`.
+    pub fn webhook_layer(&self) -> Result<WebhookLayer, SettingsError> {
+        Ok(WebhookLayer {
+            filter: WebhookFilter::new(self),
+            fields: None,
+            forwarder: Forwarder::spawn(self.get_webhook_logger()?)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn server() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn settings(server: &MockServer) -> Settings {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri(server.uri())
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap()
+    }
+
+    async fn posted_message(server: &MockServer) -> String {
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        body["Message"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_forwards_warnings_with_spans_and_fields() {
+        let server = server().await;
+        let layer = settings(&server).webhook_layer().unwrap();
+        let handle = layer.handle();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _import = tracing::warn_span!("import").entered();
+            let _batch = tracing::info_span!("batch").entered();
+            tracing::info!("starting");
+            tracing::warn!(target: "report", file = "a.csv", rows = 3, "rows skipped");
+        });
+        handle.flush();
+
+        assert_eq!(
+            posted_message(&server).await,
+            r#"WARN report import:batch: rows skipped file="a.csv" rows=3"#
+        );
+        assert_eq!(handle.dropped_events(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_filter_updates_while_installed() {
+        let server = server().await;
+        let settings = settings(&server)
+            .with_log_webhook_min_level(LogLevel::Error)
+            .with_log_webhook_module_allow_list("report");
+        let layer = settings.webhook_layer().unwrap();
+        let handle = layer.handle();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "report", "too quiet");
+            tracing::error!(target: "hyper::client", "not ours");
+            handle.filter().update(
+                &settings
+                    .clone()
+                    .with_log_webhook_min_level(LogLevel::Warning),
+            );
+            tracing::warn!(target: "report::load", "rows skipped");
+            tracing::error!(target: "reporting", "not ours either");
+        });
+        handle.flush();
+
+        assert_eq!(
+            posted_message(&server).await,
+            "WARN report::load: rows skipped"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_fields_selects_fields() {
+        let server = server().await;
+        let layer = settings(&server)
+            .webhook_layer()
+            .unwrap()
+            .with_fields(&["rows"]);
+        let handle = layer.handle();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!(target: "report", file = "a.csv", rows = 3, "import failed");
+        });
+        handle.flush();
+
+        assert_eq!(
+            posted_message(&server).await,
+            "ERROR report: import failed rows=3"
+        );
+    }
+}
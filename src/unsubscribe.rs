@@ -0,0 +1,155 @@
+use crate::{FieldError, Settings};
+
+/// The most groups SendGrid shows on one unsubscribe preferences page.
+pub const SENDGRID_MAX_ASM_GROUPS_TO_DISPLAY: usize = 25;
+
+impl Settings {
+    /// `EmailAsmGroupId`, the SendGrid unsubscribe group report emails belong
+    /// to. Without it, messages carry no unsubscribe settings.
+    pub fn email_asm_group_id(&self) -> Option<u32> {
+        self.email_asm_group_id
+    }
+
+    /// The `EmailAsmGroupsToDisplay` group ids, in order, skipping empty
+    /// entries. Entries that aren't positive integers are left out here and
+    /// reported by [`Settings::validate`].
+    pub fn email_asm_groups_to_display(&self) -> Vec<u32> {
+        self.asm_display_entries()
+            .filter_map(|(_, entry)| parse_group_id(entry))
+            .collect()
+    }
+
+    pub(crate) fn unsubscribe_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if self.email_asm_group_id == Some(0) {
+            errors.push(FieldError::new(
+                "EmailAsmGroupId",
+                "must be a positive group id",
+            ));
+        }
+
+        let mut displayed = 0;
+        for (position, entry) in self.asm_display_entries() {
+            if parse_group_id(entry).is_some() {
+                displayed += 1;
+            } else {
+                errors.push(FieldError::new(
+                    "EmailAsmGroupsToDisplay",
+                    format!(
+                        "'{}' (entry {}) is not a positive group id",
+                        entry, position
+                    ),
+                ));
+            }
+        }
+        if displayed > SENDGRID_MAX_ASM_GROUPS_TO_DISPLAY {
+            errors.push(FieldError::new(
+                "EmailAsmGroupsToDisplay",
+                format!(
+                    "lists {} groups, but SendGrid shows at most {}",
+                    displayed, SENDGRID_MAX_ASM_GROUPS_TO_DISPLAY
+                ),
+            ));
+        }
+        if displayed > 0 && self.email_asm_group_id.is_none() {
+            errors.push(FieldError::new(
+                "EmailAsmGroupsToDisplay",
+                "is only used with EmailAsmGroupId, which is not set",
+            ));
+        }
+        errors
+    }
+
+    // Trimmed non-empty entries with their 1-based positions
+    fn asm_display_entries(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.email_asm_groups_to_display
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .enumerate()
+            .map(|(index, entry)| (index + 1, entry))
+    }
+}
+
+fn parse_group_id(entry: &str) -> Option<u32> {
+    entry.parse().ok().filter(|&id| id > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_groups_to_display_are_parsed() {
+        let settings = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailAsmGroupId": 12, "EmailAsmGroupsToDisplay": "12, 14,, 15"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.email_asm_group_id(), Some(12));
+        assert_eq!(settings.email_asm_groups_to_display(), [12, 14, 15]);
+        assert!(settings.unsubscribe_errors().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_groups_are_flagged() {
+        let invalid = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailAsmGroupId": 0, "EmailAsmGroupsToDisplay": "14, -3, 0, general"}"#,
+        )
+        .unwrap();
+        let orphaned = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailAsmGroupsToDisplay": "14"}"#,
+        )
+        .unwrap();
+        let mut crowded = Settings::from_json_str(
+            r#"{"DatabaseServer": "sql01", "DatabaseName": "reports", "DatabaseAuthMethod": "Integrated",
+                "EmailAsmGroupId": 1}"#,
+        )
+        .unwrap();
+        crowded.email_asm_groups_to_display = Some(
+            (1..=26)
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+
+        assert_eq!(
+            invalid.unsubscribe_errors(),
+            [
+                FieldError::new("EmailAsmGroupId", "must be a positive group id"),
+                FieldError::new(
+                    "EmailAsmGroupsToDisplay",
+                    "'-3' (entry 2) is not a positive group id"
+                ),
+                FieldError::new(
+                    "EmailAsmGroupsToDisplay",
+                    "'0' (entry 3) is not a positive group id"
+                ),
+                FieldError::new(
+                    "EmailAsmGroupsToDisplay",
+                    "'general' (entry 4) is not a positive group id"
+                ),
+            ]
+        );
+        assert_eq!(
+            orphaned.unsubscribe_errors(),
+            [FieldError::new(
+                "EmailAsmGroupsToDisplay",
+                "is only used with EmailAsmGroupId, which is not set"
+            )]
+        );
+        assert_eq!(
+            crowded.unsubscribe_errors(),
+            [FieldError::new(
+                "EmailAsmGroupsToDisplay",
+                "lists 26 groups, but SendGrid shows at most 25"
+            )]
+        );
+    }
+}
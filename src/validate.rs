@@ -0,0 +1,189 @@
+use crate::address::{is_valid_email, split_address_list, EmailAddress};
+use crate::webhook::parse_webhook_url;
+use crate::{FieldError, Settings, SettingsError};
+
+impl Settings {
+    /// Checks every field for values that would only fail later, deep inside
+    /// tiberius or SendGrid. Errors are reported in field declaration order, and
+    /// in list order within a field.
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        for field in self.empty_required_fields() {
+            errors.push(FieldError::new(field, "must not be empty"));
+        }
+        errors.extend(self.sql_errors());
+        errors.extend(self.failover_errors());
+        errors.extend(self.timeout_errors());
+        if self.database_pool_max_size == Some(0) {
+            errors.push(FieldError::new(
+                "DatabasePoolMaxSize",
+                "must be at least 1 connection",
+            ));
+        }
+        errors.extend(self.databases_errors());
+
+        if let Some(Err(reason)) = self.log_webhook_uri().map(parse_webhook_url) {
+            errors.push(FieldError::new("LogWebhookUri", reason));
+        }
+        errors.extend(self.log_webhook_auth_errors());
+        errors.extend(self.log_webhook_module_errors());
+        if self.log_webhook_batch_size == Some(0) {
+            errors.push(FieldError::new(
+                "LogWebhookBatchSize",
+                "must be at least 1 event",
+            ));
+        }
+        if self.log_webhook_timeout_seconds == Some(0) {
+            errors.push(FieldError::new(
+                "LogWebhookTimeoutSeconds",
+                "must be at least 1 second",
+            ));
+        }
+
+        if self
+            .sendgrid_api_key()
+            .is_some_and(|key| key.inspect().trim().is_empty())
+        {
+            errors.push(FieldError::new("SendgridApiKey", "must not be empty"));
+        }
+        errors.extend(self.sendgrid_host_errors());
+        errors.extend(self.smtp_errors());
+        errors.extend(self.http_proxy_errors());
+        errors.extend(self.http_timeout_errors());
+        if self.schema_version == Some(0) {
+            errors.push(FieldError::new("SchemaVersion", "must be at least 1"));
+        }
+
+        if let Some(from) = self.email_from_address() {
+            if !is_valid_email(from.trim()) {
+                errors.push(FieldError::new(
+                    "EmailFromAddress",
+                    format!("'{}' is not a valid email address", from),
+                ));
+            }
+        }
+
+        let reply_to = self.email_reply_to_address().trim();
+        if !reply_to.is_empty() && !is_valid_email(reply_to) {
+            errors.push(FieldError::new(
+                "EmailReplyToAddress",
+                format!("'{}' is not a valid email address", reply_to),
+            ));
+        }
+
+        if self.email_max_per_hour == Some(0) {
+            errors.push(FieldError::new(
+                "EmailMaxPerHour",
+                "must be at least 1 email",
+            ));
+        }
+
+        for (field, raw) in self.recipient_fields() {
+            for (position, address) in split_address_list(raw) {
+                if EmailAddress::parse(address).is_none() {
+                    errors.push(FieldError::new(
+                        field,
+                        format!(
+                            "'{}' (entry {}) is not a valid email address",
+                            address, position
+                        ),
+                    ));
+                }
+            }
+        }
+        errors.extend(self.recipient_errors());
+        errors.extend(self.analytics_errors());
+        errors.extend(self.unsubscribe_errors());
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Problems that don't stop the settings from working but are likely
+    /// mistakes, such as an individual database field overriding part of
+    /// `DatabaseConnectionString`. These are never reported by [`Settings::validate`].
+    pub fn warnings(&self) -> Vec<FieldError> {
+        let mut warnings = self.connection_string_warnings();
+        warnings.extend(self.recipient_warnings());
+        warnings.extend(self.smtp_warnings());
+        warnings
+    }
+
+    /// Like [`Settings::get_settings`], but also runs [`Settings::validate`].
+    pub fn get_settings_validated() -> Result<Settings, SettingsError> {
+        let settings = Settings::get_settings()?;
+        settings.validate().map_err(SettingsError::Validation)?;
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SettingsBuilder;
+
+    fn valid() -> SettingsBuilder {
+        Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("https://example.com/hook")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_name("Test")
+            .email_from_address("test@example.com")
+            .email_to_addresses("user1@example.com, User Two <user2@example.com>")
+    }
+
+    #[test]
+    fn test_valid_settings_pass() {
+        assert_eq!(valid().build().unwrap().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_errors_are_stable_ordered() {
+        let settings = valid()
+            .database_server("")
+            .database_password(" ")
+            .log_webhook_uri("not a url")
+            .sendgrid_api_key("")
+            .email_from_address("nobody")
+            .email_reply_to_address("support")
+            .email_to_addresses("ok@example.com,,bad@, also@example.com")
+            .email_bcc_addresses("audit@")
+            .build()
+            .unwrap();
+
+        let errors = settings.validate().unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                FieldError::new("DatabaseServer", "must not be empty"),
+                FieldError::new("DatabasePassword", "must not be empty"),
+                FieldError::new(
+                    "LogWebhookUri",
+                    "is not a valid URL: relative URL without a base"
+                ),
+                FieldError::new("SendgridApiKey", "must not be empty"),
+                FieldError::new("EmailFromAddress", "'nobody' is not a valid email address"),
+                FieldError::new(
+                    "EmailReplyToAddress",
+                    "'support' is not a valid email address"
+                ),
+                FieldError::new(
+                    "EmailToAddresses",
+                    "'bad@' (entry 3) is not a valid email address"
+                ),
+                FieldError::new(
+                    "EmailBccAddresses",
+                    "'audit@' (entry 1) is not a valid email address"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{FieldError, Secret, Settings, SettingsError};
+use url::Url;
+
+// The characters RFC 7230 allows in a token, such as a header name, besides
+// letters and digits
+const TOKEN_PUNCTUATION: &[u8] = b"!#$%&'*+-.^_`|~";
+
+/// Parses a webhook URI, ignoring surrounding whitespace. The error is a
+/// [`crate::FieldError`]-style reason, e.g. "is not a valid URL: ...".
+pub(crate) fn parse_webhook_url(raw: &str) -> Result<Url, String> {
+    let url = Url::parse(raw.trim()).map_err(|e| format!("is not a valid URL: {}", e))?;
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(format!("must use http or https, not '{}'", scheme)),
+    }
+}
+
+impl Settings {
+    /// `LogWebhookUri` as a parsed URL. Only `http` and `https` are accepted.
+    pub fn log_webhook_url(&self) -> Result<Url, SettingsError> {
+        let raw = self.log_webhook_uri().ok_or(SettingsError::MissingField {
+            field: "LogWebhookUri",
+        })?;
+        parse_webhook_url(raw).map_err(|reason| SettingsError::InvalidUrl {
+            field: "LogWebhookUri",
+            reason,
+        })
+    }
+
+    /// `LogWebhookAuthHeader`, the header the [`crate::WebhookLogger`] sends
+    /// `LogWebhookAuthValue` in with every post.
+    pub fn log_webhook_auth_header(&self) -> Option<&str> {
+        self.log_webhook_auth_header.as_deref()
+    }
+
+    /// `LogWebhookAuthValue`, e.g. an Azure Functions key or `Bearer <token>`.
+    pub fn log_webhook_auth_value(&self) -> Option<&Secret> {
+        self.log_webhook_auth_value.as_ref()
+    }
+
+    // The header and value must come together, and the value is never quoted
+    // back, since it is a secret
+    pub(crate) fn log_webhook_auth_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(header) = &self.log_webhook_auth_header {
+            if !is_token(header) {
+                errors.push(FieldError::new(
+                    "LogWebhookAuthHeader",
+                    format!(
+                        "'{}' is not a valid header name: use letters, digits and {} only",
+                        header,
+                        String::from_utf8_lossy(TOKEN_PUNCTUATION)
+                    ),
+                ));
+            }
+        }
+        match (&self.log_webhook_auth_header, &self.log_webhook_auth_value) {
+            (Some(_), None) => errors.push(FieldError::new(
+                "LogWebhookAuthValue",
+                "must be set to use LogWebhookAuthHeader",
+            )),
+            (None, Some(_)) => errors.push(FieldError::new(
+                "LogWebhookAuthValue",
+                "is set but LogWebhookAuthHeader is not",
+            )),
+            _ => {}
+        }
+        if let Some(value) = &self.log_webhook_auth_value {
+            let value = value.inspect();
+            if value.trim().is_empty() {
+                errors.push(FieldError::new("LogWebhookAuthValue", "must not be empty"));
+            } else if value.chars().any(|c| c.is_ascii_control() && c != '\t') {
+                errors.push(FieldError::new(
+                    "LogWebhookAuthValue",
+                    "must not contain line breaks or other control characters",
+                ));
+            }
+        }
+        errors
+    }
+}
+
+fn is_token(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || TOKEN_PUNCTUATION.contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trims_copy_paste_whitespace() {
+        let url = parse_webhook_url("  https://example.com/hook\n").unwrap();
+        assert_eq!(url.as_str(), "https://example.com/hook");
+    }
+
+    #[test]
+    fn test_rejects_missing_or_unsupported_scheme() {
+        assert_eq!(
+            parse_webhook_url("example.com").unwrap_err(),
+            "is not a valid URL: relative URL without a base"
+        );
+        assert_eq!(
+            parse_webhook_url("ftp://example.com").unwrap_err(),
+            "must use http or https, not 'ftp'"
+        );
+    }
+
+    #[test]
+    fn test_auth_header_must_be_a_token() {
+        let settings = Settings::for_tests()
+            .with_log_webhook_auth_header("x functions key")
+            .with_log_webhook_auth_value("key-with\nnewline");
+
+        let errors = settings.log_webhook_auth_errors();
+
+        assert_eq!(
+            errors,
+            [
+                FieldError::new(
+                    "LogWebhookAuthHeader",
+                    "'x functions key' is not a valid header name: use letters, digits and \
+                     !#$%&'*+-.^_`|~ only"
+                ),
+                FieldError::new(
+                    "LogWebhookAuthValue",
+                    "must not contain line breaks or other control characters"
+                ),
+            ]
+        );
+        assert!(!format!("{:?}", errors).contains("newline"));
+        assert!(Settings::for_tests().log_webhook_auth_errors().is_empty());
+    }
+
+    #[test]
+    fn test_auth_header_and_value_come_together() {
+        let blob = r#"{"DatabaseServer": "sql01", "DatabaseName": "reports",
+            "DatabaseUsername": "reports", "DatabasePassword": "hunter2",
+            "LogWebhookAuthValue": "function-key"}"#;
+
+        let err = Settings::from_json_str(blob)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+
+        assert_eq!(
+            err,
+            [FieldError::new(
+                "LogWebhookAuthValue",
+                "is set but LogWebhookAuthHeader is not"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_error_names_field() {
+        let settings = Settings::builder()
+            .database_server("localhost")
+            .database_name("test_db")
+            .database_username("admin")
+            .database_password("password123")
+            .log_webhook_uri("example.com")
+            .sendgrid_api_key("sendgrid-api-key")
+            .email_from_address("test@example.com")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            settings.log_webhook_url().unwrap_err().to_string(),
+            "LogWebhookUri: is not a valid URL: relative URL without a base"
+        );
+    }
+}
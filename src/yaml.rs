@@ -0,0 +1,207 @@
+use crate::{Settings, SettingsError};
+use serde_json::{Map, Number, Value};
+use yaml_rust2::parser::{MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+use yaml_rust2::{Event, Yaml, YamlLoader};
+
+// Recipient fields may be written as YAML sequences; they are joined back into
+// the comma-separated form the rest of the crate works with
+const LIST_FIELDS: &[&str] = &["EmailToAddresses", "EmailCcAddresses", "EmailBccAddresses"];
+
+// Records the first anchor or alias in the document. Anchors let one key's
+// value silently stand in for another's, which is never what a settings blob means
+#[derive(Default)]
+struct AnchorDetector {
+    found: Option<Marker>,
+}
+
+impl MarkedEventReceiver for AnchorDetector {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let anchored = match ev {
+            Event::Alias(_) => true,
+            Event::Scalar(_, _, id, _)
+            | Event::SequenceStart(id, _)
+            | Event::MappingStart(id, _) => id > 0,
+            _ => false,
+        };
+        if anchored && self.found.is_none() {
+            self.found = Some(mark);
+        }
+    }
+}
+
+fn invalid(reason: impl ToString) -> SettingsError {
+    SettingsError::InvalidYaml(reason.to_string())
+}
+
+pub(crate) fn parse(blob: &str) -> Result<(Settings, Vec<String>), SettingsError> {
+    let mut detector = AnchorDetector::default();
+    Parser::new_from_str(blob)
+        .load(&mut detector, true)
+        .map_err(invalid)?;
+    if let Some(mark) = detector.found {
+        return Err(invalid(format!(
+            "anchors and aliases are not supported (line {} column {})",
+            mark.line(),
+            mark.col() + 1
+        )));
+    }
+
+    // The loader itself rejects duplicate keys within a mapping
+    let docs = YamlLoader::load_from_str(blob).map_err(invalid)?;
+    let doc = match docs.as_slice() {
+        [doc] => doc,
+        [] => return Err(invalid("document is empty")),
+        _ => return Err(invalid("expected a single document")),
+    };
+
+    let mut value = to_json(doc)?;
+    crate::migrate::migrate(&mut value)?;
+    if let Value::Object(map) = &mut value {
+        for (key, value) in map.iter_mut() {
+            if !LIST_FIELDS.iter().any(|field| is_spelling_of(key, field)) {
+                continue;
+            }
+            if let Value::Array(items) = value {
+                if let Some(joined) = join_strings(items) {
+                    *value = Value::String(joined);
+                }
+            }
+        }
+    }
+
+    let absent = crate::required::absent_fields(&value);
+    let mut unknown = Vec::new();
+    let settings = crate::format::deserialize(value, |path| unknown.push(path.to_string()))
+        .map_err(invalid)?;
+    Ok((settings.with_absent_fields(absent), unknown))
+}
+
+// The PascalCase name, or its camelCase or snake_case alias
+fn is_spelling_of(key: &str, field: &str) -> bool {
+    key.replace('_', "").eq_ignore_ascii_case(field)
+}
+
+fn join_strings(items: &[Value]) -> Option<String> {
+    let items = items
+        .iter()
+        .map(|item| item.as_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(items.join(","))
+}
+
+fn to_json(yaml: &Yaml) -> Result<Value, SettingsError> {
+    Ok(match yaml {
+        Yaml::Null => Value::Null,
+        Yaml::Boolean(b) => Value::Bool(*b),
+        Yaml::Integer(i) => Value::Number((*i).into()),
+        Yaml::Real(s) => s
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| invalid(format!("invalid number '{}'", s)))?,
+        Yaml::String(s) => Value::String(s.clone()),
+        Yaml::Array(items) => Value::Array(items.iter().map(to_json).collect::<Result<_, _>>()?),
+        Yaml::Hash(hash) => {
+            let mut map = Map::with_capacity(hash.len());
+            for (key, value) in hash {
+                let key = key.as_str().ok_or_else(|| {
+                    invalid(format!("mapping keys must be strings, found {:?}", key))
+                })?;
+                map.insert(key.to_string(), to_json(value)?);
+            }
+            Value::Object(map)
+        }
+        Yaml::Alias(_) | Yaml::BadValue => return Err(invalid("unsupported value")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML_BLOB: &str = r#"
+DatabaseServer: localhost
+DatabaseName: test_db
+DatabaseUsername: admin
+DatabasePassword: "password123"
+LogWebhookUri: https://example.com
+SendgridApiKey: |-
+  SG.first-half
+  second-half
+EmailFromName: Test
+EmailFromAddress: test@example.com
+EmailToAddresses: [user1@example.com, user2@example.com]
+"#;
+
+    #[test]
+    fn test_parse_yaml_blob() {
+        let settings = Settings::from_yaml(YAML_BLOB).unwrap();
+
+        assert_eq!(settings.database_server(), "localhost");
+        assert_eq!(
+            settings.sendgrid_api_key().unwrap().expose(),
+            "SG.first-half\nsecond-half"
+        );
+        assert_eq!(
+            settings.email_to_addresses(),
+            Some("user1@example.com,user2@example.com")
+        );
+    }
+
+    #[test]
+    fn test_joins_lists_under_any_spelling() {
+        let blob = YAML_BLOB
+            .replace("EmailToAddresses:", "email_to_addresses:")
+            .replace("SendgridApiKey:", "sendgridApiKey:");
+        let settings = Settings::from_yaml(&blob).unwrap();
+
+        assert_eq!(
+            settings.email_to_addresses(),
+            Some("user1@example.com,user2@example.com")
+        );
+    }
+
+    #[test]
+    fn test_file_with_yaml_extension() {
+        let mut file = tempfile::Builder::new().suffix(".yaml").tempfile().unwrap();
+        std::io::Write::write_all(&mut file, YAML_BLOB.as_bytes()).unwrap();
+
+        let settings = Settings::get_settings_from_file(file.path()).unwrap();
+        assert_eq!(settings.database_name(), "test_db");
+    }
+
+    #[test]
+    fn test_rejects_duplicate_keys() {
+        let blob = format!("{}DatabaseName: other_db\n", YAML_BLOB);
+
+        let err = Settings::from_yaml(&blob).unwrap_err();
+        assert!(err.to_string().contains("duplicated key"), "{}", err);
+    }
+
+    #[test]
+    fn test_rejects_anchors_and_aliases() {
+        let blob = YAML_BLOB
+            .replace("DatabaseName: test_db", "DatabaseName: &db test_db")
+            .replace("DatabaseUsername: admin", "DatabaseUsername: *db");
+
+        let err = Settings::from_yaml(&blob).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("anchors and aliases are not supported"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_reports_wrong_type() {
+        let err = Settings::from_yaml("DatabaseServer: localhost\nDatabasePort: lots").unwrap_err();
+        assert!(
+            err.to_string().contains("DatabasePort: invalid value"),
+            "{}",
+            err
+        );
+    }
+}